@@ -0,0 +1,75 @@
+//!
+//! Compare serial registration against add_tree on a generated tree
+//!
+
+use std::{
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+use tokinotify::{INotify, Mask};
+
+const FANOUT: usize = 8;
+const DEPTH: usize = 4;
+
+fn generate(root: &Path) -> Vec<PathBuf> {
+    let mut dirs = vec![root.to_path_buf()];
+    let mut level = vec![root.to_path_buf()];
+
+    for _ in 0..DEPTH {
+        let mut next = Vec::new();
+        for dir in &level {
+            for i in 0..FANOUT {
+                let child = dir.join(format!("d{i}"));
+                std::fs::create_dir_all(&child).unwrap();
+                next.push(child);
+            }
+        }
+
+        dirs.extend(next.iter().cloned());
+        level = next;
+    }
+
+    dirs
+}
+
+fn main() {
+    let root = std::env::temp_dir().join(format!("tokinotify-bench-{}", std::process::id()));
+    let dirs = generate(&root);
+
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+
+    let mut inotify = INotify::new().unwrap();
+    let start = Instant::now();
+    let mut stack = vec![root.clone()];
+    while let Some(dir) = stack.pop() {
+        inotify.add(&dir, Mask::CREATE | Mask::DELETE).unwrap();
+        for entry in std::fs::read_dir(&dir).unwrap() {
+            let entry = entry.unwrap();
+            if entry.file_type().unwrap().is_dir() {
+                stack.push(entry.path());
+            }
+        }
+    }
+    let serial = start.elapsed();
+    drop(inotify);
+
+    let tree = runtime.block_on(async {
+        let mut inotify = INotify::new().unwrap();
+        let start = Instant::now();
+        inotify
+            .add_tree(&root, Mask::CREATE | Mask::DELETE, None)
+            .await
+            .unwrap();
+        start.elapsed()
+    });
+
+    println!("{} directories", dirs.len());
+    println!("serial add: {serial:?}");
+    println!("add_tree:   {tree:?}");
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
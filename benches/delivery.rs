@@ -0,0 +1,50 @@
+//!
+//! Deliver bursts of churn through INotify::watch on a tokio runtime
+//!
+
+use std::time::{Duration, Instant};
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use tokinotify::{bench::Churn, INotify};
+
+fn delivery(c: &mut Criterion) {
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+
+    let mut group = c.benchmark_group("delivery");
+
+    for steps in [64, 1024] {
+        group.throughput(Throughput::Elements(steps as u64));
+
+        group.bench_with_input(BenchmarkId::new("watch", steps), &steps, |b, &steps| {
+            let mut churn = Churn::new().unwrap();
+            let mut inotify = runtime.block_on(async { INotify::new() }).unwrap();
+            inotify.add(churn.dir(), Churn::MASK).unwrap();
+
+            b.iter_custom(|iters| {
+                let mut total = Duration::ZERO;
+
+                for _ in 0..iters {
+                    churn.burst(steps).unwrap();
+
+                    let start = Instant::now();
+                    runtime.block_on(async {
+                        for _ in 0..steps {
+                            inotify.watch().await.unwrap();
+                        }
+                    });
+                    total += start.elapsed();
+                }
+
+                total
+            })
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, delivery);
+criterion_main!(benches);
@@ -0,0 +1,32 @@
+//!
+//! Parse kernel reads of generated events
+//!
+
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use tokinotify::{bench::frames, Events};
+
+const COUNT: usize = 1024;
+
+fn parse(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parse");
+    group.throughput(Throughput::Elements(COUNT as u64));
+
+    for name_len in [0, 16, 255] {
+        let buf = frames(COUNT, name_len);
+
+        group.bench_with_input(BenchmarkId::new("events", name_len), &buf, |b, buf| {
+            b.iter(|| {
+                for event in Events::new(black_box(buf)) {
+                    black_box(event.unwrap());
+                }
+            })
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, parse);
+criterion_main!(benches);
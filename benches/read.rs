@@ -0,0 +1,46 @@
+//!
+//! Read bursts of churn in batches straight from the kernel
+//!
+
+use std::time::{Duration, Instant};
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use tokinotify::{bench::Churn, Events, RawINotify};
+
+fn read(c: &mut Criterion) {
+    let mut group = c.benchmark_group("read");
+
+    for steps in [64, 1024] {
+        group.throughput(Throughput::Elements(steps as u64));
+
+        group.bench_with_input(BenchmarkId::new("raw", steps), &steps, |b, &steps| {
+            let mut churn = Churn::new().unwrap();
+            let raw = RawINotify::new().unwrap();
+            raw.add(churn.dir(), Churn::MASK).unwrap();
+            let mut buf = Vec::new();
+
+            b.iter_custom(|iters| {
+                let mut total = Duration::ZERO;
+
+                for _ in 0..iters {
+                    churn.burst(steps).unwrap();
+
+                    let start = Instant::now();
+                    let mut seen = 0;
+                    while seen < steps {
+                        let len = raw.read(&mut buf).unwrap();
+                        seen += Events::new(&buf[..len]).count();
+                    }
+                    total += start.elapsed();
+                }
+
+                total
+            })
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, read);
+criterion_main!(benches);
@@ -0,0 +1,6 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+// truncation is quadratic in the input, keep inputs to a few frames
+fuzz_target!(|data: &[u8]| tokinotify::fuzz::truncated(&data[..data.len().min(4096)]));
@@ -0,0 +1,255 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use tokio::sync::{mpsc, oneshot};
+
+use crate::{control::closed, Event, INotify, Mask, Watch, WatchStats};
+
+type Job = Box<dyn FnOnce(&mut INotify) + Send>;
+
+enum Request {
+    Run(Job),
+    Close(oneshot::Sender<io::Result<()>>),
+}
+
+/// The requesting half of an [INotify] owned by a task of its own
+///
+/// Every operation is a message to the task, answered once applied, so
+/// handles are cloned and used from any number of tasks without locking.
+/// The task stops when [WatcherHandle::close] is called, when reading from
+/// the kernel fails or once every handle and the [WatcherEvents] are gone.
+/// Afterwards every call fails with [io::ErrorKind::BrokenPipe].
+#[derive(Clone)]
+pub struct WatcherHandle {
+    tx: mpsc::Sender<Request>,
+}
+
+/// The events read by the task behind a [WatcherHandle]
+///
+/// The task reads no further while an event waits to be received, but
+/// keeps answering requests, so a handle may be awaited between events.
+/// Once dropped, events are read and dropped.
+pub struct WatcherEvents {
+    rx: mpsc::Receiver<io::Result<Event>>,
+}
+
+impl INotify {
+    /// Move the instance into a task of its own, queueing up to `capacity` requests
+    pub fn spawn(self, capacity: usize) -> (WatcherHandle, WatcherEvents) {
+        let (tx, requests) = mpsc::channel(capacity.max(1));
+        let (events, rx) = mpsc::channel(1);
+
+        tokio::spawn(run(self, requests, events));
+
+        (WatcherHandle { tx }, WatcherEvents { rx })
+    }
+}
+
+impl WatcherHandle {
+    /// Add a file (, or directory) to be watched
+    pub async fn add(&self, path: &Path, mask: Mask) -> io::Result<Watch> {
+        let path = path.to_path_buf();
+        self.with(move |inotify| inotify.add(&path, mask)).await?
+    }
+
+    /// remove a watch
+    pub async fn rm(&self, watch: Watch) -> io::Result<()> {
+        self.with(move |inotify| inotify.rm(watch)).await?
+    }
+
+    /// Replace the events a watch is interested in
+    pub async fn set_mask(&self, watch: Watch, mask: Mask) -> io::Result<()> {
+        self.with(move |inotify| inotify.set_mask(watch, mask)).await?
+    }
+
+    /// the path a watch was added with
+    pub async fn path(&self, watch: Watch) -> io::Result<Option<PathBuf>> {
+        self.with(move |inotify| inotify.path(watch).map(Path::to_path_buf))
+            .await
+    }
+
+    /// every watch and the path it was added with
+    pub async fn watches(&self) -> io::Result<Vec<(Watch, PathBuf)>> {
+        self.with(|inotify| {
+            inotify
+                .watches()
+                .map(|(watch, path)| (watch, path.to_path_buf()))
+                .collect()
+        })
+        .await
+    }
+
+    /// the counters of a watch, see [INotify::track_stats]
+    pub async fn stats(&self, watch: Watch) -> io::Result<Option<WatchStats>> {
+        self.with(move |inotify| inotify.stats(watch).cloned()).await
+    }
+
+    /// Run `f` on the instance in its task, for anything not covered above
+    pub async fn with<T: Send + 'static>(
+        &self,
+        f: impl FnOnce(&mut INotify) -> T + Send + 'static,
+    ) -> io::Result<T> {
+        let (reply, rx) = oneshot::channel();
+        let job: Job = Box::new(move |inotify| {
+            let _ = reply.send(f(inotify));
+        });
+
+        self.tx
+            .send(Request::Run(job))
+            .await
+            .map_err(|_| closed())?;
+        rx.await.map_err(|_| closed())
+    }
+
+    /// Stop the task and close the instance, see [INotify::close]
+    ///
+    /// Every watch is removed first, closing fails when removing one does.
+    pub async fn close(&self) -> io::Result<()> {
+        let (reply, rx) = oneshot::channel();
+        self.tx
+            .send(Request::Close(reply))
+            .await
+            .map_err(|_| closed())?;
+        rx.await.map_err(|_| closed())?
+    }
+}
+
+impl WatcherEvents {
+    /// Wait for the next event, `None` once the task has stopped
+    ///
+    /// An error reading from the kernel is returned once, the task stops
+    /// after it.
+    pub async fn next(&mut self) -> io::Result<Option<Event>> {
+        self.rx.recv().await.transpose()
+    }
+}
+
+async fn run(
+    mut inotify: INotify,
+    mut requests: mpsc::Receiver<Request>,
+    events: mpsc::Sender<io::Result<Event>>,
+) {
+    let mut pending = None;
+
+    loop {
+        let request = tokio::select! {
+            request = requests.recv() => match request {
+                Some(request) => request,
+                None => break,
+            },
+            permit = events.reserve(), if pending.is_some() => {
+                match permit {
+                    Ok(permit) => permit.send(Ok(pending.take().expect("pending event"))),
+                    // nobody receives events, keep answering requests
+                    Err(_) => pending = None,
+                }
+                continue;
+            }
+            event = inotify.watch(), if pending.is_none() => match event {
+                Ok(event) => {
+                    pending = Some(event);
+                    continue;
+                }
+                Err(err) => {
+                    let _ = events.send(Err(err)).await;
+                    return;
+                }
+            },
+        };
+
+        match request {
+            Request::Run(job) => job(&mut inotify),
+            Request::Close(reply) => {
+                let _ = reply.send(close(inotify).await);
+                return;
+            }
+        }
+    }
+
+    deliver(inotify, pending, events).await
+}
+
+/// deliver events while they are received, once no handle is left
+async fn deliver(
+    mut inotify: INotify,
+    mut pending: Option<Event>,
+    events: mpsc::Sender<io::Result<Event>>,
+) {
+    loop {
+        if let Some(event) = pending.take() {
+            if events.send(Ok(event)).await.is_err() {
+                return;
+            }
+        }
+
+        tokio::select! {
+            _ = events.closed() => return,
+            event = inotify.watch() => match event {
+                Ok(event) => pending = Some(event),
+                Err(err) => {
+                    let _ = events.send(Err(err)).await;
+                    return;
+                }
+            },
+        }
+    }
+}
+
+/// close an instance whose read may be in flight
+///
+/// The read interrupted by the request owns the descriptor until it
+/// completes, removing every watch queues the IGNOREDs completing it.
+/// Without watches nothing completes it, the descriptor is left to close
+/// once it does.
+async fn close(mut inotify: INotify) -> io::Result<()> {
+    if inotify.watches().next().is_none() {
+        return Ok(());
+    }
+
+    inotify.rm_all()?;
+    inotify.close().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::scratch;
+    use std::time::Duration;
+
+    async fn next(events: &mut WatcherEvents) -> Option<Event> {
+        tokio::time::timeout(Duration::from_secs(10), events.next())
+            .await
+            .unwrap()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn requests_are_answered_between_events() {
+        let dir = scratch("actor");
+        let (handle, mut events) = INotify::new().unwrap().spawn(4);
+
+        let watch = handle.add(&dir, Mask::CREATE).await.unwrap();
+        assert_eq!(handle.path(watch).await.unwrap(), Some(dir.to_path_buf()));
+
+        std::fs::File::create(dir.join("f")).unwrap();
+        let event = next(&mut events).await.unwrap();
+        assert_eq!(event.watch, watch);
+        assert_eq!(event.mask, Mask::CREATE);
+        assert_eq!(event.path, PathBuf::from("f"));
+
+        // an unreceived event does not hold off requests
+        std::fs::File::create(dir.join("g")).unwrap();
+        let clone = handle.clone();
+        let watches = tokio::spawn(async move { clone.watches().await }).await.unwrap();
+        assert_eq!(watches.unwrap(), vec![(watch, dir.to_path_buf())]);
+        assert_eq!(next(&mut events).await.unwrap().path, PathBuf::from("g"));
+
+        handle.close().await.unwrap();
+        assert!(next(&mut events).await.is_none());
+
+        let err = handle.rm(watch).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::BrokenPipe);
+    }
+}
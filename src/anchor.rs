@@ -0,0 +1,211 @@
+use std::{
+    collections::HashMap,
+    ffi::CString,
+    io,
+    os::{
+        fd::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd},
+        unix::ffi::OsStrExt,
+    },
+    path::{Component, Path, PathBuf},
+};
+
+use crate::{sys, Event, INotify, Mask, Watch};
+
+/// A watch set anchored at a directory file descriptor
+///
+/// Paths are resolved through the descriptor (`openat` style) so the
+/// process's cwd and renames of the anchor directory have no effect, and
+/// event paths are reported relative to the anchor.
+pub struct Anchor {
+    dir: OwnedFd,
+    watches: HashMap<Watch, PathBuf>,
+}
+
+impl Anchor {
+    /// Anchor at a directory
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let dir = std::fs::File::open(path)?;
+        if !dir.metadata()?.is_dir() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "anchor is not a directory",
+            ));
+        }
+
+        Ok(Self::from_fd(dir.into()))
+    }
+
+    /// Anchor at an already opened directory descriptor
+    pub fn from_fd(dir: OwnedFd) -> Self {
+        Self {
+            dir,
+            watches: HashMap::new(),
+        }
+    }
+
+    /// Add a path relative to the anchor to be watched
+    ///
+    /// Absolute paths and paths escaping the anchor through `..` are
+    /// rejected, and so are symbolic links resolving outside of it
+    /// (`EXDEV`). The path is resolved with openat2 (linux 5.6).
+    pub fn add(&mut self, inotify: &mut INotify, path: &Path, mask: Mask) -> io::Result<Watch> {
+        let rel = normalize(path)?;
+        let beneath = self.open_beneath(&rel)?;
+
+        let through = PathBuf::from(format!("/proc/self/fd/{}", beneath.as_raw_fd()));
+        let watch = inotify.add(&through, mask)?;
+        self.watches.insert(watch, rel);
+
+        Ok(watch)
+    }
+
+    /// an O_PATH descriptor of `rel`, resolved without leaving the anchor
+    fn open_beneath(&self, rel: &Path) -> io::Result<OwnedFd> {
+        let rel = match rel.as_os_str().is_empty() {
+            true => Path::new("."),
+            false => rel,
+        };
+        let cpath = CString::new(rel.as_os_str().as_bytes())?;
+
+        let how = sys::OpenHow {
+            flags: (sys::O_PATH | sys::O_CLOEXEC) as u64,
+            mode: 0,
+            resolve: sys::RESOLVE_BENEATH,
+        };
+
+        let fd = unsafe { sys::openat2(self.dir.as_raw_fd(), cpath.as_ptr(), &how) };
+        if fd == -1 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(unsafe { OwnedFd::from_raw_fd(fd) })
+    }
+
+    /// Remove a watch added through this anchor
+    pub fn rm(&mut self, inotify: &mut INotify, watch: Watch) -> io::Result<()> {
+        self.watches.remove(&watch);
+        inotify.rm(watch)
+    }
+
+    /// The anchor relative path an event refers to
+    pub fn relative(&self, event: &Event) -> Option<PathBuf> {
+        let base = self.watches.get(&event.watch)?;
+
+        if event.path.as_os_str().is_empty() {
+            Some(base.clone())
+        } else {
+            Some(base.join(&event.path))
+        }
+    }
+}
+
+impl AsFd for Anchor {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.dir.as_fd()
+    }
+}
+
+fn normalize(path: &Path) -> io::Result<PathBuf> {
+    let mut rel = PathBuf::new();
+
+    for comp in path.components() {
+        match comp {
+            Component::CurDir => (),
+            Component::Normal(name) => rel.push(name),
+            Component::ParentDir if rel.pop() => (),
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "path escapes the anchor directory",
+                ))
+            }
+        }
+    }
+
+    Ok(rel)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::scratch;
+    use std::time::Duration;
+
+    #[test]
+    fn rejects_paths_climbing_out() {
+        let dir = scratch("anchor-parent");
+        std::fs::create_dir(dir.join("sub")).unwrap();
+
+        let mut inotify = INotify::new().unwrap();
+        let mut anchor = Anchor::open(&dir).unwrap();
+
+        for path in ["..", "sub/../..", "/etc"] {
+            let err = anchor
+                .add(&mut inotify, Path::new(path), Mask::CREATE)
+                .unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput, "{path}");
+        }
+
+        // climbing back down stays inside
+        anchor
+            .add(&mut inotify, Path::new("sub/../sub"), Mask::CREATE)
+            .unwrap();
+    }
+
+    #[test]
+    fn rejects_symlinks_escaping() {
+        let dir = scratch("anchor-symlink");
+        std::fs::create_dir_all(dir.join("anchor/sub")).unwrap();
+        std::fs::create_dir(dir.join("outside")).unwrap();
+        std::os::unix::fs::symlink(dir.join("outside"), dir.join("anchor/sub/abs")).unwrap();
+        std::os::unix::fs::symlink("../../outside", dir.join("anchor/sub/rel")).unwrap();
+        std::os::unix::fs::symlink(".", dir.join("anchor/sub/here")).unwrap();
+
+        let mut inotify = INotify::new().unwrap();
+        let mut anchor = Anchor::open(&dir.join("anchor")).unwrap();
+
+        for path in ["sub/abs", "sub/rel"] {
+            let err = anchor
+                .add(&mut inotify, Path::new(path), Mask::CREATE)
+                .unwrap_err();
+            assert_eq!(err.raw_os_error(), Some(18), "{path} is EXDEV");
+        }
+
+        // links staying inside are followed
+        anchor
+            .add(&mut inotify, Path::new("sub/here"), Mask::CREATE)
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn survives_renaming_the_anchor() {
+        let dir = scratch("anchor-rename");
+        std::fs::create_dir_all(dir.join("before/sub")).unwrap();
+
+        let mut inotify = INotify::new().unwrap();
+        let mut anchor = Anchor::open(&dir.join("before")).unwrap();
+        anchor
+            .add(&mut inotify, Path::new("sub"), Mask::CREATE)
+            .unwrap();
+
+        std::fs::rename(dir.join("before"), dir.join("after")).unwrap();
+
+        // both watches added before and after the rename report relative paths
+        anchor
+            .add(&mut inotify, Path::new(""), Mask::CREATE)
+            .unwrap();
+        std::fs::write(dir.join("after/sub/f"), "").unwrap();
+        std::fs::write(dir.join("after/g"), "").unwrap();
+
+        let mut seen = Vec::new();
+        for _ in 0..2 {
+            let event = tokio::time::timeout(Duration::from_secs(10), inotify.watch())
+                .await
+                .unwrap()
+                .unwrap();
+            seen.push(anchor.relative(&event).unwrap());
+        }
+        seen.sort();
+        assert_eq!(seen, [PathBuf::from("g"), PathBuf::from("sub/f")]);
+    }
+}
@@ -0,0 +1,287 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    io,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use tokio::time::Instant;
+
+use crate::{Event, INotify, Layer, Mask};
+
+/// The most paths kept as context on an [Anomaly]
+const CONTEXT: usize = 16;
+
+/// A pattern a [Detector] looks for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AnomalyKind {
+    /// Many entries deleted under one root
+    MassDeletion,
+
+    /// Many entries renamed or moved away under one root
+    RenameStorm,
+
+    /// Many events of any kind under one root
+    Burst,
+}
+
+/// A pattern found by a [Detector], with the events that made it up
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Anomaly {
+    /// The pattern found
+    pub kind: AnomalyKind,
+
+    /// The root the events happened under
+    pub root: PathBuf,
+
+    /// The events counted within the window
+    pub count: usize,
+
+    /// The window they were counted in
+    pub window: Duration,
+
+    /// The most recent paths involved, up to 16
+    pub paths: Vec<PathBuf>,
+}
+
+/// What a [Detector] hands out
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Detected {
+    /// An event passed through as read
+    Event(Event),
+
+    /// A pattern found, delivered before the event completing it
+    Anomaly(Anomaly),
+}
+
+type Hook = Box<dyn FnMut(Anomaly) + Send>;
+
+/// Flags bursts of deletes, renames or events under a root
+///
+/// Each pattern counts events in a sliding window per root, reaching the
+/// threshold reports an [Anomaly] and starts the count over. Roots are
+/// the ones given with [Detector::root] containing an event's path, or
+/// else the path of the event's watch. Meant for agents spotting
+/// ransomware or misbehaving processes.
+#[derive(Default)]
+pub struct Detector {
+    thresholds: Vec<(AnomalyKind, usize, Duration)>,
+    roots: Vec<PathBuf>,
+    windows: HashMap<(AnomalyKind, PathBuf), VecDeque<(Instant, PathBuf)>>,
+    ready: VecDeque<Detected>,
+    hook: Option<Hook>,
+}
+
+impl Detector {
+    /// Build a detector looking for nothing
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Flag `count` deletes within `within` under one root
+    pub fn mass_deletion(self, count: usize, within: Duration) -> Self {
+        self.threshold(AnomalyKind::MassDeletion, count, within)
+    }
+
+    /// Flag `count` renames away within `within` under one root
+    pub fn rename_storm(self, count: usize, within: Duration) -> Self {
+        self.threshold(AnomalyKind::RenameStorm, count, within)
+    }
+
+    /// Flag `count` events within `within` under one root
+    pub fn burst(self, count: usize, within: Duration) -> Self {
+        self.threshold(AnomalyKind::Burst, count, within)
+    }
+
+    /// Count events at or beneath `root` together, as one root
+    pub fn root(mut self, root: impl Into<PathBuf>) -> Self {
+        self.roots.push(root.into());
+        self
+    }
+
+    /// Call `hook` with every anomaly found when used as a [Layer]
+    pub fn on_anomaly(mut self, hook: impl FnMut(Anomaly) + Send + 'static) -> Self {
+        self.hook = Some(Box::new(hook));
+        self
+    }
+
+    fn threshold(mut self, kind: AnomalyKind, count: usize, within: Duration) -> Self {
+        self.thresholds.retain(|(k, _, _)| *k != kind);
+        self.thresholds.push((kind, count.max(1), within));
+        self
+    }
+
+    /// Wait for the next event or anomaly
+    pub async fn next(&mut self, inotify: &mut INotify) -> io::Result<Detected> {
+        if let Some(detected) = self.ready.pop_front() {
+            return Ok(detected);
+        }
+
+        let event = inotify.watch().await?;
+        for anomaly in self.observe(inotify, &event) {
+            self.ready.push_back(Detected::Anomaly(anomaly));
+        }
+        self.ready.push_back(Detected::Event(event));
+
+        Ok(self.ready.pop_front().expect("an event was queued"))
+    }
+
+    /// Count an event, returning the anomalies it completes
+    pub fn observe(&mut self, inotify: &INotify, event: &Event) -> Vec<Anomaly> {
+        let now = Instant::now();
+        let path = inotify.resolve(event).unwrap_or_else(|| event.path.clone());
+        let root = self.root_of(inotify, event, &path);
+
+        let mut found = Vec::new();
+        for &(kind, count, within) in &self.thresholds {
+            if !counts(kind, event.mask) {
+                continue;
+            }
+
+            let window = self.windows.entry((kind, root.clone())).or_default();
+            window.push_back((now, path.clone()));
+            while window
+                .front()
+                .is_some_and(|(at, _)| now.duration_since(*at) > within)
+            {
+                window.pop_front();
+            }
+
+            if window.len() >= count {
+                let seen = window.len();
+                let paths = window
+                    .drain(..)
+                    .rev()
+                    .take(CONTEXT)
+                    .map(|(_, path)| path)
+                    .collect();
+
+                found.push(Anomaly {
+                    kind,
+                    root: root.clone(),
+                    count: seen,
+                    window: within,
+                    paths,
+                });
+            }
+        }
+
+        found
+    }
+
+    fn root_of(&self, inotify: &INotify, event: &Event, path: &Path) -> PathBuf {
+        let configured = self
+            .roots
+            .iter()
+            .filter(|root| path.starts_with(root))
+            .max_by_key(|root| root.as_os_str().len());
+
+        match configured {
+            Some(root) => root.clone(),
+            None => inotify
+                .path(event.watch)
+                .map_or_else(PathBuf::new, Path::to_path_buf),
+        }
+    }
+}
+
+/// Passes events on, calling the [Detector::on_anomaly] hook for anomalies
+impl Layer for Detector {
+    fn event(&mut self, inotify: &mut INotify, event: Event, out: &mut Vec<Event>) -> io::Result<()> {
+        for anomaly in self.observe(inotify, &event) {
+            if let Some(hook) = &mut self.hook {
+                hook(anomaly);
+            }
+        }
+
+        out.push(event);
+        Ok(())
+    }
+}
+
+/// whether an event counts toward a pattern
+fn counts(kind: AnomalyKind, mask: Mask) -> bool {
+    match kind {
+        AnomalyKind::MassDeletion => mask.contains(Mask::DELETE),
+        AnomalyKind::RenameStorm => mask.contains(Mask::MOVED_FROM),
+        AnomalyKind::Burst => (mask & Mask::CONTROL).0 == 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::scratch;
+
+    async fn next(detector: &mut Detector, inotify: &mut INotify) -> Detected {
+        tokio::time::timeout(Duration::from_secs(10), detector.next(inotify))
+            .await
+            .unwrap()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn deletes_under_one_root_are_counted_together() {
+        let dir = scratch("anomaly");
+        std::fs::create_dir(dir.join("sub")).unwrap();
+        for name in ["a", "b", "sub/c"] {
+            std::fs::File::create(dir.join(name)).unwrap();
+        }
+
+        let mut inotify = INotify::new().unwrap();
+        inotify.add(&dir, Mask::DELETE).unwrap();
+        inotify.add(&dir.join("sub"), Mask::DELETE).unwrap();
+        let mut detector = Detector::new()
+            .mass_deletion(3, Duration::from_secs(60))
+            .rename_storm(1, Duration::from_secs(60))
+            .root(&*dir);
+
+        for name in ["a", "b", "sub/c"] {
+            std::fs::remove_file(dir.join(name)).unwrap();
+        }
+
+        for name in ["a", "b"] {
+            let Detected::Event(event) = next(&mut detector, &mut inotify).await else {
+                panic!("two deletes are not an anomaly");
+            };
+            assert_eq!(event.path, PathBuf::from(name));
+        }
+
+        let Detected::Anomaly(anomaly) = next(&mut detector, &mut inotify).await else {
+            panic!("the third delete completes a mass deletion");
+        };
+        assert_eq!(anomaly.kind, AnomalyKind::MassDeletion);
+        assert_eq!(anomaly.root, dir.to_path_buf());
+        assert_eq!(anomaly.count, 3);
+        assert_eq!(
+            anomaly.paths,
+            vec![dir.join("sub/c"), dir.join("b"), dir.join("a")]
+        );
+
+        let Detected::Event(event) = next(&mut detector, &mut inotify).await else {
+            panic!("the event follows its anomaly");
+        };
+        assert_eq!(event.path, PathBuf::from("c"));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn deletes_outside_the_window_are_forgotten() {
+        let dir = scratch("anomaly-window");
+        std::fs::File::create(dir.join("a")).unwrap();
+        std::fs::File::create(dir.join("b")).unwrap();
+
+        let mut inotify = INotify::new().unwrap();
+        inotify.add(&dir, Mask::DELETE).unwrap();
+        let mut detector = Detector::new().mass_deletion(2, Duration::from_secs(1));
+
+        std::fs::remove_file(dir.join("a")).unwrap();
+        std::fs::remove_file(dir.join("b")).unwrap();
+
+        let first = inotify.watch().await.unwrap();
+        assert!(detector.observe(&inotify, &first).is_empty());
+
+        tokio::time::advance(Duration::from_secs(2)).await;
+        let second = inotify.watch().await.unwrap();
+        assert!(detector.observe(&inotify, &second).is_empty());
+    }
+}
@@ -0,0 +1,63 @@
+use std::{io, path::Path};
+
+use async_io::Async;
+
+use crate::{Events, Mask, RawEvent, RawINotify, Watch, READ_SIZE};
+
+/// An executor agnostic inotify instance, for async-std, smol and others
+///
+/// Built on the [RawINotify] core and `async-io`'s reactor, it yields the
+/// same [RawEvent]s as [crate::INotify::watch_raw] without a tokio runtime.
+pub struct AsyncINotify {
+    inner: Async<RawINotify>,
+    buf: Vec<u8>,
+    pos: usize,
+    end: usize,
+}
+
+impl AsyncINotify {
+    /// Build a new instance
+    pub fn new() -> io::Result<Self> {
+        Ok(Self {
+            inner: Async::new(RawINotify::nonblocking()?)?,
+            buf: vec![0; READ_SIZE],
+            pos: 0,
+            end: 0,
+        })
+    }
+
+    /// Add a file (, or directory) to be watched
+    pub fn add(&self, path: &Path, mask: Mask) -> io::Result<Watch> {
+        self.inner.get_ref().add(path, mask)
+    }
+
+    /// remove a watch
+    pub fn rm(&self, watch: Watch) -> io::Result<()> {
+        self.inner.get_ref().rm(watch)
+    }
+
+    /// wait for the next event
+    ///
+    /// cancel safe, events read from the kernel are kept until returned
+    pub async fn watch_raw(&mut self) -> io::Result<RawEvent> {
+        while self.pos >= self.end {
+            let buf = &mut self.buf;
+            self.end = self.inner.read_with(|core| core.read(buf)).await?;
+            self.pos = 0;
+        }
+
+        let mut events = Events::new(&self.buf[self.pos..self.end]);
+
+        match events.next() {
+            Some(Ok(event)) => {
+                self.pos = self.end - events.remaining();
+                Ok(event)
+            }
+            Some(Err(err)) => {
+                self.pos = self.end;
+                Err(err.into())
+            }
+            None => unreachable!("the buffer holds at least one event"),
+        }
+    }
+}
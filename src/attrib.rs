@@ -0,0 +1,284 @@
+use std::{
+    collections::HashMap,
+    io,
+    os::unix::fs::MetadataExt,
+    path::{Path, PathBuf},
+};
+
+#[cfg(feature = "xattr")]
+use std::{collections::BTreeMap, ffi::OsString};
+
+use crate::{Event, INotify, Mask};
+
+/// Reports what actually changed when an ATTRIB event arrives
+///
+/// Paths are stat'ed when tracked and again on every ATTRIB so the
+/// difference can be reported.
+#[derive(Default)]
+pub struct Attrib {
+    snapshots: HashMap<PathBuf, Snapshot>,
+}
+
+/// An old and new value pair
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Delta<T> {
+    /// The value before the change
+    pub old: T,
+
+    /// The value after the change
+    pub new: T,
+}
+
+/// The attributes that changed on a path
+#[derive(Debug, Default)]
+pub struct AttribChange {
+    /// The path that changed
+    pub path: PathBuf,
+
+    /// Permission bits changed
+    pub mode: Option<Delta<u32>>,
+
+    /// Owning user changed
+    pub uid: Option<Delta<u32>>,
+
+    /// Owning group changed
+    pub gid: Option<Delta<u32>>,
+
+    /// Access time changed (seconds, nanoseconds)
+    pub atime: Option<Delta<(i64, i64)>>,
+
+    /// Modification time changed (seconds, nanoseconds)
+    pub mtime: Option<Delta<(i64, i64)>>,
+
+    /// Link count changed
+    pub nlink: Option<Delta<u64>>,
+
+    /// Extended attributes that were added, removed or rewritten
+    #[cfg(feature = "xattr")]
+    pub xattrs: Vec<OsString>,
+}
+
+#[derive(Clone)]
+struct Snapshot {
+    mode: u32,
+    uid: u32,
+    gid: u32,
+    atime: (i64, i64),
+    mtime: (i64, i64),
+    nlink: u64,
+
+    #[cfg(feature = "xattr")]
+    xattrs: BTreeMap<OsString, Vec<u8>>,
+}
+
+impl Attrib {
+    /// Build a new Attrib tracker
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record the current attributes of a path
+    pub async fn track(&mut self, path: &Path) -> io::Result<()> {
+        let snapshot = Snapshot::take(path).await?;
+        self.snapshots.insert(path.to_path_buf(), snapshot);
+
+        Ok(())
+    }
+
+    /// Stop tracking a path
+    pub fn untrack(&mut self, path: &Path) {
+        self.snapshots.remove(path);
+    }
+
+    /// Report what changed for an ATTRIB event
+    ///
+    /// Returns `None` for events other than ATTRIB or for paths that were
+    /// never tracked, the latter are tracked from this point on. A path
+    /// already removed reports nothing and is no longer tracked.
+    pub async fn change(
+        &mut self,
+        inotify: &INotify,
+        event: &Event,
+    ) -> io::Result<Option<AttribChange>> {
+        if !event.mask.contains(Mask::ATTRIB) {
+            return Ok(None);
+        }
+
+        let Some(path) = inotify.resolve(event) else {
+            return Ok(None);
+        };
+
+        let new = match Snapshot::take(&path).await {
+            Ok(new) => new,
+            // unlinked right after, its removal follows
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                self.snapshots.remove(&path);
+                return Ok(None);
+            }
+            Err(err) => return Err(err),
+        };
+        let Some(old) = self.snapshots.insert(path.clone(), new.clone()) else {
+            return Ok(None);
+        };
+
+        Ok(Some(old.diff(&new, path)))
+    }
+}
+
+fn delta<T: PartialEq + Copy>(old: T, new: T) -> Option<Delta<T>> {
+    if old == new {
+        None
+    } else {
+        Some(Delta { old, new })
+    }
+}
+
+impl Snapshot {
+    async fn take(path: &Path) -> io::Result<Self> {
+        let meta = tokio::fs::symlink_metadata(path).await?;
+
+        Ok(Snapshot {
+            mode: meta.mode(),
+            uid: meta.uid(),
+            gid: meta.gid(),
+            atime: (meta.atime(), meta.atime_nsec()),
+            mtime: (meta.mtime(), meta.mtime_nsec()),
+            nlink: meta.nlink(),
+
+            #[cfg(feature = "xattr")]
+            xattrs: xattr::read(path.to_path_buf()).await?,
+        })
+    }
+
+    fn diff(&self, new: &Snapshot, path: PathBuf) -> AttribChange {
+        AttribChange {
+            path,
+            mode: delta(self.mode, new.mode),
+            uid: delta(self.uid, new.uid),
+            gid: delta(self.gid, new.gid),
+            atime: delta(self.atime, new.atime),
+            mtime: delta(self.mtime, new.mtime),
+            nlink: delta(self.nlink, new.nlink),
+
+            #[cfg(feature = "xattr")]
+            xattrs: xattr::diff(&self.xattrs, &new.xattrs),
+        }
+    }
+}
+
+#[cfg(feature = "xattr")]
+mod xattr {
+    use std::{
+        collections::BTreeMap,
+        ffi::{CString, OsStr, OsString},
+        io,
+        os::unix::ffi::{OsStrExt, OsStringExt},
+        path::PathBuf,
+    };
+
+    use crate::sys;
+
+    pub(super) async fn read(path: PathBuf) -> io::Result<BTreeMap<OsString, Vec<u8>>> {
+        tokio::task::spawn_blocking(move || read_blocking(path))
+            .await
+            .map_err(io::Error::other)?
+    }
+
+    fn read_blocking(path: PathBuf) -> io::Result<BTreeMap<OsString, Vec<u8>>> {
+        let cpath = CString::new(path.into_os_string().into_vec())?;
+        let mut attrs = BTreeMap::new();
+
+        let names = fill(|buf, len| unsafe { sys::llistxattr(cpath.as_ptr(), buf, len) })?;
+
+        for name in names.split(|b| *b == 0).filter(|n| !n.is_empty()) {
+            let cname = CString::new(name)?;
+            let value = fill(|buf, len| unsafe {
+                sys::lgetxattr(cpath.as_ptr(), cname.as_ptr(), buf.cast(), len)
+            })?;
+
+            attrs.insert(OsStr::from_bytes(name).to_os_string(), value);
+        }
+
+        Ok(attrs)
+    }
+
+    fn fill(f: impl Fn(*mut std::ffi::c_char, usize) -> isize) -> io::Result<Vec<u8>> {
+        loop {
+            let size = f(std::ptr::null_mut(), 0);
+            if size == -1 {
+                return Err(io::Error::last_os_error());
+            }
+
+            let mut buf = vec![0u8; size as usize];
+            let res = f(buf.as_mut_ptr().cast(), buf.len());
+            if res == -1 {
+                let err = io::Error::last_os_error();
+                if err.raw_os_error() == Some(sys::ERANGE) {
+                    continue;
+                }
+
+                return Err(err);
+            }
+
+            buf.truncate(res as usize);
+            return Ok(buf);
+        }
+    }
+
+    pub(super) fn diff(
+        old: &BTreeMap<OsString, Vec<u8>>,
+        new: &BTreeMap<OsString, Vec<u8>>,
+    ) -> Vec<OsString> {
+        let mut changed = Vec::new();
+
+        for (name, value) in old {
+            if new.get(name) != Some(value) {
+                changed.push(name.clone());
+            }
+        }
+
+        for name in new.keys() {
+            if !old.contains_key(name) {
+                changed.push(name.clone());
+            }
+        }
+
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::scratch;
+    use std::os::unix::fs::PermissionsExt;
+
+    #[tokio::test]
+    async fn reports_changes_and_forgets_removed_files() {
+        let dir = scratch("attrib");
+        let file = dir.join("f");
+        std::fs::write(&file, "").unwrap();
+        std::fs::set_permissions(&file, std::fs::Permissions::from_mode(0o600)).unwrap();
+
+        let mut inotify = INotify::new().unwrap();
+        let watch = inotify.add(&dir, Mask::ATTRIB).unwrap();
+        let attrib = Event::new(watch, Mask::ATTRIB, "f");
+
+        // untracked paths are tracked from their first ATTRIB
+        let mut attribs = Attrib::new();
+        assert!(attribs.change(&inotify, &attrib).await.unwrap().is_none());
+
+        std::fs::set_permissions(&file, std::fs::Permissions::from_mode(0o644)).unwrap();
+        let change = attribs.change(&inotify, &attrib).await.unwrap().unwrap();
+        assert_eq!(change.path, file);
+        assert_eq!(change.mode.unwrap().old & 0o777, 0o600);
+        assert_eq!(change.mode.unwrap().new & 0o777, 0o644);
+        assert!(change.uid.is_none());
+        assert!(change.nlink.is_none());
+
+        // unlinked between the event and the look
+        std::fs::remove_file(&file).unwrap();
+        assert!(attribs.change(&inotify, &attrib).await.unwrap().is_none());
+        assert!(attribs.snapshots.is_empty());
+    }
+}
@@ -0,0 +1,160 @@
+//! Event generators for benchmarks, see `benches/`
+//!
+//! [Churn] drives real file system activity in a scratch directory, either
+//! in bursts or paced at a rate, [frames] builds kernel reads without
+//! touching the file system.
+
+use std::{
+    fs::OpenOptions,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+use crate::{parse, Mask};
+
+/// Creates, appends to and deletes files in a scratch directory
+///
+/// Every file cycles through being created, appended to and deleted, one
+/// step at a time across all files. Watched with [Churn::MASK] each step
+/// causes exactly one event. The directory is removed when dropped.
+pub struct Churn {
+    dir: PathBuf,
+    files: usize,
+    rate: Option<u32>,
+    step: usize,
+}
+
+impl Churn {
+    /// The events of which each step causes exactly one
+    pub const MASK: Mask = Mask(Mask::CREATE.0 | Mask::MODIFY.0 | Mask::DELETE.0);
+
+    /// Churn in a fresh directory under the system temp directory
+    pub fn new() -> io::Result<Self> {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+
+        let dir = std::env::temp_dir().join(format!(
+            "tokinotify-churn-{}-{}",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::Relaxed)
+        ));
+        std::fs::create_dir(&dir)?;
+
+        Ok(Self {
+            dir,
+            files: 64,
+            rate: None,
+            step: 0,
+        })
+    }
+
+    /// Cycle through `files` files (64 by default)
+    pub fn files(mut self, files: usize) -> Self {
+        self.files = files.max(1);
+        self
+    }
+
+    /// Pace [Churn::run] at `per_sec` steps a second, unpaced by default
+    pub fn rate(mut self, per_sec: u32) -> Self {
+        self.rate = Some(per_sec.max(1));
+        self
+    }
+
+    /// the directory churned
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Take one step
+    pub fn step(&mut self) -> io::Result<()> {
+        let path = self.dir.join(format!("f{}", self.step % self.files));
+        let phase = self.step / self.files % 3;
+        self.step += 1;
+
+        match phase {
+            0 => OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(path)
+                .map(drop),
+            1 => OpenOptions::new()
+                .append(true)
+                .open(path)?
+                .write_all(b"churn"),
+            _ => std::fs::remove_file(path),
+        }
+    }
+
+    /// Take `steps` steps as fast as possible
+    pub fn burst(&mut self, steps: usize) -> io::Result<()> {
+        for _ in 0..steps {
+            self.step()?;
+        }
+
+        Ok(())
+    }
+
+    /// Take `steps` steps on a thread of their own, at the rate if one is set
+    pub fn run(mut self, steps: usize) -> JoinHandle<io::Result<Self>> {
+        std::thread::spawn(move || {
+            let interval = self
+                .rate
+                .map(|rate| Duration::from_secs(1) / rate)
+                .unwrap_or_default();
+            let start = Instant::now();
+
+            for n in 0..steps {
+                if let Some(wait) =
+                    (start + interval * n as u32).checked_duration_since(Instant::now())
+                {
+                    std::thread::sleep(wait);
+                }
+                self.step()?;
+            }
+
+            Ok(self)
+        })
+    }
+}
+
+impl Drop for Churn {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// A kernel read of `count` events, each named with `name_len` bytes
+///
+/// Events alternate between CREATE and DELETE on watch 1, names are
+/// padded the way the kernel pads them.
+pub fn frames(count: usize, name_len: usize) -> Vec<u8> {
+    let len = if name_len == 0 {
+        0
+    } else {
+        (name_len + 1).next_multiple_of(parse::HEADER_SIZE)
+    };
+
+    let mut buf = Vec::with_capacity(count * (parse::HEADER_SIZE + len));
+    for n in 0..count {
+        let mask = if n % 2 == 0 {
+            Mask::CREATE
+        } else {
+            Mask::DELETE
+        };
+
+        buf.extend_from_slice(&1i32.to_ne_bytes());
+        buf.extend_from_slice(&mask.0.to_ne_bytes());
+        buf.extend_from_slice(&0u32.to_ne_bytes());
+        buf.extend_from_slice(&(len as u32).to_ne_bytes());
+
+        let name = buf.len();
+        buf.resize(name + len, 0);
+        for (i, b) in buf[name..name + name_len].iter_mut().enumerate() {
+            *b = b'a' + ((n + i) % 26) as u8;
+        }
+    }
+
+    buf
+}
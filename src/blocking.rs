@@ -0,0 +1,234 @@
+//! A synchronous variant for programs without an executor
+//!
+//! Shares the parser and the [Mask] and [Event] types with the async
+//! adapters, but blocks the calling thread while waiting for events.
+
+use std::{
+    collections::HashMap,
+    io,
+    os::fd::{AsFd, AsRawFd, BorrowedFd, RawFd},
+    path::Path,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use crate::{sys, Event, Events, Mask, RawEvent, RawINotify, Watch, READ_SIZE};
+
+/// Watch filesystem changes, blocking the calling thread
+///
+/// Events carry the name reported by the kernel, enrichment such as
+/// identities or removal reasons is only offered by [crate::INotify].
+pub struct INotify {
+    core: RawINotify,
+    /// the path each watch was added with and its events of interest
+    paths: HashMap<Watch, (Arc<Path>, Mask)>,
+    buf: Vec<u8>,
+    pos: usize,
+    end: usize,
+}
+
+impl INotify {
+    /// Build a new INotify
+    pub fn new() -> io::Result<Self> {
+        Ok(Self {
+            core: RawINotify::new()?,
+            paths: HashMap::new(),
+            buf: vec![0; READ_SIZE],
+            pos: 0,
+            end: 0,
+        })
+    }
+
+    /// Add a file (, or directory) to be watched
+    pub fn add(&mut self, path: &Path, mask: Mask) -> io::Result<Watch> {
+        let watch = self.core.add(path, mask)?;
+        let interest = match self.paths.get(&watch) {
+            Some((_, prev)) if mask.contains(Mask::MASK_ADD) => {
+                Mask((prev.0 | mask.0) & Mask::INTEREST.0)
+            }
+            _ => Mask(mask.0 & Mask::INTEREST.0),
+        };
+        self.paths.insert(watch, (path.into(), interest));
+
+        Ok(watch)
+    }
+
+    /// remove a watch from this INotify
+    pub fn rm(&mut self, watch: Watch) -> io::Result<()> {
+        self.paths.remove(&watch);
+        self.core.rm(watch)
+    }
+
+    /// the path a watch was added with
+    pub fn path(&self, watch: Watch) -> Option<&Path> {
+        self.paths.get(&watch).map(|(path, _)| path.as_ref())
+    }
+
+    /// wait for the next event
+    pub fn next_event(&mut self) -> io::Result<Event> {
+        while self.pos >= self.end {
+            self.end = self.core.read(&mut self.buf)?;
+            self.pos = 0;
+        }
+
+        self.take()
+    }
+
+    /// wait up to `timeout` for the next event, `None` when none arrived
+    pub fn next_event_timeout(&mut self, timeout: Duration) -> io::Result<Option<Event>> {
+        let deadline = Instant::now() + timeout;
+
+        while self.pos >= self.end {
+            let left = deadline.saturating_duration_since(Instant::now());
+            if !self.readable(left)? {
+                if left.is_zero() {
+                    return Ok(None);
+                }
+
+                continue;
+            }
+
+            self.end = self.core.read(&mut self.buf)?;
+            self.pos = 0;
+        }
+
+        self.take().map(Some)
+    }
+
+    /// wait for the fd to become readable, false on timeout or a signal
+    fn readable(&self, timeout: Duration) -> io::Result<bool> {
+        let mut fd = sys::pollfd {
+            fd: self.core.as_raw_fd(),
+            events: sys::POLLIN,
+            revents: 0,
+        };
+
+        // round up so a short timeout still waits rather than spinning
+        let ms = timeout.as_nanos().div_ceil(1_000_000).min(i32::MAX as u128) as i32;
+
+        match unsafe { sys::poll(&mut fd, 1, ms) } {
+            -1 => {
+                let err = io::Error::last_os_error();
+                match err.raw_os_error() {
+                    // woken by a signal, the caller retries with what is left
+                    Some(sys::EINTR) => Ok(false),
+                    _ => Err(err),
+                }
+            }
+            0 => Ok(false),
+            _ => Ok(true),
+        }
+    }
+
+    fn take(&mut self) -> io::Result<Event> {
+        let mut events = Events::new(&self.buf[self.pos..self.end]);
+
+        let raw = match events.next() {
+            Some(Ok(raw)) => raw,
+            Some(Err(err)) => {
+                self.pos = self.end;
+                return Err(err.into());
+            }
+            None => unreachable!("the buffer holds at least one event"),
+        };
+        self.pos = self.end - events.remaining();
+
+        let origin = if raw.mask.contains(Mask::IGNORED) {
+            self.paths.remove(&raw.watch);
+            None
+        } else {
+            self.paths.get(&raw.watch).cloned()
+        };
+
+        Ok(event(raw, origin))
+    }
+}
+
+fn event(raw: RawEvent, origin: Option<(Arc<Path>, Mask)>) -> Event {
+    let (root, watch_mask) = origin.unzip();
+    Event {
+        watch: raw.watch,
+        mask: raw.mask,
+        cookie: raw.cookie,
+        path: raw.name.to_path_buf(),
+        identity: None,
+        link: None,
+        removal: None,
+        synthetic: false,
+        stale: false,
+        root,
+        watch_mask,
+    }
+}
+
+impl AsRawFd for INotify {
+    fn as_raw_fd(&self) -> RawFd {
+        self.core.as_raw_fd()
+    }
+}
+
+impl AsFd for INotify {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.core.as_fd()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::scratch;
+
+    #[test]
+    fn reads_events_without_a_runtime() {
+        let dir = scratch("blocking");
+
+        let mut inotify = INotify::new().unwrap();
+        let watch = inotify.add(&dir, Mask::CREATE).unwrap();
+        assert_eq!(inotify.path(watch), Some(&*dir));
+
+        let quiet = inotify
+            .next_event_timeout(Duration::from_millis(10))
+            .unwrap();
+        assert!(quiet.is_none());
+
+        std::fs::write(dir.join("a"), "").unwrap();
+        std::fs::write(dir.join("b"), "").unwrap();
+
+        let event = inotify.next_event().unwrap();
+        assert_eq!(event.watch, watch);
+        assert_eq!(event.mask, Mask::CREATE);
+        assert_eq!(event.path, Path::new("a"));
+        assert_eq!(event.root.as_deref(), Some(&*dir));
+        assert_eq!(event.watch_mask, Some(Mask::CREATE));
+
+        // the second was read along with the first
+        let event = inotify
+            .next_event_timeout(Duration::from_secs(10))
+            .unwrap()
+            .unwrap();
+        assert_eq!(event.path, Path::new("b"));
+
+        inotify.rm(watch).unwrap();
+        let event = inotify.next_event().unwrap();
+        assert_eq!(event.mask, Mask::IGNORED);
+        assert_eq!(inotify.path(watch), None);
+    }
+
+    #[test]
+    fn merges_masks_added_to() {
+        let dir = scratch("blocking-mask-add");
+
+        let mut inotify = INotify::new().unwrap();
+        let watch = inotify.add(&dir, Mask::CREATE).unwrap();
+        inotify.add(&dir, Mask::DELETE | Mask::MASK_ADD).unwrap();
+
+        std::fs::write(dir.join("f"), "").unwrap();
+        std::fs::remove_file(dir.join("f")).unwrap();
+
+        inotify.next_event().unwrap();
+        let event = inotify.next_event().unwrap();
+        assert_eq!(event.mask, Mask::DELETE);
+        assert_eq!(event.watch_mask, Some(Mask::CREATE | Mask::DELETE));
+        assert_eq!(inotify.path(watch), Some(&*dir));
+    }
+}
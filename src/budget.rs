@@ -0,0 +1,240 @@
+use std::{
+    io,
+    path::Path,
+    sync::{Arc, Mutex, MutexGuard, OnceLock},
+};
+
+use crate::{capabilities::limit, sys, INotify, Watch};
+
+/// Watches shared out between the components of a process
+///
+/// The kernel limits watches per user (`fs.inotify.max_user_watches`),
+/// one component watching a large tree can leave none for another. Each
+/// component takes a [Quota] and hands it to its [INotify], watches
+/// reserved by a quota are held back from everyone else, watches beyond
+/// the reservation come from a pool shared by all quotas. Clones share
+/// the same budget. Using a budget is opt-in, instances without a quota
+/// are not counted.
+#[derive(Debug, Clone)]
+pub struct WatchBudget {
+    state: Arc<Mutex<State>>,
+}
+
+/// A component's share of a [WatchBudget], see [INotify::set_quota]
+///
+/// Clones share the same quota. The reservation returns to the budget
+/// once every clone is dropped.
+#[derive(Debug, Clone)]
+pub struct Quota {
+    inner: Arc<QuotaInner>,
+}
+
+#[derive(Debug)]
+struct State {
+    limit: usize,
+    reserved: usize,
+    pooled: usize,
+}
+
+#[derive(Debug)]
+struct QuotaInner {
+    state: Arc<Mutex<State>>,
+    reserved: usize,
+    used: Mutex<usize>,
+}
+
+impl WatchBudget {
+    /// Share out `limit` watches
+    pub fn new(limit: usize) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(State {
+                limit,
+                reserved: 0,
+                pooled: 0,
+            })),
+        }
+    }
+
+    /// The budget of the process, sized from `fs.inotify.max_user_watches`
+    ///
+    /// Watches of other processes of the same user are not known, leave
+    /// room for them when reserving.
+    pub fn global() -> &'static WatchBudget {
+        static GLOBAL: OnceLock<WatchBudget> = OnceLock::new();
+        GLOBAL.get_or_init(|| WatchBudget::new(limit("max_user_watches").unwrap_or(usize::MAX)))
+    }
+
+    /// Take a quota with `reserved` watches held back for it alone
+    ///
+    /// Fails with ENOSPC when fewer watches are left unreserved and unused.
+    pub fn reserve(&self, reserved: usize) -> io::Result<Quota> {
+        let mut state = lock(&self.state);
+        if state.unclaimed() < reserved {
+            return Err(io::Error::from_raw_os_error(sys::ENOSPC));
+        }
+        state.reserved += reserved;
+
+        Ok(Quota {
+            inner: Arc::new(QuotaInner {
+                state: self.state.clone(),
+                reserved,
+                used: Mutex::new(0),
+            }),
+        })
+    }
+
+    /// Take a quota drawing only from the shared pool
+    pub fn shared(&self) -> Quota {
+        self.reserve(0).expect("reserving nothing always succeeds")
+    }
+
+    /// The watches neither reserved nor drawn from the pool
+    pub fn unclaimed(&self) -> usize {
+        lock(&self.state).unclaimed()
+    }
+}
+
+impl Quota {
+    /// The watches held back for this quota
+    pub fn reserved(&self) -> usize {
+        self.inner.reserved
+    }
+
+    /// The watches counted against this quota
+    pub fn used(&self) -> usize {
+        *lock(&self.inner.used)
+    }
+
+    /// The watches this quota may still add, its own and the pool's
+    pub fn available(&self) -> usize {
+        let state = lock(&self.inner.state);
+        let used = *lock(&self.inner.used);
+        self.inner.reserved.saturating_sub(used) + state.unclaimed()
+    }
+
+    /// count a watch, drawing from the pool past the reservation
+    ///
+    /// The kernel already handed the watch out, so the pool may be overdrawn.
+    fn charge(&self) {
+        let mut state = lock(&self.inner.state);
+        let mut used = lock(&self.inner.used);
+
+        if *used >= self.inner.reserved {
+            state.pooled += 1;
+        }
+        *used += 1;
+    }
+
+    /// count a watch if there is room for it, as one step so concurrent adds can't overdraw
+    pub(crate) fn claim(&self) -> bool {
+        let mut state = lock(&self.inner.state);
+        let mut used = lock(&self.inner.used);
+
+        if *used >= self.inner.reserved {
+            if state.unclaimed() == 0 {
+                return false;
+            }
+            state.pooled += 1;
+        }
+        *used += 1;
+
+        true
+    }
+
+    pub(crate) fn refund(&self) {
+        let mut state = lock(&self.inner.state);
+        let mut used = lock(&self.inner.used);
+
+        *used = used.saturating_sub(1);
+        if *used >= self.inner.reserved {
+            state.pooled = state.pooled.saturating_sub(1);
+        }
+    }
+}
+
+impl Drop for QuotaInner {
+    fn drop(&mut self) {
+        let mut state = lock(&self.state);
+        let used = *lock(&self.used);
+
+        state.reserved -= self.reserved;
+        state.pooled = state
+            .pooled
+            .saturating_sub(used.saturating_sub(self.reserved));
+    }
+}
+
+impl State {
+    fn unclaimed(&self) -> usize {
+        self.limit.saturating_sub(self.reserved + self.pooled)
+    }
+}
+
+impl INotify {
+    /// Count the watches of this instance against `quota`
+    ///
+    /// [INotify::add] and [INotify::add_tree] fail with ENOSPC once the
+    /// quota is used up, and [INotify::add_tree_within] stops there.
+    /// Watches added before are counted too. A previous quota gets its
+    /// watches back.
+    pub fn set_quota(&mut self, quota: Quota) {
+        let watches = self.paths.len() + self.owned_waits();
+        if let Some(previous) = self.quota.take() {
+            (0..watches).for_each(|_| previous.refund());
+        }
+
+        (0..watches).for_each(|_| quota.charge());
+        self.quota = Some(quota);
+        self.publish_admission();
+    }
+
+    /// refuse a new watch the quota has no room for
+    pub(crate) fn admit(&self, path: &Path) -> io::Result<()> {
+        let Some(quota) = &self.quota else {
+            return Ok(());
+        };
+
+        let watched = self.paths.values().any(|p| p.as_ref() == path);
+        if !watched && quota.available() == 0 {
+            return Err(io::Error::from_raw_os_error(sys::ENOSPC));
+        }
+
+        Ok(())
+    }
+
+    /// count a watch being registered, unless it is registered already
+    pub(crate) fn charge(&mut self, watch: Watch) {
+        if let Some(quota) = &self.quota {
+            if !self.paths.contains_key(&watch) {
+                quota.charge();
+            }
+        }
+    }
+
+    /// count a parent watched only for [INotify::add_or_wait]
+    pub(crate) fn charge_wait(&self) {
+        if let Some(quota) = &self.quota {
+            quota.charge();
+        }
+    }
+
+    /// give back a parent watched only for [INotify::add_or_wait]
+    pub(crate) fn refund_wait(&self) {
+        if let Some(quota) = &self.quota {
+            quota.refund();
+        }
+    }
+
+    /// give back a watch being forgotten
+    pub(crate) fn refund(&mut self, watch: Watch) {
+        if let Some(quota) = &self.quota {
+            if self.paths.contains_key(&watch) {
+                quota.refund();
+            }
+        }
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
@@ -0,0 +1,213 @@
+use std::{
+    ffi::c_int,
+    io,
+    path::{Component, Path, PathBuf},
+    sync::Arc,
+};
+
+use crate::{Event, Identity, LinkRole, Mask, Removal, Watch};
+
+/// Events about the watch itself, the kernel never names an entry on them
+const SELF: Mask = Mask(
+    Mask::DELETE_SELF.0
+        | Mask::MOVE_SELF.0
+        | Mask::UNMOUNT.0
+        | Mask::Q_OVERFLOW.0
+        | Mask::IGNORED.0,
+);
+
+impl Watch {
+    /// a watch from a raw watch descriptor, for events built by hand
+    pub fn from_raw(wd: c_int) -> Watch {
+        Watch { wd }
+    }
+
+    /// the raw watch descriptor
+    pub fn as_raw(self) -> c_int {
+        self.wd
+    }
+}
+
+/// Builds an [Event] outside the kernel path, e.g. for mocks or polling backends
+///
+/// Built events are synthetic unless [EventBuilder::synthetic] says otherwise.
+#[derive(Debug, Clone)]
+pub struct EventBuilder {
+    event: Event,
+}
+
+impl Event {
+    /// an event as the kernel would report it, e.g. the expected value in an assertion
+    ///
+    /// Unlike [Event::builder] nothing is checked.
+    pub fn new(watch: Watch, mask: Mask, path: impl Into<PathBuf>) -> Event {
+        Event {
+            synthetic: false,
+            stale: false,
+            path: path.into(),
+            ..Event::builder(watch, mask).event
+        }
+    }
+
+    /// start building an event for `watch`
+    pub fn builder(watch: Watch, mask: Mask) -> EventBuilder {
+        EventBuilder {
+            event: Event {
+                watch,
+                mask,
+                cookie: 0,
+                path: PathBuf::new(),
+                identity: None,
+                link: None,
+                removal: None,
+                synthetic: true,
+                stale: false,
+                root: None,
+                watch_mask: None,
+            },
+        }
+    }
+}
+
+impl EventBuilder {
+    /// the cookie pairing a MOVED_FROM with its MOVED_TO
+    pub fn cookie(mut self, cookie: u32) -> Self {
+        self.event.cookie = cookie;
+        self
+    }
+
+    /// the entry the event is about, a bare name or an absolute path
+    pub fn path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.event.path = path.into();
+        self
+    }
+
+    /// the path the originating watch was added with
+    pub fn root(mut self, root: impl AsRef<Path>) -> Self {
+        self.event.root = Some(Arc::from(root.as_ref()));
+        self
+    }
+
+    /// the events the originating watch was registered for
+    pub fn watch_mask(mut self, mask: Mask) -> Self {
+        self.event.watch_mask = Some(mask);
+        self
+    }
+
+    /// the identity of the file the event is about
+    pub fn identity(mut self, identity: Identity) -> Self {
+        self.event.identity = Some(identity);
+        self
+    }
+
+    /// the watch's role in following a symlink
+    pub fn link_role(mut self, role: LinkRole) -> Self {
+        self.event.link = Some(role);
+        self
+    }
+
+    /// why the watch ended, only for IGNORED
+    pub fn removal(mut self, removal: Removal) -> Self {
+        self.event.removal = Some(removal);
+        self
+    }
+
+    /// whether the event is reported as produced by the library
+    pub fn synthetic(mut self, synthetic: bool) -> Self {
+        self.event.synthetic = synthetic;
+        self
+    }
+
+    /// check the fields agree with each other and build the event
+    pub fn build(self) -> io::Result<Event> {
+        let event = self.event;
+        let mask = event.mask;
+
+        if mask.0 & !Mask::REPORTED.0 != 0 {
+            return Err(invalid("mask has bits the kernel never reports"));
+        }
+        if mask.0 & !Mask::ISDIR.0 == 0 {
+            return Err(invalid("mask has no event"));
+        }
+        if event.cookie != 0 && mask.0 & Mask::MOVE.0 == 0 {
+            return Err(invalid("only moves carry a cookie"));
+        }
+        if event.removal.is_some() && !mask.contains(Mask::IGNORED) {
+            return Err(invalid("only IGNORED carries a removal"));
+        }
+        if mask.contains(Mask::Q_OVERFLOW) && event.watch.wd != -1 {
+            return Err(invalid("Q_OVERFLOW is reported on watch -1"));
+        }
+
+        if event.path.is_relative() {
+            let mut components = event.path.components();
+            match (components.next(), components.next()) {
+                (None, _) => (),
+                (Some(_), _) if mask.0 & SELF.0 != 0 => {
+                    return Err(invalid("events on the watch itself name no entry"))
+                }
+                (Some(Component::Normal(_)), None) => (),
+                _ => return Err(invalid("a relative path must be a single name")),
+            }
+        }
+
+        Ok(event)
+    }
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, msg)
+}
+
+#[cfg(all(test, feature = "tokio"))]
+mod tests {
+    use super::*;
+    use crate::{testing::scratch, INotify};
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn builds_what_the_kernel_reports() {
+        let dir = scratch("builder");
+        std::fs::write(dir.join("f"), "").unwrap();
+
+        let mut inotify = INotify::new().unwrap();
+        let watch = inotify.add(&dir, Mask::MOVE).unwrap();
+        std::fs::rename(dir.join("f"), dir.join("g")).unwrap();
+
+        let read = tokio::time::timeout(Duration::from_secs(10), inotify.watch())
+            .await
+            .unwrap()
+            .unwrap();
+        let built = Event::builder(watch, Mask::MOVED_FROM)
+            .cookie(read.cookie)
+            .path("f")
+            .root(&*dir)
+            .watch_mask(Mask::MOVE)
+            .synthetic(false)
+            .build()
+            .unwrap();
+        assert_eq!(built, read);
+    }
+
+    #[test]
+    fn rejects_what_the_kernel_never_reports() {
+        let watch = Watch::from_raw(1);
+        let invalid = [
+            Event::builder(watch, Mask::ISDIR),
+            Event::builder(watch, Mask::CREATE | Mask::ONESHOT),
+            Event::builder(watch, Mask::CREATE).cookie(7),
+            Event::builder(watch, Mask::DELETE).removal(Removal::Deleted),
+            Event::builder(watch, Mask::Q_OVERFLOW),
+            Event::builder(watch, Mask::DELETE_SELF).path("f"),
+            Event::builder(watch, Mask::CREATE).path("a/b"),
+        ];
+
+        for builder in invalid {
+            let err = builder.clone().build().unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput, "{builder:?}");
+        }
+
+        let event = Event::builder(watch, Mask::CREATE).path("/abs/f").build();
+        assert!(event.unwrap().is_synthetic());
+    }
+}
@@ -0,0 +1,186 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use crate::{pseudo, Capability, INotify, Mount, PseudoFs};
+
+/// What can be promised about watching a path
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Capabilities {
+    /// The kernel reports changes to the path
+    pub native: bool,
+
+    /// Changes are found by polling, see [PseudoFs::Poll]
+    pub polling: bool,
+
+    /// The path is a directory which [INotify::add_tree] can watch recursively
+    pub recursive: bool,
+
+    /// The mount the path lives on and why its events may be incomplete
+    pub mount: Option<Capability>,
+
+    /// Directories at and beneath the path, one watch each when recursive
+    pub directories: usize,
+
+    /// Entries at and beneath the path
+    pub entries: usize,
+
+    /// The per user watch limit (`fs.inotify.max_user_watches`)
+    pub max_user_watches: Option<usize>,
+
+    /// The per instance queue limit (`fs.inotify.max_queued_events`)
+    pub max_queued_events: Option<usize>,
+
+    /// How likely the watch limit or event queue is to be exhausted
+    pub overflow_risk: OverflowRisk,
+}
+
+/// How likely watching a tree is to run into kernel limits
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum OverflowRisk {
+    /// Well within the limits
+    Low,
+
+    /// Uses over half of the watch limit, or a change to every entry at
+    /// once would overflow the event queue
+    Moderate,
+
+    /// More watches are needed than the limit allows
+    High,
+}
+
+impl Capabilities {
+    /// Changes to the path will be reported one way or another
+    pub fn supported(&self) -> bool {
+        self.native || self.polling
+    }
+}
+
+impl INotify {
+    /// report what can be promised about watching `path` with the current options
+    ///
+    /// directories beneath the path are counted, which may take a while for
+    /// large trees
+    pub async fn capabilities(&self, path: &Path) -> io::Result<Capabilities> {
+        let meta = tokio::fs::metadata(path).await?;
+        let pseudo = pseudo::is_pseudo(path)?;
+
+        let polling = pseudo && matches!(self.pseudo, PseudoFs::Poll(_));
+        let recursive = meta.is_dir() && !pseudo;
+
+        let (directories, entries) = if recursive {
+            let root = path.to_path_buf();
+            tokio::task::spawn_blocking(move || count(root))
+                .await
+                .map_err(io::Error::other)?
+        } else {
+            (usize::from(meta.is_dir()), 1)
+        };
+
+        let mount = Mount::table()
+            .ok()
+            .and_then(|table| Capability::of(&table, path));
+
+        let max_user_watches = limit("max_user_watches");
+        let max_queued_events = limit("max_queued_events");
+
+        let mut overflow_risk = OverflowRisk::Low;
+        if let Some(max) = max_user_watches {
+            let watches = self.watches().count() + directories;
+            if watches > max {
+                overflow_risk = OverflowRisk::High;
+            } else if watches > max / 2 {
+                overflow_risk = OverflowRisk::Moderate;
+            }
+        }
+        if max_queued_events.is_some_and(|max| entries > max) {
+            overflow_risk = overflow_risk.max(OverflowRisk::Moderate);
+        }
+
+        Ok(Capabilities {
+            native: !pseudo,
+            polling,
+            recursive,
+            mount,
+            directories,
+            entries,
+            max_user_watches,
+            max_queued_events,
+            overflow_risk,
+        })
+    }
+}
+
+/// Count the directories and entries of a tree, unreadable parts are skipped
+fn count(root: PathBuf) -> (usize, usize) {
+    let mut dirs = 0;
+    let mut entries = 1;
+    let mut stack = vec![root];
+
+    while let Some(dir) = stack.pop() {
+        dirs += 1;
+
+        let Ok(listing) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+
+        for entry in listing.flatten() {
+            entries += 1;
+            if entry.file_type().is_ok_and(|t| t.is_dir()) {
+                stack.push(entry.path());
+            }
+        }
+    }
+
+    (dirs, entries)
+}
+
+pub(crate) fn limit(name: &str) -> Option<usize> {
+    std::fs::read_to_string(Path::new("/proc/sys/fs/inotify").join(name))
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{testing::scratch, Mask};
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn trees_are_counted_against_the_limits() {
+        let dir = scratch("capabilities");
+        std::fs::create_dir_all(dir.join("a/b")).unwrap();
+        std::fs::write(dir.join("a/f"), "").unwrap();
+
+        let mut inotify = INotify::new().unwrap();
+        inotify.add(&dir, Mask::CREATE).unwrap();
+
+        let caps = inotify.capabilities(&dir).await.unwrap();
+        assert!(caps.native && caps.recursive && caps.supported());
+        assert!(!caps.polling);
+        assert_eq!((caps.directories, caps.entries), (3, 4));
+        assert_eq!(caps.max_user_watches, limit("max_user_watches"));
+        assert_eq!(caps.overflow_risk, OverflowRisk::Low);
+
+        let caps = inotify.capabilities(&dir.join("a/f")).await.unwrap();
+        assert!(caps.native && !caps.recursive);
+        assert_eq!((caps.directories, caps.entries), (0, 1));
+    }
+
+    #[tokio::test]
+    async fn pseudo_files_follow_the_policy() {
+        let mut inotify = INotify::new().unwrap();
+        let path = Path::new("/proc/self/status");
+
+        let caps = inotify.capabilities(path).await.unwrap();
+        assert!(!caps.native && !caps.polling && !caps.supported());
+
+        inotify.pseudo_fs(PseudoFs::Poll(Duration::from_secs(1)));
+        let caps = inotify.capabilities(path).await.unwrap();
+        assert!(!caps.native && caps.polling && caps.supported());
+    }
+}
@@ -0,0 +1,160 @@
+use std::{
+    collections::{HashMap, HashSet},
+    io,
+    path::{Path, PathBuf},
+};
+
+use crate::{Event, INotify, Mask, Sizes};
+
+const MAX_MOVES: usize = 1024;
+
+/// Turns raw events on tracked files into log-shipper friendly events
+///
+/// Files should be watched through their parent directory (so creates and
+/// renames of the name are seen) and tracked with [Classifier::track].
+#[derive(Default)]
+pub struct Classifier {
+    files: HashSet<PathBuf>,
+    sizes: Sizes,
+    moves: HashMap<u32, PathBuf>,
+}
+
+/// A higher level event on a tracked file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Classified {
+    /// Data was added to the end of the file
+    Appended {
+        /// The tracked file
+        path: PathBuf,
+
+        /// The number of bytes added
+        bytes: u64,
+    },
+
+    /// The file was shrunk
+    Truncated {
+        /// The tracked file
+        path: PathBuf,
+
+        /// The new length of the file
+        len: u64,
+    },
+
+    /// A new file has taken the place of the tracked file
+    Rotated {
+        /// The tracked file
+        path: PathBuf,
+
+        /// Where the new file was renamed from, `None` if it was created in place
+        /// or moved in from an unwatched directory
+        replaced_by: Option<PathBuf>,
+    },
+
+    /// The tracked file is gone
+    Removed {
+        /// The tracked file
+        path: PathBuf,
+    },
+}
+
+impl Classifier {
+    /// Build a new classifier
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start tracking a file
+    pub async fn track(&mut self, path: &Path) -> io::Result<()> {
+        self.sizes.track(path).await?;
+        self.files.insert(path.to_path_buf());
+
+        Ok(())
+    }
+
+    /// Stop tracking a file
+    pub fn untrack(&mut self, path: &Path) {
+        self.sizes.untrack(path);
+        self.files.remove(path);
+    }
+
+    /// Classify an event
+    pub async fn classify(
+        &mut self,
+        inotify: &INotify,
+        event: &Event,
+    ) -> io::Result<Option<Classified>> {
+        let Some(path) = inotify.resolve(event) else {
+            return Ok(None);
+        };
+
+        if event.mask.contains(Mask::MOVED_FROM) {
+            // unpaired moves (into unwatched directories) are never claimed
+            if self.moves.len() >= MAX_MOVES {
+                self.moves.clear();
+            }
+
+            self.moves.insert(event.cookie, path.clone());
+        }
+
+        if !self.files.contains(&path) {
+            if event.mask.contains(Mask::MOVED_TO) {
+                self.moves.remove(&event.cookie);
+            }
+
+            return Ok(None);
+        }
+
+        let mask = event.mask;
+
+        if mask.contains(Mask::MODIFY) {
+            // gone before it was looked at, its removal follows
+            let Some(change) = self.sizes.change(inotify, event).await? else {
+                return Ok(None);
+            };
+
+            let old = change.old_len.unwrap_or(0);
+
+            return Ok(if change.new_len > old {
+                Some(Classified::Appended {
+                    path,
+                    bytes: change.new_len - old,
+                })
+            } else if change.new_len < old {
+                Some(Classified::Truncated {
+                    path,
+                    len: change.new_len,
+                })
+            } else {
+                None
+            });
+        }
+
+        if mask.contains(Mask::CREATE) || mask.contains(Mask::MOVED_TO) {
+            let replaced_by = if mask.contains(Mask::MOVED_TO) {
+                self.moves.remove(&event.cookie)
+            } else {
+                None
+            };
+
+            match self.sizes.track(&path).await {
+                Ok(_) => (),
+                // replaced and gone again, its removal follows
+                Err(err) if err.kind() == io::ErrorKind::NotFound => self.sizes.untrack(&path),
+                Err(err) => return Err(err),
+            }
+
+            return Ok(Some(Classified::Rotated { path, replaced_by }));
+        }
+
+        if mask.contains(Mask::DELETE)
+            || mask.contains(Mask::DELETE_SELF)
+            || mask.contains(Mask::MOVED_FROM)
+        {
+            self.sizes.untrack(&path);
+
+            return Ok(Some(Classified::Removed { path }));
+        }
+
+        Ok(None)
+    }
+}
@@ -0,0 +1,263 @@
+use std::{
+    collections::HashMap,
+    io,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use notify::{
+    event::{AccessKind, AccessMode, CreateKind, DataChange, MetadataKind, ModifyKind},
+    event::{Flag, RemoveKind, RenameMode},
+    Config, EventHandler, EventKind, RecursiveMode, WatcherKind,
+};
+use tokio::runtime::Handle;
+
+use crate::{sys, Event, INotify, Mask, Shared, Watch};
+
+/// The events `notify`'s own inotify backend watches for
+const WATCHED: Mask = Mask(
+    Mask::CREATE.0
+        | Mask::MOVED_FROM.0
+        | Mask::MOVED_TO.0
+        | Mask::MODIFY.0
+        | Mask::ATTRIB.0
+        | Mask::CLOSE_WRITE.0
+        | Mask::DELETE.0
+        | Mask::DELETE_SELF.0
+        | Mask::MOVE_SELF.0,
+);
+
+/// Events announcing a new entry in a watched directory
+const ARRIVAL: Mask = Mask(Mask::CREATE.0 | Mask::MOVED_TO.0);
+
+/// An [INotify] behind the `notify::Watcher` trait
+///
+/// Events are read on the tokio runtime current when the watcher was built
+/// and handed to the event handler from there. Directories created under a
+/// recursive path are watched as they appear.
+pub struct NotifyWatcher {
+    shared: Arc<Shared>,
+    roots: Arc<Mutex<HashMap<PathBuf, Root>>>,
+    runtime: Handle,
+}
+
+struct Root {
+    recursive: bool,
+    watches: Vec<Watch>,
+}
+
+impl notify::Watcher for NotifyWatcher {
+    fn new<F: EventHandler>(mut handler: F, _config: Config) -> notify::Result<Self> {
+        let runtime = Handle::try_current()
+            .map_err(|_| notify::Error::generic("a tokio runtime is required"))?;
+        let shared = Arc::new(INotify::new().map_err(notify::Error::io)?.into_shared());
+        let roots: Arc<Mutex<HashMap<PathBuf, Root>>> = Arc::default();
+
+        let reader = (shared.clone(), roots.clone());
+        runtime.spawn(async move {
+            let (shared, roots) = reader;
+
+            loop {
+                let event = match shared.watch().await {
+                    Ok(event) => event,
+                    Err(_) if shared.is_closed() => break,
+                    Err(err) => {
+                        handler.handle_event(Err(notify::Error::io(err)));
+                        break;
+                    }
+                };
+
+                if event.mask.contains(Mask::ISDIR) && (event.mask & ARRIVAL).0 != 0 {
+                    follow(&shared, &roots, &full_path(&event));
+                }
+
+                for event in event.to_notify() {
+                    handler.handle_event(Ok(event));
+                }
+            }
+        });
+
+        Ok(NotifyWatcher {
+            shared,
+            roots,
+            runtime,
+        })
+    }
+
+    fn watch(&mut self, path: &Path, recursive_mode: RecursiveMode) -> notify::Result<()> {
+        let recursive = recursive_mode == RecursiveMode::Recursive;
+        let watches = if recursive && path.is_dir() {
+            add_tree(&self.shared, path)
+        } else {
+            self.shared.add(path, WATCHED).map(|watch| vec![watch])
+        };
+
+        let watches = watches.map_err(|err| match err.kind() {
+            io::ErrorKind::NotFound => notify::Error::path_not_found(),
+            _ => notify::Error::io_watch(err),
+        })?;
+
+        let mut roots = self
+            .roots
+            .lock()
+            .expect("reader never panics holding the lock");
+        roots.insert(path.to_path_buf(), Root { recursive, watches });
+
+        Ok(())
+    }
+
+    fn unwatch(&mut self, path: &Path) -> notify::Result<()> {
+        let mut roots = self
+            .roots
+            .lock()
+            .expect("reader never panics holding the lock");
+        let root = roots
+            .remove(path)
+            .ok_or_else(notify::Error::watch_not_found)?;
+
+        for watch in root.watches {
+            match self.shared.rm(watch) {
+                // gone already, or shared with a root still watched
+                Err(err) if err.raw_os_error() == Some(sys::EINVAL) => (),
+                res => res.map_err(notify::Error::io)?,
+            }
+        }
+
+        Ok(())
+    }
+
+    fn kind() -> WatcherKind {
+        WatcherKind::Inotify
+    }
+}
+
+impl Drop for NotifyWatcher {
+    fn drop(&mut self) {
+        let shared = self.shared.clone();
+        self.runtime.spawn(async move {
+            let _ = shared.close().await;
+        });
+    }
+}
+
+/// Watch a new directory when it lies under a recursive root
+fn follow(shared: &Shared, roots: &Mutex<HashMap<PathBuf, Root>>, dir: &Path) {
+    let mut roots = roots.lock().expect("callers never panic holding the lock");
+    let Some(root) = roots
+        .iter_mut()
+        .find(|(path, root)| root.recursive && dir.starts_with(path))
+        .map(|(_, root)| root)
+    else {
+        return;
+    };
+
+    // removed again already, its removal is reported by the parent
+    if let Ok(watches) = add_tree(shared, dir) {
+        root.watches.extend(watches);
+    }
+}
+
+/// Watch a directory and every directory beneath it, skipping what vanishes meanwhile
+fn add_tree(shared: &Shared, root: &Path) -> io::Result<Vec<Watch>> {
+    let mut watches = vec![shared.add(root, WATCHED | Mask::ONLYDIR)?];
+    let mut dirs = vec![root.to_path_buf()];
+
+    while let Some(dir) = dirs.pop() {
+        let entries = match std::fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+            Err(err) => return Err(err),
+        };
+
+        for entry in entries.flatten() {
+            if !entry.file_type().is_ok_and(|kind| kind.is_dir()) {
+                continue;
+            }
+
+            let path = entry.path();
+            match shared.add(&path, WATCHED | Mask::ONLYDIR) {
+                Ok(watch) => watches.push(watch),
+                Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+                Err(err) => return Err(err),
+            }
+            dirs.push(path);
+        }
+    }
+
+    Ok(watches)
+}
+
+/// the path an event is about, as far as it is known
+fn full_path(event: &Event) -> PathBuf {
+    match event.root() {
+        Some(root) if event.path.as_os_str().is_empty() => root.to_path_buf(),
+        Some(root) => root.join(&event.path),
+        None => event.path.clone(),
+    }
+}
+
+impl Event {
+    /// the events `notify`'s inotify backend reports for this event
+    ///
+    /// Moves carry their cookie as the tracker, IGNORED and flags `notify`
+    /// leaves out (ACCESS, OPEN, CLOSE_NOWRITE) map to nothing.
+    pub fn to_notify(&self) -> Vec<notify::Event> {
+        if self.mask.contains(Mask::Q_OVERFLOW) {
+            return vec![notify::Event::new(EventKind::Other).set_flag(Flag::Rescan)];
+        }
+
+        let dir = self.mask.contains(Mask::ISDIR);
+        let mut kinds = Vec::new();
+
+        if self.mask.contains(Mask::MOVED_FROM) {
+            kinds.push(EventKind::Modify(ModifyKind::Name(RenameMode::From)));
+        }
+        if self.mask.contains(Mask::MOVED_TO) {
+            kinds.push(EventKind::Modify(ModifyKind::Name(RenameMode::To)));
+        }
+        if self.mask.contains(Mask::MOVE_SELF) {
+            kinds.push(EventKind::Modify(ModifyKind::Name(RenameMode::From)));
+        }
+        if self.mask.contains(Mask::CREATE) {
+            kinds.push(EventKind::Create(if dir {
+                CreateKind::Folder
+            } else {
+                CreateKind::File
+            }));
+        }
+        if self.mask.contains(Mask::DELETE) {
+            kinds.push(EventKind::Remove(if dir {
+                RemoveKind::Folder
+            } else {
+                RemoveKind::File
+            }));
+        }
+        if self.mask.contains(Mask::DELETE_SELF) {
+            kinds.push(EventKind::Remove(RemoveKind::Other));
+        }
+        if self.mask.contains(Mask::MODIFY) {
+            kinds.push(EventKind::Modify(ModifyKind::Data(DataChange::Any)));
+        }
+        if self.mask.contains(Mask::CLOSE_WRITE) {
+            kinds.push(EventKind::Access(AccessKind::Close(AccessMode::Write)));
+        }
+        if self.mask.contains(Mask::ATTRIB) {
+            kinds.push(EventKind::Modify(ModifyKind::Metadata(MetadataKind::Any)));
+        }
+
+        let path = full_path(self);
+        kinds
+            .into_iter()
+            .map(|kind| {
+                let mut event = notify::Event::new(kind);
+                if !path.as_os_str().is_empty() {
+                    event = event.add_path(path.clone());
+                }
+                if self.mask.0 & Mask::MOVE.0 != 0 && self.cookie != 0 {
+                    event = event.set_tracker(self.cookie as usize);
+                }
+                event
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,146 @@
+use std::{io, path::PathBuf, time::Duration};
+
+use crate::{Debounce, ExcludeSet, Glob, INotify, Mask};
+
+/// A watcher declared in an application's own configuration
+///
+/// With the `serde` feature it deserializes from any serde format, every
+/// field is optional:
+///
+/// ```toml
+/// roots = ["/srv/site"]
+/// recursive = true
+/// mask = ["close_write", "moved_to", "delete"]
+/// exclude = [".git", "*.swp"]
+/// debounce = { quiet_ms = 200, max_latency_ms = 2000 }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default, deny_unknown_fields))]
+pub struct WatcherConfig {
+    /// Paths to watch
+    pub roots: Vec<PathBuf>,
+
+    /// Watch every directory beneath each root
+    pub recursive: bool,
+
+    /// Events of interest by name, as in the inotify headers without `IN_`
+    ///
+    /// Case is ignored, every event is watched when empty.
+    pub mask: Vec<String>,
+
+    /// Globs of paths left unwatched, along with everything beneath them
+    pub exclude: Vec<String>,
+
+    /// Settings for [WatcherConfig::debounce]
+    pub debounce: Option<DebounceConfig>,
+}
+
+/// Debounce settings of a [WatcherConfig]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default, deny_unknown_fields))]
+pub struct DebounceConfig {
+    /// Quiet period in milliseconds, see [Debounce::new]
+    pub quiet_ms: u64,
+
+    /// Longest delay in milliseconds, see [Debounce::max_latency]
+    pub max_latency_ms: Option<u64>,
+}
+
+impl WatcherConfig {
+    /// the events of interest
+    pub fn mask(&self) -> io::Result<Mask> {
+        if self.mask.is_empty() {
+            return Ok(Mask::INTEREST);
+        }
+
+        let mut mask = Mask(0);
+        for name in &self.mask {
+            mask |= Mask::from_name(&name.to_ascii_uppercase()).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("unknown event {name:?}"),
+                )
+            })?;
+        }
+
+        Ok(mask)
+    }
+
+    /// the compiled exclude globs
+    pub fn excludes(&self) -> io::Result<ExcludeSet> {
+        self.exclude
+            .iter()
+            .map(|pattern| {
+                Glob::new(pattern).map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))
+            })
+            .collect()
+    }
+
+    /// a debouncer with the configured settings
+    pub fn debounce(&self) -> Option<Debounce> {
+        let config = self.debounce.as_ref()?;
+        let debounce = Debounce::new(Duration::from_millis(config.quiet_ms));
+
+        Some(match config.max_latency_ms {
+            Some(ms) => debounce.max_latency(Duration::from_millis(ms)),
+            None => debounce,
+        })
+    }
+}
+
+impl INotify {
+    /// Build an instance watching what a configuration declares
+    ///
+    /// Debouncing is left to the caller, see [WatcherConfig::debounce].
+    pub async fn from_config(config: &WatcherConfig) -> io::Result<INotify> {
+        let mask = config.mask()?;
+        let excludes = config.excludes()?;
+
+        let mut inotify = INotify::new()?;
+        for root in &config.roots {
+            if config.recursive {
+                // only what lies beneath a root is matched, the root itself is kept
+                inotify
+                    .add_tree_excluding(root, mask, Some(&excludes), None)
+                    .await?;
+            } else {
+                inotify.add(root, mask)?;
+            }
+        }
+
+        Ok(inotify)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::scratch;
+
+    #[tokio::test]
+    async fn excluded_directories_are_never_watched() {
+        let dir = scratch("config-exclude");
+        for sub in ["src/bin", "target/debug/deps", ".git/objects"] {
+            std::fs::create_dir_all(dir.join(sub)).unwrap();
+        }
+
+        let config = WatcherConfig {
+            roots: vec![dir.to_path_buf()],
+            recursive: true,
+            mask: vec!["create".to_string()],
+            exclude: vec!["**/target".to_string(), "**/.git".to_string()],
+            ..WatcherConfig::default()
+        };
+
+        let inotify = INotify::from_config(&config).await.unwrap();
+        let mut watched: Vec<PathBuf> = inotify.watches().map(|(_, p)| p.to_path_buf()).collect();
+        watched.sort();
+
+        assert_eq!(watched, vec![dir.to_path_buf(), dir.join("src"), dir.join("src/bin")]);
+
+        // descriptors are handed out in turn, none went to an excluded directory
+        assert!(inotify.watches().all(|(watch, _)| watch.wd <= 3));
+    }
+}
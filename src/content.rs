@@ -0,0 +1,169 @@
+use std::{io, path::Path};
+
+use crate::{Event, INotify, Mask};
+
+/// Events whose file may be read to tell its content type
+#[cfg(feature = "sniff")]
+const WRITTEN: Mask = Mask(Mask::CREATE.0 | Mask::CLOSE_WRITE.0 | Mask::MOVED_TO.0);
+
+const EXTENSIONS: &[(&str, &str)] = &[
+    ("avif", "image/avif"),
+    ("bmp", "image/bmp"),
+    ("gif", "image/gif"),
+    ("heic", "image/heic"),
+    ("ico", "image/vnd.microsoft.icon"),
+    ("jpeg", "image/jpeg"),
+    ("jpg", "image/jpeg"),
+    ("png", "image/png"),
+    ("svg", "image/svg+xml"),
+    ("tif", "image/tiff"),
+    ("tiff", "image/tiff"),
+    ("webp", "image/webp"),
+    ("avi", "video/x-msvideo"),
+    ("mkv", "video/x-matroska"),
+    ("mov", "video/quicktime"),
+    ("mp4", "video/mp4"),
+    ("webm", "video/webm"),
+    ("flac", "audio/flac"),
+    ("m4a", "audio/mp4"),
+    ("mp3", "audio/mpeg"),
+    ("ogg", "audio/ogg"),
+    ("opus", "audio/opus"),
+    ("wav", "audio/wav"),
+    ("css", "text/css"),
+    ("csv", "text/csv"),
+    ("htm", "text/html"),
+    ("html", "text/html"),
+    ("md", "text/markdown"),
+    ("txt", "text/plain"),
+    ("json", "application/json"),
+    ("pdf", "application/pdf"),
+    ("gz", "application/gzip"),
+    ("tar", "application/x-tar"),
+    ("zip", "application/zip"),
+];
+
+/// Forwards only events on files of the requested content types
+///
+/// Types are MIME types, `image/*` accepts every image. A file's type is
+/// told by its extension, with the `sniff` feature a created or written
+/// file is read and its leading bytes take precedence. Events on
+/// directories are dropped, control events (IGNORED, Q_OVERFLOW, UNMOUNT)
+/// always pass.
+#[derive(Debug, Clone, Default)]
+pub struct ContentFilter {
+    accepted: Vec<String>,
+}
+
+impl ContentFilter {
+    /// Accept files of any of `types`
+    pub fn new<I, S>(types: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self {
+            accepted: types.into_iter().map(Into::into).collect(),
+        }
+    }
+
+    /// whether a content type is one of the accepted ones
+    pub fn accepts_type(&self, content_type: &str) -> bool {
+        self.accepted.iter().any(|accepted| match accepted.strip_suffix("/*") {
+            Some(top) => content_type
+                .split_once('/')
+                .is_some_and(|(ty, _)| ty == top),
+            None => accepted == content_type,
+        })
+    }
+
+    /// whether an event should be forwarded
+    pub async fn accepts(&self, inotify: &INotify, event: &Event) -> bool {
+        if (event.mask & Mask::CONTROL).0 != 0 {
+            return true;
+        }
+        if event.mask.contains(Mask::ISDIR) {
+            return false;
+        }
+
+        let Some(path) = inotify.resolve(event) else {
+            return false;
+        };
+
+        #[cfg(feature = "sniff")]
+        if (event.mask & WRITTEN).0 != 0 {
+            if let Some(sniffed) = sniff(&path).await {
+                return self.accepts_type(sniffed);
+            }
+        }
+
+        content_type(&path).is_some_and(|ty| self.accepts_type(ty))
+    }
+
+    /// Wait for the next event on a file of an accepted type
+    pub async fn watch(&self, inotify: &mut INotify) -> io::Result<Event> {
+        loop {
+            let event = inotify.watch().await?;
+            if self.accepts(inotify, &event).await {
+                return Ok(event);
+            }
+        }
+    }
+}
+
+/// the content type a path's extension stands for
+pub fn content_type(path: &Path) -> Option<&'static str> {
+    let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+
+    EXTENSIONS
+        .iter()
+        .find(|(known, _)| *known == ext)
+        .map(|(_, ty)| *ty)
+}
+
+/// the content type told by a file's leading bytes
+#[cfg(feature = "sniff")]
+pub async fn sniff(path: &Path) -> Option<&'static str> {
+    use tokio::io::AsyncReadExt;
+
+    let mut file = tokio::fs::File::open(path).await.ok()?;
+    let mut head = [0; 16];
+    let mut len = 0;
+    while len < head.len() {
+        match file.read(&mut head[len..]).await.ok()? {
+            0 => break,
+            n => len += n,
+        }
+    }
+
+    magic(&head[..len])
+}
+
+#[cfg(feature = "sniff")]
+fn magic(head: &[u8]) -> Option<&'static str> {
+    let at = |offset: usize, sig: &[u8]| head.get(offset..offset + sig.len()) == Some(sig);
+
+    Some(match () {
+        _ if at(0, b"\x89PNG\r\n\x1a\n") => "image/png",
+        _ if at(0, b"\xff\xd8\xff") => "image/jpeg",
+        _ if at(0, b"GIF87a") || at(0, b"GIF89a") => "image/gif",
+        _ if at(0, b"RIFF") && at(8, b"WEBP") => "image/webp",
+        _ if at(0, b"RIFF") && at(8, b"WAVE") => "audio/wav",
+        _ if at(0, b"RIFF") && at(8, b"AVI ") => "video/x-msvideo",
+        _ if at(0, b"II*\0") || at(0, b"MM\0*") => "image/tiff",
+        _ if at(0, b"BM") => "image/bmp",
+        _ if at(4, b"ftypavif") => "image/avif",
+        _ if at(4, b"ftypheic") => "image/heic",
+        _ if at(4, b"ftypqt") => "video/quicktime",
+        _ if at(4, b"ftypM4A") => "audio/mp4",
+        _ if at(4, b"ftyp") => "video/mp4",
+        _ if at(0, b"\x1a\x45\xdf\xa3") => "video/x-matroska",
+        _ if at(0, b"OggS") => "audio/ogg",
+        _ if at(0, b"fLaC") => "audio/flac",
+        _ if at(0, b"ID3") || at(0, b"\xff\xfb") => "audio/mpeg",
+        _ if at(0, b"%PDF-") => "application/pdf",
+        _ if at(0, b"PK\x03\x04") => "application/zip",
+        _ if at(0, b"\x1f\x8b") => "application/gzip",
+        _ => return None,
+    })
+}
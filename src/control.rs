@@ -0,0 +1,125 @@
+use std::{
+    io,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use tokio::sync::mpsc;
+
+use crate::{INotify, Mask, Watch};
+
+/// A change to the watch set, applied by the [INotify] a [Control] belongs to
+#[derive(Debug, Clone, PartialEq)]
+pub enum WatchCommand {
+    /// Add a path to be watched
+    Add {
+        /// The path to watch
+        path: PathBuf,
+
+        /// The events of interest
+        mask: Mask,
+    },
+
+    /// Remove a watch
+    Remove(Watch),
+
+    /// Replace the events a watch is interested in
+    UpdateMask {
+        /// The watch to update
+        watch: Watch,
+
+        /// The new events of interest
+        mask: Mask,
+    },
+}
+
+/// The control half of an [INotify]
+///
+/// Commands are queued (bounded, so senders see backpressure) and applied
+/// while the [INotify] is waiting for events. A command that fails is
+/// reported as an error from the next call to [INotify::watch].
+#[derive(Clone)]
+pub struct Control {
+    tx: mpsc::Sender<WatchCommand>,
+
+    #[cfg(feature = "sink")]
+    sink: tokio_util::sync::PollSender<WatchCommand>,
+}
+
+impl INotify {
+    /// Build a control half which queues up to `capacity` commands
+    ///
+    /// Building another control half disconnects the previous ones.
+    pub fn control(&mut self, capacity: usize) -> Control {
+        let (tx, rx) = mpsc::channel(capacity.max(1));
+        self.commands = Some(Arc::new(Mutex::new(rx)));
+
+        Control {
+            #[cfg(feature = "sink")]
+            sink: tokio_util::sync::PollSender::new(tx.clone()),
+            tx,
+        }
+    }
+
+    pub(crate) fn apply(&mut self, command: WatchCommand) -> io::Result<()> {
+        match command {
+            WatchCommand::Add { path, mask } => self.add(&path, mask).map(|_| ()),
+            WatchCommand::Remove(watch) => self.rm(watch),
+            WatchCommand::UpdateMask { watch, mask } => self.set_mask(watch, mask),
+        }
+    }
+}
+
+/// The receiving half, shared so a cancelled [INotify::watch] drops only its clone
+pub(crate) type Commands = Arc<Mutex<mpsc::Receiver<WatchCommand>>>;
+
+/// the next command, `None` once every control half is gone
+pub(crate) async fn recv(commands: &Commands) -> Option<WatchCommand> {
+    std::future::poll_fn(|cx| commands.lock().expect("never held across an await").poll_recv(cx))
+        .await
+}
+
+impl Control {
+    /// Queue a command, waiting for room
+    pub async fn send(&self, command: WatchCommand) -> io::Result<()> {
+        self.tx.send(command).await.map_err(|_| closed())
+    }
+}
+
+pub(crate) fn closed() -> io::Error {
+    io::Error::new(io::ErrorKind::BrokenPipe, "inotify instance is gone")
+}
+
+#[cfg(feature = "sink")]
+impl futures_sink::Sink<WatchCommand> for Control {
+    type Error = io::Error;
+
+    fn poll_ready(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Result<(), Self::Error>> {
+        self.sink.poll_reserve(cx).map_err(|_| closed())
+    }
+
+    fn start_send(
+        mut self: std::pin::Pin<&mut Self>,
+        item: WatchCommand,
+    ) -> Result<(), Self::Error> {
+        self.sink.send_item(item).map_err(|_| closed())
+    }
+
+    fn poll_flush(
+        self: std::pin::Pin<&mut Self>,
+        _: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Result<(), Self::Error>> {
+        std::task::Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(
+        mut self: std::pin::Pin<&mut Self>,
+        _: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Result<(), Self::Error>> {
+        self.sink.close();
+        std::task::Poll::Ready(Ok(()))
+    }
+}
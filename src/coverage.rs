@@ -0,0 +1,161 @@
+use std::{
+    collections::{HashSet, VecDeque},
+    io,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    canonicalize, sys,
+    tree::{vanished, Walk},
+    INotify, Mask, TreeProgress, Watch,
+};
+
+/// How much of a tree [INotify::add_tree_within] could watch
+///
+/// Directories are watched breadth first, so the shallow part of the tree
+/// is covered natively and whole deeper subtrees are left out once the
+/// budget runs out. Changes in uncovered subtrees are only found by
+/// scanning them, see [Rescan::uncovered](crate::Rescan::uncovered).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PartialCoverage {
+    /// The watches added, one per covered directory
+    pub watches: Vec<Watch>,
+
+    /// Directories watched natively
+    pub covered: Vec<PathBuf>,
+
+    /// Roots of subtrees left unwatched, each the child of a covered directory
+    pub uncovered: Vec<PathBuf>,
+}
+
+impl PartialCoverage {
+    /// whether every directory of the tree is watched
+    pub fn is_complete(&self) -> bool {
+        self.uncovered.is_empty()
+    }
+}
+
+impl INotify {
+    /// Watch a tree as far as `budget` watches go, reporting what is left out
+    ///
+    /// Without a budget directories are watched until the kernel refuses
+    /// more (ENOSPC, `fs.inotify.max_user_watches`) or the instance's
+    /// [Quota](crate::Quota) is used up. Directories are checked against
+    /// the [PseudoFs](crate::PseudoFs) policy as with [INotify::add_tree].
+    /// Other errors abort the walk and remove the watches it added,
+    /// directories removed while walking are skipped.
+    pub async fn add_tree_within(
+        &mut self,
+        root: &Path,
+        mask: Mask,
+        budget: Option<usize>,
+    ) -> io::Result<PartialCoverage> {
+        let root = if self.canonical {
+            canonicalize(root, false)?
+        } else {
+            root.to_path_buf()
+        };
+
+        let mask = mask | Mask::ONLYDIR;
+        let walk = Walk::new(self, mask, TreeProgress::default());
+        let budget = budget.unwrap_or(usize::MAX);
+
+        let known: HashSet<Watch> = self.paths.keys().copied().collect();
+        let task = {
+            let walk = walk.clone();
+            tokio::task::spawn_blocking(move || walk_within(&walk, root, budget))
+        };
+        // what a panicking walk added is only known to the walk
+        let walked = match task.await {
+            Ok(walked) => walked,
+            Err(err) => {
+                self.undo(walk.added(), &known);
+                return Err(io::Error::other(err));
+            }
+        };
+
+        let mut coverage = PartialCoverage {
+            uncovered: walked.uncovered,
+            ..PartialCoverage::default()
+        };
+        for (watch, path, polled) in walked.watches {
+            self.register(watch, path.clone(), mask);
+            if polled {
+                self.poll_pseudo(watch, path.clone(), mask);
+            }
+            coverage.watches.push(watch);
+            coverage.covered.push(path);
+        }
+
+        if let Some(err) = walked.error {
+            self.undo(coverage.watches, &known);
+            return Err(err);
+        }
+
+        Ok(coverage)
+    }
+}
+
+/// the watches added breadth first, whether each is polled, the
+/// directories left unwatched and the error stopping the walk
+struct Walked {
+    watches: Vec<(Watch, PathBuf, bool)>,
+    uncovered: Vec<PathBuf>,
+    error: Option<io::Error>,
+}
+
+fn walk_within(walk: &Walk, root: PathBuf, budget: usize) -> Walked {
+    let mut walked = Walked {
+        watches: Vec::new(),
+        uncovered: Vec::new(),
+        error: None,
+    };
+    let mut queue = VecDeque::from([root]);
+
+    while let Some(dir) = queue.pop_front() {
+        if walked.watches.len() >= budget {
+            queue.push_front(dir);
+            break;
+        }
+
+        let (watch, polled) = match walk.add(&dir) {
+            Ok(Some(added)) => added,
+            Ok(None) => continue,
+            // the kernel or the quota is out of watches
+            Err(err) if err.raw_os_error() == Some(sys::ENOSPC) => {
+                queue.push_front(dir);
+                break;
+            }
+            Err(err) => {
+                walked.error = Some(err);
+                break;
+            }
+        };
+        walked.watches.push((watch, dir.clone(), polled));
+
+        if let Err(err) = children(&dir, &mut queue) {
+            walked.error = Some(err);
+            break;
+        }
+    }
+
+    walked.uncovered = queue.into();
+    walked
+}
+
+fn children(dir: &Path, queue: &mut VecDeque<PathBuf>) -> io::Result<()> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) if vanished(&err) => return Ok(()),
+        Err(err) => return Err(err),
+    };
+
+    for entry in entries {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() {
+            queue.push_back(entry.path());
+        }
+    }
+
+    Ok(())
+}
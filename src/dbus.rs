@@ -0,0 +1,582 @@
+use std::{
+    env, io,
+    os::{
+        linux::net::SocketAddrExt,
+        unix::{ffi::OsStrExt, net::SocketAddr},
+    },
+    path::PathBuf,
+};
+
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    net::UnixStream,
+};
+
+use crate::{sys, Event, Glob, INotify, Mask};
+
+const DEFAULT_PATH: &str = "/io/github/eulegang/Tokinotify";
+const DEFAULT_INTERFACE: &str = "io.github.eulegang.Tokinotify";
+
+const METHOD_CALL: u8 = 1;
+const METHOD_RETURN: u8 = 2;
+const ERROR: u8 = 3;
+const SIGNAL: u8 = 4;
+
+const NO_REPLY_EXPECTED: u8 = 0x1;
+
+/// Emits a D-Bus signal for every event on a message bus
+///
+/// Signals are sent from `/io/github/eulegang/Tokinotify` on the
+/// `io.github.eulegang.Tokinotify` interface as `Event` with the signature
+/// `sasuu`: the full path, the flag names, the mask and the cookie.
+pub struct DbusEmitter {
+    stream: UnixStream,
+    inbox: Vec<u8>,
+    serial: u32,
+    path: String,
+    interface: String,
+    filters: Vec<(Mask, Glob)>,
+}
+
+impl DbusEmitter {
+    /// Connect to the session bus named by `DBUS_SESSION_BUS_ADDRESS`
+    pub async fn session() -> io::Result<Self> {
+        let address = match env::var("DBUS_SESSION_BUS_ADDRESS") {
+            Ok(address) => address,
+            Err(_) => {
+                let runtime = env::var_os("XDG_RUNTIME_DIR").ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::NotFound, "no session bus address")
+                })?;
+                let path = PathBuf::from(runtime).join("bus");
+                format!("unix:path={}", path.display())
+            }
+        };
+
+        Self::connect(&address).await
+    }
+
+    /// Connect to a bus at a D-Bus address, e.g. `unix:path=/run/dbus/system_bus_socket`
+    pub async fn connect(address: &str) -> io::Result<Self> {
+        let mut last = io::Error::new(io::ErrorKind::InvalidInput, "no usable bus address");
+
+        for address in address.split(';') {
+            match connect(address) {
+                Ok(stream) => return Self::handshake(stream).await,
+                Err(err) => last = err,
+            }
+        }
+
+        Err(last)
+    }
+
+    /// Send signals from `path` on `interface` instead
+    ///
+    /// Fails with [io::ErrorKind::InvalidInput] unless `path` is a valid
+    /// object path and `interface` a valid interface name, the bus drops
+    /// the connection on the first signal otherwise.
+    pub fn object(mut self, path: &str, interface: &str) -> io::Result<Self> {
+        if !object_path(path) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid object path {path:?}"),
+            ));
+        }
+
+        if !interface_name(interface) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid interface name {interface:?}"),
+            ));
+        }
+
+        self.path = path.to_string();
+        self.interface = interface.to_string();
+        Ok(self)
+    }
+
+    /// Only emit events with a flag of `mask` whose full path matches `glob`
+    ///
+    /// Filters accumulate, an event matching any of them is emitted. Without
+    /// a filter every event is emitted.
+    pub fn filter(mut self, mask: Mask, glob: Glob) -> Self {
+        self.filters.push((mask, glob));
+        self
+    }
+
+    /// Emit the signal for an event unless filtered, returning whether it was
+    ///
+    /// Fails if the bus closed the connection or sent an error since the
+    /// last signal.
+    pub async fn emit(&mut self, inotify: &INotify, event: &Event) -> io::Result<bool> {
+        let path = inotify.resolve(event).unwrap_or_else(|| event.path.clone());
+
+        let wanted = self.filters.is_empty()
+            || self
+                .filters
+                .iter()
+                .any(|(mask, glob)| (*mask & event.mask).0 != 0 && glob.matches(&path));
+        if !wanted {
+            return Ok(false);
+        }
+
+        self.drain()?;
+
+        let mut body = Vec::new();
+        string(&mut body, &path.to_string_lossy());
+        let names: Vec<&str> = event.mask.names().collect();
+        string_array(&mut body, &names);
+        uint(&mut body, event.mask.0);
+        uint(&mut body, event.cookie);
+
+        let fields = [
+            (1, 'o', self.path.as_str()),
+            (2, 's', self.interface.as_str()),
+            (3, 's', "Event"),
+            (8, 'g', "sasuu"),
+        ];
+        self.serial += 1;
+        let msg = message(self.serial, SIGNAL, NO_REPLY_EXPECTED, &fields, &body);
+        self.stream.write_all(&msg).await?;
+
+        Ok(true)
+    }
+
+    /// Emit signals until reading from the kernel or writing to the bus fails
+    pub async fn run(&mut self, inotify: &mut INotify) -> io::Result<()> {
+        loop {
+            let event = inotify.watch().await?;
+            self.emit(inotify, &event).await?;
+        }
+    }
+
+    async fn handshake(stream: UnixStream) -> io::Result<Self> {
+        let mut stream = BufReader::new(stream);
+
+        let uid = unsafe { sys::getuid() }.to_string();
+        let hex: String = uid.bytes().map(|b| format!("{b:02x}")).collect();
+        stream
+            .write_all(format!("\0AUTH EXTERNAL {hex}\r\n").as_bytes())
+            .await?;
+
+        let mut line = String::new();
+        stream.read_line(&mut line).await?;
+        if !line.starts_with("OK ") {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!("bus refused authentication: {}", line.trim()),
+            ));
+        }
+        stream.write_all(b"BEGIN\r\n").await?;
+
+        let mut emitter = Self {
+            inbox: stream.buffer().to_vec(),
+            stream: stream.into_inner(),
+            serial: 0,
+            path: DEFAULT_PATH.to_string(),
+            interface: DEFAULT_INTERFACE.to_string(),
+            filters: Vec::new(),
+        };
+
+        let fields = [
+            (1, 'o', "/org/freedesktop/DBus"),
+            (6, 's', "org.freedesktop.DBus"),
+            (2, 's', "org.freedesktop.DBus"),
+            (3, 's', "Hello"),
+        ];
+        emitter.send(METHOD_CALL, 0, &fields, &[]).await?;
+        emitter.reply().await?;
+
+        Ok(emitter)
+    }
+
+    async fn send(
+        &mut self,
+        kind: u8,
+        flags: u8,
+        fields: &[(u8, char, &str)],
+        body: &[u8],
+    ) -> io::Result<()> {
+        self.serial += 1;
+        let msg = message(self.serial, kind, flags, fields, body);
+
+        self.stream.write_all(&msg).await
+    }
+
+    /// wait for the reply to a method call, skipping anything else
+    async fn reply(&mut self) -> io::Result<()> {
+        loop {
+            while let Some((kind, len)) = frame(&self.inbox) {
+                let msg: Vec<u8> = self.inbox.drain(..len).collect();
+                match kind {
+                    METHOD_RETURN => return Ok(()),
+                    ERROR => return Err(refused(&msg)),
+                    _ => continue,
+                }
+            }
+
+            if self.stream.read_buf(&mut self.inbox).await? == 0 {
+                return Err(closed());
+            }
+        }
+    }
+
+    /// read whatever the bus sent without waiting, failing on an error
+    ///
+    /// Nothing else is expected, signals the bus sends us (e.g.
+    /// `NameAcquired`) are dropped so they don't pile up.
+    fn drain(&mut self) -> io::Result<()> {
+        loop {
+            match self.stream.try_read_buf(&mut self.inbox) {
+                Ok(0) => return Err(closed()),
+                Ok(_) => continue,
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                Err(err) => return Err(err),
+            }
+        }
+
+        while let Some((kind, len)) = frame(&self.inbox) {
+            let msg: Vec<u8> = self.inbox.drain(..len).collect();
+            if kind == ERROR {
+                return Err(refused(&msg));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn closed() -> io::Error {
+    io::Error::new(io::ErrorKind::UnexpectedEof, "bus closed the connection")
+}
+
+fn refused(msg: &[u8]) -> io::Error {
+    let name = error_name(msg).unwrap_or_else(|| "an error".to_string());
+    io::Error::new(
+        io::ErrorKind::ConnectionRefused,
+        format!("bus replied with {name}"),
+    )
+}
+
+/// the kind and length of the first whole message in `buf`
+fn frame(buf: &[u8]) -> Option<(u8, usize)> {
+    let header = buf.get(..16)?;
+    let fields = word(header, 12) as usize;
+    let len = 16 + fields.next_multiple_of(8) + word(header, 4) as usize;
+
+    (buf.len() >= len).then_some((header[1], len))
+}
+
+/// the `ERROR_NAME` header field of a message
+fn error_name(msg: &[u8]) -> Option<String> {
+    let end = 16 + word(msg, 12) as usize;
+    let mut at = 16;
+
+    while at < end {
+        at = at.next_multiple_of(8);
+        let code = *msg.get(at)?;
+        let sig = *msg.get(at + 2)?;
+        at += 4;
+
+        let len = match sig {
+            b's' | b'o' => {
+                at = at.next_multiple_of(4);
+                let len = word(msg, at) as usize;
+                at += 4;
+                len
+            }
+            b'g' => {
+                at += 1;
+                *msg.get(at - 1)? as usize
+            }
+            b'u' => {
+                at = at.next_multiple_of(4) + 4;
+                continue;
+            }
+            _ => return None,
+        };
+
+        let value = msg.get(at..at + len)?;
+        at += len + 1;
+        if code == 4 {
+            return Some(String::from_utf8_lossy(value).into_owned());
+        }
+    }
+
+    None
+}
+
+/// a u32 of a message at `at`, in the message's byte order
+fn word(msg: &[u8], at: usize) -> u32 {
+    let bytes = msg
+        .get(at..at + 4)
+        .and_then(|bytes| bytes.try_into().ok())
+        .unwrap_or_default();
+
+    match msg[0] {
+        b'B' => u32::from_be_bytes(bytes),
+        _ => u32::from_le_bytes(bytes),
+    }
+}
+
+/// `/` or `/`-separated non-empty elements of `[A-Za-z0-9_]`
+fn object_path(path: &str) -> bool {
+    match path.strip_prefix('/') {
+        Some("") => true,
+        Some(rest) => rest.split('/').all(|element| {
+            !element.is_empty()
+                && element
+                    .bytes()
+                    .all(|b| b.is_ascii_alphanumeric() || b == b'_')
+        }),
+        None => false,
+    }
+}
+
+/// two or more `.`-separated elements of `[A-Za-z0-9_]` not starting with a digit
+fn interface_name(name: &str) -> bool {
+    name.len() <= 255
+        && name.split('.').count() >= 2
+        && name.split('.').all(|element| {
+            element.bytes().next().is_some_and(|b| !b.is_ascii_digit())
+                && element
+                    .bytes()
+                    .all(|b| b.is_ascii_alphanumeric() || b == b'_')
+        })
+}
+
+fn message(serial: u32, kind: u8, flags: u8, fields: &[(u8, char, &str)], body: &[u8]) -> Vec<u8> {
+    let mut msg = vec![b'l', kind, flags, 1];
+    uint(&mut msg, body.len() as u32);
+    uint(&mut msg, serial);
+
+    uint(&mut msg, 0);
+    let start = msg.len();
+    for (code, sig, value) in fields {
+        pad(&mut msg, 8);
+        msg.push(*code);
+        signature(&mut msg, &sig.to_string());
+        match sig {
+            'g' => signature(&mut msg, value),
+            _ => string(&mut msg, value),
+        }
+    }
+
+    let len = (msg.len() - start) as u32;
+    msg[12..16].copy_from_slice(&len.to_le_bytes());
+
+    pad(&mut msg, 8);
+    msg.extend_from_slice(body);
+    msg
+}
+
+fn connect(address: &str) -> io::Result<UnixStream> {
+    let params = address
+        .strip_prefix("unix:")
+        .ok_or_else(|| io::Error::new(io::ErrorKind::Unsupported, "only unix transports"))?;
+
+    for param in params.split(',') {
+        let stream = match param.split_once('=') {
+            Some(("path", path)) => std::os::unix::net::UnixStream::connect(path)?,
+            Some(("abstract", name)) => {
+                let addr = SocketAddr::from_abstract_name(std::ffi::OsStr::new(name).as_bytes())?;
+                std::os::unix::net::UnixStream::connect_addr(&addr)?
+            }
+            _ => continue,
+        };
+
+        stream.set_nonblocking(true)?;
+        return UnixStream::from_std(stream);
+    }
+
+    Err(io::Error::new(
+        io::ErrorKind::InvalidInput,
+        "address names no socket",
+    ))
+}
+
+fn pad(buf: &mut Vec<u8>, align: usize) {
+    buf.resize(buf.len().next_multiple_of(align), 0);
+}
+
+fn uint(buf: &mut Vec<u8>, value: u32) {
+    pad(buf, 4);
+    buf.extend_from_slice(&value.to_le_bytes());
+}
+
+fn string(buf: &mut Vec<u8>, value: &str) {
+    uint(buf, value.len() as u32);
+    buf.extend_from_slice(value.as_bytes());
+    buf.push(0);
+}
+
+fn signature(buf: &mut Vec<u8>, value: &str) {
+    buf.push(value.len() as u8);
+    buf.extend_from_slice(value.as_bytes());
+    buf.push(0);
+}
+
+fn string_array(buf: &mut Vec<u8>, values: &[&str]) {
+    uint(buf, 0);
+    let len_at = buf.len() - 4;
+    let start = buf.len();
+
+    for value in values {
+        string(buf, value);
+    }
+
+    let len = (buf.len() - start) as u32;
+    buf[len_at..len_at + 4].copy_from_slice(&len.to_le_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signal_bytes() {
+        let fields = [
+            (1, 'o', "/a"),
+            (2, 's', "b.c"),
+            (3, 's', "E"),
+            (8, 'g', "s"),
+        ];
+        let mut body = Vec::new();
+        string(&mut body, "x");
+
+        #[rustfmt::skip]
+        let expected: &[u8] = &[
+            b'l', SIGNAL, NO_REPLY_EXPECTED, 1,
+            6, 0, 0, 0, // body length
+            7, 0, 0, 0, // serial
+            55, 0, 0, 0, // header fields length
+            // PATH
+            1, 1, b'o', 0, 2, 0, 0, 0, b'/', b'a', 0, 0, 0, 0, 0, 0,
+            // INTERFACE
+            2, 1, b's', 0, 3, 0, 0, 0, b'b', b'.', b'c', 0, 0, 0, 0, 0,
+            // MEMBER
+            3, 1, b's', 0, 1, 0, 0, 0, b'E', 0, 0, 0, 0, 0, 0, 0,
+            // SIGNATURE, then padding to the body
+            8, 1, b'g', 0, 1, b's', 0, 0,
+            // body
+            1, 0, 0, 0, b'x', 0,
+        ];
+
+        let msg = message(7, SIGNAL, NO_REPLY_EXPECTED, &fields, &body);
+        assert_eq!(msg, expected);
+        assert_eq!(frame(&msg), Some((SIGNAL, msg.len())));
+        assert_eq!(frame(&msg[..msg.len() - 1]), None);
+    }
+
+    #[test]
+    fn body_bytes() {
+        let mut body = Vec::new();
+        string(&mut body, "/t");
+        string_array(&mut body, &["CREATE", "ISDIR"]);
+        uint(&mut body, 0x4000_0100);
+        uint(&mut body, 0);
+
+        #[rustfmt::skip]
+        let expected: &[u8] = &[
+            2, 0, 0, 0, b'/', b't', 0, 0,
+            // array length, up to the end of the last element
+            22, 0, 0, 0,
+            6, 0, 0, 0, b'C', b'R', b'E', b'A', b'T', b'E', 0, 0,
+            5, 0, 0, 0, b'I', b'S', b'D', b'I', b'R', 0, 0, 0,
+            0x00, 0x01, 0x00, 0x40,
+            0, 0, 0, 0,
+        ];
+        assert_eq!(body, expected);
+    }
+
+    #[test]
+    fn reads_error_names() {
+        let fields = [
+            (7, 's', "org.freedesktop.DBus"),
+            (8, 'g', "s"),
+            (4, 's', "org.freedesktop.DBus.Error.AccessDenied"),
+        ];
+        let mut body = Vec::new();
+        string(&mut body, "denied");
+        let msg = message(1, ERROR, 0, &fields, &body);
+        assert_eq!(frame(&msg), Some((ERROR, msg.len())));
+        assert_eq!(
+            error_name(&msg).as_deref(),
+            Some("org.freedesktop.DBus.Error.AccessDenied")
+        );
+
+        let msg = message(1, METHOD_RETURN, 0, &[(3, 's', "Hello")], &[]);
+        assert_eq!(error_name(&msg), None);
+    }
+
+    #[tokio::test]
+    async fn drains_the_bus() {
+        let (stream, mut bus) = UnixStream::pair().unwrap();
+        let mut emitter = DbusEmitter {
+            stream,
+            inbox: Vec::new(),
+            serial: 1,
+            path: DEFAULT_PATH.to_string(),
+            interface: DEFAULT_INTERFACE.to_string(),
+            filters: Vec::new(),
+        };
+
+        let acquired = [
+            (1, 'o', "/org/freedesktop/DBus"),
+            (2, 's', "org.freedesktop.DBus"),
+            (3, 's', "NameAcquired"),
+        ];
+        let mut body = Vec::new();
+        string(&mut body, ":1.1");
+        let signal = message(1, SIGNAL, NO_REPLY_EXPECTED, &acquired, &body);
+        bus.write_all(&signal).await.unwrap();
+        bus.write_all(&signal[..20]).await.unwrap();
+        emitter.stream.readable().await.unwrap();
+
+        emitter.drain().unwrap();
+        assert_eq!(emitter.inbox, &signal[..20]);
+
+        bus.write_all(&signal[20..]).await.unwrap();
+        let error = message(
+            2,
+            ERROR,
+            0,
+            &[(4, 's', "org.freedesktop.DBus.Error.LimitsExceeded")],
+            &[],
+        );
+        bus.write_all(&error).await.unwrap();
+        emitter.stream.readable().await.unwrap();
+
+        let err = emitter.drain().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::ConnectionRefused);
+        assert!(err.to_string().contains("LimitsExceeded"));
+        assert!(emitter.inbox.is_empty());
+
+        drop(bus);
+        emitter.stream.readable().await.unwrap();
+        let err = emitter.drain().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn object_paths() {
+        for path in ["/", "/a", "/io/github/eulegang/Tokinotify", "/a_1/B2"] {
+            assert!(object_path(path), "{path:?}");
+        }
+
+        for path in ["", "a", "/a/", "//a", "/a//b", "/a-b", "/a.b", "/ä"] {
+            assert!(!object_path(path), "{path:?}");
+        }
+    }
+
+    #[test]
+    fn interface_names() {
+        for name in ["a.b", "io.github.eulegang.Tokinotify", "_a.b_2"] {
+            assert!(interface_name(name), "{name:?}");
+        }
+
+        let long = format!("a.{}", "b".repeat(254));
+        for name in ["", "a", "a.", ".a", "a..b", "a.1b", "a-b.c", long.as_str()] {
+            assert!(!interface_name(name), "{name:?}");
+        }
+    }
+}
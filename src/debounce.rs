@@ -0,0 +1,242 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    io,
+    path::PathBuf,
+    time::Duration,
+};
+
+use tokio::time::Instant;
+
+use crate::{Event, Glob, INotify, Mask, Watch};
+
+/// Coalesces bursts of events on the same path
+///
+/// An event is held until its path has been quiet for the quiet period,
+/// the masks of events arriving meanwhile are merged into it. With a max
+/// latency a continuously changing path still emits at least that often.
+/// Events on hot paths skip the wait and are delivered as they arrive.
+///
+/// Control events (Q_OVERFLOW, UNMOUNT, IGNORED) are never held or merged.
+/// The events held for a watch going away are delivered just before them.
+pub struct Debounce {
+    quiet: Duration,
+    max_latency: Option<Duration>,
+    hot: Vec<Glob>,
+    pending: HashMap<(Watch, PathBuf), Pending>,
+    ready: VecDeque<Event>,
+}
+
+struct Pending {
+    first: Instant,
+    last: Instant,
+    event: Event,
+}
+
+impl Debounce {
+    /// Hold events until their path has been quiet for `quiet`
+    pub fn new(quiet: Duration) -> Self {
+        Self {
+            quiet,
+            max_latency: None,
+            hot: Vec::new(),
+            pending: HashMap::new(),
+            ready: VecDeque::new(),
+        }
+    }
+
+    /// Emit a held event no later than `max_latency` after it first arrived
+    pub fn max_latency(mut self, max_latency: Duration) -> Self {
+        self.max_latency = Some(max_latency);
+        self
+    }
+
+    /// Deliver events whose full path matches `glob` immediately, e.g. for a trigger file
+    pub fn hot(mut self, glob: Glob) -> Self {
+        self.hot.push(glob);
+        self
+    }
+
+    /// The number of paths with held events
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Wait for the next coalesced event
+    pub async fn watch(&mut self, inotify: &mut INotify) -> io::Result<Event> {
+        loop {
+            if let Some(event) = self.take_due() {
+                return Ok(event);
+            }
+
+            let deadline = self.next_deadline();
+
+            // a deadline interrupting the watch loses nothing, it is cancel safe
+            tokio::select! {
+                _ = sleep(deadline) => (),
+                event = inotify.watch() => {
+                    let event = event?;
+                    if (event.mask & Mask::CONTROL).0 != 0 {
+                        self.pass(event);
+                    } else if self.is_hot(inotify, &event) {
+                        return Ok(event);
+                    } else {
+                        self.hold(event, Instant::now());
+                    }
+                }
+            }
+        }
+    }
+
+    /// Take a held event whose quiet period is over, without waiting
+    pub(crate) fn take_due(&mut self) -> Option<Event> {
+        if let Some(event) = self.ready.pop_front() {
+            return Some(event);
+        }
+
+        let key = self.due(Instant::now())?;
+        let pending = self.pending.remove(&key).expect("due key is pending");
+        Some(pending.event)
+    }
+
+    pub(crate) fn is_hot(&self, inotify: &INotify, event: &Event) -> bool {
+        if self.hot.is_empty() {
+            return false;
+        }
+
+        let path = inotify.resolve(event).unwrap_or_else(|| event.path.clone());
+        self.hot.iter().any(|glob| glob.matches(&path))
+    }
+
+    fn deadline(&self, pending: &Pending) -> Instant {
+        let quiet = pending.last + self.quiet;
+
+        match self.max_latency {
+            Some(max) => quiet.min(pending.first + max),
+            None => quiet,
+        }
+    }
+
+    fn due(&self, now: Instant) -> Option<(Watch, PathBuf)> {
+        self.pending
+            .iter()
+            .filter(|(_, p)| self.deadline(p) <= now)
+            .min_by_key(|(_, p)| self.deadline(p))
+            .map(|(key, _)| key.clone())
+    }
+
+    pub(crate) fn next_deadline(&self) -> Option<Instant> {
+        self.pending.values().map(|p| self.deadline(p)).min()
+    }
+
+    /// queue a control event, after whatever is held for a watch it ends
+    pub(crate) fn pass(&mut self, event: Event) {
+        if event.mask.contains(Mask::IGNORED) {
+            let ended: Vec<_> = self
+                .pending
+                .keys()
+                .filter(|(watch, _)| *watch == event.watch)
+                .cloned()
+                .collect();
+            let mut held: Vec<Pending> = ended
+                .iter()
+                .filter_map(|key| self.pending.remove(key))
+                .collect();
+            held.sort_by_key(|pending| pending.first);
+            self.ready.extend(held.into_iter().map(|pending| pending.event));
+        }
+
+        self.ready.push_back(event);
+    }
+
+    pub(crate) fn hold(&mut self, event: Event, now: Instant) {
+        let key = (event.watch, event.path.clone());
+
+        match self.pending.get_mut(&key) {
+            Some(pending) => {
+                let mask = pending.event.mask | event.mask;
+                pending.event = event;
+                pending.event.mask = mask;
+                pending.last = now;
+            }
+            None => {
+                self.pending.insert(
+                    key,
+                    Pending {
+                        first: now,
+                        last: now,
+                        event,
+                    },
+                );
+            }
+        }
+    }
+}
+
+async fn sleep(deadline: Option<Instant>) {
+    match deadline {
+        Some(deadline) => tokio::time::sleep_until(deadline).await,
+        None => std::future::pending().await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::scratch;
+
+    #[tokio::test]
+    async fn control_events_are_not_held() {
+        let dir = scratch("debounce");
+
+        let mut inotify = INotify::new().unwrap();
+        let watch = inotify.add(&dir, Mask::CREATE).unwrap();
+        let mut debounce = Debounce::new(Duration::from_secs(3600));
+
+        std::fs::File::create(dir.join("f")).unwrap();
+        let held = tokio::time::timeout(Duration::from_millis(50), debounce.watch(&mut inotify));
+        assert!(held.await.is_err());
+        assert_eq!(debounce.pending(), 1);
+
+        // the held event comes first, both without waiting out the quiet period
+        inotify.rm(watch).unwrap();
+        let event = tokio::time::timeout(Duration::from_secs(10), debounce.watch(&mut inotify))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(event.mask, Mask::CREATE);
+        assert_eq!(event.path, PathBuf::from("f"));
+
+        let event = tokio::time::timeout(Duration::from_secs(10), debounce.watch(&mut inotify))
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(event.mask.contains(Mask::IGNORED));
+        assert_eq!(debounce.pending(), 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn max_latency_bounds_an_endless_stream() {
+        let watch = Watch::from_raw(1);
+        let mut debounce = Debounce::new(Duration::from_millis(100))
+            .max_latency(Duration::from_millis(250));
+
+        // a write every 50ms never leaves the path quiet for 100ms
+        for _ in 0..5 {
+            debounce.hold(Event::new(watch, Mask::MODIFY, "log"), Instant::now());
+            assert!(debounce.take_due().is_none());
+            tokio::time::advance(Duration::from_millis(50)).await;
+        }
+
+        debounce.hold(Event::new(watch, Mask::CLOSE_WRITE, "log"), Instant::now());
+        let event = debounce.take_due().unwrap();
+        assert_eq!(event.mask, Mask::MODIFY | Mask::CLOSE_WRITE);
+        assert_eq!(debounce.pending(), 0);
+
+        // the stream goes on, the next event is held afresh
+        debounce.hold(Event::new(watch, Mask::MODIFY, "log"), Instant::now());
+        tokio::time::advance(Duration::from_millis(99)).await;
+        assert!(debounce.take_due().is_none());
+        tokio::time::advance(Duration::from_millis(1)).await;
+        assert_eq!(debounce.take_due().unwrap().mask, Mask::MODIFY);
+    }
+}
@@ -0,0 +1,184 @@
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    io,
+    path::{Path, PathBuf},
+};
+
+use crate::{Debounce, Event, INotify, Mask, Watch};
+
+/// The events that change the content of an input
+const CHANGED: Mask = Mask(
+    Mask::CLOSE_WRITE.0 | Mask::MOVED_TO.0 | Mask::MOVED_FROM.0 | Mask::DELETE.0,
+);
+
+/// Maps changed inputs to the outputs built from them
+///
+/// Register "output X depends on inputs A, B and C" and wait for the
+/// outputs made dirty by changes to their inputs, as needed to rebuild
+/// templates or pages on a hot reloading server. Inputs are watched
+/// through their directories, so editors replacing files by renaming are
+/// followed. Changes are debounced, and outputs dirtied by inputs which
+/// settle together are reported in one batch.
+///
+/// The watches are added to and removed from the [INotify] as inputs come
+/// and go, it should not be shared with other watches of those directories.
+pub struct DependencyWatcher {
+    debounce: Debounce,
+    inputs: HashMap<PathBuf, HashSet<PathBuf>>,
+    dependents: HashMap<PathBuf, HashSet<PathBuf>>,
+    dirs: HashMap<PathBuf, (Watch, usize)>,
+}
+
+impl DependencyWatcher {
+    /// Debounce input changes with `debounce`
+    pub fn new(debounce: Debounce) -> Self {
+        Self {
+            debounce,
+            inputs: HashMap::new(),
+            dependents: HashMap::new(),
+            dirs: HashMap::new(),
+        }
+    }
+
+    /// Declare the inputs of an output, replacing any declared before
+    pub fn depend<P: AsRef<Path>>(
+        &mut self,
+        inotify: &mut INotify,
+        output: &Path,
+        inputs: impl IntoIterator<Item = P>,
+    ) -> io::Result<()> {
+        let inputs: HashSet<PathBuf> = inputs
+            .into_iter()
+            .map(|input| normalize(input.as_ref()))
+            .collect();
+
+        for input in &inputs {
+            if !self.dependents.contains_key(input) {
+                self.watch_dir(inotify, input)?;
+            }
+
+            self.dependents
+                .entry(input.clone())
+                .or_default()
+                .insert(output.to_path_buf());
+        }
+
+        if let Some(previous) = self.inputs.insert(output.to_path_buf(), inputs) {
+            let current = self.inputs[output].clone();
+            for input in previous.difference(&current) {
+                self.drop_dependent(inotify, input, output)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Stop tracking an output
+    pub fn forget(&mut self, inotify: &mut INotify, output: &Path) -> io::Result<()> {
+        let Some(inputs) = self.inputs.remove(output) else {
+            return Ok(());
+        };
+
+        for input in &inputs {
+            self.drop_dependent(inotify, input, output)?;
+        }
+
+        Ok(())
+    }
+
+    /// The inputs declared for an output
+    pub fn inputs(&self, output: &Path) -> Option<&HashSet<PathBuf>> {
+        self.inputs.get(output)
+    }
+
+    /// Wait for outputs made dirty by changed inputs, sorted
+    pub async fn dirty(&mut self, inotify: &mut INotify) -> io::Result<Vec<PathBuf>> {
+        loop {
+            let mut dirty = BTreeSet::new();
+
+            let event = self.debounce.watch(inotify).await?;
+            self.mark(inotify, &event, &mut dirty);
+
+            while let Some(event) = self.debounce.take_due() {
+                self.mark(inotify, &event, &mut dirty);
+            }
+
+            if !dirty.is_empty() {
+                return Ok(dirty.into_iter().collect());
+            }
+        }
+    }
+
+    fn mark(&self, inotify: &INotify, event: &Event, dirty: &mut BTreeSet<PathBuf>) {
+        if event.mask.contains(Mask::Q_OVERFLOW) {
+            // any input may have changed
+            dirty.extend(self.inputs.keys().cloned());
+            return;
+        }
+
+        if (event.mask & CHANGED).0 == 0 {
+            return;
+        }
+
+        let Some(path) = inotify.resolve(event) else {
+            return;
+        };
+
+        if let Some(outputs) = self.dependents.get(&path) {
+            dirty.extend(outputs.iter().cloned());
+        }
+    }
+
+    fn watch_dir(&mut self, inotify: &mut INotify, input: &Path) -> io::Result<()> {
+        let dir = input.parent().expect("normalized inputs have a parent");
+
+        match self.dirs.get_mut(dir) {
+            Some((_, inputs)) => *inputs += 1,
+            None => {
+                let watch = inotify.add(dir, CHANGED | Mask::ONLYDIR)?;
+                self.dirs.insert(dir.to_path_buf(), (watch, 1));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn drop_dependent(
+        &mut self,
+        inotify: &mut INotify,
+        input: &Path,
+        output: &Path,
+    ) -> io::Result<()> {
+        let Some(outputs) = self.dependents.get_mut(input) else {
+            return Ok(());
+        };
+
+        outputs.remove(output);
+        if !outputs.is_empty() {
+            return Ok(());
+        }
+
+        self.dependents.remove(input);
+
+        let dir = input.parent().expect("normalized inputs have a parent");
+        if let Some((watch, inputs)) = self.dirs.get_mut(dir) {
+            *inputs -= 1;
+            if *inputs == 0 {
+                let watch = *watch;
+                self.dirs.remove(dir);
+                inotify.rm(watch)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// spell an input as events for it resolve, its directory joined with its name
+fn normalize(input: &Path) -> PathBuf {
+    match (input.parent(), input.file_name()) {
+        (Some(dir), Some(name)) if !dir.as_os_str().is_empty() => dir.join(name),
+        (_, Some(name)) => Path::new(".").join(name),
+        _ => input.to_path_buf(),
+    }
+}
@@ -0,0 +1,221 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use crate::{capabilities::limit, pseudo, Capability, Mount, Quirk};
+
+/// What [doctor] found about the environment events are watched in
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnosis {
+    /// The per user watch limit (`fs.inotify.max_user_watches`)
+    pub max_user_watches: Option<usize>,
+
+    /// The per user instance limit (`fs.inotify.max_user_instances`)
+    pub max_user_instances: Option<usize>,
+
+    /// The per instance queue limit (`fs.inotify.max_queued_events`)
+    pub max_queued_events: Option<usize>,
+
+    /// inotify instances open in this process
+    pub instances: usize,
+
+    /// Watches held by this process's instances
+    pub watches: usize,
+
+    /// What was found for each root, in the order given
+    pub roots: Vec<RootDiagnosis>,
+}
+
+/// What [doctor] found about one watch root
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RootDiagnosis {
+    /// The root as given
+    pub path: PathBuf,
+
+    /// Why the root could not be inspected
+    pub error: Option<io::ErrorKind>,
+
+    /// The root lives on a filesystem which never generates events
+    pub pseudo: bool,
+
+    /// The mount the root lives on and why its events may be incomplete
+    pub mount: Option<Capability>,
+}
+
+/// A likely reason for missing events
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Problem {
+    /// This process holds over half of the per user watch limit
+    Watches {
+        /// Watches held
+        used: usize,
+
+        /// The limit
+        limit: usize,
+    },
+
+    /// This process holds over half of the per user instance limit
+    Instances {
+        /// Instances open
+        used: usize,
+
+        /// The limit
+        limit: usize,
+    },
+
+    /// A root could not be inspected
+    Inaccessible(PathBuf, io::ErrorKind),
+
+    /// A root lives on a pseudo filesystem, see [crate::PseudoFs]
+    Pseudo(PathBuf),
+
+    /// A root lives on a mount whose events may be incomplete
+    Quirks(PathBuf, Vec<Quirk>),
+}
+
+impl Diagnosis {
+    /// the likely reasons for missing events, empty when none were found
+    pub fn problems(&self) -> Vec<Problem> {
+        let mut problems = Vec::new();
+
+        if let Some(limit) = self.max_user_watches {
+            if self.watches > limit / 2 {
+                problems.push(Problem::Watches {
+                    used: self.watches,
+                    limit,
+                });
+            }
+        }
+
+        if let Some(limit) = self.max_user_instances {
+            if self.instances > limit / 2 {
+                problems.push(Problem::Instances {
+                    used: self.instances,
+                    limit,
+                });
+            }
+        }
+
+        for root in &self.roots {
+            if let Some(kind) = root.error {
+                problems.push(Problem::Inaccessible(root.path.clone(), kind));
+            } else if root.pseudo {
+                problems.push(Problem::Pseudo(root.path.clone()));
+            } else if let Some(mount) = root.mount.as_ref().filter(|m| !m.reliable()) {
+                problems.push(Problem::Quirks(root.path.clone(), mount.quirks.clone()));
+            }
+        }
+
+        problems
+    }
+}
+
+/// Check the kernel limits, this process's use of them and the filesystems of `roots`
+///
+/// Nothing here fails, whatever can not be read is left out of the report.
+pub fn doctor<P: AsRef<Path>>(roots: &[P]) -> Diagnosis {
+    let (instances, watches) = usage();
+    let table = Mount::table().ok();
+
+    let roots = roots
+        .iter()
+        .map(|root| {
+            let path = root.as_ref();
+            let mut diagnosis = RootDiagnosis {
+                path: path.to_path_buf(),
+                error: None,
+                pseudo: false,
+                mount: None,
+            };
+
+            match pseudo::is_pseudo(path) {
+                Ok(pseudo) => diagnosis.pseudo = pseudo,
+                Err(err) => diagnosis.error = Some(err.kind()),
+            }
+
+            if diagnosis.error.is_none() {
+                // the mount table lists resolved paths
+                let resolved = std::fs::canonicalize(path).unwrap_or_else(|_| path.into());
+                diagnosis.mount = table
+                    .as_deref()
+                    .and_then(|table| Capability::of(table, &resolved));
+            }
+
+            diagnosis
+        })
+        .collect();
+
+    Diagnosis {
+        max_user_watches: limit("max_user_watches"),
+        max_user_instances: limit("max_user_instances"),
+        max_queued_events: limit("max_queued_events"),
+        instances,
+        watches,
+        roots,
+    }
+}
+
+/// inotify instances and watches of this process, from `/proc/self`
+fn usage() -> (usize, usize) {
+    let Ok(fds) = std::fs::read_dir("/proc/self/fd") else {
+        return (0, 0);
+    };
+
+    let mut instances = 0;
+    let mut watches = 0;
+
+    for fd in fds.flatten() {
+        let Ok(target) = std::fs::read_link(fd.path()) else {
+            continue;
+        };
+        if target != Path::new("anon_inode:inotify") {
+            continue;
+        }
+
+        instances += 1;
+
+        let info = Path::new("/proc/self/fdinfo").join(fd.file_name());
+        if let Ok(info) = std::fs::read_to_string(info) {
+            watches += info
+                .lines()
+                .filter(|line| line.starts_with("inotify wd:"))
+                .count();
+        }
+    }
+
+    (instances, watches)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{testing::scratch, INotify, Mask};
+
+    #[test]
+    fn reports_usage_and_unwatchable_roots() {
+        let dir = scratch("doctor");
+        let mut inotify = INotify::new().unwrap();
+        inotify.add(&dir, Mask::CREATE).unwrap();
+        inotify.add(Path::new("/"), Mask::CREATE).unwrap();
+
+        let missing = dir.join("missing");
+        let proc = Path::new("/proc/self");
+        let diagnosis = doctor(&[dir.as_ref(), proc, &missing]);
+
+        // other tests hold instances of their own
+        assert!(diagnosis.instances >= 1);
+        assert!(diagnosis.watches >= 2);
+        assert_eq!(diagnosis.max_user_watches, limit("max_user_watches"));
+
+        let roots: Vec<_> = diagnosis.roots.iter().map(|r| (r.error, r.pseudo)).collect();
+        assert_eq!(
+            roots,
+            [(None, false), (None, true), (Some(io::ErrorKind::NotFound), false)]
+        );
+
+        let problems = diagnosis.problems();
+        assert!(problems.contains(&Problem::Pseudo(proc.to_path_buf())));
+        assert!(problems.contains(&Problem::Inaccessible(missing, io::ErrorKind::NotFound)));
+    }
+}
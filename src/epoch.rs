@@ -0,0 +1,119 @@
+use std::path::Path;
+
+use crate::{INotify, Identity, Mask, RawEvent, Removal, Watch};
+
+/// Where an event handed out by an [INotify] comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Origin {
+    /// Read from the kernel for the current registration of its watch
+    Kernel,
+
+    /// Made up by polling a pseudo filesystem path
+    Polled,
+
+    /// Read from the kernel but queued for an earlier watch given the same descriptor
+    Stale,
+}
+
+/// An earlier registration of a descriptor whose IGNORED is still queued
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Retired {
+    reason: Removal,
+
+    /// Removed with [Drain::Discard](crate::Drain::Discard), its queued events are dropped
+    discard: bool,
+}
+
+impl INotify {
+    /// the generation of a watch descriptor's current registration
+    ///
+    /// The kernel may hand a descriptor out again once its watch is gone,
+    /// while events for the old watch are still queued. Every registration
+    /// gets a new epoch, events queued for an earlier one are delivered
+    /// with [Event::is_stale](crate::Event::is_stale) rather than under the
+    /// new watch's path.
+    pub fn epoch(&self, watch: Watch) -> Option<u64> {
+        self.epochs.get(&watch).copied()
+    }
+
+    /// Start a new epoch when the kernel handed out a descriptor whose last watch is still queued
+    pub(crate) fn renew(&mut self, watch: Watch, path: &Path) {
+        if self.recycled(watch, path) {
+            let discard = self.removed.remove(&watch);
+            let explicit = discard | self.draining.remove(&watch);
+            let dying = self.dying.remove(&watch);
+            let reason = match (explicit, dying) {
+                (true, _) => Removal::ExplicitlyRemoved,
+                (false, Some(reason)) => reason,
+                (false, None) => Removal::Deleted,
+            };
+
+            self.oneshot.remove(&watch);
+            self.release(watch, reason);
+            self.retiring
+                .entry(watch)
+                .or_default()
+                .push_back(Retired { reason, discard });
+        }
+
+        if !self.epochs.contains_key(&watch) {
+            self.next_epoch += 1;
+            self.epochs.insert(watch, self.next_epoch);
+        }
+    }
+
+    /// whether a descriptor returned by the kernel names another watch than the one registered
+    ///
+    /// Without an IGNORED pending a descriptor already registered is taken
+    /// to be the same watch, unless identities show another object.
+    fn recycled(&self, watch: Watch, path: &Path) -> bool {
+        if self.removed.contains(&watch)
+            || self.draining.contains(&watch)
+            || self.dying.contains_key(&watch)
+        {
+            return true;
+        }
+        if !self.paths.contains_key(&watch) {
+            return false;
+        }
+
+        self.identity(watch)
+            .is_some_and(|old| Identity::of(path).is_ok_and(|new| new != old))
+    }
+
+    /// Classify an event read from the kernel, ending an earlier epoch on its IGNORED
+    ///
+    /// Returns the removal of a stale IGNORED, its state was released when
+    /// the descriptor was handed out again.
+    pub(crate) fn stale(&mut self, event: &RawEvent) -> Option<Option<Removal>> {
+        let retiring = self.retiring.get_mut(&event.watch)?;
+        let reason = retiring.front()?.reason;
+
+        if !event.mask.contains(Mask::IGNORED) {
+            return Some(None);
+        }
+
+        retiring.pop_front();
+        if retiring.is_empty() {
+            self.retiring.remove(&event.watch);
+        }
+
+        Some(Some(reason))
+    }
+
+    /// whether an event is dropped, those queued before [INotify::rm] returned are except IGNORED
+    pub(crate) fn discarded(&self, event: &RawEvent, removal: Option<Removal>, origin: Origin) -> bool {
+        if removal.is_some() {
+            return false;
+        }
+
+        match origin {
+            Origin::Stale => self
+                .retiring
+                .get(&event.watch)
+                .and_then(|retiring| retiring.front())
+                .is_some_and(|retired| retired.discard),
+            _ => self.removed.contains(&event.watch),
+        }
+    }
+}
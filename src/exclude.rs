@@ -0,0 +1,260 @@
+use std::{
+    cell::OnceCell,
+    io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use crate::{Filter, Glob, GlobError};
+
+/// Rules for paths left unwatched, built once and shared
+///
+/// Rules come from globs or gitignore files, the last rule matching a
+/// path decides as in git. A path is excluded along with everything
+/// beneath it, a negated rule can not bring back a path whose parent is
+/// excluded. Clones share the compiled rules.
+#[derive(Debug, Clone, Default)]
+pub struct ExcludeSet {
+    rules: Arc<Vec<Rules>>,
+}
+
+/// A run of rules agreeing in everything but their patterns
+#[derive(Debug, Clone)]
+struct Rules {
+    filter: Filter,
+    negated: bool,
+    dir_only: bool,
+    base: Option<PathBuf>,
+}
+
+impl ExcludeSet {
+    /// Build a set excluding nothing
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Build a set from a gitignore file, relative patterns are relative to its directory
+    pub fn from_gitignore(path: &Path) -> io::Result<Self> {
+        let contents = std::fs::read_to_string(path)?;
+        let base = path.parent().unwrap_or(Path::new("/"));
+
+        let mut set = Self::new();
+        set.add_gitignore(base, &contents)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        Ok(set)
+    }
+
+    /// exclude paths matching `glob`
+    pub fn add(&mut self, glob: Glob) {
+        self.push(glob, false, false, None);
+    }
+
+    /// add the rules of a gitignore file whose directory is `base`
+    pub fn add_gitignore(&mut self, base: &Path, contents: &str) -> Result<(), GlobError> {
+        for line in contents.lines() {
+            let line = line.trim_end();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (negated, line) = match line.strip_prefix('!') {
+                Some(rest) => (true, rest),
+                None => (false, line.strip_prefix('\\').unwrap_or(line)),
+            };
+            let (dir_only, line) = match line.strip_suffix('/') {
+                Some(rest) => (true, rest),
+                None => (false, line),
+            };
+
+            // a slash other than a trailing one anchors the pattern to `base`
+            let pattern = match line.strip_prefix("**/") {
+                Some(rest) if !rest.contains('/') => rest.to_string(),
+                _ if line.contains('/') => {
+                    format!("{}/{}", escape(base), line.trim_start_matches('/'))
+                }
+                _ => line.to_string(),
+            };
+
+            let glob = Glob::new(&pattern)?;
+            self.push(glob, negated, dir_only, Some(base.to_path_buf()));
+        }
+
+        Ok(())
+    }
+
+    /// whether no rule was added
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// whether a path or any directory above it is excluded
+    pub fn matches(&self, path: &Path) -> bool {
+        self.matches_beneath(Path::new(""), path)
+    }
+
+    /// whether a path or a directory between it and `root` is excluded, `root` itself is not tested
+    pub fn matches_beneath(&self, root: &Path, path: &Path) -> bool {
+        if self.rules.is_empty() {
+            return false;
+        }
+
+        let mut above: Vec<&Path> = path
+            .ancestors()
+            .take_while(|dir| *dir != root && !dir.as_os_str().is_empty())
+            .collect();
+        above.reverse();
+
+        let Some((last, dirs)) = above.split_last() else {
+            return false;
+        };
+
+        dirs.iter().any(|dir| self.decide(dir, &|| true)) || {
+            let is_dir = OnceCell::new();
+            self.decide(last, &|| {
+                *is_dir.get_or_init(|| last.symlink_metadata().is_ok_and(|m| m.is_dir()))
+            })
+        }
+    }
+
+    /// whether the last rule matching `path` excludes it
+    fn decide(&self, path: &Path, is_dir: &dyn Fn() -> bool) -> bool {
+        self.rules
+            .iter()
+            .rev()
+            .filter(|rules| {
+                rules
+                    .base
+                    .as_ref()
+                    .is_none_or(|base| path.starts_with(base))
+            })
+            .find(|rules| rules.filter.matches(path) && (!rules.dir_only || is_dir()))
+            .is_some_and(|rules| !rules.negated)
+    }
+
+    fn push(&mut self, glob: Glob, negated: bool, dir_only: bool, base: Option<PathBuf>) {
+        let rules = Arc::make_mut(&mut self.rules);
+
+        match rules.last_mut() {
+            Some(last)
+                if last.negated == negated && last.dir_only == dir_only && last.base == base =>
+            {
+                last.filter.push(glob)
+            }
+            _ => rules.push(Rules {
+                filter: [glob].into_iter().collect(),
+                negated,
+                dir_only,
+                base,
+            }),
+        }
+    }
+}
+
+impl FromIterator<Glob> for ExcludeSet {
+    fn from_iter<I: IntoIterator<Item = Glob>>(iter: I) -> Self {
+        let mut set = ExcludeSet::new();
+        for glob in iter {
+            set.add(glob);
+        }
+        set
+    }
+}
+
+/// a path as a glob matching only itself
+fn escape(path: &Path) -> String {
+    let mut out = String::new();
+    for c in path.to_string_lossy().chars() {
+        if matches!(c, '*' | '?' | '[' | '\\') {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out.trim_end_matches('/').to_string()
+}
+
+#[cfg(all(test, feature = "tokio"))]
+mod tests {
+    use super::*;
+    use crate::{testing::scratch, INotify, Mask, ProjectEvent, ProjectWatcher, RootSettings};
+    use std::{collections::HashSet, time::Duration};
+
+    #[test]
+    fn gitignore_rules_decide_in_order() {
+        let dir = scratch("exclude-rules");
+        std::fs::create_dir(dir.join("target")).unwrap();
+        std::fs::write(
+            dir.join(".gitignore"),
+            "# build output\ntarget/\n*.log\n!keep.log\n",
+        )
+        .unwrap();
+
+        let set = ExcludeSet::from_gitignore(&dir.join(".gitignore")).unwrap();
+        assert!(set.matches(&dir.join("target")));
+        assert!(set.matches(&dir.join("target/debug/keep.log")));
+        assert!(set.matches(&dir.join("src/x.log")));
+        assert!(!set.matches(&dir.join("src/keep.log")));
+        // only directories are excluded by a trailing slash
+        assert!(!set.matches(&dir.join("src/target")));
+
+        // rules are relative to the gitignore's directory
+        assert!(!set.matches(Path::new("/elsewhere/x.log")));
+    }
+
+    #[tokio::test]
+    async fn overlapping_roots_share_one_set() {
+        let dir = scratch("exclude-roots");
+        for sub in ["target", "app/target", "app/src"] {
+            std::fs::create_dir_all(dir.join(sub)).unwrap();
+        }
+        std::fs::write(dir.join(".gitignore"), "target/\n*.log\n!keep.log\n").unwrap();
+        let set = ExcludeSet::from_gitignore(&dir.join(".gitignore")).unwrap();
+
+        let mut inotify = INotify::new().unwrap();
+        let mut project = ProjectWatcher::new();
+        let settings = RootSettings {
+            mask: Mask::CREATE,
+            recursive: true,
+            exclude: set.clone(),
+        };
+        let outer = project
+            .add_root(&mut inotify, &dir, settings.clone())
+            .await
+            .unwrap();
+        let inner = project
+            .add_root(&mut inotify, &dir.join("app"), settings)
+            .await
+            .unwrap();
+
+        let watched: HashSet<PathBuf> = inotify.watches().map(|(_, p)| p.to_path_buf()).collect();
+        let expected = [dir.to_path_buf(), dir.join("app"), dir.join("app/src")];
+        assert_eq!(watched, HashSet::from(expected));
+
+        for name in ["x.log", "keep.log", "main.rs"] {
+            std::fs::File::create(dir.join("app/src").join(name)).unwrap();
+        }
+
+        let mut seen = Vec::new();
+        for _ in 0..4 {
+            let ProjectEvent { root, event } =
+                tokio::time::timeout(Duration::from_secs(10), project.watch(&mut inotify))
+                    .await
+                    .unwrap()
+                    .unwrap();
+            seen.push((root, event.path));
+        }
+        let keep = PathBuf::from("keep.log");
+        let main = PathBuf::from("main.rs");
+        assert_eq!(
+            seen,
+            [
+                (outer, keep.clone()),
+                (inner, keep),
+                (outer, main.clone()),
+                (inner, main)
+            ]
+        );
+
+        // the same rules serve a scanner of the application's own
+        assert!(set.matches(&dir.join("app/src/x.log")));
+    }
+}
@@ -0,0 +1,106 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    io,
+};
+
+use crate::{Event, INotify, Watch};
+
+/// Round-robin delivery across watches
+///
+/// Every event already read from the kernel is queued per watch and
+/// delivered one watch at a time, so a flooding directory can not starve
+/// quieter ones queued behind it.
+#[derive(Default)]
+pub struct Fair {
+    queues: HashMap<Watch, VecDeque<Event>>,
+    order: VecDeque<Watch>,
+}
+
+impl Fair {
+    /// Build an empty fairness layer
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The number of events queued
+    pub fn pending(&self) -> usize {
+        self.queues.values().map(VecDeque::len).sum()
+    }
+
+    /// Wait for the next event, taking turns between watches
+    pub async fn watch(&mut self, inotify: &mut INotify) -> io::Result<Event> {
+        if self.order.is_empty() {
+            let event = inotify.watch().await?;
+            self.push(event);
+        }
+
+        while inotify.buffered() {
+            let Some(event) = inotify.try_watch().await? else {
+                break;
+            };
+            self.push(event);
+        }
+
+        Ok(self.pop().expect("an event was just queued"))
+    }
+
+    fn push(&mut self, event: Event) {
+        let queue = self.queues.entry(event.watch).or_default();
+        if queue.is_empty() {
+            self.order.push_back(event.watch);
+        }
+
+        queue.push_back(event);
+    }
+
+    fn pop(&mut self) -> Option<Event> {
+        let watch = self.order.pop_front()?;
+        let queue = self.queues.get_mut(&watch)?;
+        let event = queue.pop_front();
+
+        if queue.is_empty() {
+            self.queues.remove(&watch);
+        } else {
+            self.order.push_back(watch);
+        }
+
+        event
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{testing::scratch, Mask};
+    use std::path::Path;
+
+    #[tokio::test]
+    async fn takes_turns_between_watches() {
+        let dir = scratch("fair");
+        std::fs::create_dir(dir.join("busy")).unwrap();
+        std::fs::create_dir(dir.join("quiet")).unwrap();
+
+        let mut inotify = INotify::new().unwrap();
+        let busy = inotify.add(&dir.join("busy"), Mask::CREATE).unwrap();
+        let quiet = inotify.add(&dir.join("quiet"), Mask::CREATE).unwrap();
+
+        for name in ["1", "2", "3"] {
+            std::fs::write(dir.join("busy").join(name), "").unwrap();
+        }
+        std::fs::write(dir.join("quiet/1"), "").unwrap();
+
+        let mut fair = Fair::new();
+        let mut seen = Vec::new();
+        for _ in 0..4 {
+            let event = fair.watch(&mut inotify).await.unwrap();
+            seen.push((event.watch, event.path));
+        }
+
+        let at = |watch, name: &str| (watch, Path::new(name).to_path_buf());
+        assert_eq!(
+            seen,
+            [at(busy, "1"), at(quiet, "1"), at(busy, "2"), at(busy, "3")]
+        );
+        assert_eq!(fair.pending(), 0);
+    }
+}
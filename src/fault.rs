@@ -0,0 +1,206 @@
+use std::{io, path::Path};
+
+use crate::{parse, sys, Event, INotify, Mask, Watch};
+
+/// An [INotify] that fails on demand, for testing recovery paths
+///
+/// Each fault is armed ahead of time and fires on a later call to
+/// [FaultInjector::add] or [FaultInjector::watch]. Anything done through
+/// [FaultInjector::inner_mut] goes straight to the instance.
+pub struct FaultInjector {
+    inner: INotify,
+    adds: usize,
+    interrupts: usize,
+    short: Option<usize>,
+    held: Vec<u8>,
+}
+
+impl FaultInjector {
+    /// Wrap an instance, no faults are armed
+    pub fn new(inner: INotify) -> FaultInjector {
+        FaultInjector {
+            inner,
+            adds: 0,
+            interrupts: 0,
+            short: None,
+            held: Vec::new(),
+        }
+    }
+
+    /// fail the next `count` adds with ENOSPC, as when out of watches
+    pub fn fail_adds(&mut self, count: usize) {
+        self.adds = count;
+    }
+
+    /// fail the next `count` watches with EINTR, nothing is consumed
+    pub fn interrupt_reads(&mut self, count: usize) {
+        self.interrupts = count;
+    }
+
+    /// cut the next read from the kernel to `len` bytes
+    ///
+    /// events past the cut are delivered by the read after, a cut inside
+    /// an event tears it, which is reported as a [crate::ParseError] and
+    /// loses the rest of the read
+    pub fn short_read(&mut self, len: usize) {
+        self.short = Some(len);
+    }
+
+    /// queue a Q_OVERFLOW after the events already read
+    pub fn overflow(&mut self) {
+        let mut frame = Vec::with_capacity(parse::HEADER_SIZE);
+        frame.extend_from_slice(&(-1i32).to_ne_bytes());
+        frame.extend_from_slice(&Mask::Q_OVERFLOW.0.to_ne_bytes());
+        frame.extend_from_slice(&0u32.to_ne_bytes());
+        frame.extend_from_slice(&0u32.to_ne_bytes());
+
+        if !self.held.is_empty() {
+            self.held.extend_from_slice(&frame);
+            return;
+        }
+
+        let end = self.inner.end;
+        self.inner.buf.splice(end..end, frame);
+        self.inner.end += parse::HEADER_SIZE;
+    }
+
+    /// Add a file (, or directory) to be watched
+    pub fn add(&mut self, path: &Path, mask: Mask) -> io::Result<Watch> {
+        if self.adds > 0 {
+            self.adds -= 1;
+            return Err(io::Error::from_raw_os_error(sys::ENOSPC));
+        }
+
+        self.inner.add(path, mask)
+    }
+
+    /// wait for the next event, or the next armed fault
+    pub async fn watch(&mut self) -> io::Result<Event> {
+        if self.interrupts > 0 {
+            self.interrupts -= 1;
+            return Err(io::Error::from_raw_os_error(sys::EINTR));
+        }
+
+        if !self.inner.buffered() {
+            if !self.held.is_empty() {
+                self.restore();
+            } else if let Some(len) = self.short.take() {
+                self.inner.fill().await?;
+                self.cut(len);
+            }
+        }
+
+        self.inner.watch().await
+    }
+
+    /// the wrapped instance
+    pub fn inner(&self) -> &INotify {
+        &self.inner
+    }
+
+    /// the wrapped instance, faults do not apply to calls made through it
+    pub fn inner_mut(&mut self) -> &mut INotify {
+        &mut self.inner
+    }
+
+    /// recover the wrapped instance, bytes held back by a short read are lost
+    pub fn into_inner(self) -> INotify {
+        self.inner
+    }
+
+    /// keep what follows a cut for the next read, unless an event was torn
+    fn cut(&mut self, len: usize) {
+        let (pos, end) = (self.inner.pos, self.inner.end);
+        let cut = pos + len;
+        if cut >= end {
+            return;
+        }
+
+        let mut at = pos;
+        while at < cut {
+            match parse::next(&self.inner.buf[at..end]) {
+                Ok((_, _, used)) => at += used,
+                Err(_) => break,
+            }
+        }
+
+        if at == cut {
+            self.held = self.inner.buf[cut..end].to_vec();
+        }
+
+        self.inner.end = cut;
+    }
+
+    /// hand back what a short read held back, as a read of its own
+    fn restore(&mut self) {
+        let held = std::mem::take(&mut self.held);
+        if self.inner.buf.len() < held.len() {
+            self.inner.buf.resize(held.len(), 0);
+        }
+
+        self.inner.buf[..held.len()].copy_from_slice(&held);
+        self.inner.pos = 0;
+        self.inner.end = held.len();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::scratch;
+    use std::{path::PathBuf, time::Duration};
+
+    async fn next(faults: &mut FaultInjector) -> io::Result<Event> {
+        tokio::time::timeout(Duration::from_secs(10), faults.watch())
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn armed_faults_fire_once() {
+        let dir = scratch("fault");
+        let mut faults = FaultInjector::new(INotify::new().unwrap());
+
+        faults.fail_adds(1);
+        let err = faults.add(&dir, Mask::CREATE).unwrap_err();
+        assert_eq!(err.raw_os_error(), Some(sys::ENOSPC));
+        faults.add(&dir, Mask::CREATE).unwrap();
+
+        std::fs::File::create(dir.join("a")).unwrap();
+        faults.interrupt_reads(1);
+        let err = next(&mut faults).await.unwrap_err();
+        assert_eq!(err.raw_os_error(), Some(sys::EINTR));
+
+        // nothing was consumed by the interrupted read
+        let event = next(&mut faults).await.unwrap();
+        assert_eq!(event.path, PathBuf::from("a"));
+
+        faults.overflow();
+        assert_eq!(next(&mut faults).await.unwrap().mask, Mask::Q_OVERFLOW);
+    }
+
+    #[tokio::test]
+    async fn short_reads_deliver_the_rest_later() {
+        let dir = scratch("fault-short");
+        let mut faults = FaultInjector::new(INotify::new().unwrap());
+        faults.add(&dir, Mask::CREATE).unwrap();
+
+        std::fs::File::create(dir.join("a")).unwrap();
+        std::fs::File::create(dir.join("b")).unwrap();
+
+        // a header and a name padded to 16 bytes, the cut falls between them
+        faults.short_read(2 * parse::HEADER_SIZE);
+        assert_eq!(next(&mut faults).await.unwrap().path, PathBuf::from("a"));
+
+        // the overflow follows what the cut held back
+        faults.overflow();
+        assert_eq!(next(&mut faults).await.unwrap().path, PathBuf::from("b"));
+        assert_eq!(next(&mut faults).await.unwrap().mask, Mask::Q_OVERFLOW);
+
+        // a cut inside an event tears it
+        std::fs::File::create(dir.join("c")).unwrap();
+        faults.short_read(parse::HEADER_SIZE);
+        let err = next(&mut faults).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
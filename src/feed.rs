@@ -0,0 +1,123 @@
+use std::{
+    io,
+    process::{ExitStatus, Stdio},
+    time::Duration,
+};
+
+use tokio::{io::AsyncWriteExt, process::Command};
+
+use crate::{Format, INotify};
+
+/// When a command fed by a [Feed] is started again after it exits
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Restart {
+    /// Stop feeding once it exits
+    #[default]
+    Never,
+
+    /// Start it again when it exits unsuccessfully
+    OnFailure,
+
+    /// Start it again whenever it exits
+    Always,
+}
+
+/// Feeds events to the stdin of a spawned command
+///
+/// Events are serialized with a [Format], one record per event. A record
+/// the command did not take before exiting is sent again to its
+/// replacement, events that arrive in between stay queued in the kernel.
+pub struct Feed {
+    command: Command,
+    format: Format,
+    restart: Restart,
+    delay: Duration,
+    out: Vec<u8>,
+}
+
+impl Feed {
+    /// Feed `command` JSONL records, without restarting it
+    pub fn new(mut command: Command) -> Self {
+        command.stdin(Stdio::piped());
+
+        Self {
+            command,
+            format: Format::Jsonl,
+            restart: Restart::Never,
+            delay: Duration::from_secs(1),
+            out: Vec::new(),
+        }
+    }
+
+    /// Serialize events with `format`
+    pub fn format(mut self, format: Format) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Start the command again according to `restart`
+    pub fn restart(mut self, restart: Restart) -> Self {
+        self.restart = restart;
+        self
+    }
+
+    /// Wait `delay` before starting the command again, (default 1s)
+    pub fn delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    /// Feed events until the command exits and is not restarted
+    ///
+    /// Returns the status of the last run. On an error the running command
+    /// is left to see its stdin closed.
+    pub async fn run(&mut self, inotify: &mut INotify) -> io::Result<ExitStatus> {
+        loop {
+            let status = self.once(inotify).await?;
+
+            let again = match self.restart {
+                Restart::Never => false,
+                Restart::OnFailure => !status.success(),
+                Restart::Always => true,
+            };
+
+            if !again {
+                return Ok(status);
+            }
+
+            tokio::time::sleep(self.delay).await;
+        }
+    }
+
+    /// Run the command once, feeding it until it exits
+    async fn once(&mut self, inotify: &mut INotify) -> io::Result<ExitStatus> {
+        let mut child = self.command.spawn()?;
+        let mut stdin = child.stdin.take().expect("stdin is piped");
+
+        loop {
+            if self.out.is_empty() {
+                tokio::select! {
+                    biased;
+
+                    status = child.wait() => return status,
+                    event = inotify.watch() => self.format.encode(&event?, &mut self.out),
+                }
+            }
+
+            tokio::select! {
+                biased;
+
+                status = child.wait() => return status,
+                res = stdin.write_all(&self.out) => match res {
+                    Ok(()) => self.out.clear(),
+                    // it closed stdin, the record is kept for a restart
+                    Err(err) if err.kind() == io::ErrorKind::BrokenPipe => {
+                        drop(stdin);
+                        return child.wait().await;
+                    }
+                    Err(err) => return Err(err),
+                },
+            }
+        }
+    }
+}
@@ -0,0 +1,206 @@
+use std::{collections::HashSet, os::unix::ffi::OsStrExt, path::Path};
+
+use crate::Glob;
+
+/// A set of [Glob]s compiled for matching many paths
+///
+/// Patterns without wildcards are looked up in hash sets and `*.ext` style
+/// patterns by the ending of the file name, only the rest are evaluated
+/// glob by glob. A path matches when any of the patterns does.
+#[derive(Debug, Clone, Default)]
+pub struct Filter {
+    globs: Vec<Glob>,
+    names: HashSet<Vec<u8>>,
+    paths: HashSet<Vec<u8>>,
+    suffixes: HashSet<Vec<u8>>,
+    suffix_lens: Vec<usize>,
+    rest: Vec<Glob>,
+}
+
+impl Filter {
+    /// Build a filter matching nothing
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// add a pattern
+    pub fn push(&mut self, glob: Glob) {
+        if let Some(literal) = glob.literal() {
+            if glob.name_only() {
+                self.names.insert(literal);
+            } else {
+                self.paths.insert(literal);
+            }
+        } else if let Some(suffix) = glob.suffix() {
+            if !self.suffix_lens.contains(&suffix.len()) {
+                self.suffix_lens.push(suffix.len());
+            }
+            self.suffixes.insert(suffix);
+        } else {
+            self.rest.push(glob.clone());
+        }
+
+        self.globs.push(glob);
+    }
+
+    /// whether no pattern was added
+    pub fn is_empty(&self) -> bool {
+        self.globs.is_empty()
+    }
+
+    /// the patterns in the order they were added
+    pub fn globs(&self) -> &[Glob] {
+        &self.globs
+    }
+
+    /// test a path against every pattern
+    pub fn matches(&self, path: &Path) -> bool {
+        let name = path.file_name().map(|n| n.as_bytes()).unwrap_or_default();
+
+        self.names.contains(name)
+            || self.paths.contains(path.as_os_str().as_bytes())
+            || self
+                .suffix_lens
+                .iter()
+                .any(|len| name.len() >= *len && self.suffixes.contains(&name[name.len() - len..]))
+            || self.rest.iter().any(|glob| glob.matches(path))
+    }
+}
+
+impl FromIterator<Glob> for Filter {
+    fn from_iter<I: IntoIterator<Item = Glob>>(iter: I) -> Self {
+        let mut filter = Filter::new();
+        for glob in iter {
+            filter.push(glob);
+        }
+        filter
+    }
+}
+
+impl PartialEq for Filter {
+    fn eq(&self, other: &Self) -> bool {
+        self.globs == other.globs
+    }
+}
+
+impl Eq for Filter {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PATHS: &[&str] = &[
+        "/",
+        "",
+        "lib.rs",
+        "/src/lib.rs",
+        "/src/lib.rs/",
+        "/src/lib.rsx",
+        "/src/.rs",
+        "/src/a/main.rs",
+        "/target/debug/build",
+        "/src/*.rs",
+        "/src/[x].rs",
+        "/src/a?b",
+        "/src/a\\b",
+        "*",
+        "Cargo.toml",
+        "/Cargo.toml",
+    ];
+
+    fn filter(pattern: &str) -> (Glob, Filter) {
+        let glob = Glob::new(pattern).unwrap();
+        (glob.clone(), Filter::from_iter([glob]))
+    }
+
+    fn agrees(pattern: &str) {
+        let (glob, filter) = filter(pattern);
+        for path in PATHS {
+            let path = Path::new(path);
+            assert_eq!(
+                filter.matches(path),
+                glob.matches(path),
+                "{pattern:?} on {path:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn literal_names() {
+        for pattern in [
+            "lib.rs",
+            "Cargo.toml",
+            "\\*",
+            "\\[x\\].rs",
+            "a\\?b",
+            "a\\\\b",
+        ] {
+            let (_, filter) = filter(pattern);
+            assert_eq!(filter.names.len(), 1, "{pattern:?}");
+            agrees(pattern);
+        }
+    }
+
+    #[test]
+    fn literal_paths() {
+        for pattern in [
+            "/src/lib.rs",
+            "/Cargo.toml",
+            "/src/\\*.rs",
+            "/src/\\[x\\].rs",
+            "/src/a\\?b",
+        ] {
+            let (_, filter) = filter(pattern);
+            assert_eq!(filter.paths.len(), 1, "{pattern:?}");
+            agrees(pattern);
+        }
+    }
+
+    #[test]
+    fn suffixes() {
+        for pattern in [
+            "*.rs",
+            "*",
+            "*lib.rs",
+            "*.toml",
+            "*\\*",
+            "*\\?b",
+            "*\\[x\\].rs",
+        ] {
+            let (_, filter) = filter(pattern);
+            assert_eq!(filter.suffixes.len(), 1, "{pattern:?}");
+            agrees(pattern);
+        }
+    }
+
+    #[test]
+    fn the_rest() {
+        for pattern in [
+            "/src/*.rs",
+            "*.r?",
+            "**/*.rs",
+            "/src/**",
+            "[a-z]*.rs",
+            "lib.*",
+            "[x].rs",
+            "*.rs*",
+        ] {
+            let (_, filter) = filter(pattern);
+            assert_eq!(filter.rest.len(), 1, "{pattern:?}");
+            agrees(pattern);
+        }
+    }
+
+    #[test]
+    fn any_pattern_matches() {
+        let filter: Filter = ["Cargo.toml", "*.rs", "/target/**"]
+            .into_iter()
+            .map(|pattern| Glob::new(pattern).unwrap())
+            .collect();
+
+        assert!(filter.matches(Path::new("/Cargo.toml")));
+        assert!(filter.matches(Path::new("/src/main.rs")));
+        assert!(filter.matches(Path::new("/target/debug/build")));
+        assert!(!filter.matches(Path::new("/src/Cargo.lock")));
+    }
+}
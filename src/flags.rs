@@ -0,0 +1,49 @@
+use bitflags::{Flag, Flags};
+
+use crate::Mask;
+
+/// Every single flag, in the order [Mask]'s `Debug` lists them
+const FLAGS: &[Flag<Mask>] = &[
+    Flag::new("ACCESS", Mask::ACCESS),
+    Flag::new("MODIFY", Mask::MODIFY),
+    Flag::new("ATTRIB", Mask::ATTRIB),
+    Flag::new("CLOSE_WRITE", Mask::CLOSE_WRITE),
+    Flag::new("CLOSE_NOWRITE", Mask::CLOSE_NOWRITE),
+    Flag::new("OPEN", Mask::OPEN),
+    Flag::new("MOVED_FROM", Mask::MOVED_FROM),
+    Flag::new("MOVED_TO", Mask::MOVED_TO),
+    Flag::new("CREATE", Mask::CREATE),
+    Flag::new("DELETE", Mask::DELETE),
+    Flag::new("DELETE_SELF", Mask::DELETE_SELF),
+    Flag::new("MOVE_SELF", Mask::MOVE_SELF),
+    Flag::new("UNMOUNT", Mask::UNMOUNT),
+    Flag::new("Q_OVERFLOW", Mask::Q_OVERFLOW),
+    Flag::new("IGNORED", Mask::IGNORED),
+    Flag::new("ONLYDIR", Mask::ONLYDIR),
+    Flag::new("DONT_FOLLOW", Mask::DONT_FOLLOW),
+    Flag::new("EXCL_UNLINK", Mask::EXCL_UNLINK),
+    Flag::new("MASK_CREATE", Mask::MASK_CREATE),
+    Flag::new("MASK_ADD", Mask::MASK_ADD),
+    Flag::new("ISDIR", Mask::ISDIR),
+    Flag::new("ONESHOT", Mask::ONESHOT),
+];
+
+/// The bitflags operations on a [Mask], such as `complement`,
+/// `from_bits_truncate` and `iter_names`
+///
+/// Flags are named as in the inotify headers without `IN_`, the merged
+/// helpers and presets are not flags of their own. Complements and
+/// truncation keep to the bits of these flags.
+impl Flags for Mask {
+    const FLAGS: &'static [Flag<Self>] = FLAGS;
+
+    type Bits = u32;
+
+    fn bits(&self) -> u32 {
+        self.0
+    }
+
+    fn from_bits_retain(bits: u32) -> Self {
+        Mask(bits)
+    }
+}
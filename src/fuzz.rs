@@ -0,0 +1,87 @@
+//! Entry points for fuzzing the event parser, see `fuzz/`
+//!
+//! Each function takes arbitrary bytes and panics when the parser breaks
+//! one of its invariants, which is what a fuzzer reports.
+
+use crate::{parse, Events, ParseError, RawEvent};
+
+/// Parse arbitrary bytes as a read from the kernel
+///
+/// Parsing must consume the buffer, stop at the first error and never
+/// produce a name with a NUL or longer than the kernel allows.
+pub fn parse(data: &[u8]) {
+    let mut events = Events::new(data);
+    let mut consumed = 0;
+
+    while let Some(item) = events.next() {
+        let used = data.len() - consumed - events.remaining();
+
+        match item {
+            Ok(event) => {
+                assert!(
+                    used >= parse::HEADER_SIZE,
+                    "an event shorter than its header"
+                );
+                check(&event);
+            }
+            Err(_) => {
+                assert_eq!(events.remaining(), 0, "parsing continued past an error");
+                assert!(events.next().is_none(), "an event after an error");
+                return;
+            }
+        }
+
+        consumed += used;
+    }
+
+    assert_eq!(consumed, data.len(), "bytes left unparsed");
+}
+
+/// Parse every prefix of arbitrary bytes, as reads cut short would leave them
+///
+/// A prefix must yield the events of the whole buffer that fit in it, then
+/// at most a [ParseError::Truncated] for the event it cuts through.
+pub fn truncated(data: &[u8]) {
+    let full: Vec<(usize, RawEvent)> = frames(data)
+        .into_iter()
+        .map_while(|(end, item)| item.ok().map(|event| (end, event)))
+        .collect();
+
+    for cut in 0..=data.len() {
+        let prefix = frames(&data[..cut]);
+
+        let whole = full.iter().take_while(|(end, _)| *end <= cut).count();
+        for ((end, item), (full_end, event)) in prefix.iter().zip(&full[..whole]) {
+            assert_eq!(end, full_end, "an event changed size when truncated");
+            let parsed = item.as_ref().expect("a whole event failed to parse");
+            assert_eq!(parsed.name, event.name, "an event changed when truncated");
+        }
+
+        match prefix.get(whole) {
+            None => (),
+            Some((_, Err(ParseError::Truncated { needed, available }))) => {
+                assert!(needed > available, "truncation reported with room to spare");
+            }
+            Some((_, Err(ParseError::NameTooLong { .. }))) => (),
+            Some((_, other)) => panic!("unexpected result past the last whole event: {other:?}"),
+        }
+    }
+}
+
+/// every item with the offset it ends at
+fn frames(data: &[u8]) -> Vec<(usize, Result<RawEvent, ParseError>)> {
+    let mut events = Events::new(data);
+    let mut items = Vec::new();
+
+    while let Some(item) = events.next() {
+        items.push((data.len() - events.remaining(), item));
+    }
+
+    items
+}
+
+fn check(event: &RawEvent) {
+    let name = event.name.as_bytes();
+    assert!(!name.contains(&0), "a name kept a NUL");
+    assert!(name.len() <= parse::NAME_LIMIT, "a name past the limit");
+}
@@ -0,0 +1,363 @@
+use std::{os::unix::ffi::OsStrExt, path::Path};
+
+/// A shell style path pattern
+///
+/// `*` and `?` match within a path component, `**` matches across
+/// components and `[a-z]` / `[!a-z]` match character classes. `**/` at
+/// the start of a component matches any number of whole directories,
+/// none included. Patterns without a `/` are matched against the file
+/// name only.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Glob {
+    pattern: String,
+    tokens: Vec<Token>,
+    name_only: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Literal(u8),
+    Any,
+    Star,
+    DoubleStar {
+        dirs: bool,
+    },
+    Class {
+        negated: bool,
+        ranges: Vec<(u8, u8)>,
+    },
+}
+
+/// A pattern that could not be compiled
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GlobError {
+    /// The offending pattern
+    pub pattern: String,
+
+    /// What is wrong with it
+    pub reason: &'static str,
+}
+
+impl std::fmt::Display for GlobError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid glob {:?}: {}", self.pattern, self.reason)
+    }
+}
+
+impl std::error::Error for GlobError {}
+
+impl Glob {
+    /// Compile a pattern
+    pub fn new(pattern: &str) -> Result<Glob, GlobError> {
+        let bytes = pattern.as_bytes();
+        let mut tokens = Vec::new();
+        let mut i = 0;
+
+        while i < bytes.len() {
+            match bytes[i] {
+                b'*' if bytes.get(i + 1) == Some(&b'*') => {
+                    // `**/` starting a component matches whole directories,
+                    // including none at all
+                    let component = i == 0 || bytes[i - 1] == b'/';
+                    i += 2;
+                    let dirs = component && bytes.get(i) == Some(&b'/');
+                    if dirs {
+                        i += 1;
+                    }
+                    tokens.push(Token::DoubleStar { dirs });
+                    continue;
+                }
+                b'*' => tokens.push(Token::Star),
+                b'?' => tokens.push(Token::Any),
+                b'[' => {
+                    let (token, next) = class(bytes, i).ok_or(GlobError {
+                        pattern: pattern.to_string(),
+                        reason: "unterminated character class",
+                    })?;
+                    tokens.push(token);
+                    i = next;
+                    continue;
+                }
+                b'\\' if i + 1 < bytes.len() => {
+                    i += 1;
+                    tokens.push(Token::Literal(bytes[i]));
+                }
+                b => tokens.push(Token::Literal(b)),
+            }
+
+            i += 1;
+        }
+
+        Ok(Glob {
+            pattern: pattern.to_string(),
+            tokens,
+            name_only: !pattern.contains('/'),
+        })
+    }
+
+    /// The pattern this glob was compiled from
+    pub fn as_str(&self) -> &str {
+        &self.pattern
+    }
+
+    /// Test a path against the pattern
+    pub fn matches(&self, path: &Path) -> bool {
+        let subject = if self.name_only {
+            path.file_name().map(|n| n.as_bytes()).unwrap_or_default()
+        } else {
+            path.as_os_str().as_bytes()
+        };
+
+        matches(&self.tokens, subject)
+    }
+
+    /// whether the pattern is matched against the file name only
+    pub(crate) fn name_only(&self) -> bool {
+        self.name_only
+    }
+
+    /// the bytes a pattern without wildcards matches exactly
+    pub(crate) fn literal(&self) -> Option<Vec<u8>> {
+        literal(&self.tokens)
+    }
+
+    /// the ending a file name pattern of `*` and literals matches
+    pub(crate) fn suffix(&self) -> Option<Vec<u8>> {
+        match self.tokens.split_first() {
+            Some((Token::Star, rest)) if self.name_only => literal(rest),
+            _ => None,
+        }
+    }
+}
+
+fn literal(tokens: &[Token]) -> Option<Vec<u8>> {
+    tokens
+        .iter()
+        .map(|token| match token {
+            Token::Literal(b) => Some(*b),
+            _ => None,
+        })
+        .collect()
+}
+
+fn class(bytes: &[u8], start: usize) -> Option<(Token, usize)> {
+    let mut i = start + 1;
+    let negated = matches!(bytes.get(i), Some(b'!') | Some(b'^'));
+    if negated {
+        i += 1;
+    }
+
+    let mut ranges = Vec::new();
+    let mut first = true;
+
+    loop {
+        let b = *bytes.get(i)?;
+        if b == b']' && !first {
+            return Some((Token::Class { negated, ranges }, i + 1));
+        }
+
+        first = false;
+
+        if bytes.get(i + 1) == Some(&b'-') && bytes.get(i + 2).is_some_and(|e| *e != b']') {
+            ranges.push((b, bytes[i + 2]));
+            i += 3;
+        } else {
+            ranges.push((b, b));
+            i += 1;
+        }
+    }
+}
+
+/// match with one restart point per kind of star rather than recursion
+///
+/// On a mismatch the last `*` takes one more byte of its component, once
+/// it can not the last `**` takes more and everything after it is matched
+/// again. Trying only the latest restart is enough as a later star can
+/// take whatever an earlier one would have, so no input is backtracked
+/// over more than once per token.
+fn matches(tokens: &[Token], subject: &[u8]) -> bool {
+    let (mut t, mut s) = (0, 0);
+    // the token after the star and where the star's match ends
+    let mut star: Option<(usize, usize)> = None;
+    let mut globstar: Option<(usize, usize, bool)> = None;
+
+    while t < tokens.len() || s < subject.len() {
+        let next = subject.get(s).filter(|b| **b != b'/');
+
+        match tokens.get(t) {
+            Some(Token::Literal(b)) if subject.get(s) == Some(b) => {
+                t += 1;
+                s += 1;
+                continue;
+            }
+            Some(Token::Any) if next.is_some() => {
+                t += 1;
+                s += 1;
+                continue;
+            }
+            Some(Token::Class { negated, ranges }) => {
+                if let Some(b) = next {
+                    let hit = ranges.iter().any(|(lo, hi)| lo <= b && b <= hi);
+                    if hit != *negated {
+                        t += 1;
+                        s += 1;
+                        continue;
+                    }
+                }
+            }
+            Some(Token::Star) => {
+                t += 1;
+                star = Some((t, s));
+                continue;
+            }
+            Some(Token::DoubleStar { dirs }) => {
+                t += 1;
+                star = None;
+                globstar = Some((t, s, *dirs));
+                continue;
+            }
+            _ => (),
+        }
+
+        if let Some((after, end)) = star {
+            if subject.get(end).is_some_and(|b| *b != b'/') {
+                star = Some((after, end + 1));
+                (t, s) = (after, end + 1);
+                continue;
+            }
+        }
+
+        if let Some((after, end, dirs)) = globstar {
+            // `**/` only ends just past a `/`, `**` anywhere
+            let end = if dirs {
+                subject[end..]
+                    .iter()
+                    .position(|b| *b == b'/')
+                    .map(|at| end + at + 1)
+            } else {
+                (end < subject.len()).then_some(end + 1)
+            };
+
+            if let Some(end) = end {
+                star = None;
+                globstar = Some((after, end, dirs));
+                (t, s) = (after, end);
+                continue;
+            }
+        }
+
+        return false;
+    }
+
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::*;
+
+    fn glob(pattern: &str) -> Glob {
+        Glob::new(pattern).unwrap()
+    }
+
+    fn hit(pattern: &str, path: &str) -> bool {
+        glob(pattern).matches(Path::new(path))
+    }
+
+    #[test]
+    fn literals() {
+        assert!(hit("/a/b.txt", "/a/b.txt"));
+        assert!(!hit("/a/b.txt", "/a/b.txt2"));
+        assert!(!hit("/a/b.txt", "/a/b.tx"));
+    }
+
+    #[test]
+    fn question_mark() {
+        assert!(hit("/a/?.rs", "/a/b.rs"));
+        assert!(!hit("/a/?.rs", "/a/bc.rs"));
+        assert!(!hit("/a?b", "/a/b"));
+    }
+
+    #[test]
+    fn star_stays_in_a_component() {
+        assert!(hit("/src/*.rs", "/src/lib.rs"));
+        assert!(hit("/src/*.rs", "/src/.rs"));
+        assert!(!hit("/src/*.rs", "/src/a/lib.rs"));
+        assert!(hit("/*/*/c", "/a/b/c"));
+    }
+
+    #[test]
+    fn double_star_crosses_components() {
+        assert!(hit("/src/**.rs", "/src/a/b/lib.rs"));
+        assert!(hit("/src/**", "/src/"));
+        assert!(!hit("/src/**.rs", "/lib/lib.rs"));
+    }
+
+    #[test]
+    fn double_star_slash_matches_whole_directories() {
+        assert!(hit("/src/**/lib.rs", "/src/lib.rs"));
+        assert!(hit("/src/**/lib.rs", "/src/a/b/lib.rs"));
+        assert!(!hit("/src/**/lib.rs", "/src/a/xlib.rs"));
+        assert!(hit("**/target/x", "target/x"));
+        assert!(hit("**/target/x", "/a/target/x"));
+
+        // only at the start of a component, elsewhere it is a plain `**`
+        assert!(hit("/a**/b", "/ax/y/b"));
+        assert!(!hit("/a**/b", "/ab"));
+    }
+
+    #[test]
+    fn classes() {
+        assert!(hit("[a-c]x", "bx"));
+        assert!(!hit("[a-c]x", "dx"));
+        assert!(hit("[!a-c]x", "dx"));
+        assert!(hit("[^a-c]x", "dx"));
+        assert!(!hit("[!a-c]x", "ax"));
+        assert!(hit("[]]", "]"));
+        assert!(hit("[a-]", "-"));
+        assert!(!hit("/a[!x]b", "/a/b"));
+    }
+
+    #[test]
+    fn unterminated_class() {
+        let err = Glob::new("[abc").unwrap_err();
+        assert_eq!(err.pattern, "[abc");
+        assert_eq!(err.reason, "unterminated character class");
+    }
+
+    #[test]
+    fn escapes() {
+        assert!(hit("\\*", "*"));
+        assert!(!hit("\\*", "a"));
+        assert!(hit("a\\?", "a?"));
+        assert!(!hit("a\\?", "ab"));
+        assert!(hit("\\[x]", "[x]"));
+        assert!(hit("trailing\\", "trailing\\"));
+    }
+
+    #[test]
+    fn name_only_without_slash() {
+        assert!(glob("*.rs").name_only());
+        assert!(hit("*.rs", "/deep/down/lib.rs"));
+        assert!(!hit("*.rs", "/deep/lib.rs/x"));
+        assert!(hit("lib.rs", "/src/lib.rs"));
+
+        assert!(!glob("src/*.rs").name_only());
+        assert!(!hit("src/*.rs", "/src/lib.rs"));
+        assert!(hit("src/*.rs", "src/lib.rs"));
+    }
+
+    #[test]
+    fn pathological_patterns_finish() {
+        let subject = format!("/{}", "a".repeat(200));
+        let started = Instant::now();
+
+        assert!(!hit("*a*a*a*a*a*a*a*a*a*a*b", &subject));
+        assert!(!hit("/**a**a**a**a**a**a**a**a**b", &subject));
+        assert!(!hit("/**/**/**/**/**/**/**/b", &"a/".repeat(100)));
+
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+}
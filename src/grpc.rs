@@ -0,0 +1,436 @@
+use std::{
+    convert::Infallible,
+    io,
+    net::SocketAddr,
+    os::unix::ffi::{OsStrExt, OsStringExt},
+    path::{Component, Path, PathBuf},
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use tokio::{
+    net::TcpListener,
+    sync::{mpsc, OwnedSemaphorePermit, Semaphore},
+};
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{
+    body::Body,
+    client::Grpc,
+    codegen::{self, http, BoxFuture, Service, StdError},
+    server::{NamedService, ServerStreamingService},
+    transport::{server::TcpIncoming, Channel, Endpoint, Server},
+    Code, Request, Response, Status, Streaming,
+};
+use tonic_prost::ProstCodec;
+
+use crate::{sys, Event, INotify, Mask, Watch};
+
+const WATCH: &str = "/tokinotify.Watcher/Watch";
+
+/// Watches served at once unless [GrpcService::max_watches] says otherwise
+const MAX_WATCHES: usize = 32;
+
+/// Serves the `tokinotify.Watcher` gRPC service
+///
+/// The service is defined in `proto/tokinotify.proto`. Each `Watch` call
+/// gets an [INotify] of its own, streaming events until the watch is
+/// dropped or the client goes away.
+///
+/// Only paths beneath the allowed roots are watched. Anything else is
+/// refused with PERMISSION_DENIED before it is looked at, so a client
+/// can not learn which paths exist outside the roots.
+#[derive(Clone)]
+pub struct GrpcService {
+    roots: Arc<[PathBuf]>,
+    watches: Arc<Semaphore>,
+}
+
+/// A client of the `tokinotify.Watcher` gRPC service
+pub struct GrpcClient {
+    grpc: Grpc<Channel>,
+}
+
+/// Events streamed from a remote `Watch` call
+pub struct RemoteEvents {
+    stream: Streaming<EventReply>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct AddRequest {
+    #[prost(bytes = "vec", tag = "1")]
+    path: Vec<u8>,
+    #[prost(uint32, tag = "2")]
+    mask: u32,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct EventReply {
+    #[prost(int32, tag = "1")]
+    watch: i32,
+    #[prost(uint32, tag = "2")]
+    mask: u32,
+    #[prost(uint32, tag = "3")]
+    cookie: u32,
+    #[prost(bytes = "vec", tag = "4")]
+    path: Vec<u8>,
+    #[prost(string, repeated, tag = "5")]
+    events: Vec<String>,
+}
+
+impl GrpcService {
+    /// Build the service, watching `root` and everything beneath it
+    pub fn new(root: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self {
+            roots: Arc::from([std::fs::canonicalize(root)?]),
+            watches: Arc::new(Semaphore::new(MAX_WATCHES)),
+        })
+    }
+
+    /// Also watch `root` and everything beneath it
+    pub fn allow(mut self, root: impl AsRef<Path>) -> io::Result<Self> {
+        let mut roots = self.roots.to_vec();
+        roots.push(std::fs::canonicalize(root)?);
+        self.roots = roots.into();
+        Ok(self)
+    }
+
+    /// Serve at most `limit` watches at once, 32 by default
+    ///
+    /// Every watch is an inotify instance of its own, counted against the
+    /// serving user's `fs.inotify.max_user_instances`. Calls beyond the
+    /// limit are refused with RESOURCE_EXHAUSTED.
+    pub fn max_watches(mut self, limit: usize) -> Self {
+        self.watches = Arc::new(Semaphore::new(limit));
+        self
+    }
+
+    /// Serve connections accepted from `listener`, until accepting fails
+    pub async fn serve(self, listener: TcpListener) -> io::Result<()> {
+        Server::builder()
+            .serve_with_incoming(self, TcpIncoming::from(listener))
+            .await
+            .map_err(io::Error::other)
+    }
+
+    /// the path to watch, refusing anything outside the roots
+    async fn allowed(&self, path: &Path) -> Result<PathBuf, Status> {
+        let denied = || Status::permission_denied("path outside the served roots");
+
+        // checked as written first, nothing outside the roots is looked at
+        let plain = path.is_absolute()
+            && path
+                .components()
+                .all(|c| matches!(c, Component::RootDir | Component::Normal(_)));
+        if !plain || !self.beneath(path) {
+            return Err(denied());
+        }
+
+        // a symlink beneath a root may lead out of it
+        let canonical = tokio::fs::canonicalize(path)
+            .await
+            .map_err(|err| status(&err))?;
+        if !self.beneath(&canonical) {
+            return Err(denied());
+        }
+
+        Ok(canonical)
+    }
+
+    fn beneath(&self, path: &Path) -> bool {
+        self.roots.iter().any(|root| path.starts_with(root))
+    }
+
+    async fn watch(
+        &self,
+        req: AddRequest,
+    ) -> Result<ReceiverStream<Result<EventReply, Status>>, Status> {
+        let permit = self
+            .watches
+            .clone()
+            .try_acquire_owned()
+            .map_err(|_| Status::resource_exhausted("too many watches"))?;
+
+        let path = PathBuf::from(std::ffi::OsString::from_vec(req.path));
+        let path = self.allowed(&path).await?;
+
+        let mut inotify = INotify::new().map_err(|err| status(&err))?;
+        inotify
+            .add(&path, Mask(req.mask))
+            .map_err(|err| status(&err))?;
+
+        let (tx, rx) = mpsc::channel(16);
+        tokio::spawn(stream(inotify, tx, permit));
+
+        Ok(ReceiverStream::new(rx))
+    }
+}
+
+impl NamedService for GrpcService {
+    const NAME: &'static str = "tokinotify.Watcher";
+}
+
+impl<B> Service<http::Request<B>> for GrpcService
+where
+    B: codegen::Body + Send + 'static,
+    B::Error: Into<StdError> + Send + 'static,
+{
+    type Response = http::Response<Body>;
+    type Error = Infallible;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Infallible>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: http::Request<B>) -> Self::Future {
+        if req.uri().path() != WATCH {
+            return Box::pin(async { Ok(Status::unimplemented("unknown method").into_http()) });
+        }
+
+        let service = self.clone();
+        Box::pin(async move {
+            let mut grpc = tonic::server::Grpc::new(ProstCodec::default());
+            Ok(grpc.server_streaming(service, req).await)
+        })
+    }
+}
+
+impl ServerStreamingService<AddRequest> for GrpcService {
+    type Response = EventReply;
+    type ResponseStream = ReceiverStream<Result<EventReply, Status>>;
+    type Future = BoxFuture<Response<Self::ResponseStream>, Status>;
+
+    fn call(&mut self, req: Request<AddRequest>) -> Self::Future {
+        let service = self.clone();
+        Box::pin(async move { service.watch(req.into_inner()).await.map(Response::new) })
+    }
+}
+
+async fn stream(
+    mut inotify: INotify,
+    tx: mpsc::Sender<Result<EventReply, Status>>,
+    _permit: OwnedSemaphorePermit,
+) {
+    loop {
+        let event = tokio::select! {
+            event = inotify.watch() => event,
+            // the client went away, close rather than wait for an event
+            _ = tx.closed() => {
+                let _ = inotify.close().await;
+                return;
+            }
+        };
+
+        let event = match event {
+            Ok(event) => event,
+            Err(err) => {
+                let _ = tx.send(Err(status(&err))).await;
+                return;
+            }
+        };
+
+        if tx.send(Ok(reply(&event))).await.is_err() {
+            return;
+        }
+
+        // the stream ends with OK once the watch is gone
+        if event.mask.contains(Mask::IGNORED) {
+            return;
+        }
+    }
+}
+
+impl GrpcClient {
+    /// Connect to a server over plaintext HTTP/2
+    pub async fn connect(addr: SocketAddr) -> io::Result<Self> {
+        let channel = Endpoint::from_shared(format!("http://{addr}"))
+            .map_err(io::Error::other)?
+            .connect()
+            .await
+            .map_err(io::Error::other)?;
+
+        Ok(Self {
+            grpc: Grpc::new(channel),
+        })
+    }
+
+    /// Watch a path on the server
+    pub async fn watch(&mut self, path: &Path, mask: Mask) -> io::Result<RemoteEvents> {
+        self.grpc.ready().await.map_err(io::Error::other)?;
+
+        let req = AddRequest {
+            path: path.as_os_str().as_bytes().to_vec(),
+            mask: mask.0,
+        };
+        let res = self
+            .grpc
+            .server_streaming(
+                Request::new(req),
+                http::uri::PathAndQuery::from_static(WATCH),
+                ProstCodec::default(),
+            )
+            .await
+            .map_err(error)?;
+
+        Ok(RemoteEvents {
+            stream: res.into_inner(),
+        })
+    }
+}
+
+impl RemoteEvents {
+    /// the next event, `None` once the server ends the stream
+    pub async fn next(&mut self) -> io::Result<Option<Event>> {
+        let reply = self.stream.message().await.map_err(error)?;
+        Ok(reply.map(event))
+    }
+}
+
+fn reply(event: &Event) -> EventReply {
+    EventReply {
+        watch: event.watch.wd,
+        mask: event.mask.0,
+        cookie: event.cookie,
+        path: event.path.as_os_str().as_bytes().to_vec(),
+        events: event.mask.names().map(str::to_string).collect(),
+    }
+}
+
+fn event(reply: EventReply) -> Event {
+    Event {
+        watch: Watch { wd: reply.watch },
+        mask: Mask(reply.mask),
+        cookie: reply.cookie,
+        path: PathBuf::from(std::ffi::OsString::from_vec(reply.path)),
+        identity: None,
+        link: None,
+        removal: None,
+        synthetic: false,
+        stale: false,
+        root: None,
+        watch_mask: None,
+    }
+}
+
+fn status(err: &io::Error) -> Status {
+    let msg = err.to_string();
+    match err.kind() {
+        io::ErrorKind::NotFound => Status::not_found(msg),
+        io::ErrorKind::PermissionDenied => Status::permission_denied(msg),
+        io::ErrorKind::InvalidInput => Status::invalid_argument(msg),
+        _ => Status::unknown(msg),
+    }
+}
+
+fn error(status: Status) -> io::Error {
+    let kind = match status.code() {
+        Code::InvalidArgument => io::ErrorKind::InvalidInput,
+        Code::NotFound => io::ErrorKind::NotFound,
+        Code::PermissionDenied => io::ErrorKind::PermissionDenied,
+        // the server is out of watches, as the kernel would say
+        Code::ResourceExhausted => return io::Error::from_raw_os_error(sys::ENOSPC),
+        Code::Unimplemented => io::ErrorKind::Unsupported,
+        _ => io::ErrorKind::Other,
+    };
+
+    io::Error::new(kind, status.message().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::testing::scratch;
+
+    async fn serve(service: GrpcService) -> GrpcClient {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(service.serve(listener));
+
+        GrpcClient::connect(addr).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn paths_outside_the_roots() {
+        let dir = scratch("grpc-roots");
+        let root = dir.join("root");
+        std::fs::create_dir(&root).unwrap();
+        std::os::unix::fs::symlink(&dir, root.join("out")).unwrap();
+
+        let service = GrpcService::new(&root).unwrap();
+        let denied = |res: Result<PathBuf, Status>| res.unwrap_err().code();
+
+        assert_eq!(service.allowed(&root).await.unwrap(), root);
+        assert_eq!(
+            denied(service.allowed(&root.join("missing")).await),
+            Code::NotFound
+        );
+
+        // whether a path outside exists is never revealed
+        assert_eq!(
+            denied(service.allowed(&dir.join("missing")).await),
+            Code::PermissionDenied
+        );
+        assert_eq!(
+            denied(service.allowed(&root.join("../missing")).await),
+            Code::PermissionDenied
+        );
+        assert_eq!(
+            denied(service.allowed(Path::new("root")).await),
+            Code::PermissionDenied
+        );
+        assert_eq!(
+            denied(service.allowed(&root.join("out")).await),
+            Code::PermissionDenied
+        );
+
+        let service = service.allow(&dir).unwrap();
+        assert_eq!(service.allowed(&root.join("out")).await.unwrap(), *dir);
+    }
+
+    #[tokio::test]
+    async fn remote_watch() {
+        let dir = scratch("grpc-watch");
+        let mut client = serve(GrpcService::new(&dir).unwrap()).await;
+
+        let mut events = client.watch(&dir, Mask::CREATE).await.unwrap();
+        std::fs::File::create(dir.join("f")).unwrap();
+
+        let event = tokio::time::timeout(Duration::from_secs(10), events.next())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        assert_eq!(event.mask, Mask::CREATE);
+        assert_eq!(event.path, PathBuf::from("f"));
+
+        let err = client.watch(Path::new("/etc"), Mask::CREATE).await;
+        assert_eq!(err.err().unwrap().kind(), io::ErrorKind::PermissionDenied);
+    }
+
+    #[tokio::test]
+    async fn watches_are_limited() {
+        let dir = scratch("grpc-limit");
+        let service = GrpcService::new(&dir).unwrap().max_watches(1);
+        let mut client = serve(service).await;
+
+        let first = client.watch(&dir, Mask::CREATE).await.unwrap();
+        let err = client.watch(&dir, Mask::CREATE).await;
+        assert_eq!(err.err().unwrap().raw_os_error(), Some(sys::ENOSPC));
+
+        // the stream going away hands its watch back
+        drop(first);
+        let retry = async {
+            loop {
+                match client.watch(&dir, Mask::CREATE).await {
+                    Ok(events) => return events,
+                    Err(_) => tokio::time::sleep(Duration::from_millis(10)).await,
+                }
+            }
+        };
+        tokio::time::timeout(Duration::from_secs(10), retry)
+            .await
+            .unwrap();
+    }
+}
@@ -0,0 +1,277 @@
+use std::{collections::HashMap, io, time::Duration};
+
+use tokio::time::Instant;
+
+use crate::{Event, INotify, Mask, Watch};
+
+/// Protects against watches producing more events than a consumer can handle
+///
+/// Once a watch exceeds the configured events per second its mask is
+/// narrowed, or it is paused, and the consumer is told with [Guarded].
+pub struct RateGuard {
+    limit: u64,
+    action: GuardAction,
+    windows: HashMap<Watch, (Instant, u64)>,
+    guarded: HashMap<Watch, (Mask, Option<Instant>)>,
+}
+
+/// What a [RateGuard] does to a watch over its limit
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GuardAction {
+    /// Remove these flags from the watch's mask
+    ///
+    /// A watch left with no events is still told of DELETE_SELF and MOVE_SELF.
+    Narrow(Mask),
+
+    /// Only watch for the watched object going away for a while
+    Pause(Duration),
+}
+
+/// A watch that was throttled by a [RateGuard]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Guarded {
+    /// The throttled watch
+    pub watch: Watch,
+
+    /// The mask the watch had before being throttled, with its flags
+    pub original: Mask,
+
+    /// The action taken
+    pub action: GuardAction,
+}
+
+impl RateGuard {
+    /// Narrow the mask of watches over `limit` events per second
+    ///
+    /// ACCESS, OPEN and CLOSE_NOWRITE are dropped, see [RateGuard::with_action] for other policies.
+    pub fn new(limit: u64) -> Self {
+        Self::with_action(
+            limit,
+            GuardAction::Narrow(Mask::ACCESS | Mask::OPEN | Mask::CLOSE_NOWRITE),
+        )
+    }
+
+    /// Apply `action` to watches over `limit` events per second
+    pub fn with_action(limit: u64, action: GuardAction) -> Self {
+        Self {
+            limit,
+            action,
+            windows: HashMap::new(),
+            guarded: HashMap::new(),
+        }
+    }
+
+    /// Count an event, throttling its watch if it went over the limit
+    ///
+    /// IGNORED forgets the watch, its descriptor may be reused.
+    pub fn observe(&mut self, inotify: &mut INotify, event: &Event) -> io::Result<Option<Guarded>> {
+        let watch = event.watch;
+        if event.mask.contains(Mask::IGNORED) {
+            self.windows.remove(&watch);
+            self.guarded.remove(&watch);
+            return Ok(None);
+        }
+
+        if self.guarded.contains_key(&watch) {
+            return Ok(None);
+        }
+
+        let now = Instant::now();
+        let (start, count) = self.windows.entry(watch).or_insert((now, 0));
+
+        if now.duration_since(*start) >= Duration::from_secs(1) {
+            *start = now;
+            *count = 0;
+        }
+
+        *count += 1;
+        if *count <= self.limit {
+            return Ok(None);
+        }
+
+        // flags such as DONT_FOLLOW must survive, or the watch moves to another object
+        let Some(original) = inotify.registration(watch) else {
+            return Ok(None);
+        };
+        let flags = Mask(original.0 & !Mask::INTEREST.0);
+
+        let (mask, resume) = match self.action {
+            GuardAction::Narrow(drop) => match original.0 & !drop.0 & Mask::INTEREST.0 {
+                // a mask of flags alone is refused by the kernel
+                0 => (Mask::DELETE_SELF | Mask::MOVE_SELF | flags, None),
+                kept => (Mask(kept | flags.0), None),
+            },
+            GuardAction::Pause(period) => (
+                Mask::DELETE_SELF | Mask::MOVE_SELF | flags,
+                Some(now + period),
+            ),
+        };
+
+        inotify.set_mask(watch, mask)?;
+        self.windows.remove(&watch);
+        self.guarded.insert(watch, (original, resume));
+
+        Ok(Some(Guarded {
+            watch,
+            original,
+            action: self.action,
+        }))
+    }
+
+    /// Restore paused watches whose pause has elapsed
+    ///
+    /// Every due watch is tried, the first error is returned once all
+    /// were. Watches failing to restore stay paused and are tried again,
+    /// watches no longer known to the INotify are dropped.
+    pub fn resume(&mut self, inotify: &mut INotify) -> io::Result<Vec<Watch>> {
+        let now = Instant::now();
+        let due: Vec<(Watch, Mask)> = self
+            .guarded
+            .iter()
+            .filter(|(_, (_, until))| until.is_some_and(|until| until <= now))
+            .map(|(watch, (original, _))| (*watch, *original))
+            .collect();
+
+        let mut resumed = Vec::new();
+        let mut res = Ok(());
+        for (watch, original) in due {
+            match inotify.set_mask(watch, original) {
+                Ok(()) => {
+                    self.guarded.remove(&watch);
+                    resumed.push(watch);
+                }
+                Err(_) if inotify.path(watch).is_none() => {
+                    self.guarded.remove(&watch);
+                }
+                Err(err) => {
+                    if res.is_ok() {
+                        res = Err(err);
+                    }
+                }
+            }
+        }
+
+        res.map(|()| resumed)
+    }
+
+    /// Restore a throttled watch's original mask
+    pub fn release(&mut self, inotify: &mut INotify, watch: Watch) -> io::Result<()> {
+        if let Some((original, _)) = self.guarded.remove(&watch) {
+            inotify.set_mask(watch, original)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::scratch;
+
+    /// observe `n` events of `mask` on `watch`, returning the last outcome
+    fn burst(
+        guard: &mut RateGuard,
+        inotify: &mut INotify,
+        watch: Watch,
+        mask: Mask,
+        n: usize,
+    ) -> Option<Guarded> {
+        let event = Event::new(watch, mask, "f");
+        let mut last = None;
+        for _ in 0..n {
+            last = guard.observe(inotify, &event).unwrap().or(last);
+        }
+        last
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn pauses_and_resumes() {
+        let dir = scratch("guard-pause");
+        let mut inotify = INotify::new().unwrap();
+        let mask = Mask::MODIFY | Mask::ONLYDIR;
+        let watch = inotify.add(&dir, mask).unwrap();
+
+        let action = GuardAction::Pause(Duration::from_secs(5));
+        let mut guard = RateGuard::with_action(3, action);
+        assert_eq!(burst(&mut guard, &mut inotify, watch, Mask::MODIFY, 3), None);
+
+        let guarded = burst(&mut guard, &mut inotify, watch, Mask::MODIFY, 1).unwrap();
+        assert_eq!(guarded.original, mask);
+        assert_eq!(guarded.action, action);
+        assert_eq!(
+            inotify.registration(watch),
+            Some(Mask::DELETE_SELF | Mask::MOVE_SELF | Mask::ONLYDIR)
+        );
+
+        assert!(guard.resume(&mut inotify).unwrap().is_empty());
+        tokio::time::advance(Duration::from_secs(5)).await;
+        assert_eq!(guard.resume(&mut inotify).unwrap(), [watch]);
+        assert_eq!(inotify.registration(watch), Some(mask));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn windows_reset_every_second() {
+        let dir = scratch("guard-window");
+        let mut inotify = INotify::new().unwrap();
+        let watch = inotify.add(&dir, Mask::MODIFY).unwrap();
+
+        let mut guard = RateGuard::new(2);
+        for _ in 0..3 {
+            assert_eq!(burst(&mut guard, &mut inotify, watch, Mask::MODIFY, 2), None);
+            tokio::time::advance(Duration::from_secs(1)).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn narrows_keeping_interest() {
+        let dir = scratch("guard-narrow");
+        let mut inotify = INotify::new().unwrap();
+        let busy = inotify
+            .add(&dir, Mask::OPEN | Mask::MODIFY | Mask::DONT_FOLLOW)
+            .unwrap();
+        std::fs::create_dir(dir.join("quiet")).unwrap();
+        let quiet = inotify.add(&dir.join("quiet"), Mask::OPEN).unwrap();
+
+        let mut guard = RateGuard::new(1);
+        assert!(burst(&mut guard, &mut inotify, busy, Mask::OPEN, 2).is_some());
+        assert_eq!(
+            inotify.registration(busy),
+            Some(Mask::MODIFY | Mask::DONT_FOLLOW)
+        );
+
+        // nothing would be left, the watch still hears of its end
+        assert!(burst(&mut guard, &mut inotify, quiet, Mask::OPEN, 2).is_some());
+        assert_eq!(
+            inotify.registration(quiet),
+            Some(Mask::DELETE_SELF | Mask::MOVE_SELF)
+        );
+
+        // narrowed watches stay so until released
+        assert!(guard.resume(&mut inotify).unwrap().is_empty());
+        guard.release(&mut inotify, busy).unwrap();
+        assert_eq!(
+            inotify.registration(busy),
+            Some(Mask::OPEN | Mask::MODIFY | Mask::DONT_FOLLOW)
+        );
+    }
+
+    #[tokio::test]
+    async fn forgets_ignored_watches() {
+        let dir = scratch("guard-ignored");
+        let mut inotify = INotify::new().unwrap();
+        let watch = inotify.add(&dir, Mask::OPEN | Mask::MODIFY).unwrap();
+
+        let mut guard = RateGuard::new(1);
+        assert!(burst(&mut guard, &mut inotify, watch, Mask::OPEN, 2).is_some());
+        assert!(burst(&mut guard, &mut inotify, watch, Mask::OPEN, 5).is_none());
+
+        burst(&mut guard, &mut inotify, watch, Mask::IGNORED, 1);
+        assert!(guard.windows.is_empty());
+        assert!(guard.guarded.is_empty());
+
+        // the descriptor reused for another watch is throttled anew
+        inotify.set_mask(watch, Mask::OPEN | Mask::MODIFY).unwrap();
+        assert!(burst(&mut guard, &mut inotify, watch, Mask::OPEN, 2).is_some());
+    }
+}
@@ -0,0 +1,149 @@
+use std::{
+    collections::HashMap,
+    io,
+    os::unix::fs::MetadataExt,
+    path::{Path, PathBuf},
+};
+
+use crate::{Event, INotify, Identity, Watch};
+
+/// Flags events on watched files that have more than one hard link
+///
+/// Writes through any link of a watched file are reported against the
+/// path it was watched by, these events are marked as possibly via a hardlink.
+#[derive(Default)]
+pub struct Hardlinks {
+    linked: HashMap<Watch, Identity>,
+    index: Option<LinkIndex>,
+}
+
+/// An index of the paths of multiply linked files under a set of roots
+#[derive(Default)]
+pub struct LinkIndex {
+    paths: HashMap<Identity, Vec<PathBuf>>,
+}
+
+impl Hardlinks {
+    /// Build a new hardlink tracker
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Use an index to enumerate alternate paths
+    pub fn with_index(index: LinkIndex) -> Self {
+        Self {
+            linked: HashMap::new(),
+            index: Some(index),
+        }
+    }
+
+    /// Check the link count of a watch, returning it
+    pub async fn check(&mut self, inotify: &INotify, watch: Watch) -> io::Result<u64> {
+        let Some(path) = inotify.path(watch) else {
+            return Ok(0);
+        };
+
+        let meta = tokio::fs::metadata(path).await?;
+        let nlink = meta.nlink();
+
+        if !meta.is_dir() && nlink > 1 {
+            self.linked.insert(watch, Identity::from(&meta));
+        } else {
+            self.linked.remove(&watch);
+        }
+
+        Ok(nlink)
+    }
+
+    /// Forget a watch
+    pub fn forget(&mut self, watch: Watch) {
+        self.linked.remove(&watch);
+    }
+
+    /// Whether an event may have been caused through another hardlink
+    pub fn possibly_via_hardlink(&self, event: &Event) -> bool {
+        event.path.as_os_str().is_empty() && self.linked.contains_key(&event.watch)
+    }
+
+    /// The other paths of the file an event refers to (requires an index)
+    pub fn alternates(&self, inotify: &INotify, event: &Event) -> Vec<PathBuf> {
+        let (Some(identity), Some(index)) = (self.linked.get(&event.watch), &self.index) else {
+            return Vec::new();
+        };
+
+        let watched = inotify.path(event.watch);
+
+        index
+            .paths(*identity)
+            .iter()
+            .filter(|p| Some(p.as_path()) != watched)
+            .cloned()
+            .collect()
+    }
+}
+
+impl LinkIndex {
+    /// Index all multiply linked files under the given roots
+    pub fn build(roots: &[&Path]) -> io::Result<LinkIndex> {
+        let mut index = LinkIndex::default();
+        let mut stack: Vec<PathBuf> = roots.iter().map(|p| p.to_path_buf()).collect();
+
+        while let Some(dir) = stack.pop() {
+            for entry in std::fs::read_dir(&dir)? {
+                let entry = entry?;
+                let meta = entry.metadata()?;
+
+                if meta.is_dir() {
+                    stack.push(entry.path());
+                } else if meta.nlink() > 1 {
+                    index.insert(Identity::from(&meta), entry.path());
+                }
+            }
+        }
+
+        Ok(index)
+    }
+
+    /// Add a path to the index
+    pub fn insert(&mut self, identity: Identity, path: PathBuf) {
+        self.paths.entry(identity).or_default().push(path);
+    }
+
+    /// All known paths to a file
+    pub fn paths(&self, identity: Identity) -> &[PathBuf] {
+        self.paths.get(&identity).map(Vec::as_slice).unwrap_or(&[])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{testing::scratch, Mask};
+
+    #[tokio::test]
+    async fn flags_and_lists_other_links() {
+        let dir = scratch("hardlinks");
+        std::fs::write(dir.join("a"), "").unwrap();
+        std::fs::hard_link(dir.join("a"), dir.join("b")).unwrap();
+        std::fs::write(dir.join("single"), "").unwrap();
+
+        let mut inotify = INotify::new().unwrap();
+        let linked = inotify.add(&dir.join("a"), Mask::MODIFY).unwrap();
+        let single = inotify.add(&dir.join("single"), Mask::MODIFY).unwrap();
+
+        let index = LinkIndex::build(&[&dir]).unwrap();
+        let mut hardlinks = Hardlinks::with_index(index);
+        assert_eq!(hardlinks.check(&inotify, linked).await.unwrap(), 2);
+        assert_eq!(hardlinks.check(&inotify, single).await.unwrap(), 1);
+
+        let modified = Event::new(linked, Mask::MODIFY, "");
+        assert!(hardlinks.possibly_via_hardlink(&modified));
+        assert!(!hardlinks.possibly_via_hardlink(&Event::new(single, Mask::MODIFY, "")));
+        assert_eq!(hardlinks.alternates(&inotify, &modified), [dir.join("b")]);
+
+        // once unlinked the other way, writes can only come through the watched path
+        std::fs::remove_file(dir.join("b")).unwrap();
+        assert_eq!(hardlinks.check(&inotify, linked).await.unwrap(), 1);
+        assert!(!hardlinks.possibly_via_hardlink(&modified));
+    }
+}
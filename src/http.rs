@@ -0,0 +1,784 @@
+use std::{
+    convert::Infallible,
+    future::{ready, Ready},
+    io,
+    path::PathBuf,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use bytes::Bytes;
+use hyper::{
+    body::{Body, Frame, Incoming},
+    header::{self, HeaderMap},
+    service::Service,
+    Method, Request, Response, StatusCode,
+};
+use hyper_util::rt::TokioIo;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    sync::{broadcast, mpsc},
+};
+
+use crate::{Event, Format, INotify, Mask};
+
+const WS_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// The largest client frame accepted, clients only need control frames
+const WS_MAX_FRAME: usize = 64 * 1024;
+
+/// Close status for a protocol error, e.g. an unmasked client frame
+const WS_PROTOCOL_ERROR: u16 = 1002;
+
+/// Close status for data the server does not take, clients only send control frames
+const WS_UNSUPPORTED_DATA: u16 = 1003;
+
+/// Close status for a frame too big to process
+const WS_TOO_BIG: u16 = 1009;
+
+/// Publishes events to every client of its [EventService]s
+///
+/// Events are published with their full path. A client too slow to keep
+/// up with `capacity` events is told how many it missed.
+pub struct Publisher {
+    tx: broadcast::Sender<Arc<Event>>,
+}
+
+/// A hyper service streaming published events
+///
+/// Every request subscribes to the events of its [Publisher]. A WebSocket
+/// upgrade receives a text frame per event, any other `GET` a stream of
+/// Server-Sent Events. Both carry the JSON of [Format::Jsonl]. Clients
+/// filter with query parameters:
+///
+/// - `prefix`: only events whose path starts with this path
+/// - `mask`: only events with one of these flags, either a number or
+///   names separated by `,` or `|` (e.g. `CREATE,DELETE`)
+#[derive(Clone)]
+pub struct EventService {
+    tx: broadcast::Sender<Arc<Event>>,
+}
+
+/// The body of an [EventService] response
+pub struct EventBody {
+    rx: Option<mpsc::Receiver<Bytes>>,
+    once: Option<Bytes>,
+}
+
+struct Filter {
+    prefix: Option<PathBuf>,
+    mask: Option<Mask>,
+}
+
+impl Publisher {
+    /// Build a publisher buffering up to `capacity` events per client
+    pub fn new(capacity: usize) -> Self {
+        let (tx, _) = broadcast::channel(capacity.max(1));
+        Self { tx }
+    }
+
+    /// A service subscribing to this publisher
+    pub fn service(&self) -> EventService {
+        EventService {
+            tx: self.tx.clone(),
+        }
+    }
+
+    /// Publish an event, returning how many clients will receive it
+    pub fn publish(&self, inotify: &INotify, event: &Event) -> usize {
+        let mut event = event.clone();
+        if let Some(path) = inotify.resolve(&event) {
+            event.path = path;
+        }
+
+        self.tx.send(Arc::new(event)).unwrap_or(0)
+    }
+
+    /// Publish events until reading from the kernel fails
+    pub async fn run(&self, inotify: &mut INotify) -> io::Result<()> {
+        loop {
+            let event = inotify.watch().await?;
+            self.publish(inotify, &event);
+        }
+    }
+}
+
+impl Service<Request<Incoming>> for EventService {
+    type Response = Response<EventBody>;
+    type Error = Infallible;
+    type Future = Ready<Result<Self::Response, Infallible>>;
+
+    fn call(&self, req: Request<Incoming>) -> Self::Future {
+        ready(Ok(self.respond(req)))
+    }
+}
+
+impl EventService {
+    fn respond(&self, mut req: Request<Incoming>) -> Response<EventBody> {
+        if req.method() != Method::GET {
+            return reply(StatusCode::METHOD_NOT_ALLOWED, "only GET is supported\n");
+        }
+
+        let filter = match Filter::parse(req.uri().query().unwrap_or("")) {
+            Ok(filter) => filter,
+            Err(msg) => return reply(StatusCode::BAD_REQUEST, msg),
+        };
+
+        let key = match websocket_key(req.headers()) {
+            Ok(key) => key,
+            Err(StatusCode::UPGRADE_REQUIRED) => {
+                return Response::builder()
+                    .status(StatusCode::UPGRADE_REQUIRED)
+                    .header(header::SEC_WEBSOCKET_VERSION, "13")
+                    .body(EventBody {
+                        rx: None,
+                        once: Some(Bytes::from_static(
+                            b"only WebSocket version 13 is supported\n",
+                        )),
+                    })
+                    .expect("valid response");
+            }
+            Err(status) => return reply(status, "malformed WebSocket handshake\n"),
+        };
+
+        let rx = self.tx.subscribe();
+
+        if let Some(key) = key {
+            let upgrade = hyper::upgrade::on(&mut req);
+            tokio::spawn(async move {
+                if let Ok(upgraded) = upgrade.await {
+                    let _ = websocket(TokioIo::new(upgraded), rx, filter).await;
+                }
+            });
+
+            return Response::builder()
+                .status(StatusCode::SWITCHING_PROTOCOLS)
+                .header(header::CONNECTION, "upgrade")
+                .header(header::UPGRADE, "websocket")
+                .header(header::SEC_WEBSOCKET_ACCEPT, accept(key.as_bytes()))
+                .body(EventBody::empty())
+                .expect("valid response");
+        }
+
+        let (tx, body) = mpsc::channel(16);
+        tokio::spawn(sse(rx, tx, filter));
+
+        Response::builder()
+            .header(header::CONTENT_TYPE, "text/event-stream")
+            .header(header::CACHE_CONTROL, "no-cache")
+            .body(EventBody {
+                rx: Some(body),
+                once: None,
+            })
+            .expect("valid response")
+    }
+}
+
+impl EventBody {
+    fn empty() -> Self {
+        Self {
+            rx: None,
+            once: None,
+        }
+    }
+}
+
+impl Body for EventBody {
+    type Data = Bytes;
+    type Error = Infallible;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Bytes>, Infallible>>> {
+        if let Some(once) = self.once.take() {
+            return Poll::Ready(Some(Ok(Frame::data(once))));
+        }
+
+        match &mut self.rx {
+            Some(rx) => rx
+                .poll_recv(cx)
+                .map(|data| data.map(|data| Ok(Frame::data(data)))),
+            None => Poll::Ready(None),
+        }
+    }
+}
+
+fn reply(status: StatusCode, msg: &'static str) -> Response<EventBody> {
+    Response::builder()
+        .status(status)
+        .body(EventBody {
+            rx: None,
+            once: Some(Bytes::from_static(msg.as_bytes())),
+        })
+        .expect("valid response")
+}
+
+impl Filter {
+    fn parse(query: &str) -> Result<Filter, &'static str> {
+        let mut filter = Filter {
+            prefix: None,
+            mask: None,
+        };
+
+        for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            let value = percent_decode(value).ok_or("malformed query\n")?;
+
+            match key {
+                "prefix" => filter.prefix = Some(PathBuf::from(value)),
+                "mask" => filter.mask = Some(parse_mask(&value).ok_or("unknown mask\n")?),
+                _ => return Err("unknown query parameter\n"),
+            }
+        }
+
+        Ok(filter)
+    }
+
+    fn matches(&self, event: &Event) -> bool {
+        let prefix = self
+            .prefix
+            .as_ref()
+            .is_none_or(|prefix| event.path.starts_with(prefix));
+        let mask = self.mask.is_none_or(|mask| (mask & event.mask).0 != 0);
+
+        prefix && mask
+    }
+}
+
+fn parse_mask(value: &str) -> Option<Mask> {
+    if let Ok(bits) = value.parse() {
+        return Some(Mask(bits));
+    }
+
+    let mut mask = Mask(0);
+    for name in value.split([',', '|']) {
+        mask |= Mask::from_name(name.trim())?;
+    }
+
+    Some(mask)
+}
+
+fn percent_decode(value: &str) -> Option<String> {
+    let bytes = value.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' => {
+                let hex = std::str::from_utf8(bytes.get(i + 1..i + 3)?).ok()?;
+                out.push(u8::from_str_radix(hex, 16).ok()?);
+                i += 3;
+            }
+            b'+' => {
+                out.push(b' ');
+                i += 1;
+            }
+            b => {
+                out.push(b);
+                i += 1;
+            }
+        }
+    }
+
+    String::from_utf8(out).ok()
+}
+
+/// the next record for a client, `None` once the publisher is gone
+async fn next(
+    rx: &mut broadcast::Receiver<Arc<Event>>,
+    filter: &Filter,
+) -> Option<Result<Vec<u8>, u64>> {
+    loop {
+        match rx.recv().await {
+            Ok(event) if filter.matches(&event) => {
+                let mut out = Vec::new();
+                Format::Jsonl.encode(&event, &mut out);
+                out.pop(); // the newline
+                return Some(Ok(out));
+            }
+            Ok(_) => continue,
+            Err(broadcast::error::RecvError::Lagged(missed)) => return Some(Err(missed)),
+            Err(broadcast::error::RecvError::Closed) => return None,
+        }
+    }
+}
+
+async fn sse(mut rx: broadcast::Receiver<Arc<Event>>, tx: mpsc::Sender<Bytes>, filter: Filter) {
+    while let Some(record) = next(&mut rx, &filter).await {
+        let data = match record {
+            Ok(json) => [b"data: ", &json[..], b"\n\n"].concat(),
+            Err(missed) => format!("event: lagged\ndata: {missed}\n\n").into_bytes(),
+        };
+
+        if tx.send(Bytes::from(data)).await.is_err() {
+            return;
+        }
+    }
+}
+
+async fn websocket<S>(
+    io: S,
+    mut rx: broadcast::Receiver<Arc<Event>>,
+    filter: Filter,
+) -> io::Result<()>
+where
+    S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin,
+{
+    let (mut reader, mut writer) = tokio::io::split(io);
+    let mut incoming = Vec::new();
+    let mut chunk = [0; 512];
+
+    loop {
+        tokio::select! {
+            record = next(&mut rx, &filter) => {
+                let Some(record) = record else {
+                    writer.write_all(&frame(0x8, &[])).await?;
+                    return Ok(());
+                };
+
+                let json = match record {
+                    Ok(json) => json,
+                    Err(missed) => format!("{{\"lagged\":{missed}}}").into_bytes(),
+                };
+                writer.write_all(&frame(0x1, &json)).await?;
+            }
+
+            n = reader.read(&mut chunk) => {
+                let n = n?;
+                if n == 0 {
+                    return Ok(());
+                }
+                incoming.extend_from_slice(&chunk[..n]);
+
+                loop {
+                    let (opcode, payload, used) = match client_frame(&incoming) {
+                        Ok(Some(frame)) => frame,
+                        Ok(None) if incoming.len() > WS_MAX_FRAME + 14 => {
+                            writer.write_all(&frame(0x8, &WS_TOO_BIG.to_be_bytes())).await?;
+                            return Ok(());
+                        }
+                        Ok(None) => break,
+                        Err(status) => {
+                            writer.write_all(&frame(0x8, &status.to_be_bytes())).await?;
+                            return Ok(());
+                        }
+                    };
+
+                    match opcode {
+                        0x8 => {
+                            writer.write_all(&frame(0x8, &[])).await?;
+                            return Ok(());
+                        }
+                        0x9 => writer.write_all(&frame(0xA, &payload)).await?,
+                        0xA => (),
+                        // a continuation, with no message ever started
+                        0x0 => {
+                            let status = WS_PROTOCOL_ERROR.to_be_bytes();
+                            writer.write_all(&frame(0x8, &status)).await?;
+                            return Ok(());
+                        }
+                        _ => {
+                            let status = WS_UNSUPPORTED_DATA.to_be_bytes();
+                            writer.write_all(&frame(0x8, &status)).await?;
+                            return Ok(());
+                        }
+                    }
+                    incoming.drain(..used);
+                }
+            }
+        }
+    }
+}
+
+/// the client key of a WebSocket upgrade, `None` for other requests
+///
+/// Fails with 400 when the handshake is malformed and 426 for versions
+/// other than 13, as RFC 6455 §4.2.1 requires.
+fn websocket_key(headers: &HeaderMap) -> Result<Option<String>, StatusCode> {
+    let header = |name| headers.get(name).and_then(|v| v.to_str().ok());
+
+    let Some(upgrade) = header(header::UPGRADE) else {
+        return Ok(None);
+    };
+    if !upgrade
+        .split(',')
+        .any(|token| token.trim().eq_ignore_ascii_case("websocket"))
+    {
+        return Ok(None);
+    }
+
+    let connection = header(header::CONNECTION).unwrap_or("");
+    if !connection
+        .split(',')
+        .any(|token| token.trim().eq_ignore_ascii_case("upgrade"))
+    {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    if header(header::SEC_WEBSOCKET_VERSION).map(str::trim) != Some("13") {
+        return Err(StatusCode::UPGRADE_REQUIRED);
+    }
+
+    match header(header::SEC_WEBSOCKET_KEY).map(str::trim) {
+        Some(key) if !key.is_empty() => Ok(Some(key.to_string())),
+        _ => Err(StatusCode::BAD_REQUEST),
+    }
+}
+
+/// an unmasked, unfragmented server frame
+fn frame(opcode: u8, payload: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x80 | opcode];
+
+    match payload.len() {
+        len @ 0..=125 => out.push(len as u8),
+        len @ 126..=0xFFFF => {
+            out.push(126);
+            out.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            out.push(127);
+            out.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+
+    out.extend_from_slice(payload);
+    out
+}
+
+/// a complete masked client frame: opcode, unmasked payload and its size
+///
+/// Fails with the close status for frames over [WS_MAX_FRAME], and for
+/// unmasked frames, reserved bits or opcodes and fragmented or long
+/// control frames, which RFC 6455 §5 requires a server to reject.
+fn client_frame(buf: &[u8]) -> Result<Option<(u8, Vec<u8>, usize)>, u16> {
+    let (Some(&first), Some(&second)) = (buf.first(), buf.get(1)) else {
+        return Ok(None);
+    };
+    let opcode = first & 0x0F;
+
+    if second & 0x80 == 0 || first & 0x70 != 0 {
+        return Err(WS_PROTOCOL_ERROR);
+    }
+
+    match opcode {
+        0x0..=0x2 => (),
+        0x8..=0xA if first & 0x80 != 0 && second & 0x7F <= 125 => (),
+        _ => return Err(WS_PROTOCOL_ERROR),
+    }
+
+    let (len, at) = match second & 0x7F {
+        126 => match buf.get(2..4) {
+            Some(len) => (u16::from_be_bytes([len[0], len[1]]) as u64, 4),
+            None => return Ok(None),
+        },
+        127 => match buf.get(2..10) {
+            Some(len) => (u64::from_be_bytes(len.try_into().expect("8 bytes")), 10),
+            None => return Ok(None),
+        },
+        len => (len as u64, 2),
+    };
+
+    if len > WS_MAX_FRAME as u64 {
+        return Err(WS_TOO_BIG);
+    }
+    let len = len as usize;
+
+    let Some(key) = buf.get(at..at + 4) else {
+        return Ok(None);
+    };
+    let Some(payload) = buf.get(at + 4..at + 4 + len) else {
+        return Ok(None);
+    };
+
+    let payload = payload
+        .iter()
+        .enumerate()
+        .map(|(i, b)| b ^ key[i % 4])
+        .collect();
+
+    Ok(Some((opcode, payload, at + 4 + len)))
+}
+
+/// the Sec-WebSocket-Accept for a client key
+fn accept(key: &[u8]) -> String {
+    base64(&sha1(&[key, WS_GUID.as_bytes()].concat()))
+}
+
+fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut h: [u32; 5] = [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476, 0xC3D2E1F0];
+
+    let mut msg = data.to_vec();
+    msg.push(0x80);
+    while msg.len() % 64 != 56 {
+        msg.push(0);
+    }
+    msg.extend_from_slice(&((data.len() as u64) * 8).to_be_bytes());
+
+    for block in msg.chunks(64) {
+        let mut w = [0u32; 80];
+        for (i, word) in block.chunks(4).enumerate() {
+            w[i] = u32::from_be_bytes(word.try_into().expect("4 byte chunk"));
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e] = h;
+        for (i, w) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5A827999),
+                20..=39 => (b ^ c ^ d, 0x6ED9EBA1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1BBCDC),
+                _ => (b ^ c ^ d, 0xCA62C1D6),
+            };
+
+            let t = a
+                .rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(*w);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = t;
+        }
+
+        for (h, v) in h.iter_mut().zip([a, b, c, d, e]) {
+            *h = h.wrapping_add(v);
+        }
+    }
+
+    let mut out = [0; 20];
+    for (out, h) in out.chunks_mut(4).zip(h) {
+        out.copy_from_slice(&h.to_be_bytes());
+    }
+    out
+}
+
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut out = String::new();
+    for chunk in data.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, b)| n | (*b as u32) << (16 - 8 * i));
+
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 0x3F) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accept_matches_rfc_6455() {
+        assert_eq!(
+            accept(b"dGhlIHNhbXBsZSBub25jZQ=="),
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+    }
+
+    #[test]
+    fn sha1_matches_fips_180() {
+        let hex = |hash: [u8; 20]| hash.iter().map(|b| format!("{b:02x}")).collect::<String>();
+
+        assert_eq!(hex(sha1(b"")), "da39a3ee5e6b4b0d3255bfef95601890afd80709");
+        assert_eq!(
+            hex(sha1(b"abc")),
+            "a9993e364706816aba3e25717850c26c9cd0d89d"
+        );
+        assert_eq!(
+            hex(sha1(
+                b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"
+            )),
+            "84983e441c3bd26ebaae4aa1f95129e5e54670f1"
+        );
+    }
+
+    #[test]
+    fn base64_matches_rfc_4648() {
+        for (plain, encoded) in [
+            ("", ""),
+            ("f", "Zg=="),
+            ("fo", "Zm8="),
+            ("foo", "Zm9v"),
+            ("foob", "Zm9vYg=="),
+            ("fooba", "Zm9vYmE="),
+            ("foobar", "Zm9vYmFy"),
+        ] {
+            assert_eq!(base64(plain.as_bytes()), encoded);
+        }
+    }
+
+    #[test]
+    fn handshake_requires_version_and_connection() {
+        let headers = |pairs: &[(header::HeaderName, &'static str)]| {
+            let mut headers = HeaderMap::new();
+            for (name, value) in pairs {
+                headers.insert(name, header::HeaderValue::from_static(value));
+            }
+            headers
+        };
+        let key = (header::SEC_WEBSOCKET_KEY, "dGhlIHNhbXBsZSBub25jZQ==");
+
+        assert_eq!(websocket_key(&headers(&[])), Ok(None));
+
+        let valid = headers(&[
+            (header::UPGRADE, "websocket"),
+            (header::CONNECTION, "keep-alive, Upgrade"),
+            (header::SEC_WEBSOCKET_VERSION, "13"),
+            key.clone(),
+        ]);
+        assert_eq!(
+            websocket_key(&valid),
+            Ok(Some("dGhlIHNhbXBsZSBub25jZQ==".to_string()))
+        );
+
+        let no_connection = headers(&[
+            (header::UPGRADE, "websocket"),
+            (header::SEC_WEBSOCKET_VERSION, "13"),
+            key.clone(),
+        ]);
+        assert_eq!(websocket_key(&no_connection), Err(StatusCode::BAD_REQUEST));
+
+        let old_version = headers(&[
+            (header::UPGRADE, "websocket"),
+            (header::CONNECTION, "upgrade"),
+            (header::SEC_WEBSOCKET_VERSION, "8"),
+            key.clone(),
+        ]);
+        assert_eq!(
+            websocket_key(&old_version),
+            Err(StatusCode::UPGRADE_REQUIRED)
+        );
+
+        let no_key = headers(&[
+            (header::UPGRADE, "websocket"),
+            (header::CONNECTION, "upgrade"),
+            (header::SEC_WEBSOCKET_VERSION, "13"),
+        ]);
+        assert_eq!(websocket_key(&no_key), Err(StatusCode::BAD_REQUEST));
+    }
+
+    #[test]
+    fn malformed_client_frames() {
+        let masked = |first: u8, payload: &[u8]| {
+            let mut buf = vec![first, 0x80 | payload.len() as u8, 0, 0, 0, 0];
+            buf.extend_from_slice(payload);
+            buf
+        };
+
+        // reserved bits, reserved opcodes
+        assert_eq!(client_frame(&masked(0xC1, b"x")), Err(WS_PROTOCOL_ERROR));
+        assert_eq!(client_frame(&masked(0x83, b"x")), Err(WS_PROTOCOL_ERROR));
+        assert_eq!(client_frame(&masked(0x8B, b"x")), Err(WS_PROTOCOL_ERROR));
+
+        // control frames are never fragmented nor longer than 125 bytes
+        assert_eq!(client_frame(&masked(0x09, b"x")), Err(WS_PROTOCOL_ERROR));
+        let mut long = vec![0x89, 0xFE, 0, 126, 0, 0, 0, 0];
+        long.extend_from_slice(&[0; 126]);
+        assert_eq!(client_frame(&long), Err(WS_PROTOCOL_ERROR));
+
+        assert_eq!(
+            client_frame(&masked(0x89, b"x")),
+            Ok(Some((0x9, b"x".to_vec(), 7)))
+        );
+    }
+
+    /// the close status a server answers `sent` with
+    async fn closed_with(sent: &[u8]) -> Vec<u8> {
+        let (client, server) = tokio::io::duplex(1024);
+        let (tx, rx) = broadcast::channel(1);
+        let filter = Filter::parse("").unwrap();
+        let served = tokio::spawn(websocket(server, rx, filter));
+
+        let (mut reader, mut writer) = tokio::io::split(client);
+        writer.write_all(sent).await.unwrap();
+
+        let mut reply = vec![0; 4];
+        reader.read_exact(&mut reply).await.unwrap();
+        served.await.unwrap().unwrap();
+        drop(tx);
+        reply
+    }
+
+    #[tokio::test]
+    async fn closes_on_unexpected_frames() {
+        let masked = |first: u8| vec![first, 0x81, 0, 0, 0, 0, b'x'];
+
+        // text, binary and fragmented data are not taken
+        for first in [0x81, 0x82, 0x01] {
+            assert_eq!(closed_with(&masked(first)).await, [0x88, 2, 0x03, 0xEB]);
+        }
+
+        // a continuation of nothing
+        assert_eq!(closed_with(&masked(0x80)).await, [0x88, 2, 0x03, 0xEA]);
+    }
+
+    #[tokio::test]
+    async fn answers_pings() {
+        let (client, server) = tokio::io::duplex(1024);
+        let (_tx, rx) = broadcast::channel(1);
+        let served = tokio::spawn(websocket(server, rx, Filter::parse("").unwrap()));
+
+        let (mut reader, mut writer) = tokio::io::split(client);
+        writer
+            .write_all(&[0x89, 0x82, 0, 0, 0, 0, b'h', b'i'])
+            .await
+            .unwrap();
+        let mut pong = vec![0; 4];
+        reader.read_exact(&mut pong).await.unwrap();
+        assert_eq!(pong, [0x8A, 2, b'h', b'i']);
+
+        writer.write_all(&[0x88, 0x80, 0, 0, 0, 0]).await.unwrap();
+        let mut close = vec![0; 2];
+        reader.read_exact(&mut close).await.unwrap();
+        assert_eq!(close, [0x88, 0]);
+        served.await.unwrap().unwrap();
+    }
+
+    #[test]
+    fn masked_client_frame() {
+        // RFC 6455 §5.7, a masked "Hello"
+        let buf = [
+            0x81, 0x85, 0x37, 0xfa, 0x21, 0x3d, 0x7f, 0x9f, 0x4d, 0x51, 0x58,
+        ];
+
+        assert_eq!(client_frame(&buf), Ok(Some((0x1, b"Hello".to_vec(), 11))));
+        assert_eq!(client_frame(&buf[..7]), Ok(None));
+    }
+
+    #[test]
+    fn unmasked_client_frame() {
+        // RFC 6455 §5.7, an unmasked "Hello"
+        let buf = [0x81, 0x05, 0x48, 0x65, 0x6c, 0x6c, 0x6f];
+
+        assert_eq!(client_frame(&buf), Err(WS_PROTOCOL_ERROR));
+    }
+
+    #[test]
+    fn oversized_client_frame() {
+        let mut buf = vec![0x82, 0xFF];
+        buf.extend_from_slice(&(u64::MAX).to_be_bytes());
+        assert_eq!(client_frame(&buf), Err(WS_TOO_BIG));
+
+        let mut buf = vec![0x82, 0xFF];
+        buf.extend_from_slice(&(WS_MAX_FRAME as u64 + 1).to_be_bytes());
+        assert_eq!(client_frame(&buf), Err(WS_TOO_BIG));
+    }
+}
@@ -0,0 +1,74 @@
+use std::{io, os::unix::fs::MetadataExt, path::Path};
+
+/// A stable file identity (device and inode number)
+///
+/// Two paths with the same identity refer to the same file, a path whose
+/// identity changed now refers to a different file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Identity {
+    /// The device containing the file
+    pub dev: u64,
+
+    /// The inode number of the file
+    pub ino: u64,
+}
+
+impl Identity {
+    /// The identity of a path (symlinks are not followed)
+    pub fn of(path: &Path) -> io::Result<Identity> {
+        let meta = std::fs::symlink_metadata(path)?;
+
+        Ok(Identity::from(&meta))
+    }
+
+    #[cfg(feature = "tokio")]
+    pub(crate) async fn of_async(path: &Path) -> io::Result<Identity> {
+        let meta = tokio::fs::symlink_metadata(path).await?;
+
+        Ok(Identity::from(&meta))
+    }
+}
+
+impl From<&std::fs::Metadata> for Identity {
+    fn from(meta: &std::fs::Metadata) -> Self {
+        Identity {
+            dev: meta.dev(),
+            ino: meta.ino(),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "tokio"))]
+mod tests {
+    use super::*;
+    use crate::{testing::scratch, INotify, Mask};
+    use std::time::Duration;
+
+    async fn next(inotify: &mut INotify) -> crate::Event {
+        tokio::time::timeout(Duration::from_secs(10), inotify.watch())
+            .await
+            .unwrap()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn renamed_files_keep_their_identity() {
+        let dir = scratch("identity");
+        let mut inotify = INotify::new().unwrap();
+        inotify.track_identity(true);
+        let watch = inotify.add(&dir, Mask::CREATE | Mask::MOVED_TO).unwrap();
+        assert_eq!(inotify.identity(watch), Some(Identity::of(&dir).unwrap()));
+
+        std::fs::File::create(dir.join("f")).unwrap();
+        let created = next(&mut inotify).await.identity().unwrap();
+        assert_eq!(created, Identity::of(&dir.join("f")).unwrap());
+
+        std::fs::rename(dir.join("f"), dir.join("g")).unwrap();
+        assert_eq!(next(&mut inotify).await.identity(), Some(created));
+
+        // a different file under the old name
+        std::fs::File::create(dir.join("f")).unwrap();
+        let replaced = next(&mut inotify).await.identity().unwrap();
+        assert_ne!(replaced, created);
+    }
+}
@@ -0,0 +1,317 @@
+use std::{
+    io,
+    os::{linux::net::SocketAddrExt, unix::ffi::OsStrExt},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{UnixListener, UnixStream},
+    sync::broadcast,
+    task::JoinHandle,
+};
+
+use crate::{parse, Event, INotify, Mask, Watch};
+
+/// The longest name field of a frame, a full path and its NUL
+const PATH_LIMIT: usize = 4096;
+
+/// Attempts at claiming a root while its leader is going away
+const ATTEMPTS: usize = 3;
+
+/// One process per watched tree, found through an abstract unix socket
+///
+/// The first process to claim a root becomes its [Leader] and binds a
+/// socket named after the canonical root, later processes find it taken
+/// and become [Follower]s. A follower may read the leader's events instead
+/// of adding kernel watches of its own, or just take note that the tree is
+/// watched already. The name is released when the leader is dropped or its
+/// process exits, a follower then sees the end of its events and can claim
+/// the root itself.
+pub enum Instance {
+    /// This process owns the root
+    Leader(Leader),
+
+    /// Another process owns the root
+    Follower(Follower),
+}
+
+/// The process owning a root, broadcasting its events to followers
+pub struct Leader {
+    root: PathBuf,
+    tx: broadcast::Sender<Arc<[u8]>>,
+    accept: JoinHandle<()>,
+}
+
+/// A connection to the leader of a root
+///
+/// Events carry the leader's watch descriptors and their full path. A
+/// follower falling behind the leader receives a Q_OVERFLOW in place of
+/// the events it missed.
+pub struct Follower {
+    root: PathBuf,
+    stream: UnixStream,
+    buf: Vec<u8>,
+}
+
+impl Instance {
+    /// Claim `root`, following its leader when another process claimed it first
+    ///
+    /// Followers falling more than `capacity` events behind lose events.
+    pub async fn claim(root: &Path, capacity: usize) -> io::Result<Instance> {
+        let root = std::fs::canonicalize(root)?;
+        let addr = std::os::unix::net::SocketAddr::from_abstract_name(name(&root))?;
+
+        for _ in 0..ATTEMPTS {
+            match std::os::unix::net::UnixListener::bind_addr(&addr) {
+                Ok(listener) => {
+                    listener.set_nonblocking(true)?;
+                    let listener = UnixListener::from_std(listener)?;
+                    return Ok(Instance::Leader(Leader::new(root, listener, capacity)));
+                }
+                Err(err) if err.kind() != io::ErrorKind::AddrInUse => return Err(err),
+                Err(_) => (),
+            }
+
+            match std::os::unix::net::UnixStream::connect_addr(&addr) {
+                Ok(stream) => {
+                    stream.set_nonblocking(true)?;
+                    return Ok(Instance::Follower(Follower {
+                        root,
+                        stream: UnixStream::from_std(stream)?,
+                        buf: Vec::new(),
+                    }));
+                }
+                // the leader let go of the name in between, try taking it again
+                Err(err) if err.kind() == io::ErrorKind::ConnectionRefused => (),
+                Err(err) => return Err(err),
+            }
+        }
+
+        Err(io::Error::new(
+            io::ErrorKind::AddrInUse,
+            "root changes hands too quickly to claim",
+        ))
+    }
+
+    /// whether another process owns the root
+    pub fn is_follower(&self) -> bool {
+        matches!(self, Instance::Follower(_))
+    }
+
+    /// the canonical root claimed
+    pub fn root(&self) -> &Path {
+        match self {
+            Instance::Leader(leader) => leader.root(),
+            Instance::Follower(follower) => follower.root(),
+        }
+    }
+}
+
+impl Leader {
+    fn new(root: PathBuf, listener: UnixListener, capacity: usize) -> Self {
+        let (tx, _) = broadcast::channel(capacity.max(1));
+
+        let clients = tx.clone();
+        let accept = tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(forward(stream, clients.subscribe()));
+            }
+        });
+
+        Leader { root, tx, accept }
+    }
+
+    /// the canonical root owned
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// The number of connected followers
+    pub fn followers(&self) -> usize {
+        self.tx.receiver_count()
+    }
+
+    /// Send an event to every follower, returning how many will receive it
+    pub fn publish(&self, inotify: &INotify, event: &Event) -> usize {
+        let path = inotify
+            .resolve(event)
+            .unwrap_or_else(|| event.path.clone());
+
+        let frame = frame(event.watch.as_raw(), event.mask, event.cookie, &path);
+        self.tx.send(frame.into()).unwrap_or(0)
+    }
+
+    /// Publish events until reading from the kernel fails
+    pub async fn run(&self, inotify: &mut INotify) -> io::Result<()> {
+        loop {
+            let event = inotify.watch().await?;
+            self.publish(inotify, &event);
+        }
+    }
+}
+
+impl Drop for Leader {
+    fn drop(&mut self) {
+        self.accept.abort();
+    }
+}
+
+impl Follower {
+    /// the canonical root followed
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Wait for the leader's next event, `None` once the leader is gone
+    pub async fn next(&mut self) -> io::Result<Option<Event>> {
+        loop {
+            match parse::next_within(&self.buf, PATH_LIMIT) {
+                Ok((header, name, used)) => {
+                    let path = PathBuf::from(std::ffi::OsStr::from_bytes(name));
+                    let mut event = Event::new(Watch::from_raw(header.wd), Mask(header.mask), path);
+                    event.cookie = header.cookie;
+
+                    self.buf.drain(..used);
+                    return Ok(Some(event));
+                }
+                Err(parse::ParseError::Truncated { .. }) => (),
+                Err(err) => return Err(err.into()),
+            }
+
+            if self.stream.read_buf(&mut self.buf).await? == 0 {
+                return Ok(None);
+            }
+        }
+    }
+}
+
+/// Write broadcast frames to a follower until either side goes away
+async fn forward(mut stream: UnixStream, mut rx: broadcast::Receiver<Arc<[u8]>>) {
+    loop {
+        let frame = match rx.recv().await {
+            Ok(frame) => frame,
+            Err(broadcast::error::RecvError::Lagged(_)) => {
+                frame(-1, Mask::Q_OVERFLOW, 0, Path::new("")).into()
+            }
+            Err(broadcast::error::RecvError::Closed) => return,
+        };
+
+        if stream.write_all(&frame).await.is_err() {
+            return;
+        }
+    }
+}
+
+/// an event in the layout of `struct inotify_event`, named by its full path
+fn frame(wd: std::ffi::c_int, mask: Mask, cookie: u32, path: &Path) -> Vec<u8> {
+    let name = path.as_os_str().as_bytes();
+    let len = if name.is_empty() { 0 } else { name.len() + 1 };
+
+    let mut frame = Vec::with_capacity(parse::HEADER_SIZE + len);
+    frame.extend_from_slice(&wd.to_ne_bytes());
+    frame.extend_from_slice(&mask.0.to_ne_bytes());
+    frame.extend_from_slice(&cookie.to_ne_bytes());
+    frame.extend_from_slice(&(len as u32).to_ne_bytes());
+    if len != 0 {
+        frame.extend_from_slice(name);
+        frame.push(0);
+    }
+    frame
+}
+
+/// the abstract socket name of a root, a hash keeps it within the address limit
+fn name(root: &Path) -> Vec<u8> {
+    // FNV-1a, stable across builds unlike the std hasher
+    let hash = root
+        .as_os_str()
+        .as_bytes()
+        .iter()
+        .fold(0xcbf29ce484222325u64, |hash, b| {
+            (hash ^ *b as u64).wrapping_mul(0x100000001b3)
+        });
+
+    format!("tokinotify/{hash:016x}").into_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::scratch;
+    use std::time::Duration;
+
+    async fn pair(root: &Path) -> (Leader, Follower) {
+        let Instance::Leader(leader) = Instance::claim(root, 16).await.unwrap() else {
+            panic!("the first claim leads");
+        };
+        let Instance::Follower(follower) = Instance::claim(root, 16).await.unwrap() else {
+            panic!("a later claim follows");
+        };
+
+        // the leader subscribes a follower once it accepted the connection
+        while leader.followers() == 0 {
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+
+        (leader, follower)
+    }
+
+    async fn next(inotify: &mut INotify) -> Event {
+        tokio::time::timeout(Duration::from_secs(10), inotify.watch())
+            .await
+            .unwrap()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn followers_share_the_leaders_events() {
+        let dir = scratch("instance");
+        let mut inotify = INotify::new().unwrap();
+        let watch = inotify.add(&dir, Mask::CREATE).unwrap();
+
+        let (leader, mut follower) = pair(&dir).await;
+        assert_eq!(follower.root(), leader.root());
+
+        std::fs::File::create(dir.join("f")).unwrap();
+        let event = next(&mut inotify).await;
+        assert_eq!(leader.publish(&inotify, &event), 1);
+
+        let event = follower.next().await.unwrap().unwrap();
+        assert_eq!(event.watch, watch);
+        assert_eq!(event.mask, Mask::CREATE);
+        assert_eq!(event.path, dir.join("f"));
+    }
+
+    #[tokio::test]
+    async fn followers_take_over_once_the_leader_is_gone() {
+        let dir = scratch("instance-takeover");
+        let (leader, mut follower) = pair(&dir).await;
+
+        drop(leader);
+        assert!(follower.next().await.unwrap().is_none());
+
+        let instance = Instance::claim(&dir, 16).await.unwrap();
+        assert!(!instance.is_follower());
+        assert_eq!(instance.root(), &*dir);
+    }
+
+    #[tokio::test]
+    async fn followers_receive_paths_longer_than_a_name() {
+        let dir = scratch("instance-long");
+        let deep = dir.join("d".repeat(200)).join("e".repeat(200));
+        std::fs::create_dir_all(&deep).unwrap();
+
+        let mut inotify = INotify::new().unwrap();
+        inotify.add(&deep, Mask::CREATE).unwrap();
+        let (leader, mut follower) = pair(&dir).await;
+
+        std::fs::File::create(deep.join("f")).unwrap();
+        let event = next(&mut inotify).await;
+        assert_eq!(leader.publish(&inotify, &event), 1);
+
+        let event = follower.next().await.unwrap().unwrap();
+        assert!(deep.join("f").as_os_str().len() > parse::NAME_LIMIT);
+        assert_eq!(event.path, deep.join("f"));
+    }
+}
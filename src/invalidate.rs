@@ -0,0 +1,88 @@
+use std::{io, path::Path};
+
+use crate::{Debounce, Event, INotify, Mask};
+
+type Batch = Box<dyn FnMut(&Path) + Send>;
+
+/// Invalidates path keyed caches as the files behind them change
+///
+/// Events go through a [Debounce] so a burst of writes invalidates once.
+/// A change to a single entry calls the invalidation callback with its
+/// full path. Directory wide events, a subdirectory moving or going away,
+/// a watch being dropped or the kernel queue overflowing, instead call the
+/// batch callback with the directory, which should drop every key under
+/// it. Without a batch callback they call the invalidation callback with
+/// the directory.
+pub struct Invalidator<F> {
+    debounce: Debounce,
+    invalidate: F,
+    batch: Option<Batch>,
+}
+
+/// Events that change what lives under a directory, not a single entry
+const DIRECTORY_WIDE: Mask = Mask(
+    Mask::DELETE_SELF.0 | Mask::MOVE_SELF.0 | Mask::UNMOUNT.0 | Mask::IGNORED.0,
+);
+
+/// Events that move or remove an entry, a whole tree when it is a directory
+const ENTRY_GONE: Mask = Mask(Mask::DELETE.0 | Mask::MOVED_FROM.0 | Mask::MOVED_TO.0);
+
+impl<F: FnMut(&Path)> Invalidator<F> {
+    /// Invalidate with `invalidate` once events have been debounced
+    pub fn new(debounce: Debounce, invalidate: F) -> Self {
+        Self {
+            debounce,
+            invalidate,
+            batch: None,
+        }
+    }
+
+    /// Invalidate everything under a directory with `batch`
+    pub fn batch(mut self, batch: impl FnMut(&Path) + Send + 'static) -> Self {
+        self.batch = Some(Box::new(batch));
+        self
+    }
+
+    /// Invalidate for an event, returning how many callbacks ran
+    pub fn handle(&mut self, inotify: &INotify, event: &Event) -> usize {
+        if event.mask.contains(Mask::Q_OVERFLOW) {
+            // anything may have changed
+            let roots: Vec<_> = inotify.paths.values().map(|path| path.to_path_buf()).collect();
+            for root in &roots {
+                self.invalidate_under(root);
+            }
+            return roots.len();
+        }
+
+        let Some(path) = inotify.resolve(event) else {
+            return 0;
+        };
+
+        let self_event = event.path.as_os_str().is_empty();
+        let wide = (self_event && (event.mask & DIRECTORY_WIDE).0 != 0)
+            || (event.mask.contains(Mask::ISDIR) && (event.mask & ENTRY_GONE).0 != 0);
+
+        if wide {
+            self.invalidate_under(&path);
+        } else {
+            (self.invalidate)(&path);
+        }
+
+        1
+    }
+
+    /// Invalidate until reading from the kernel fails
+    pub async fn run(&mut self, inotify: &mut INotify) -> io::Result<()> {
+        loop {
+            let event = self.debounce.watch(inotify).await?;
+            self.handle(inotify, &event);
+        }
+    }
+
+    fn invalidate_under(&mut self, dir: &Path) {
+        match &mut self.batch {
+            Some(batch) => batch(dir),
+            None => (self.invalidate)(dir),
+        }
+    }
+}
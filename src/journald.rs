@@ -0,0 +1,199 @@
+use std::{io, path::Path};
+
+use tokio::net::UnixDatagram;
+
+use crate::{Event, Glob, INotify, Mask};
+
+const DEFAULT_SOCKET: &str = "/run/systemd/journal/socket";
+const DEFAULT_IDENTIFIER: &str = "tokinotify";
+
+/// Informational, see syslog(3)
+const LOG_INFO: u8 = 6;
+
+/// Writes events to systemd-journald with structured fields
+///
+/// Each event becomes one journal entry with a readable `MESSAGE` and the
+/// fields `TOKINOTIFY_PATH`, `TOKINOTIFY_KIND` (the flag names joined by
+/// `|`), `TOKINOTIFY_MASK`, `TOKINOTIFY_COOKIE` and `TOKINOTIFY_WATCH`, so
+/// entries can be queried with e.g. `journalctl TOKINOTIFY_KIND=DELETE`.
+/// journald adds the trusted fields of the sender itself, `_SYSTEMD_UNIT`,
+/// `_PID`, `_COMM` and the like.
+pub struct JournalSink {
+    socket: UnixDatagram,
+    identifier: String,
+    priority: u8,
+    fields: Vec<(String, String)>,
+    filters: Vec<(Mask, Glob)>,
+}
+
+impl JournalSink {
+    /// Connect to the journal of the system
+    pub fn new() -> io::Result<Self> {
+        Self::at(Path::new(DEFAULT_SOCKET))
+    }
+
+    /// Connect to a journal socket at `path`
+    pub fn at(path: &Path) -> io::Result<Self> {
+        let socket = UnixDatagram::unbound()?;
+        socket.connect(path)?;
+
+        Ok(Self {
+            socket,
+            identifier: DEFAULT_IDENTIFIER.to_string(),
+            priority: LOG_INFO,
+            fields: Vec::new(),
+            filters: Vec::new(),
+        })
+    }
+
+    /// Log as `identifier` (`SYSLOG_IDENTIFIER`) instead of `tokinotify`
+    pub fn identifier(mut self, identifier: &str) -> Self {
+        self.identifier = identifier.to_string();
+        self
+    }
+
+    /// Log at syslog `priority`, 0 (emergency) to 7 (debug), instead of 6 (info)
+    pub fn priority(mut self, priority: u8) -> Self {
+        self.priority = priority.min(7);
+        self
+    }
+
+    /// Add a field to every entry, e.g. the unit or role of the watcher
+    ///
+    /// Names are uppercased, with anything but letters, digits and `_`
+    /// replaced by `_`, leading `_` dropped, `X_` put before a leading digit
+    /// and cut to 64 characters, as journald drops other names. A name left
+    /// empty adds nothing.
+    pub fn field(mut self, name: &str, value: &str) -> Self {
+        if let Some(name) = field_name(name) {
+            self.fields.push((name, value.to_string()));
+        }
+        self
+    }
+
+    /// Only write events with a flag of `mask` whose full path matches `glob`
+    ///
+    /// Filters accumulate, an event matching any of them is written. Without
+    /// a filter every event is written.
+    pub fn filter(mut self, mask: Mask, glob: Glob) -> Self {
+        self.filters.push((mask, glob));
+        self
+    }
+
+    /// Write the entry for an event unless filtered, returning whether it was
+    pub async fn emit(&self, inotify: &INotify, event: &Event) -> io::Result<bool> {
+        let path = inotify.resolve(event).unwrap_or_else(|| event.path.clone());
+
+        let wanted = self.filters.is_empty()
+            || self
+                .filters
+                .iter()
+                .any(|(mask, glob)| (*mask & event.mask).0 != 0 && glob.matches(&path));
+        if !wanted {
+            return Ok(false);
+        }
+
+        let names: Vec<&str> = event.mask.names().collect();
+        let kind = names.join("|");
+        let path = path.to_string_lossy();
+
+        let mut entry = Vec::new();
+        field(&mut entry, "MESSAGE", &format!("{kind} {path}"));
+        field(&mut entry, "PRIORITY", &self.priority.to_string());
+        field(&mut entry, "SYSLOG_IDENTIFIER", &self.identifier);
+        field(&mut entry, "TOKINOTIFY_PATH", &path);
+        field(&mut entry, "TOKINOTIFY_KIND", &kind);
+        field(&mut entry, "TOKINOTIFY_MASK", &event.mask.0.to_string());
+        field(&mut entry, "TOKINOTIFY_COOKIE", &event.cookie.to_string());
+        field(
+            &mut entry,
+            "TOKINOTIFY_WATCH",
+            &event.watch.as_raw().to_string(),
+        );
+        for (name, value) in &self.fields {
+            field(&mut entry, name, value);
+        }
+
+        self.socket.send(&entry).await?;
+        Ok(true)
+    }
+
+    /// Write entries until reading from the kernel or writing to the journal fails
+    pub async fn run(&self, inotify: &mut INotify) -> io::Result<()> {
+        loop {
+            let event = inotify.watch().await?;
+            self.emit(inotify, &event).await?;
+        }
+    }
+}
+
+/// a field name journald accepts, see `journal_field_valid` in systemd
+fn field_name(name: &str) -> Option<String> {
+    let name: String = name
+        .chars()
+        .map(|c| match c {
+            'a'..='z' => c.to_ascii_uppercase(),
+            'A'..='Z' | '0'..='9' => c,
+            _ => '_',
+        })
+        .collect();
+    let name = name.trim_start_matches('_');
+
+    let mut name = match name.bytes().next()? {
+        b'0'..=b'9' => format!("X_{name}"),
+        _ => name.to_string(),
+    };
+    name.truncate(64);
+
+    Some(name)
+}
+
+/// append a field in the journal's native protocol
+///
+/// Values with a newline are sent length prefixed, others as `NAME=value`.
+fn field(entry: &mut Vec<u8>, name: &str, value: &str) {
+    entry.extend_from_slice(name.as_bytes());
+
+    if value.contains('\n') {
+        entry.push(b'\n');
+        entry.extend_from_slice(&(value.len() as u64).to_le_bytes());
+    } else {
+        entry.push(b'=');
+    }
+
+    entry.extend_from_slice(value.as_bytes());
+    entry.push(b'\n');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn field_names() {
+        assert_eq!(field_name("role").as_deref(), Some("ROLE"));
+        assert_eq!(field_name("my-unit.name").as_deref(), Some("MY_UNIT_NAME"));
+        assert_eq!(field_name("__trusted").as_deref(), Some("TRUSTED"));
+        assert_eq!(field_name("9lives").as_deref(), Some("X_9LIVES"));
+        assert_eq!(field_name("_1").as_deref(), Some("X_1"));
+        assert_eq!(field_name("é").as_deref(), None);
+        assert_eq!(field_name("").as_deref(), None);
+        assert_eq!(field_name(&"a".repeat(70)), Some("A".repeat(64)));
+    }
+
+    #[test]
+    fn native_protocol() {
+        let mut entry = Vec::new();
+        field(&mut entry, "MESSAGE", "CREATE /tmp/a");
+        field(&mut entry, "TOKINOTIFY_PATH", "/tmp/a\nb");
+        field(&mut entry, "EMPTY", "");
+
+        let mut expected = b"MESSAGE=CREATE /tmp/a\n".to_vec();
+        expected.extend_from_slice(b"TOKINOTIFY_PATH\n");
+        expected.extend_from_slice(&[8, 0, 0, 0, 0, 0, 0, 0]);
+        expected.extend_from_slice(b"/tmp/a\nb\n");
+        expected.extend_from_slice(b"EMPTY=\n");
+
+        assert_eq!(entry, expected);
+    }
+}
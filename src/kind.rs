@@ -0,0 +1,118 @@
+use crate::{Mask, Watch};
+#[cfg(feature = "tokio")]
+use {
+    crate::{sys, INotify},
+    std::{io, path::Path},
+};
+
+/// A watch on a single file, added with [INotify::watch_file]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FileWatch(Watch);
+
+/// A watch on a directory and its entries, added with [INotify::watch_dir]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DirWatch(Watch);
+
+impl FileWatch {
+    /// Changes to the file's contents and metadata, and it going away
+    pub const MASK: Mask = Mask::FILE_CHANGES;
+
+    /// the underlying watch
+    pub fn watch(self) -> Watch {
+        self.0
+    }
+}
+
+impl DirWatch {
+    /// Entries coming, going and being written, and the directory going away
+    pub const MASK: Mask =
+        Mask(Mask::DIR_CHILD_CHANGES.0 | Mask::MOVE_SELF.0 | Mask::DELETE_SELF.0 | Mask::ONLYDIR.0);
+
+    /// the underlying watch
+    pub fn watch(self) -> Watch {
+        self.0
+    }
+}
+
+impl From<FileWatch> for Watch {
+    fn from(watch: FileWatch) -> Watch {
+        watch.0
+    }
+}
+
+impl From<DirWatch> for Watch {
+    fn from(watch: DirWatch) -> Watch {
+        watch.0
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl INotify {
+    /// Watch a file with [FileWatch::MASK], failing with EISDIR on a directory
+    pub fn watch_file(&mut self, path: &Path) -> io::Result<FileWatch> {
+        if std::fs::metadata(path)?.is_dir() {
+            return Err(io::Error::from_raw_os_error(sys::EISDIR));
+        }
+
+        self.add(path, FileWatch::MASK).map(FileWatch)
+    }
+
+    /// Watch a directory with [DirWatch::MASK], failing with ENOTDIR on anything else
+    pub fn watch_dir(&mut self, path: &Path) -> io::Result<DirWatch> {
+        self.add(path, DirWatch::MASK).map(DirWatch)
+    }
+}
+
+#[cfg(all(test, feature = "tokio"))]
+mod tests {
+    use super::*;
+    use crate::testing::scratch;
+    use std::{path::PathBuf, time::Duration};
+
+    async fn next(inotify: &mut INotify) -> crate::Event {
+        tokio::time::timeout(Duration::from_secs(10), inotify.watch())
+            .await
+            .unwrap()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn each_kind_refuses_the_other() {
+        let dir = scratch("kind");
+        let file = dir.join("f");
+        std::fs::write(&file, "").unwrap();
+
+        let mut inotify = INotify::new().unwrap();
+        let err = inotify.watch_file(&dir).unwrap_err();
+        assert_eq!(err.raw_os_error(), Some(sys::EISDIR));
+        let err = inotify.watch_dir(&file).unwrap_err();
+        assert_eq!(err.raw_os_error(), Some(sys::ENOTDIR));
+        assert_eq!(inotify.watches().count(), 0);
+
+        let file_watch = inotify.watch_file(&file).unwrap();
+        let dir_watch = inotify.watch_dir(&dir).unwrap();
+        assert_eq!(inotify.mask(file_watch.watch()), Some(FileWatch::MASK));
+        assert_eq!(
+            inotify.registration(dir_watch.watch()),
+            Some(DirWatch::MASK)
+        );
+
+        // the file reports the write itself, the directory as an entry's
+        std::fs::write(&file, "data").unwrap();
+        let mut seen = Vec::new();
+        while seen.len() < 2 {
+            let event = next(&mut inotify).await;
+            if event.mask == Mask::MODIFY {
+                seen.push((event.watch, event.path));
+            }
+        }
+        seen.sort_by_key(|(watch, _)| watch.as_raw());
+        assert_eq!(
+            seen,
+            [
+                (file_watch.into(), PathBuf::new()),
+                (dir_watch.into(), PathBuf::from("f"))
+            ]
+        );
+    }
+}
@@ -0,0 +1,89 @@
+use std::{collections::VecDeque, io};
+
+use crate::{Event, INotify, Mask};
+
+/// The most data events held while looking for control events
+const LOOKAHEAD: usize = 0x4000;
+
+/// Delivers control events (UNMOUNT, Q_OVERFLOW, IGNORED) ahead of data events
+///
+/// Everything the kernel has queued (up to a bounded lookahead) is read
+/// before an event is returned, so queue loss and invalidated watches are
+/// learned about before draining a backlog of stale data events.
+///
+/// A watch's state is released when its IGNORED is read, so data events
+/// delivered after it can no longer be resolved through [INotify::resolve].
+#[derive(Default)]
+pub struct Lanes {
+    control: VecDeque<Event>,
+    data: VecDeque<Event>,
+}
+
+impl Lanes {
+    /// Build empty lanes
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Wait for the next event, control events first
+    pub async fn watch(&mut self, inotify: &mut INotify) -> io::Result<Event> {
+        if self.control.is_empty() && self.data.is_empty() {
+            let event = inotify.watch().await?;
+            self.push(event);
+        }
+
+        while self.data.len() < LOOKAHEAD {
+            let Some(event) = inotify.try_watch().await? else {
+                break;
+            };
+            self.push(event);
+        }
+
+        let event = self.control.pop_front().or_else(|| self.data.pop_front());
+
+        Ok(event.expect("an event was just queued"))
+    }
+
+    /// Take the next queued control event without waiting
+    pub fn try_control(&mut self) -> Option<Event> {
+        self.control.pop_front()
+    }
+
+    fn push(&mut self, event: Event) {
+        if (event.mask & Mask::CONTROL).0 != 0 {
+            self.control.push_back(event);
+        } else {
+            self.data.push_back(event);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::scratch;
+
+    #[tokio::test]
+    async fn control_events_overtake_data() {
+        let dir = scratch("lanes");
+        std::fs::create_dir(dir.join("sub")).unwrap();
+
+        let mut inotify = INotify::new().unwrap();
+        let watch = inotify.add(&dir.join("sub"), Mask::CREATE).unwrap();
+
+        // the kernel queues the CREATE, then the IGNORED of the removed directory
+        std::fs::write(dir.join("sub/f"), "").unwrap();
+        std::fs::remove_file(dir.join("sub/f")).unwrap();
+        std::fs::remove_dir(dir.join("sub")).unwrap();
+
+        let mut lanes = Lanes::new();
+        let first = lanes.watch(&mut inotify).await.unwrap();
+        assert_eq!(first.watch, watch);
+        assert!(first.mask.contains(Mask::IGNORED));
+        assert!(lanes.try_control().is_none());
+
+        let second = lanes.watch(&mut inotify).await.unwrap();
+        assert_eq!(second.mask, Mask::CREATE);
+        assert_eq!(second.path, std::path::Path::new("f"));
+    }
+}
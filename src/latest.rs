@@ -0,0 +1,84 @@
+use std::path::{Path, PathBuf};
+
+use tokio::sync::watch;
+
+use crate::{Event, INotify};
+
+impl INotify {
+    /// the most recent event for a path, updated as events are watched
+    ///
+    /// `path` must be spelled as the path of a watch, or the path of a
+    /// watched directory joined with an entry name, to match the full path
+    /// of events (see [INotify::resolve]). Nothing is watched on its own
+    /// behalf and the receiver only sees events while [INotify::watch] is
+    /// driven. It holds `None` until the first event.
+    pub fn latest(&mut self, path: &Path) -> watch::Receiver<Option<Event>> {
+        self.latest.retain(|_, tx| !tx.is_closed());
+
+        match self.latest.get(path) {
+            Some(tx) => tx.subscribe(),
+            None => {
+                let (tx, rx) = watch::channel(None);
+                self.latest.insert(path.to_path_buf(), tx);
+                rx
+            }
+        }
+    }
+
+    pub(crate) fn publish_latest(&mut self, event: &Event) {
+        if self.latest.is_empty() {
+            return;
+        }
+
+        let Some(path) = self.resolve(event) else {
+            return;
+        };
+
+        if let Some(tx) = self.latest.get(&path) {
+            if tx.send(Some(event.clone())).is_err() {
+                // every receiver is gone
+                self.latest.remove::<PathBuf>(&path);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{testing::scratch, Mask};
+    use std::time::Duration;
+
+    async fn next(inotify: &mut INotify) -> Event {
+        tokio::time::timeout(Duration::from_secs(10), inotify.watch())
+            .await
+            .unwrap()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn receivers_hold_the_most_recent_event() {
+        let dir = scratch("latest");
+        let mut inotify = INotify::new().unwrap();
+        inotify.add(&dir, Mask::CREATE | Mask::MODIFY).unwrap();
+
+        let mut cert = inotify.latest(&dir.join("cert"));
+        let other = inotify.latest(&dir.join("cert"));
+        assert!(cert.borrow().is_none());
+
+        std::fs::File::create(dir.join("cert")).unwrap();
+        std::fs::write(dir.join("cert"), "pem").unwrap();
+        std::fs::File::create(dir.join("key")).unwrap();
+        for _ in 0..3 {
+            next(&mut inotify).await;
+        }
+
+        // the key is not the cert, only the last of the cert's events is kept
+        assert!(cert.has_changed().unwrap());
+        let latest = cert.borrow_and_update().clone().unwrap();
+        assert_eq!(latest.mask, Mask::MODIFY);
+        assert_eq!(latest.path, PathBuf::from("cert"));
+        assert_eq!(other.borrow().as_ref().unwrap().mask, Mask::MODIFY);
+        assert!(!cert.has_changed().unwrap());
+    }
+}
@@ -0,0 +1,246 @@
+use std::{collections::VecDeque, io};
+
+use tokio::time::Instant;
+
+use crate::{Debounce, Event, ExcludeSet, INotify, Mask, Matcher};
+
+/// A step events pass through in [Layers]
+///
+/// A layer takes each event the layer before passed on and pushes what it
+/// passes on itself, none for a filter, one for a map or several. A layer
+/// holding events back, such as [Debounce], reports when they are due and
+/// passes them on from [Layer::flush].
+pub trait Layer: Send {
+    /// Handle an event, pushing the events passed on to `out`
+    fn event(&mut self, inotify: &mut INotify, event: Event, out: &mut Vec<Event>)
+        -> io::Result<()>;
+
+    /// when held events are next due, `None` while nothing is held
+    fn deadline(&self) -> Option<Instant> {
+        None
+    }
+
+    /// Push held events due by `now` to `out`
+    fn flush(&mut self, inotify: &mut INotify, now: Instant, out: &mut Vec<Event>) -> io::Result<()> {
+        let _ = (inotify, now, out);
+        Ok(())
+    }
+}
+
+/// A chain of [Layer]s applied to the events of an [INotify]
+///
+/// Layers run in the order added, each sees only what the ones before it
+/// passed on. Events a layer flushes once due continue through the layers
+/// after it.
+#[derive(Default)]
+pub struct Layers {
+    layers: Vec<Box<dyn Layer>>,
+    ready: VecDeque<Event>,
+}
+
+impl Layers {
+    /// Build a chain passing every event on
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add `layer` after the layers added so far
+    pub fn layer(mut self, layer: impl Layer + 'static) -> Self {
+        self.layers.push(Box::new(layer));
+        self
+    }
+
+    /// Pass on only events for which `keep` holds
+    pub fn filter(self, keep: impl FnMut(&INotify, &Event) -> bool + Send + 'static) -> Self {
+        self.layer(FilterFn(keep))
+    }
+
+    /// Replace every event with what `map` makes of it
+    pub fn map(self, map: impl FnMut(&INotify, Event) -> Event + Send + 'static) -> Self {
+        self.layer(MapFn(map))
+    }
+
+    /// Look at every event passing, e.g. to count or trace it
+    pub fn inspect(self, inspect: impl FnMut(&INotify, &Event) + Send + 'static) -> Self {
+        self.layer(InspectFn(inspect))
+    }
+
+    /// The number of layers
+    pub fn len(&self) -> usize {
+        self.layers.len()
+    }
+
+    /// whether the chain has no layers
+    pub fn is_empty(&self) -> bool {
+        self.layers.is_empty()
+    }
+
+    /// Wait for the next event passed on by every layer
+    pub async fn next(&mut self, inotify: &mut INotify) -> io::Result<Event> {
+        loop {
+            if let Some(event) = self.ready.pop_front() {
+                return Ok(event);
+            }
+
+            let deadline = self.layers.iter().filter_map(|l| l.deadline()).min();
+
+            tokio::select! {
+                _ = sleep(deadline) => self.flush(inotify)?,
+                event = inotify.watch() => self.run(inotify, 0, vec![event?])?,
+            }
+        }
+    }
+
+    /// flush due events from every layer, front to back
+    fn flush(&mut self, inotify: &mut INotify) -> io::Result<()> {
+        let now = Instant::now();
+
+        for i in 0..self.layers.len() {
+            if self.layers[i].deadline().is_none_or(|due| due > now) {
+                continue;
+            }
+
+            let mut out = Vec::new();
+            self.layers[i].flush(inotify, now, &mut out)?;
+            self.run(inotify, i + 1, out)?;
+        }
+
+        Ok(())
+    }
+
+    /// pass events through the layers from `start` on
+    fn run(&mut self, inotify: &mut INotify, start: usize, mut events: Vec<Event>) -> io::Result<()> {
+        for layer in &mut self.layers[start..] {
+            if events.is_empty() {
+                return Ok(());
+            }
+
+            let mut out = Vec::with_capacity(events.len());
+            for event in events {
+                layer.event(inotify, event, &mut out)?;
+            }
+            events = out;
+        }
+
+        self.ready.extend(events);
+        Ok(())
+    }
+}
+
+/// Passes on matching events
+impl Layer for Matcher {
+    fn event(&mut self, _: &mut INotify, event: Event, out: &mut Vec<Event>) -> io::Result<()> {
+        if self.matches(&event) {
+            out.push(event);
+        }
+        Ok(())
+    }
+}
+
+/// Drops events whose full path is excluded
+impl Layer for ExcludeSet {
+    fn event(&mut self, inotify: &mut INotify, event: Event, out: &mut Vec<Event>) -> io::Result<()> {
+        let path = inotify.resolve(&event).unwrap_or_else(|| event.path.clone());
+        if !self.matches(&path) {
+            out.push(event);
+        }
+        Ok(())
+    }
+}
+
+/// Holds events until their path is quiet, as [Debounce::watch] does
+impl Layer for Debounce {
+    fn event(&mut self, inotify: &mut INotify, event: Event, out: &mut Vec<Event>) -> io::Result<()> {
+        if (event.mask & Mask::CONTROL).0 != 0 {
+            // after what was held for a watch going away
+            self.pass(event);
+            out.extend(std::iter::from_fn(|| self.take_due()));
+        } else if self.is_hot(inotify, &event) {
+            out.push(event);
+        } else {
+            self.hold(event, Instant::now());
+        }
+        Ok(())
+    }
+
+    fn deadline(&self) -> Option<Instant> {
+        self.next_deadline()
+    }
+
+    fn flush(&mut self, _: &mut INotify, _: Instant, out: &mut Vec<Event>) -> io::Result<()> {
+        out.extend(std::iter::from_fn(|| self.take_due()));
+        Ok(())
+    }
+}
+
+struct FilterFn<F>(F);
+
+impl<F: FnMut(&INotify, &Event) -> bool + Send> Layer for FilterFn<F> {
+    fn event(&mut self, inotify: &mut INotify, event: Event, out: &mut Vec<Event>) -> io::Result<()> {
+        if (self.0)(inotify, &event) {
+            out.push(event);
+        }
+        Ok(())
+    }
+}
+
+struct MapFn<F>(F);
+
+impl<F: FnMut(&INotify, Event) -> Event + Send> Layer for MapFn<F> {
+    fn event(&mut self, inotify: &mut INotify, event: Event, out: &mut Vec<Event>) -> io::Result<()> {
+        out.push((self.0)(inotify, event));
+        Ok(())
+    }
+}
+
+struct InspectFn<F>(F);
+
+impl<F: FnMut(&INotify, &Event) + Send> Layer for InspectFn<F> {
+    fn event(&mut self, inotify: &mut INotify, event: Event, out: &mut Vec<Event>) -> io::Result<()> {
+        (self.0)(inotify, &event);
+        out.push(event);
+        Ok(())
+    }
+}
+
+async fn sleep(deadline: Option<Instant>) {
+    match deadline {
+        Some(deadline) => tokio::time::sleep_until(deadline).await,
+        None => std::future::pending().await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::scratch;
+    use std::{path::Path, time::Duration};
+
+    #[tokio::test]
+    async fn debounce_layer_passes_control_events_on() {
+        let dir = scratch("layer-debounce");
+
+        let mut inotify = INotify::new().unwrap();
+        let watch = inotify.add(&dir, Mask::CREATE).unwrap();
+        let mut layers = Layers::new().layer(Debounce::new(Duration::from_secs(3600)));
+
+        std::fs::File::create(dir.join("f")).unwrap();
+        let held = tokio::time::timeout(Duration::from_millis(50), layers.next(&mut inotify));
+        assert!(held.await.is_err());
+
+        // the held event comes first, neither waits out the quiet period
+        inotify.rm(watch).unwrap();
+        let event = tokio::time::timeout(Duration::from_secs(10), layers.next(&mut inotify))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(event.mask, Mask::CREATE);
+        assert_eq!(event.path, Path::new("f"));
+
+        let event = tokio::time::timeout(Duration::from_secs(10), layers.next(&mut inotify))
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(event.mask.contains(Mask::IGNORED));
+    }
+}
@@ -1,44 +1,343 @@
 //!
 //! Using the inotify syscalls with tokio support
 //!
+//! # Ordering
+//!
+//! Events of one watch are delivered in the order the kernel queued them,
+//! nothing is reordered or dropped between the kernel and the caller.
+//! Events of different watches are delivered in the order they were read,
+//! which is the order the kernel queued them in. This holds for
+//! [INotify::watch], [INotify::watch_raw], [blocking::INotify] and
+//! [RawINotify] with [Events], and is exercised by `tests/ordering.rs`.
+//!
+//! A watch removed with [INotify::rm] reports nothing but its IGNORED
+//! once `rm` returns, events already queued for it are dropped.
+//! [INotify::rm_with] and [Drain::Collect] deliver them instead, in order
+//! and with the watch's path, up to the IGNORED. Nothing follows the
+//! IGNORED of a watch either way.
+//!
+//! The kernel itself merges an event identical to the last unread one and
+//! drops events once its queue is full, reporting [Mask::Q_OVERFLOW].
+//! Events made by the library rather than the kernel, such as polled pseudo
+//! filesystem changes or [Event::is_synthetic] ones, are not ordered
+//! relative to kernel events.
+//!
+//! # Time
+//!
+//! Everything timed, such as [Debounce], [RateGuard] pauses, pseudo
+//! filesystem polling, rescans and move pairing in [Mirror], runs on the
+//! tokio clock. Under `tokio::time::pause` (the `test-util` feature) it
+//! only moves when the test advances it. A read from the kernel runs as a
+//! blocking task and holds off auto advance while in flight, so advance by
+//! hand when a timer races a pending read.
+//!
 
 #![warn(missing_docs)]
 
 use std::{
-    ffi::{c_int, OsStr},
-    io,
-    mem::size_of,
-    os::{fd::FromRawFd, unix::ffi::OsStrExt},
+    ffi::c_int,
     path::{Path, PathBuf},
+    sync::Arc,
 };
 
+#[cfg(feature = "tokio")]
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    io,
+    os::fd::{AsRawFd, OwnedFd},
+};
+#[cfg(feature = "tokio")]
 use tokio::{fs::File, io::AsyncReadExt};
 
+/// Items built on the tokio adapter, [INotify]
+macro_rules! cfg_tokio {
+    ($($item:item)*) => {
+        $(
+            #[cfg(feature = "tokio")]
+            $item
+        )*
+    };
+}
+
+#[cfg(feature = "async-io")]
+mod async_io;
+#[cfg(feature = "bench")]
+pub mod bench;
+pub mod blocking;
+mod builder;
+mod exclude;
+mod filter;
+#[cfg(feature = "fuzzing")]
+pub mod fuzz;
+mod glob;
+mod identity;
+mod kind;
 mod mask;
+mod matcher;
+mod name;
+mod parse;
+mod raw;
+mod removal;
+mod split;
+pub mod stub;
+mod symlink;
+#[cfg_attr(not(feature = "tokio"), allow(dead_code, unused_imports))]
+mod sys;
+#[cfg(test)]
+mod testing;
 
+cfg_tokio! {
+    mod actor;
+    mod anomaly;
+    mod anchor;
+    mod attrib;
+    mod budget;
+    mod capabilities;
+    mod classify;
+    mod config;
+    mod content;
+    mod control;
+    mod coverage;
+    mod debounce;
+    mod deps;
+    mod doctor;
+    mod epoch;
+    mod fair;
+    mod feed;
+    mod guard;
+    mod hardlink;
+    mod instance;
+    mod invalidate;
+    mod lanes;
+    mod latest;
+    mod layer;
+    mod lifecycle;
+    mod manifest;
+    mod mirror;
+    mod mount;
+    mod ns;
+    mod pool;
+    mod project;
+    mod pseudo;
+    mod readiness;
+    mod reconcile;
+    mod record;
+    mod registry;
+    mod rename;
+    mod rescan;
+    mod router;
+    mod sample;
+    mod settle;
+    mod shared;
+    mod shutdown;
+    mod size;
+    mod stable;
+    mod stats;
+    mod summary;
+    mod tree;
+    mod validate;
+    mod wait;
+}
+
+#[cfg(feature = "notify")]
+mod compat;
+#[cfg(feature = "dbus")]
+mod dbus;
+#[cfg(feature = "test-util")]
+mod fault;
+#[cfg(feature = "bitflags")]
+mod flags;
+#[cfg(feature = "grpc")]
+mod grpc;
+#[cfg(feature = "http")]
+mod http;
+#[cfg(feature = "journald")]
+mod journald;
+#[cfg(feature = "lsp-types")]
+mod lsp;
+#[cfg(feature = "tower")]
+mod service;
+#[cfg(feature = "syslog")]
+mod syslog;
+#[cfg(feature = "io-uring")]
+mod uring;
+
+#[cfg(feature = "async-io")]
+pub use async_io::AsyncINotify;
+pub use builder::EventBuilder;
+pub use exclude::ExcludeSet;
+pub use filter::Filter;
+pub use glob::{Glob, GlobError};
+pub use identity::Identity;
+pub use kind::{DirWatch, FileWatch};
 pub use mask::Mask;
+pub use matcher::Matcher;
+pub use name::Name;
+pub use parse::ParseError;
+pub use raw::{Events, RawINotify};
+pub use removal::Removal;
+pub use symlink::{LinkRole, LinkWatch, SymlinkPolicy};
+
+cfg_tokio! {
+    pub use actor::{WatcherEvents, WatcherHandle};
+    pub use anomaly::{Anomaly, AnomalyKind, Detected, Detector};
+    pub use anchor::Anchor;
+    pub use attrib::{Attrib, AttribChange, Delta};
+    pub use budget::{Quota, WatchBudget};
+    pub use capabilities::{Capabilities, OverflowRisk};
+    pub use classify::{Classified, Classifier};
+    pub use config::{DebounceConfig, WatcherConfig};
+    pub use content::{content_type, ContentFilter};
+    pub use control::{Control, WatchCommand};
+    pub use coverage::PartialCoverage;
+    pub use debounce::Debounce;
+    pub use deps::DependencyWatcher;
+    pub use doctor::{doctor, Diagnosis, Problem, RootDiagnosis};
+    pub use fair::Fair;
+    pub use feed::{Feed, Restart};
+    pub use guard::{GuardAction, Guarded, RateGuard};
+    pub use hardlink::{Hardlinks, LinkIndex};
+    pub use instance::{Follower, Instance, Leader};
+    pub use invalidate::Invalidator;
+    pub use lanes::Lanes;
+    pub use layer::{Layer, Layers};
+    pub use lifecycle::{Exited, Lifecycle, Lifetime};
+    pub use manifest::{Manifest, ManifestEntry};
+    pub use mirror::{Mirror, OrphanMove, SyncOp};
+    pub use mount::{Capability, Mount, MountEvent, MountWatcher, Quirk};
+    pub use ns::Namespace;
+    pub use project::{ProjectEvent, ProjectWatcher, RootId, RootSettings};
+    pub use pseudo::PseudoFs;
+    pub use readiness::Readiness;
+    pub use reconcile::Reconciled;
+    pub use record::{EventReader, Format};
+    pub use registry::{Released, Tag};
+    pub use rename::{Change, Renames, TreeMoves};
+    pub use rescan::Rescan;
+    pub use router::{Router, Subscription};
+    pub use sample::{Sample, Sampler, Sampling, Tally};
+    pub use settle::{DirSettle, Settled};
+    pub use shared::Shared;
+    pub use shutdown::Drain;
+    pub use size::{SizeChange, Sizes};
+    pub use stable::SizeGate;
+    pub use stats::WatchStats;
+    pub use summary::{Summaries, WatchSummary};
+    pub use tree::TreeProgress;
+    pub use validate::{Diverged, Validator};
+    pub use wait::Phase;
+}
+
+#[cfg(feature = "notify")]
+pub use compat::NotifyWatcher;
+#[cfg(feature = "sniff")]
+pub use content::sniff;
+#[cfg(feature = "dbus")]
+pub use dbus::DbusEmitter;
+#[cfg(feature = "test-util")]
+pub use fault::FaultInjector;
+#[cfg(feature = "grpc")]
+pub use grpc::{GrpcClient, GrpcService, RemoteEvents};
+#[cfg(feature = "http")]
+pub use http::{EventBody, EventService, Publisher};
+#[cfg(feature = "journald")]
+pub use journald::JournalSink;
+#[cfg(feature = "lsp-types")]
+pub use lsp::{file_change, file_uri, LspEvents};
+#[cfg(feature = "tower")]
+pub use service::Dispatcher;
+#[cfg(feature = "syslog")]
+pub use syslog::{Facility, Severity, SyslogSink};
 
+#[cfg(feature = "tokio")]
+use epoch::Origin;
+#[cfg(feature = "tokio")]
+use raw::{add_watch, rm_watch};
+
+#[cfg(not(any(feature = "raw-syscall", feature = "libc-backed")))]
 extern "C" {
     fn inotify_init1(flag: c_int) -> c_int;
     fn inotify_add_watch(fd: c_int, buf: *const u8, mask: u32) -> c_int;
     fn inotify_rm_watch(fd: c_int, wd: c_int) -> c_int;
+    #[cfg(feature = "tokio")]
     fn close(fd: c_int) -> c_int;
 }
 
+#[cfg(all(feature = "raw-syscall", feature = "tokio"))]
+use sys::raw::close;
+#[cfg(feature = "raw-syscall")]
+use sys::raw::{inotify_add_watch, inotify_init1, inotify_rm_watch};
+
+#[cfg(all(
+    feature = "libc-backed",
+    not(feature = "raw-syscall"),
+    feature = "tokio"
+))]
+use libc::close;
+#[cfg(all(feature = "libc-backed", not(feature = "raw-syscall")))]
+use libc::{inotify_add_watch, inotify_init1, inotify_rm_watch};
+
+/// Smallest read, large enough for any legal event including its padding
+const READ_SIZE: usize = parse::HEADER_SIZE + parse::NAME_LIMIT;
+
+/// Flags a watch is registered with besides its interest
+#[cfg(feature = "tokio")]
+const WATCH_FLAGS: Mask =
+    Mask(Mask::ONLYDIR.0 | Mask::DONT_FOLLOW.0 | Mask::EXCL_UNLINK.0 | Mask::ONESHOT.0);
+
 /// Watch filesytem changes on linux
+#[cfg(feature = "tokio")]
 pub struct INotify {
     fd: c_int,
+    // dropped first, a read still armed is cancelled before the descriptor closes
+    #[cfg(feature = "io-uring")]
+    uring: Option<uring::Uring>,
     file: File,
+    paths: HashMap<Watch, Arc<Path>>,
+    masks: HashMap<Watch, Mask>,
+    identities: Option<HashMap<Watch, Identity>>,
+    links: HashMap<Watch, LinkRole>,
+    latest: HashMap<PathBuf, tokio::sync::watch::Sender<Option<Event>>>,
+    tags: HashMap<Watch, Tag>,
+    dying: HashMap<Watch, Removal>,
+    epochs: HashMap<Watch, u64>,
+    next_epoch: u64,
+    retiring: HashMap<Watch, VecDeque<epoch::Retired>>,
+    oneshot: HashSet<Watch>,
+    removed: HashSet<Watch>,
+    draining: HashSet<Watch>,
+    waiting: HashMap<Watch, wait::Waiting>,
+    hidden: HashSet<Watch>,
+    stats: Option<HashMap<Watch, WatchStats>>,
+    quota: Option<Quota>,
+    release_hook: Option<registry::ReleaseHook>,
+    pseudo: PseudoFs,
+    poller: Option<pseudo::Poller>,
+    commands: Option<control::Commands>,
+    registrations: Option<tokio::sync::mpsc::UnboundedReceiver<shared::Registration>>,
+    admission: Option<Arc<std::sync::Mutex<shared::Admission>>>,
+    canonical: bool,
+    strict: bool,
+    split: Option<VecDeque<Event>>,
+    unfinished: Option<Event>,
+    polled: VecDeque<RawEvent>,
+    buf: Vec<u8>,
+    pos: usize,
+    end: usize,
 }
 
 /// A WatchDescriptor
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Watch {
     wd: c_int,
 }
 
 /// An event returned by the kernel
-#[derive(Debug)]
+///
+/// Events compare equal when their watch, mask, cookie, path and
+/// [Event::is_synthetic] agree, the context the instance attaches (identity,
+/// link role, removal, root and watch mask) is left out.
+#[derive(Debug, Clone)]
 pub struct Event {
     /// The Watch associated with this event
     pub watch: Watch,
@@ -51,94 +350,657 @@ pub struct Event {
 
     /// A path associated with this event (empty unless disambigous to the kernel)
     pub path: PathBuf,
+
+    identity: Option<Identity>,
+    link: Option<LinkRole>,
+    removal: Option<Removal>,
+    synthetic: bool,
+    stale: bool,
+    root: Option<Arc<Path>>,
+    watch_mask: Option<Mask>,
 }
 
-#[repr(C)]
-struct EventHeader {
-    wd: c_int,
-    mask: u32,
-    cookie: u32,
-    len: u32,
+/// An event as read from the kernel, without enrichment or path allocation
+#[derive(Debug, Clone)]
+pub struct RawEvent {
+    /// The Watch associated with this event
+    pub watch: Watch,
+
+    /// The mask associated with this event
+    pub mask: Mask,
+
+    /// A cookie associated with the event
+    pub cookie: u32,
+
+    /// The name associated with this event (empty unless disambigous to the kernel)
+    pub name: Name,
 }
 
+#[cfg(feature = "tokio")]
 impl INotify {
     /// Build a new INotify
     pub fn new() -> io::Result<Self> {
-        let fd = unsafe { inotify_init1(0) };
-
-        if fd == -1 {
-            return Err(io::Error::from_raw_os_error(fd));
-        }
+        let core = RawINotify::new()?;
+        let fd = core.as_raw_fd();
+        let file = File::from_std(std::fs::File::from(OwnedFd::from(core)));
 
-        let file = unsafe { File::from_raw_fd(fd) };
-
-        Ok(Self { fd, file })
+        Ok(Self {
+            fd,
+            file,
+            paths: HashMap::new(),
+            masks: HashMap::new(),
+            identities: None,
+            links: HashMap::new(),
+            latest: HashMap::new(),
+            tags: HashMap::new(),
+            dying: HashMap::new(),
+            epochs: HashMap::new(),
+            next_epoch: 0,
+            retiring: HashMap::new(),
+            oneshot: HashSet::new(),
+            removed: HashSet::new(),
+            draining: HashSet::new(),
+            waiting: HashMap::new(),
+            hidden: HashSet::new(),
+            stats: None,
+            quota: None,
+            release_hook: None,
+            pseudo: PseudoFs::default(),
+            poller: None,
+            commands: None,
+            registrations: None,
+            admission: None,
+            canonical: false,
+            strict: false,
+            split: None,
+            unfinished: None,
+            polled: VecDeque::new(),
+            #[cfg(feature = "io-uring")]
+            uring: None,
+            buf: Vec::new(),
+            pos: 0,
+            end: 0,
+        })
     }
 
     /// Add a file (, or directory) to be watched
     pub fn add(&mut self, path: &Path, mask: Mask) -> io::Result<Watch> {
-        let path: &OsStr = path.as_ref();
-        let res = unsafe { inotify_add_watch(self.fd, path.as_bytes().as_ptr(), mask.0) };
-        if res == -1 {
-            return Err(io::Error::from_raw_os_error(res));
+        let canonical;
+        let path = if self.canonical {
+            canonical = canonicalize(path, mask.contains(Mask::DONT_FOLLOW))?;
+            canonical.as_path()
+        } else {
+            path
+        };
+
+        let polled = self.check_pseudo(path)?;
+        self.admit(path)?;
+        let watch = add_watch(self.fd, path, mask)?;
+        self.register(watch, path.to_path_buf(), mask);
+
+        if polled {
+            self.poll_pseudo(watch, path.to_path_buf(), mask);
+        }
+
+        Ok(watch)
+    }
+
+    fn register(&mut self, watch: Watch, path: PathBuf, mask: Mask) {
+        self.renew(watch, &path);
+        self.note_oneshot(watch, mask);
+        self.adopt(watch, &path, mask);
+
+        let registered = Mask(mask.0 & (Mask::INTEREST.0 | WATCH_FLAGS.0));
+        match self.masks.get_mut(&watch) {
+            Some(existing) if mask.contains(Mask::MASK_ADD) => *existing |= registered,
+            _ => {
+                self.masks.insert(watch, registered);
+            }
+        }
+
+        if let Some(identities) = &mut self.identities {
+            if let Ok(identity) = Identity::of(&path) {
+                identities.insert(watch, identity);
+            }
         }
 
-        Ok(Watch { wd: res })
+        self.charge(watch);
+        self.paths.insert(watch, path.into());
     }
 
     /// remove a watch from this INotify
+    ///
+    /// events still queued for the watch are dropped, only its IGNORED is reported
     pub fn rm(&mut self, watch: Watch) -> io::Result<()> {
-        let res = unsafe { inotify_rm_watch(self.fd, watch.wd) };
-        if res == -1 {
-            return Err(io::Error::from_raw_os_error(res));
+        self.rm_with(watch, Drain::Discard)
+    }
+
+    /// remove a watch, choosing what becomes of events already queued for it
+    ///
+    /// see [crate#ordering], nothing is reported for the watch after its IGNORED
+    pub fn rm_with(&mut self, watch: Watch, drain: Drain) -> io::Result<()> {
+        // nothing is read in between, the IGNORED can't be missed
+        rm_watch(self.fd, watch)?;
+        self.removing(watch, drain);
+
+        Ok(())
+    }
+
+    /// remove every watch from this INotify
+    pub fn rm_all(&mut self) -> io::Result<()> {
+        self.retain(|_, _| false)
+    }
+
+    /// remove every watch `keep` returns false for, given its path and mask
+    ///
+    /// paths waited on with [INotify::add_or_wait] are offered too, with the
+    /// mask they would be watched with. watches the kernel already dropped
+    /// are skipped, the first other error is returned once every watch was tried
+    pub fn retain(&mut self, mut keep: impl FnMut(&Path, Mask) -> bool) -> io::Result<()> {
+        let waits = self.retain_waits(&mut keep);
+
+        let doomed: Vec<Watch> = self
+            .paths
+            .iter()
+            .filter(|(watch, path)| !keep(path, self.mask(**watch).unwrap_or(Mask(0))))
+            .map(|(watch, _)| *watch)
+            .collect();
+
+        let mut res = Ok(());
+        for watch in doomed {
+            match self.rm(watch) {
+                // its IGNORED is already queued
+                Err(err) if err.raw_os_error() == Some(sys::EINVAL) => (),
+                Err(err) if res.is_ok() => res = Err(err),
+                _ => (),
+            }
+        }
+
+        res.and(waits)
+    }
+
+    /// the path a watch was added with
+    pub fn path(&self, watch: Watch) -> Option<&Path> {
+        self.paths.get(&watch).map(AsRef::as_ref)
+    }
+
+    /// the events a watch is interested in
+    pub fn mask(&self, watch: Watch) -> Option<Mask> {
+        self.masks.get(&watch).map(|mask| *mask & Mask::INTEREST)
+    }
+
+    /// the mask a watch was registered with, its interest along with
+    /// ONLYDIR, DONT_FOLLOW, EXCL_UNLINK and ONESHOT
+    pub fn registration(&self, watch: Watch) -> Option<Mask> {
+        self.masks.get(&watch).copied()
+    }
+
+    /// replace the events a watch is interested in
+    pub fn set_mask(&mut self, watch: Watch, mask: Mask) -> io::Result<()> {
+        let Some(path) = self.paths.get(&watch).map(|path| path.to_path_buf()) else {
+            return Err(io::Error::new(io::ErrorKind::NotFound, "unknown watch"));
+        };
+
+        let mask = Mask(mask.0 & !Mask::MASK_ADD.0 & !Mask::MASK_CREATE.0);
+        let updated = add_watch(self.fd, &path, mask)?;
+        if updated != watch {
+            // the path now names a different object, keep both consistent
+            self.register(updated, path.clone(), mask);
+            return Err(io::Error::other("watched path was replaced"));
         }
 
+        self.register(watch, path, mask);
+
         Ok(())
     }
 
-    /// start watching for events
-    pub async fn watch(&mut self) -> io::Result<Event> {
-        const SIZE: usize = size_of::<EventHeader>();
-        let mut buffer = [0u8; SIZE];
+    /// canonicalize watched paths when added and deliver absolute paths on events
+    ///
+    /// when enabled [Event::path] holds the full canonical path instead of the
+    /// name relative to the watch, only watches added after enabling are affected
+    pub fn canonicalize(&mut self, enabled: bool) {
+        self.canonical = enabled;
+        self.publish_admission();
+    }
 
-        let mut amt = 0;
-        while amt < SIZE {
-            amt += self.file.read(&mut buffer[amt..SIZE]).await?;
+    /// track the (dev, ino) identity of watched paths and event targets
+    ///
+    /// only watches added after enabling are tracked
+    pub fn track_identity(&mut self, enabled: bool) {
+        if !enabled {
+            self.identities = None;
+        } else if self.identities.is_none() {
+            self.identities = Some(HashMap::new());
+        }
+    }
+
+    /// the identity of a watched path at the time it was added
+    pub fn identity(&self, watch: Watch) -> Option<Identity> {
+        self.identities.as_ref()?.get(&watch).copied()
+    }
+
+    /// every watch and the path it was added with
+    pub fn watches(&self) -> impl Iterator<Item = (Watch, &Path)> {
+        self.paths.iter().map(|(w, p)| (*w, p.as_ref()))
+    }
+
+    /// the full path an event refers to, unknown for stale events
+    pub fn resolve(&self, event: &Event) -> Option<PathBuf> {
+        if event.stale {
+            return None;
         }
 
-        let header: EventHeader = unsafe { std::mem::transmute(buffer) };
-        let total = header.len as usize;
-        let mut buffer = [0u8; 0x1000];
+        let base = self.path(event.watch)?;
 
-        let mut amt: usize = 0;
-        while amt < total {
-            amt += self.file.read(&mut buffer[amt..total]).await?;
+        if event.path.as_os_str().is_empty() {
+            Some(base.to_path_buf())
+        } else {
+            Some(base.join(&event.path))
         }
+    }
+
+    /// start watching for raw events
+    ///
+    /// short names are kept inline, no options (canonical paths,
+    /// identities, link roles) are applied. see [crate#ordering]
+    pub async fn watch_raw(&mut self) -> io::Result<RawEvent> {
+        let next = self.next_event(true).await?;
+        Ok(next.expect("a blocking read returns an event").0)
+    }
+
+    /// the next event for the caller, `None` without `block` once nothing
+    /// is buffered or queued in the kernel
+    async fn next_event(
+        &mut self,
+        block: bool,
+    ) -> io::Result<Option<(RawEvent, Option<Removal>, Origin)>> {
+        loop {
+            while self.pos >= self.end {
+                if let Some(event) = self.polled.pop_front() {
+                    let next = (event, None, Origin::Polled);
+                    if !self.internal(&next)? {
+                        return Ok(Some(next));
+                    }
+                    continue;
+                }
+
+                if !block {
+                    if self.pending()? == 0 {
+                        return Ok(None);
+                    }
+                    self.fill().await?;
+                    continue;
+                }
 
-        let os = OsStr::from_bytes(&buffer[0..total]);
-        let path = PathBuf::from(os);
+                let Some(commands) = self.commands.clone() else {
+                    self.wait().await?;
+                    continue;
+                };
 
-        Ok(Event {
+                // a pending read survives being interrupted by a command
+                tokio::select! {
+                    command = control::recv(&commands) => match command {
+                        Some(command) => self.apply(command)?,
+                        None => self.commands = None,
+                    },
+                    res = self.wait() => res?,
+                };
+            }
+
+            if let Some(next) = self.take()? {
+                return Ok(Some(next));
+            }
+        }
+    }
+
+    /// take the next buffered event for the caller
+    ///
+    /// discarded events and those of parents watched only for
+    /// [INotify::add_or_wait] are consumed on the way, `None` when
+    /// nothing else was buffered
+    pub(crate) fn take(&mut self) -> io::Result<Option<(RawEvent, Option<Removal>, Origin)>> {
+        while self.buffered() {
+            let next = self.take_next()?;
+            if !self.internal(&next)? {
+                return Ok(Some(next));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// whether an event is consumed by the library rather than returned
+    fn internal(&mut self, next: &(RawEvent, Option<Removal>, Origin)) -> io::Result<bool> {
+        let (event, removal, origin) = next;
+        if self.discarded(event, *removal, *origin) {
+            return Ok(true);
+        }
+
+        // parents watched only for add_or_wait
+        Ok(*origin != Origin::Stale && self.arrive(event)?)
+    }
+
+    /// take the next buffered event, there must be one
+    fn take_next(&mut self) -> io::Result<(RawEvent, Option<Removal>, Origin)> {
+        self.drain_registrations();
+
+        let (header, name, consumed) = match parse::next(&self.buf[self.pos..self.end]) {
+            Ok(next) => next,
+            Err(err) => {
+                // framing can not be trusted past a bad event, drop the rest
+                self.pos = self.end;
+                return Err(err.into());
+            }
+        };
+
+        let event = RawEvent {
             watch: Watch { wd: header.wd },
             mask: Mask(header.mask),
             cookie: header.cookie,
-            path,
-        })
+            name: Name::new(name),
+        };
+
+        self.pos += consumed;
+
+        if self.strict {
+            self.check(&event)?;
+        }
+
+        if let Some(removal) = self.stale(&event) {
+            return Ok((event, removal, Origin::Stale));
+        }
+
+        // a removed watch was forgotten already, its stats with it
+        if let (Some(stats), true) = (&mut self.stats, self.paths.contains_key(&event.watch)) {
+            let now = tokio::time::Instant::now();
+            stats
+                .entry(event.watch)
+                .or_default()
+                .record(event.mask, now);
+        }
+
+        let removal = self.removal(&event);
+        if let Some(reason) = removal {
+            self.release(event.watch, reason);
+        }
+
+        Ok((event, removal, Origin::Kernel))
     }
 
-    /// intentionally close the inotify instance
-    pub async fn close(self) -> io::Result<()> {
-        std::mem::forget(self.file);
-        let res = unsafe { close(self.fd) };
+    /// read from the kernel, or poll pseudo filesystem paths when due
+    async fn wait(&mut self) -> io::Result<()> {
+        let Some(deadline) = self.poller.as_ref().and_then(pseudo::Poller::deadline) else {
+            return self.fill().await;
+        };
 
-        if res == -1 {
-            return Err(io::Error::from_raw_os_error(res));
+        tokio::select! {
+            _ = tokio::time::sleep_until(deadline) => {
+                let poller = self.poller.as_mut().expect("a deadline implies a poller");
+                self.polled.extend(poller.poll().await?);
+                Ok(())
+            }
+            res = self.fill() => res,
+        }
+    }
+
+    /// bytes of events queued in the kernel (FIONREAD)
+    pub(crate) fn pending(&self) -> io::Result<usize> {
+        raw::pending(self.fd)
+    }
+
+    /// events have been read from the kernel but not yet returned
+    pub(crate) fn buffered(&self) -> bool {
+        self.pos < self.end
+    }
+
+    /// reject events for unknown watches or with unknown mask bits
+    ///
+    /// malformed frames are always rejected, strict mode additionally
+    /// validates the decoded event against this instance's state
+    pub fn strict(&mut self, enabled: bool) {
+        self.strict = enabled;
+    }
+
+    fn check(&self, event: &RawEvent) -> Result<(), ParseError> {
+        if event.mask.0 & !Mask::REPORTED.0 != 0 {
+            return Err(ParseError::UnknownMask { mask: event.mask.0 });
+        }
+
+        let overflow = event.watch.wd == -1 && event.mask.contains(Mask::Q_OVERFLOW);
+        let known = self.paths.contains_key(&event.watch)
+            || self.removed.contains(&event.watch)
+            || self.waiting.contains_key(&event.watch)
+            || self.hidden.contains(&event.watch);
+        if !overflow && !known {
+            return Err(ParseError::UnknownWatch { wd: event.watch.wd });
         }
 
         Ok(())
     }
+
+    /// read every pending event in one syscall, sized from FIONREAD
+    ///
+    /// the buffer holds several back to back events which are decoded
+    /// one at a time by [INotify::watch_raw]
+    async fn fill(&mut self) -> io::Result<()> {
+        #[cfg(feature = "io-uring")]
+        if let Some(uring) = &mut self.uring {
+            match uring.read(&mut self.buf).await {
+                Ok(amt) => {
+                    self.pos = 0;
+                    self.end = amt;
+                    return Ok(());
+                }
+
+                // multishot reads are not supported by this kernel
+                Err(err) if err.raw_os_error() == Some(sys::EINVAL) => self.drop_uring()?,
+                Err(err) => return Err(err),
+            }
+        }
+
+        let pending = self.pending()?;
+
+        // never shrink, an interrupted read may complete into the larger size
+        let mut want = pending.max(READ_SIZE).max(self.buf.len());
+
+        self.pos = 0;
+        self.end = 0;
+
+        while self.end == 0 {
+            if self.buf.len() < want {
+                self.buf.resize(want, 0);
+            }
+
+            match self.file.read(&mut self.buf[..want]).await {
+                Ok(amt) => self.end = amt,
+
+                // the kernel refuses reads too small for the next event
+                Err(err) if err.raw_os_error() == Some(sys::EINVAL) => want *= 2,
+                Err(err) => return Err(err),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// start watching for events, in the order described in [crate#ordering]
+    ///
+    /// Cancel safe, an event is only taken once it is returned. A dropped
+    /// call leaves its read in flight for the next one to complete, so
+    /// `watch` can sit in a `tokio::select!` loop or under a timeout.
+    pub async fn watch(&mut self) -> io::Result<Event> {
+        let event = self.next(true).await?;
+        Ok(event.expect("a blocking read returns an event"))
+    }
+
+    /// the next event if one is buffered or queued in the kernel, never waiting for more
+    pub(crate) async fn try_watch(&mut self) -> io::Result<Option<Event>> {
+        self.next(false).await
+    }
+
+    async fn next(&mut self, block: bool) -> io::Result<Option<Event>> {
+        if let Some(event) = self.next_part() {
+            return Ok(Some(event));
+        }
+
+        let mut event = match self.unfinished.take() {
+            Some(event) => event,
+            None => {
+                let Some((raw, removal, origin)) = self.next_event(block).await? else {
+                    return Ok(None);
+                };
+                self.event(raw, removal, origin)
+            }
+        };
+
+        if self.identities.is_some() && !event.stale {
+            if event.path.as_os_str().is_empty() {
+                event.identity = self.identity(event.watch);
+            } else if let Some(path) = self.resolve(&event) {
+                // kept while looking it up, a cancelled call picks the event up again
+                self.unfinished = Some(event.clone());
+                event.identity = Identity::of_async(&path).await.ok();
+                self.unfinished = None;
+            }
+        }
+
+        self.publish_latest(&event);
+
+        Ok(Some(self.first_part(event)))
+    }
+
+    /// the event delivered for a raw one, without its identity
+    fn event(&self, raw: RawEvent, removal: Option<Removal>, origin: Origin) -> Event {
+        let stale = origin == Origin::Stale;
+
+        let mut event = Event {
+            watch: raw.watch,
+            mask: raw.mask,
+            cookie: raw.cookie,
+            path: raw.name.to_path_buf(),
+            identity: None,
+            removal,
+            synthetic: origin == Origin::Polled,
+            stale,
+            link: None,
+            root: None,
+            watch_mask: None,
+        };
+
+        if !stale {
+            event.link = self.links.get(&raw.watch).copied();
+            event.root = self.paths.get(&raw.watch).cloned();
+            event.watch_mask = self.mask(raw.watch);
+        }
+
+        if self.canonical {
+            if let Some(path) = self.resolve(&event) {
+                event.path = path;
+            }
+        }
+
+        event
+    }
+
+    /// intentionally close the inotify instance, see [INotify::shutdown]
+    pub async fn close(self) -> io::Result<()> {
+        self.shutdown(Drain::Discard).await.map(drop)
+    }
+}
+
+#[cfg(feature = "tokio")]
+fn canonicalize(path: &Path, no_follow: bool) -> io::Result<PathBuf> {
+    if !no_follow {
+        return std::fs::canonicalize(path);
+    }
+
+    // resolve the ancestors but leave a final symlink in place
+    match (path.parent(), path.file_name()) {
+        (Some(parent), Some(name)) if !parent.as_os_str().is_empty() => {
+            Ok(std::fs::canonicalize(parent)?.join(name))
+        }
+        (_, Some(name)) => Ok(std::env::current_dir()?.join(name)),
+        _ => std::fs::canonicalize(path),
+    }
 }
 
+impl Event {
+    /// an event produced by the library rather than read from the kernel
+    #[cfg(feature = "tokio")]
+    pub(crate) fn synthetic(watch: Watch, mask: Mask, path: PathBuf) -> Event {
+        Event {
+            watch,
+            mask,
+            cookie: 0,
+            path,
+            identity: None,
+            link: None,
+            removal: None,
+            synthetic: true,
+            stale: false,
+            root: None,
+            watch_mask: None,
+        }
+    }
+
+    /// the event was produced by the library (e.g. a rescan) rather than the kernel
+    pub fn is_synthetic(&self) -> bool {
+        self.synthetic
+    }
+
+    /// the event was queued for an earlier watch with the same descriptor, see [INotify::epoch]
+    ///
+    /// stale events carry only what the kernel reported
+    pub fn is_stale(&self) -> bool {
+        self.stale
+    }
+
+    /// the (dev, ino) identity of the file this event refers to
+    ///
+    /// only available when identity tracking is enabled and the file still exists
+    pub fn identity(&self) -> Option<Identity> {
+        self.identity
+    }
+
+    /// why the watch was dropped, set on the terminal IGNORED event of a watch
+    pub fn removal(&self) -> Option<Removal> {
+        self.removal
+    }
+
+    /// the side of a symbolic link the originating watch observes
+    ///
+    /// only available for watches added with [INotify::add_link] on a link
+    pub fn link_role(&self) -> Option<LinkRole> {
+        self.link
+    }
+
+    /// the path the originating watch was added with
+    ///
+    /// not known on the IGNORED ending a watch, see [INotify::on_released]
+    pub fn root(&self) -> Option<&Path> {
+        self.root.as_deref()
+    }
+
+    /// the events the originating watch was registered for
+    ///
+    /// not known on the IGNORED ending a watch
+    pub fn watch_mask(&self) -> Option<Mask> {
+        self.watch_mask
+    }
+}
+
+impl PartialEq for Event {
+    fn eq(&self, other: &Self) -> bool {
+        self.watch == other.watch
+            && self.mask == other.mask
+            && self.cookie == other.cookie
+            && self.path == other.path
+            && self.synthetic == other.synthetic
+    }
+}
+
+impl Eq for Event {}
+
 impl std::fmt::Debug for Watch {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_tuple("Watch").field(&self.wd).finish()?;
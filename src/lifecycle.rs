@@ -0,0 +1,155 @@
+use std::{
+    ffi::c_int,
+    io,
+    os::fd::{FromRawFd, OwnedFd},
+};
+
+use tokio::io::unix::AsyncFd;
+
+use crate::{sys, Event, INotify, Watch};
+
+/// A group of watches tied to the lifetime of a process
+///
+/// Once the process exits every watch in the group is removed and
+/// [Lifetime::Exited] is reported in place of further events.
+pub struct Lifecycle {
+    pid: c_int,
+    pidfd: AsyncFd<OwnedFd>,
+    watches: Vec<Watch>,
+}
+
+/// An item produced while watching a [Lifecycle]
+#[derive(Debug)]
+pub enum Lifetime {
+    /// An event from the kernel
+    Event(Event),
+
+    /// The observed process exited and its watches were removed
+    Exited(Exited),
+}
+
+/// The terminal event of a [Lifecycle]
+#[derive(Debug)]
+pub struct Exited {
+    /// The pid of the process that exited
+    pub pid: c_int,
+
+    /// The watches that were removed
+    pub watches: Vec<Watch>,
+}
+
+impl Lifecycle {
+    /// Track the lifetime of a running process
+    pub fn open(pid: c_int) -> io::Result<Self> {
+        let fd = unsafe { sys::pidfd_open(pid, 0) };
+        if fd == -1 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let pidfd = AsyncFd::new(unsafe { OwnedFd::from_raw_fd(fd) })?;
+
+        Ok(Self {
+            pid,
+            pidfd,
+            watches: Vec::new(),
+        })
+    }
+
+    /// The pid of the observed process
+    pub fn pid(&self) -> c_int {
+        self.pid
+    }
+
+    /// Tie a watch to this process
+    pub fn track(&mut self, watch: Watch) {
+        self.watches.push(watch);
+    }
+
+    /// Stop tying a watch to this process
+    pub fn untrack(&mut self, watch: Watch) {
+        self.watches.retain(|w| *w != watch);
+    }
+
+    /// Wait for the observed process to exit
+    pub async fn exited(&self) -> io::Result<()> {
+        let mut guard = self.pidfd.readable().await?;
+        guard.retain_ready();
+
+        Ok(())
+    }
+
+    /// Remove every tracked watch, producing the terminal event
+    pub fn release(&mut self, inotify: &mut INotify) -> Exited {
+        let watches = std::mem::take(&mut self.watches);
+
+        for watch in &watches {
+            // the kernel may have already dropped the watch (IN_IGNORED)
+            let _ = inotify.rm(*watch);
+        }
+
+        Exited {
+            pid: self.pid,
+            watches,
+        }
+    }
+
+    /// Watch for events until the observed process exits
+    ///
+    /// Process exit is checked before events, an in flight event read is
+    /// abandoned when the process exits.
+    pub async fn watch(&mut self, inotify: &mut INotify) -> io::Result<Lifetime> {
+        tokio::select! {
+            biased;
+
+            res = self.exited() => res?,
+            event = inotify.watch() => return Ok(Lifetime::Event(event?)),
+        }
+
+        Ok(Lifetime::Exited(self.release(inotify)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{testing::scratch, Mask};
+    use std::{path::PathBuf, time::Duration};
+
+    async fn next(lifecycle: &mut Lifecycle, inotify: &mut INotify) -> Lifetime {
+        tokio::time::timeout(Duration::from_secs(10), lifecycle.watch(inotify))
+            .await
+            .unwrap()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn exiting_removes_the_tracked_watches() {
+        let dir = scratch("lifecycle");
+        std::fs::create_dir(dir.join("scratch")).unwrap();
+
+        let mut inotify = INotify::new().unwrap();
+        let tracked = inotify.add(&dir.join("scratch"), Mask::CREATE).unwrap();
+        let kept = inotify.add(&dir, Mask::CREATE).unwrap();
+
+        let mut child = std::process::Command::new("sleep").arg("60").spawn().unwrap();
+        let mut lifecycle = Lifecycle::open(child.id() as c_int).unwrap();
+        lifecycle.track(tracked);
+
+        std::fs::File::create(dir.join("scratch/f")).unwrap();
+        let Lifetime::Event(event) = next(&mut lifecycle, &mut inotify).await else {
+            panic!("the process is still running");
+        };
+        assert_eq!(event.watch, tracked);
+        assert_eq!(event.path, PathBuf::from("f"));
+
+        child.kill().unwrap();
+        child.wait().unwrap();
+        let Lifetime::Exited(exited) = next(&mut lifecycle, &mut inotify).await else {
+            panic!("the process exited");
+        };
+        assert_eq!(exited.pid, lifecycle.pid());
+        assert_eq!(exited.watches, vec![tracked]);
+        assert_eq!(inotify.path(tracked), None);
+        assert_eq!(inotify.path(kept), Some(&*dir));
+    }
+}
@@ -0,0 +1,160 @@
+use std::{
+    collections::HashMap,
+    io,
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use lsp_types::{FileChangeType, FileEvent, Uri};
+
+use crate::{Event, INotify, Mask};
+
+const CREATED: Mask = Mask(Mask::CREATE.0 | Mask::MOVED_TO.0);
+const DELETED: Mask =
+    Mask(Mask::DELETE.0 | Mask::MOVED_FROM.0 | Mask::DELETE_SELF.0 | Mask::MOVE_SELF.0);
+const CHANGED: Mask = Mask(Mask::MODIFY.0 | Mask::CLOSE_WRITE.0 | Mask::ATTRIB.0);
+
+/// Batches events into the changes of an LSP `workspace/didChangeWatchedFiles`
+///
+/// A rename is reported as the old path deleted and the new one created.
+/// Changes to one path within a batch collapse the way language servers
+/// expect: created then changed is created, deleted then created is
+/// changed and created then deleted is dropped. A batch is handed out once
+/// no event arrived for the quiet period.
+pub struct LspEvents {
+    quiet: Duration,
+    pending: HashMap<PathBuf, Change>,
+    order: Vec<PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Change {
+    Created,
+    Changed,
+    Deleted,
+
+    /// Created and deleted again within the batch
+    Vanished,
+}
+
+impl LspEvents {
+    /// Batch changes until `quiet` passed without an event
+    pub fn new(quiet: Duration) -> Self {
+        Self {
+            quiet,
+            pending: HashMap::new(),
+            order: Vec::new(),
+        }
+    }
+
+    /// Wait for the next batch of changes, never empty
+    ///
+    /// On a queue overflow every watched path is reported changed.
+    pub async fn next(&mut self, inotify: &mut INotify) -> io::Result<Vec<FileEvent>> {
+        loop {
+            let event = if self.order.is_empty() {
+                inotify.watch().await?
+            } else {
+                match tokio::time::timeout(self.quiet, inotify.watch()).await {
+                    Ok(event) => event?,
+                    Err(_) => {
+                        let batch = self.take();
+                        if !batch.is_empty() {
+                            return Ok(batch);
+                        }
+                        continue;
+                    }
+                }
+            };
+
+            self.record(inotify, &event);
+        }
+    }
+
+    fn record(&mut self, inotify: &INotify, event: &Event) {
+        if event.mask.contains(Mask::Q_OVERFLOW) {
+            let watched: Vec<PathBuf> = inotify.watches().map(|(_, p)| p.to_path_buf()).collect();
+            for path in watched {
+                self.merge(path, Change::Changed);
+            }
+            return;
+        }
+
+        let Some(change) = change(event.mask) else {
+            return;
+        };
+        if let Some(path) = inotify.resolve(event) {
+            self.merge(path, change);
+        }
+    }
+
+    fn merge(&mut self, path: PathBuf, next: Change) {
+        let Some(prev) = self.pending.get_mut(&path) else {
+            self.order.push(path.clone());
+            self.pending.insert(path, next);
+            return;
+        };
+
+        *prev = match (*prev, next) {
+            (Change::Created | Change::Vanished, Change::Deleted) => Change::Vanished,
+            (Change::Created | Change::Vanished, _) => Change::Created,
+            (_, Change::Deleted) => Change::Deleted,
+            _ => Change::Changed,
+        };
+    }
+
+    fn take(&mut self) -> Vec<FileEvent> {
+        let mut batch = Vec::new();
+
+        for path in self.order.drain(..) {
+            let typ = match self.pending.remove(&path) {
+                Some(Change::Created) => FileChangeType::CREATED,
+                Some(Change::Changed) => FileChangeType::CHANGED,
+                Some(Change::Deleted) => FileChangeType::DELETED,
+                Some(Change::Vanished) | None => continue,
+            };
+
+            batch.push(FileEvent::new(file_uri(&path), typ));
+        }
+
+        batch
+    }
+}
+
+/// the LSP change an event amounts to on its own, if any
+pub fn file_change(event: &Event) -> Option<FileChangeType> {
+    match change(event.mask)? {
+        Change::Created => Some(FileChangeType::CREATED),
+        Change::Deleted => Some(FileChangeType::DELETED),
+        _ => Some(FileChangeType::CHANGED),
+    }
+}
+
+fn change(mask: Mask) -> Option<Change> {
+    if (mask & CREATED).0 != 0 {
+        Some(Change::Created)
+    } else if (mask & DELETED).0 != 0 {
+        Some(Change::Deleted)
+    } else if (mask & CHANGED).0 != 0 {
+        Some(Change::Changed)
+    } else {
+        None
+    }
+}
+
+/// the `file://` URI of a path, made absolute against the working directory
+pub fn file_uri(path: &Path) -> Uri {
+    let path = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    let mut uri = String::from("file://");
+
+    for &b in path.as_os_str().as_bytes() {
+        if b.is_ascii_alphanumeric() || b"-._~/".contains(&b) {
+            uri.push(b as char);
+        } else {
+            uri.push_str(&format!("%{b:02X}"));
+        }
+    }
+
+    uri.parse().expect("percent encoded paths are valid URIs")
+}
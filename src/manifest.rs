@@ -0,0 +1,637 @@
+use std::{
+    collections::BTreeMap,
+    ffi::OsString,
+    fmt::Write as _,
+    io::{self, Read},
+    os::unix::{
+        ffi::{OsStrExt, OsStringExt},
+        fs::MetadataExt,
+    },
+    path::{Path, PathBuf},
+};
+
+use crate::{sys, Event, INotify, Mask};
+
+/// The events a manifest is kept current with
+const TRACKED: Mask = Mask(
+    Mask::ATTRIB.0
+        | Mask::CLOSE_WRITE.0
+        | Mask::CREATE.0
+        | Mask::DELETE.0
+        | Mask::MOVED_FROM.0
+        | Mask::MOVED_TO.0,
+);
+
+/// The first line of a saved manifest
+const HEADER: &str = "tokinotify-manifest 1";
+
+/// An index of the files in a tree: size, modification time and SHA-256
+///
+/// Built once, then kept current from events so only changed files are
+/// hashed again. It can be saved and loaded, [Manifest::refresh] catches a
+/// loaded manifest up by hashing files whose size or modification time
+/// differ. Paths are relative to the root, symbolic links are not
+/// followed and only regular files are listed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Manifest {
+    root: PathBuf,
+    entries: BTreeMap<PathBuf, ManifestEntry>,
+}
+
+/// The recorded state of a file in a [Manifest]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ManifestEntry {
+    /// Size in bytes
+    pub size: u64,
+
+    /// Modification time, seconds and nanoseconds since the epoch
+    pub mtime: (i64, i64),
+
+    /// SHA-256 of the content
+    pub hash: [u8; 32],
+}
+
+impl Manifest {
+    /// An empty manifest of the tree at `root`
+    pub fn new(root: &Path) -> Self {
+        Self {
+            root: root.to_path_buf(),
+            entries: BTreeMap::new(),
+        }
+    }
+
+    /// Hash every file in the tree at `root`
+    pub async fn build(root: &Path) -> io::Result<Self> {
+        let mut manifest = Self::new(root);
+        manifest.refresh().await?;
+        Ok(manifest)
+    }
+
+    /// The root of the tree
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// The entry for a path relative to the root
+    pub fn get(&self, path: &Path) -> Option<&ManifestEntry> {
+        self.entries.get(path)
+    }
+
+    /// Every entry, ordered by path
+    pub fn iter(&self) -> impl Iterator<Item = (&Path, &ManifestEntry)> {
+        self.entries.iter().map(|(p, e)| (p.as_path(), e))
+    }
+
+    /// The number of files listed
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// No files are listed
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Walk the tree, hashing only files which are new or changed
+    pub async fn refresh(&mut self) -> io::Result<()> {
+        self.refresh_under(Path::new("")).await
+    }
+
+    /// Watch the tree to keep the manifest current with [Manifest::update]
+    pub async fn watch(&self, inotify: &mut INotify) -> io::Result<()> {
+        inotify.add_tree(&self.root, TRACKED, None).await?;
+        Ok(())
+    }
+
+    /// Apply an event, returning whether the manifest changed
+    pub async fn update(&mut self, inotify: &mut INotify, event: &Event) -> io::Result<bool> {
+        if event.mask.contains(Mask::Q_OVERFLOW) {
+            let before = self.entries.clone();
+            self.refresh().await?;
+            return Ok(before != self.entries);
+        }
+
+        let Some(path) = inotify.resolve(event) else {
+            return Ok(false);
+        };
+        let Ok(rel) = path.strip_prefix(&self.root).map(Path::to_path_buf) else {
+            return Ok(false);
+        };
+
+        let dir = event.mask.contains(Mask::ISDIR);
+
+        if (event.mask & (Mask::DELETE | Mask::MOVED_FROM)).0 != 0 {
+            let before = self.entries.len();
+            if dir {
+                self.entries.retain(|p, _| !p.starts_with(&rel));
+            } else {
+                self.entries.remove(&rel);
+            }
+            return Ok(self.entries.len() != before);
+        }
+
+        if dir && (event.mask & (Mask::CREATE | Mask::MOVED_TO)).0 != 0 {
+            // entries may have appeared before the watch
+            inotify.add_tree(&path, TRACKED, None).await?;
+            let before = self.entries.len();
+            self.refresh_under(&rel).await?;
+            return Ok(self.entries.len() != before);
+        }
+
+        if dir {
+            return Ok(false);
+        }
+
+        // a hard link only creates, an attribute change may only touch mtime
+        let touched = Mask::ATTRIB | Mask::CLOSE_WRITE | Mask::CREATE | Mask::MOVED_TO;
+        if (event.mask & touched).0 != 0 {
+            // content only changes unseen on a write or replacement
+            let known = match (event.mask & (Mask::CLOSE_WRITE | Mask::MOVED_TO)).0 {
+                0 => self.entries.get(&rel).copied(),
+                _ => None,
+            };
+            let hashed = tokio::task::spawn_blocking(move || hash_file(&path, known.as_ref()))
+                .await
+                .map_err(io::Error::other)??;
+
+            return Ok(match hashed {
+                Some(entry) => self.entries.insert(rel, entry) != Some(entry),
+                None => self.entries.remove(&rel).is_some(),
+            });
+        }
+
+        Ok(false)
+    }
+
+    /// Keep the manifest current until reading from the kernel fails
+    pub async fn run(&mut self, inotify: &mut INotify) -> io::Result<()> {
+        loop {
+            let event = inotify.watch().await?;
+            self.update(inotify, &event).await?;
+        }
+    }
+
+    /// Write the manifest to `path`, replacing it atomically
+    pub async fn save(&self, path: &Path) -> io::Result<()> {
+        let mut out = String::new();
+        out.push_str(HEADER);
+        out.push('\n');
+        escape(self.root.as_os_str().as_bytes(), &mut out);
+        out.push('\n');
+
+        for (rel, entry) in &self.entries {
+            for b in entry.hash {
+                let _ = write!(out, "{b:02x}");
+            }
+            let _ = write!(out, "\t{}\t{}.{:09}\t", entry.size, entry.mtime.0, entry.mtime.1);
+            escape(rel.as_os_str().as_bytes(), &mut out);
+            out.push('\n');
+        }
+
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        tokio::fs::write(&tmp, out).await?;
+        tokio::fs::rename(&tmp, path).await
+    }
+
+    /// Read a manifest written by [Manifest::save]
+    pub async fn load(path: &Path) -> io::Result<Self> {
+        let text = tokio::fs::read_to_string(path).await?;
+        let invalid = || io::Error::new(io::ErrorKind::InvalidData, "malformed manifest");
+
+        let mut lines = text.lines();
+        if lines.next() != Some(HEADER) {
+            return Err(invalid());
+        }
+
+        let root = unescape(lines.next().ok_or_else(invalid)?).ok_or_else(invalid)?;
+        let mut manifest = Self::new(Path::new(&root));
+
+        for line in lines {
+            let mut fields = line.splitn(4, '\t');
+            let (Some(hash), Some(size), Some(mtime), Some(rel)) =
+                (fields.next(), fields.next(), fields.next(), fields.next())
+            else {
+                return Err(invalid());
+            };
+
+            let (secs, nanos) = mtime.split_once('.').ok_or_else(invalid)?;
+            let entry = ManifestEntry {
+                size: size.parse().map_err(|_| invalid())?,
+                mtime: (
+                    secs.parse().map_err(|_| invalid())?,
+                    nanos.parse().map_err(|_| invalid())?,
+                ),
+                hash: parse_hash(hash).ok_or_else(invalid)?,
+            };
+
+            let rel = unescape(rel).ok_or_else(invalid)?;
+            manifest.entries.insert(PathBuf::from(rel), entry);
+        }
+
+        Ok(manifest)
+    }
+
+    async fn refresh_under(&mut self, rel: &Path) -> io::Result<()> {
+        let known: BTreeMap<PathBuf, ManifestEntry> = self
+            .entries
+            .iter()
+            .filter(|(p, _)| p.starts_with(rel))
+            .map(|(p, e)| (p.clone(), *e))
+            .collect();
+
+        let root = self.root.clone();
+        let under = rel.to_path_buf();
+        let scanned = tokio::task::spawn_blocking(move || scan(&root, &under, &known))
+            .await
+            .map_err(io::Error::other)??;
+
+        self.entries.retain(|p, _| !p.starts_with(rel));
+        self.entries.extend(scanned);
+        Ok(())
+    }
+}
+
+/// list the files under `rel`, rehashing those unlike their known entry
+fn scan(
+    root: &Path,
+    rel: &Path,
+    known: &BTreeMap<PathBuf, ManifestEntry>,
+) -> io::Result<BTreeMap<PathBuf, ManifestEntry>> {
+    let mut entries = BTreeMap::new();
+    let mut dirs = vec![rel.to_path_buf()];
+
+    while let Some(dir) = dirs.pop() {
+        let listing = match std::fs::read_dir(root.join(&dir)) {
+            Ok(listing) => listing,
+            // gone or replaced while scanning, its events will follow
+            Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+            Err(err) if err.raw_os_error() == Some(sys::ENOTDIR) => continue,
+            Err(err) => return Err(err),
+        };
+
+        for entry in listing.flatten() {
+            let Ok(kind) = entry.file_type() else {
+                continue;
+            };
+
+            let rel = dir.join(entry.file_name());
+            if kind.is_dir() {
+                dirs.push(rel);
+            } else if kind.is_file() {
+                if let Some(entry) = hash_file(&entry.path(), known.get(&rel))? {
+                    entries.insert(rel, entry);
+                }
+            }
+        }
+    }
+
+    Ok(entries)
+}
+
+/// the entry for a file, `known` is reused when size and mtime still match
+fn hash_file(path: &Path, known: Option<&ManifestEntry>) -> io::Result<Option<ManifestEntry>> {
+    let mut file = match std::fs::File::open(path) {
+        Ok(file) => file,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err),
+    };
+
+    let meta = file.metadata()?;
+    if !meta.is_file() {
+        return Ok(None);
+    }
+
+    let size = meta.len();
+    let mtime = (meta.mtime(), meta.mtime_nsec());
+
+    if let Some(known) = known {
+        if known.size == size && known.mtime == mtime {
+            return Ok(Some(*known));
+        }
+    }
+
+    let mut sha = Sha256::new();
+    let mut buf = vec![0; 64 * 1024];
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        sha.update(&buf[..n]);
+    }
+
+    Ok(Some(ManifestEntry {
+        size,
+        mtime,
+        hash: sha.finish(),
+    }))
+}
+
+fn escape(bytes: &[u8], out: &mut String) {
+    for &b in bytes {
+        match b {
+            b'\\' => out.push_str("\\\\"),
+            b'\t' => out.push_str("\\t"),
+            b'\n' => out.push_str("\\n"),
+            b' '..=b'~' => out.push(b as char),
+            b => {
+                let _ = write!(out, "\\x{b:02x}");
+            }
+        }
+    }
+}
+
+fn unescape(s: &str) -> Option<OsString> {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] != b'\\' {
+            out.push(bytes[i]);
+            i += 1;
+            continue;
+        }
+
+        match bytes.get(i + 1)? {
+            b'\\' => out.push(b'\\'),
+            b't' => out.push(b'\t'),
+            b'n' => out.push(b'\n'),
+            b'x' => {
+                let hex = std::str::from_utf8(bytes.get(i + 2..i + 4)?).ok()?;
+                out.push(u8::from_str_radix(hex, 16).ok()?);
+                i += 2;
+            }
+            _ => return None,
+        }
+        i += 2;
+    }
+
+    Some(OsString::from_vec(out))
+}
+
+fn parse_hash(hex: &str) -> Option<[u8; 32]> {
+    if hex.len() != 64 {
+        return None;
+    }
+
+    let mut hash = [0; 32];
+    for (i, b) in hash.iter_mut().enumerate() {
+        *b = u8::from_str_radix(hex.get(2 * i..2 * i + 2)?, 16).ok()?;
+    }
+    Some(hash)
+}
+
+const K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+/// an incremental SHA-256
+struct Sha256 {
+    state: [u32; 8],
+    block: [u8; 64],
+    filled: usize,
+    len: u64,
+}
+
+impl Sha256 {
+    fn new() -> Self {
+        Self {
+            state: [
+                0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab,
+                0x5be0cd19,
+            ],
+            block: [0; 64],
+            filled: 0,
+            len: 0,
+        }
+    }
+
+    fn update(&mut self, mut data: &[u8]) {
+        self.len += data.len() as u64;
+
+        while !data.is_empty() {
+            let n = (64 - self.filled).min(data.len());
+            self.block[self.filled..self.filled + n].copy_from_slice(&data[..n]);
+            self.filled += n;
+            data = &data[n..];
+
+            if self.filled == 64 {
+                self.compress();
+                self.filled = 0;
+            }
+        }
+    }
+
+    fn finish(mut self) -> [u8; 32] {
+        let bits = self.len * 8;
+
+        self.block[self.filled] = 0x80;
+        self.block[self.filled + 1..].fill(0);
+        if self.filled >= 56 {
+            self.compress();
+            self.block.fill(0);
+        }
+        self.block[56..].copy_from_slice(&bits.to_be_bytes());
+        self.compress();
+
+        let mut out = [0; 32];
+        for (out, word) in out.chunks_mut(4).zip(self.state) {
+            out.copy_from_slice(&word.to_be_bytes());
+        }
+        out
+    }
+
+    fn compress(&mut self) {
+        let mut w = [0u32; 64];
+        for (i, word) in self.block.chunks(4).enumerate() {
+            w[i] = u32::from_be_bytes(word.try_into().expect("4 byte chunk"));
+        }
+        for i in 16..64 {
+            let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+            let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+            w[i] = w[i - 16]
+                .wrapping_add(s0)
+                .wrapping_add(w[i - 7])
+                .wrapping_add(s1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = self.state;
+        for (k, w) in K.iter().zip(w) {
+            let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let ch = (e & f) ^ (!e & g);
+            let t1 = h
+                .wrapping_add(s1)
+                .wrapping_add(ch)
+                .wrapping_add(*k)
+                .wrapping_add(w);
+            let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let maj = (a & b) ^ (a & c) ^ (b & c);
+            let t2 = s0.wrapping_add(maj);
+
+            h = g;
+            g = f;
+            f = e;
+            e = d.wrapping_add(t1);
+            d = c;
+            c = b;
+            b = a;
+            a = t1.wrapping_add(t2);
+        }
+
+        for (s, v) in self.state.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+            *s = s.wrapping_add(v);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::scratch;
+    use std::{ffi::OsStr, time::Duration};
+
+    fn sha256(data: &[u8]) -> String {
+        let mut sha = Sha256::new();
+        sha.update(data);
+        sha.finish().iter().map(|b| format!("{b:02x}")).collect()
+    }
+
+    /// apply events until none arrive for a while
+    async fn settle(manifest: &mut Manifest, inotify: &mut INotify) {
+        while let Ok(event) = tokio::time::timeout(Duration::from_millis(200), inotify.watch()).await
+        {
+            manifest.update(inotify, &event.unwrap()).await.unwrap();
+        }
+    }
+
+    #[test]
+    fn sha256_matches_nist_vectors() {
+        assert_eq!(
+            sha256(b""),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert_eq!(
+            sha256(b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(
+            sha256(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"),
+            "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1"
+        );
+
+        // fed in uneven pieces to cross block boundaries
+        let mut sha = Sha256::new();
+        for _ in 0..10_000 {
+            sha.update(&[b'a'; 100]);
+        }
+        let hex: String = sha.finish().iter().map(|b| format!("{b:02x}")).collect();
+        assert_eq!(
+            hex,
+            "cdc76e5c9914fb9281a1c7e284d73e67f1809a48a497200e046d39ccc7112cd0"
+        );
+    }
+
+    #[tokio::test]
+    async fn save_and_load_round_trip() {
+        let dir = scratch("manifest-save");
+
+        let mut manifest = Manifest::new(Path::new("/some\\root\nwith\tescapes"));
+        let entry = ManifestEntry {
+            size: 42,
+            mtime: (1_700_000_000, 123),
+            hash: [7; 32],
+        };
+        for name in [
+            OsStr::new("plain"),
+            OsStr::new("new\nline"),
+            OsStr::new("back\\slash"),
+            OsStr::new("tab\tand space"),
+            OsStr::from_bytes(b"not utf8 \xff\xfe"),
+            OsStr::new("dir/\u{e9}"),
+        ] {
+            manifest.entries.insert(PathBuf::from(name), entry);
+        }
+
+        let saved = dir.join("manifest");
+        manifest.save(&saved).await.unwrap();
+        assert_eq!(Manifest::load(&saved).await.unwrap(), manifest);
+    }
+
+    #[tokio::test]
+    async fn load_rejects_malformed_manifests() {
+        let dir = scratch("manifest-malformed");
+        let saved = dir.join("manifest");
+
+        for text in [
+            "not a manifest\n/\n",
+            "tokinotify-manifest 1\n/\nshort\t1\t1.0\tf\n",
+            "tokinotify-manifest 1\n/\nff\t1\t1.0\tbad\\escape\n",
+        ] {
+            std::fs::write(&saved, text).unwrap();
+            let err = Manifest::load(&saved).await.unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        }
+    }
+
+    #[tokio::test]
+    async fn update_follows_the_tree() {
+        let dir = scratch("manifest-update");
+        let root = dir.join("root");
+        std::fs::create_dir(&root).unwrap();
+
+        let mut manifest = Manifest::build(&root).await.unwrap();
+        let mut inotify = INotify::new().unwrap();
+        manifest.watch(&mut inotify).await.unwrap();
+
+        // CLOSE_WRITE
+        std::fs::write(root.join("a"), "abc").unwrap();
+        settle(&mut manifest, &mut inotify).await;
+        let a = *manifest.get(Path::new("a")).unwrap();
+        assert_eq!(a.size, 3);
+        assert_eq!(
+            a.hash.iter().map(|b| format!("{b:02x}")).collect::<String>(),
+            sha256(b"abc")
+        );
+
+        // a hard link is only a CREATE
+        std::fs::hard_link(root.join("a"), root.join("link")).unwrap();
+        settle(&mut manifest, &mut inotify).await;
+        assert_eq!(manifest.get(Path::new("link")).unwrap().hash, a.hash);
+
+        // ATTRIB, only the modification time changes
+        let file = std::fs::File::options().write(true).open(root.join("a")).unwrap();
+        let mtime = std::time::UNIX_EPOCH + Duration::from_secs(1_000_000);
+        file.set_modified(mtime).unwrap();
+        drop(file);
+        settle(&mut manifest, &mut inotify).await;
+        assert_eq!(manifest.get(Path::new("a")).unwrap().mtime, (1_000_000, 0));
+
+        // DELETE
+        std::fs::remove_file(root.join("link")).unwrap();
+        settle(&mut manifest, &mut inotify).await;
+        assert!(manifest.get(Path::new("link")).is_none());
+
+        // a directory MOVED_TO brings its files along
+        std::fs::create_dir_all(dir.join("outside/nested")).unwrap();
+        std::fs::write(dir.join("outside/nested/b"), "b").unwrap();
+        std::fs::rename(dir.join("outside"), root.join("moved")).unwrap();
+        settle(&mut manifest, &mut inotify).await;
+        assert!(manifest.get(Path::new("moved/nested/b")).is_some());
+
+        // and the moved directory is watched
+        std::fs::write(root.join("moved/c"), "c").unwrap();
+        settle(&mut manifest, &mut inotify).await;
+        assert!(manifest.get(Path::new("moved/c")).is_some());
+
+        assert_eq!(manifest, Manifest::build(&root).await.unwrap());
+    }
+}
@@ -1,3 +1,8 @@
+use crate::sys;
+
+/// Every bit with a flag of its own
+const KNOWN: u32 = 0xF700EFFF;
+
 /// A mask specifying event type interest
 #[derive(Clone, Copy)]
 pub struct Mask(pub(crate) u32);
@@ -6,51 +11,51 @@ impl Mask {
     // basic event masks
 
     /// File Accessed
-    pub const ACCESS: Mask = Mask(0x00000001);
+    pub const ACCESS: Mask = Mask(sys::IN_ACCESS);
 
     /// File modified
-    pub const MODIFY: Mask = Mask(0x00000002);
+    pub const MODIFY: Mask = Mask(sys::IN_MODIFY);
 
     /// Metadata changed
-    pub const ATTRIB: Mask = Mask(0x00000004);
+    pub const ATTRIB: Mask = Mask(sys::IN_ATTRIB);
 
     /// Writable file was closed
-    pub const CLOSE_WRITE: Mask = Mask(0x00000008);
+    pub const CLOSE_WRITE: Mask = Mask(sys::IN_CLOSE_WRITE);
 
     /// Unwritable file closed
-    pub const CLOSE_NOWRITE: Mask = Mask(0x00000010);
+    pub const CLOSE_NOWRITE: Mask = Mask(sys::IN_CLOSE_NOWRITE);
 
     /// File was opened
-    pub const OPEN: Mask = Mask(0x00000020);
+    pub const OPEN: Mask = Mask(sys::IN_OPEN);
 
     /// File was moved from X
-    pub const MOVED_FROM: Mask = Mask(0x00000040);
+    pub const MOVED_FROM: Mask = Mask(sys::IN_MOVED_FROM);
 
     /// File was moved to Y
-    pub const MOVED_TO: Mask = Mask(0x00000080);
+    pub const MOVED_TO: Mask = Mask(sys::IN_MOVED_TO);
 
     /// Subfile was created
-    pub const CREATE: Mask = Mask(0x00000100);
+    pub const CREATE: Mask = Mask(sys::IN_CREATE);
 
     /// Subfile was deleted
-    pub const DELETE: Mask = Mask(0x00000200);
+    pub const DELETE: Mask = Mask(sys::IN_DELETE);
 
     /// Self was deleted
-    pub const DELETE_SELF: Mask = Mask(0x00000400);
+    pub const DELETE_SELF: Mask = Mask(sys::IN_DELETE_SELF);
 
     /// Self was moved
-    pub const MOVE_SELF: Mask = Mask(0x00000800);
+    pub const MOVE_SELF: Mask = Mask(sys::IN_MOVE_SELF);
 
     // status masks
 
     /// Backing fs was unmounted
-    pub const UNMOUNT: Mask = Mask(0x00002000);
+    pub const UNMOUNT: Mask = Mask(sys::IN_UNMOUNT);
 
     /// Event queued overflowed
-    pub const Q_OVERFLOW: Mask = Mask(0x00004000);
+    pub const Q_OVERFLOW: Mask = Mask(sys::IN_Q_OVERFLOW);
 
     /// File was ignored
-    pub const IGNORED: Mask = Mask(0x00008000);
+    pub const IGNORED: Mask = Mask(sys::IN_IGNORED);
 
     // helper merged flags
 
@@ -60,43 +65,141 @@ impl Mask {
     /// Moves
     pub const MOVE: Mask = Mask(Self::MOVED_TO.0 | Self::MOVED_FROM.0);
 
+    /// Events about the state of a watch or the queue rather than file data
+    pub const CONTROL: Mask = Mask(Self::UNMOUNT.0 | Self::Q_OVERFLOW.0 | Self::IGNORED.0);
+
+    // presets
+
+    /// A single file's contents or metadata changing, and the file going away
+    ///
+    /// MODIFY fires on every write, CLOSE_WRITE alone reports each finished
+    /// write once. A file replaced by a rename over it ends with DELETE_SELF
+    /// or MOVE_SELF, watch its directory to follow the new file.
+    pub const FILE_CHANGES: Mask = Mask(
+        Self::MODIFY.0
+            | Self::ATTRIB.0
+            | Self::CLOSE_WRITE.0
+            | Self::MOVE_SELF.0
+            | Self::DELETE_SELF.0,
+    );
+
+    /// Entries of a directory coming, going and being written
+    ///
+    /// The directory going away is not included, add DELETE_SELF and
+    /// MOVE_SELF for it. Subdirectories need watches of their own.
+    pub const DIR_CHILD_CHANGES: Mask = Mask(
+        Self::CREATE.0
+            | Self::DELETE.0
+            | Self::MOVED_FROM.0
+            | Self::MOVED_TO.0
+            | Self::MODIFY.0
+            | Self::ATTRIB.0
+            | Self::CLOSE_WRITE.0,
+    );
+
+    /// Every event but ACCESS
+    ///
+    /// ACCESS fires on every read and floods the queue while anything reads
+    /// the watched files. OPEN and CLOSE_NOWRITE still fire once per read
+    /// only open, leave them out too unless opens matter.
+    pub const EVERYTHING_EXCEPT_ACCESS: Mask = Mask(Self::INTEREST.0 & !Self::ACCESS.0);
+
     // special flaqs
 
     /// Only watch the path if it is a directory
-    pub const ONLYDIR: Mask = Mask(0x01000000);
+    pub const ONLYDIR: Mask = Mask(sys::IN_ONLYDIR);
 
     /// Don't follow a sym link
-    pub const DONT_FOLLOW: Mask = Mask(0x02000000);
+    pub const DONT_FOLLOW: Mask = Mask(sys::IN_DONT_FOLLOW);
 
     /// Exclude events on unlinked objects
-    pub const EXCL_UNLINK: Mask = Mask(0x04000000);
+    pub const EXCL_UNLINK: Mask = Mask(sys::IN_EXCL_UNLINK);
 
     /// Only create watches
-    pub const MASK_CREATE: Mask = Mask(0x10000000);
+    pub const MASK_CREATE: Mask = Mask(sys::IN_MASK_CREATE);
 
     /// Add to the mask of an already existing watch
-    pub const MASK_ADD: Mask = Mask(0x20000000);
+    pub const MASK_ADD: Mask = Mask(sys::IN_MASK_ADD);
 
     /// Event occurred against dir
-    pub const ISDIR: Mask = Mask(0x40000000);
+    pub const ISDIR: Mask = Mask(sys::IN_ISDIR);
 
     /// Only send event once
-    pub const ONESHOT: Mask = Mask(0x80000000);
+    pub const ONESHOT: Mask = Mask(sys::IN_ONESHOT);
+
+    /// Every event a watch may be interested in
+    pub const INTEREST: Mask = Mask(0x00000FFF);
+
+    /// Every bit the kernel may report on an event
+    pub const REPORTED: Mask = Mask(0x4000FFFF & !0x00001000);
 
     /// test if a mask constains a submask
     pub fn contains(self, other: Mask) -> bool {
         (self & other) == other
     }
+
+    /// the raw bits, as the kernel and the inotify headers define them
+    ///
+    /// ```
+    /// use tokinotify::Mask;
+    ///
+    /// let mask = Mask::CREATE | Mask::ISDIR;
+    /// assert_eq!(Mask::from_bits(mask.bits()), Some(mask));
+    /// assert_eq!(Mask::from_bits(0x0800_0000), None);
+    /// ```
+    pub const fn bits(self) -> u32 {
+        self.0
+    }
+
+    /// a mask of raw bits, `None` when a bit has no flag
+    pub const fn from_bits(bits: u32) -> Option<Mask> {
+        if bits & !KNOWN == 0 {
+            Some(Mask(bits))
+        } else {
+            None
+        }
+    }
+
+    /// a mask of raw bits, keeping bits without a flag
+    ///
+    /// Unknown bits round-trip through [Mask::bits] but are ignored when
+    /// comparing masks.
+    pub const fn from_bits_retain(bits: u32) -> Mask {
+        Mask(bits)
+    }
+
+    /// the flags set in either mask, usable in const contexts unlike `|`
+    pub const fn union(self, other: Mask) -> Mask {
+        Mask(self.0 | other.0)
+    }
+
+    /// the flag with a name as in the inotify headers without `IN_`
+    #[cfg_attr(not(feature = "tokio"), allow(dead_code))]
+    pub(crate) fn from_name(name: &str) -> Option<Mask> {
+        CHECK
+            .iter()
+            .find(|(_, n)| *n == name)
+            .map(|(mask, _)| *mask)
+    }
+
+    /// the names of the flags set, as in the inotify headers without `IN_`
+    #[cfg_attr(not(feature = "tokio"), allow(dead_code))]
+    pub(crate) fn names(self) -> impl Iterator<Item = &'static str> {
+        CHECK
+            .iter()
+            .filter(move |(mask, _)| (self & *mask).0 != 0)
+            .map(|(_, name)| *name)
+    }
 }
 
 impl PartialEq for Mask {
     fn eq(&self, other: &Self) -> bool {
-        const ALL: u32 = 0xF700EFFF;
-
-        (self.0 & ALL) == (other.0 & ALL)
+        (self.0 & KNOWN) == (other.0 & KNOWN)
     }
 }
 
+impl Eq for Mask {}
+
 impl std::ops::BitAnd<Mask> for Mask {
     type Output = Mask;
 
@@ -171,3 +274,20 @@ impl std::fmt::Debug for Mask {
         Ok(())
     }
 }
+
+/// Build a [Mask] from flag names, in const contexts too
+///
+/// ```
+/// use tokinotify::{mask, Mask};
+///
+/// const WRITTEN: Mask = mask!(MODIFY | CLOSE_WRITE | ONLYDIR);
+/// assert!(WRITTEN.contains(Mask::CLOSE_WRITE));
+/// ```
+///
+/// A misspelled flag fails to compile rather than leaving its bit unset.
+#[macro_export]
+macro_rules! mask {
+    ($first:ident $(| $flag:ident)* $(,)?) => {
+        $crate::Mask::$first$(.union($crate::Mask::$flag))*
+    };
+}
@@ -0,0 +1,87 @@
+use std::{borrow::Cow, path::Path};
+
+use crate::{Event, Filter, Glob, Mask};
+
+/// A predicate over events combining a set of flags and path patterns
+///
+/// An event matches when it carries any of the flags and its path matches
+/// any of the globs, either part left out matches everything. See
+/// [matcher!](crate::matcher) and [event_matches!](crate::event_matches)
+/// for the short forms.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Matcher {
+    mask: Option<Mask>,
+    filter: Filter,
+}
+
+impl Matcher {
+    /// Build a matcher accepting every event
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// only accept events carrying any flag of `mask`, adding to earlier calls
+    pub fn mask(mut self, mask: Mask) -> Self {
+        self.mask = Some(match self.mask {
+            Some(prev) => prev | mask,
+            None => mask,
+        });
+        self
+    }
+
+    /// only accept paths matching `glob` or any other glob given
+    pub fn glob(mut self, glob: Glob) -> Self {
+        self.filter.push(glob);
+        self
+    }
+
+    /// test an event, its path is joined to [Event::root] when relative
+    pub fn matches(&self, event: &Event) -> bool {
+        let path = match event.root() {
+            Some(root) if event.path.is_relative() => Cow::Owned(root.join(&event.path)),
+            _ => Cow::Borrowed(event.path.as_path()),
+        };
+
+        self.matches_path(event.mask, &path)
+    }
+
+    /// test the flags and full path of an event
+    pub fn matches_path(&self, mask: Mask, path: &Path) -> bool {
+        let flags = self.mask.is_none_or(|m| (m & mask).0 != 0);
+        flags && (self.filter.is_empty() || self.filter.matches(path))
+    }
+}
+
+/// Build a [Matcher] from flag names and glob patterns
+///
+/// ```
+/// let toml = tokinotify::matcher!(CREATE | MOVED_TO, "*.toml");
+/// ```
+///
+/// Panics when a pattern does not compile, use [Matcher] for patterns not
+/// known up front.
+#[macro_export]
+macro_rules! matcher {
+    ($($flag:ident)|+ $(, $glob:expr)* $(,)?) => {
+        $crate::Matcher::new()
+            .mask($crate::mask!($($flag)|+))
+            $(.glob($crate::Glob::new($glob).expect("invalid glob")))*
+    };
+}
+
+/// Test an event against flag names and glob patterns
+///
+/// ```
+/// use tokinotify::{event_matches, Event, Mask, Watch};
+///
+/// let event = Event::new(Watch::from_raw(1), Mask::CREATE, "Cargo.toml");
+/// assert!(event_matches!(event, CREATE | MOVED_TO, "*.toml"));
+/// ```
+///
+/// Short for [matcher!](crate::matcher) followed by [Matcher::matches].
+#[macro_export]
+macro_rules! event_matches {
+    ($event:expr, $($rest:tt)+) => {
+        $crate::matcher!($($rest)+).matches(&$event)
+    };
+}
@@ -0,0 +1,314 @@
+use std::{
+    collections::VecDeque,
+    io,
+    os::unix::fs::MetadataExt,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use crate::{Event, INotify, Mask};
+
+/// The events a mirrored tree is watched for
+const MIRRORED: Mask = Mask(
+    Mask::CREATE.0
+        | Mask::CLOSE_WRITE.0
+        | Mask::DELETE.0
+        | Mask::MOVED_FROM.0
+        | Mask::MOVED_TO.0
+        | Mask::DELETE_SELF.0,
+);
+
+/// How long a MOVED_FROM waits for its MOVED_TO by default
+const MOVE_WINDOW: Duration = Duration::from_millis(10);
+
+/// A step bringing the destination of a [Mirror] in line with its source
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SyncOp {
+    /// Create a directory in the destination
+    Mkdir(PathBuf),
+
+    /// Copy a file from the source over its destination counterpart
+    Copy {
+        /// The file in the source
+        from: PathBuf,
+
+        /// The file in the destination
+        to: PathBuf,
+    },
+
+    /// Remove a file or, recursively, a directory from the destination
+    Remove(PathBuf),
+
+    /// Rename within the destination, as was done in the source
+    Rename {
+        /// The old destination path
+        from: PathBuf,
+
+        /// The new destination path
+        to: PathBuf,
+    },
+
+    /// A file or directory was moved out of the source, see [OrphanMove::MovedOut]
+    MovedOut(PathBuf),
+}
+
+/// What a MOVED_FROM whose MOVED_TO never arrived is planned as
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OrphanMove {
+    /// Remove the destination counterpart, as if deleted
+    #[default]
+    Deleted,
+
+    /// Leave the destination counterpart to the caller with [SyncOp::MovedOut]
+    MovedOut,
+}
+
+/// Plans the operations keeping a destination tree a copy of a source tree
+///
+/// Starting compares both trees, afterwards events on the source are turned
+/// into operations. Nothing is executed, the caller applies each [SyncOp]
+/// in order, and a queue overflow or failed operation is recovered from by
+/// calling [Mirror::resync]. Files are copied once written and closed.
+///
+/// A MOVED_FROM is held for the move window (10ms by default) to be paired
+/// with its MOVED_TO into a [SyncOp::Rename]. Left unpaired it is planned
+/// according to the [OrphanMove] policy.
+pub struct Mirror {
+    src: PathBuf,
+    dst: PathBuf,
+    ops: VecDeque<SyncOp>,
+    moved: Option<(u32, PathBuf)>,
+    window: Duration,
+    orphans: OrphanMove,
+}
+
+impl Mirror {
+    /// Plan mirroring `src` into `dst`
+    pub fn new(src: &Path, dst: &Path) -> Self {
+        Self {
+            src: src.to_path_buf(),
+            dst: dst.to_path_buf(),
+            ops: VecDeque::new(),
+            moved: None,
+            window: MOVE_WINDOW,
+            orphans: OrphanMove::default(),
+        }
+    }
+
+    /// Hold a MOVED_FROM up to `window` for its MOVED_TO
+    pub fn move_window(mut self, window: Duration) -> Self {
+        self.window = window;
+        self
+    }
+
+    /// Plan unpaired MOVED_FROMs according to `policy`
+    pub fn orphans(mut self, policy: OrphanMove) -> Self {
+        self.orphans = policy;
+        self
+    }
+
+    /// Watch the source tree and plan the operations to catch the destination up
+    pub async fn start(&mut self, inotify: &mut INotify) -> io::Result<()> {
+        inotify.add_tree(&self.src, MIRRORED, None).await?;
+        self.resync().await
+    }
+
+    /// Compare both trees again, planning whatever differs
+    pub async fn resync(&mut self) -> io::Result<()> {
+        self.ops.clear();
+        self.moved = None;
+        self.plan(Path::new("")).await
+    }
+
+    /// Operations planned but not yet taken
+    pub fn pending(&self) -> usize {
+        self.ops.len()
+    }
+
+    /// Wait for the next operation
+    pub async fn next(&mut self, inotify: &mut INotify) -> io::Result<SyncOp> {
+        loop {
+            if let Some(op) = self.ops.pop_front() {
+                return Ok(op);
+            }
+
+            let event = if self.moved.is_some() {
+                // the window closing interrupts the watch, it is cancel safe
+                tokio::select! {
+                    _ = tokio::time::sleep(self.window) => {
+                        self.moved_out();
+                        continue;
+                    }
+                    event = inotify.watch() => event?,
+                }
+            } else {
+                inotify.watch().await?
+            };
+
+            self.observe(inotify, &event).await?;
+        }
+    }
+
+    async fn observe(&mut self, inotify: &mut INotify, event: &Event) -> io::Result<()> {
+        if event.mask.contains(Mask::Q_OVERFLOW) {
+            return self.resync().await;
+        }
+
+        let Some(path) = inotify.resolve(event) else {
+            return Ok(());
+        };
+        let Ok(rel) = path.strip_prefix(&self.src).map(Path::to_path_buf) else {
+            return Ok(());
+        };
+
+        let dir = event.mask.contains(Mask::ISDIR);
+
+        if event.mask.contains(Mask::MOVED_TO) {
+            match self.moved.take() {
+                Some((cookie, from)) if cookie == event.cookie => {
+                    self.ops.push_back(SyncOp::Rename {
+                        from: self.dst.join(from),
+                        to: self.dst.join(&rel),
+                    });
+
+                    if dir {
+                        // rewatch so the moved watches resolve to their new paths
+                        inotify.add_tree(&path, MIRRORED, None).await?;
+                    }
+                    return Ok(());
+                }
+                Some(moved) => {
+                    self.moved = Some(moved);
+                    self.moved_out();
+                }
+                None => (),
+            }
+        } else {
+            self.moved_out();
+        }
+
+        if event.mask.contains(Mask::MOVED_FROM) {
+            self.moved = Some((event.cookie, rel));
+        } else if event.mask.contains(Mask::DELETE) {
+            self.ops.push_back(SyncOp::Remove(self.dst.join(&rel)));
+        } else if event.mask.contains(Mask::DELETE_SELF) && rel.as_os_str().is_empty() {
+            self.ops.push_back(SyncOp::Remove(self.dst.clone()));
+        } else if dir && (event.mask & (Mask::CREATE | Mask::MOVED_TO)).0 != 0 {
+            // entries may have appeared before the watch, plan the whole directory
+            inotify.add_tree(&path, MIRRORED, None).await?;
+            self.plan(&rel).await?;
+        } else if event.mask.contains(Mask::CLOSE_WRITE) || event.mask.contains(Mask::MOVED_TO) {
+            self.ops.push_back(SyncOp::Copy {
+                from: path,
+                to: self.dst.join(&rel),
+            });
+        }
+
+        Ok(())
+    }
+
+    /// a MOVED_FROM without its MOVED_TO left the tree
+    fn moved_out(&mut self) {
+        if let Some((_, rel)) = self.moved.take() {
+            let dst = self.dst.join(rel);
+            self.ops.push_back(match self.orphans {
+                OrphanMove::Deleted => SyncOp::Remove(dst),
+                OrphanMove::MovedOut => SyncOp::MovedOut(dst),
+            });
+        }
+    }
+
+    /// plan the operations for a subtree, relative to both roots
+    async fn plan(&mut self, rel: &Path) -> io::Result<()> {
+        let src = self.src.join(rel);
+        let dst = self.dst.join(rel);
+
+        let ops = tokio::task::spawn_blocking(move || diff(&src, &dst))
+            .await
+            .map_err(io::Error::other)??;
+
+        self.ops.extend(ops);
+        Ok(())
+    }
+}
+
+/// the operations turning `dst` into a copy of `src`, parents before children
+fn diff(src: &Path, dst: &Path) -> io::Result<Vec<SyncOp>> {
+    let mut ops = Vec::new();
+    let mut dirs = vec![(src.to_path_buf(), dst.to_path_buf())];
+
+    match std::fs::symlink_metadata(dst) {
+        Ok(meta) if meta.is_dir() => (),
+        Ok(_) => {
+            ops.push(SyncOp::Remove(dst.to_path_buf()));
+            ops.push(SyncOp::Mkdir(dst.to_path_buf()));
+        }
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {
+            ops.push(SyncOp::Mkdir(dst.to_path_buf()))
+        }
+        Err(err) => return Err(err),
+    }
+
+    while let Some((src, dst)) = dirs.pop() {
+        let entries = match std::fs::read_dir(&src) {
+            Ok(entries) => entries,
+            // removed while planning, its events will follow
+            Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+            Err(err) => return Err(err),
+        };
+
+        let mut seen = Vec::new();
+
+        for entry in entries {
+            let entry = entry?;
+            let Ok(meta) = entry.metadata() else {
+                continue;
+            };
+
+            let from = entry.path();
+            let to = dst.join(entry.file_name());
+            seen.push(entry.file_name());
+
+            let existing = std::fs::symlink_metadata(&to).ok();
+
+            if meta.is_dir() {
+                match existing {
+                    Some(existing) if existing.is_dir() => (),
+                    Some(_) => {
+                        ops.push(SyncOp::Remove(to.clone()));
+                        ops.push(SyncOp::Mkdir(to.clone()));
+                    }
+                    None => ops.push(SyncOp::Mkdir(to.clone())),
+                }
+                dirs.push((from, to));
+                continue;
+            }
+
+            match existing {
+                Some(existing) if existing.is_dir() => {
+                    ops.push(SyncOp::Remove(to.clone()));
+                    ops.push(SyncOp::Copy { from, to });
+                }
+                Some(existing) if !stale(&meta, &existing) => (),
+                _ => ops.push(SyncOp::Copy { from, to }),
+            }
+        }
+
+        let Ok(existing) = std::fs::read_dir(&dst) else {
+            continue;
+        };
+
+        for entry in existing.flatten() {
+            if !seen.contains(&entry.file_name()) {
+                ops.push(SyncOp::Remove(entry.path()));
+            }
+        }
+    }
+
+    Ok(ops)
+}
+
+/// a copy differs in size or is older than its source
+fn stale(src: &std::fs::Metadata, dst: &std::fs::Metadata) -> bool {
+    src.len() != dst.len() || (src.mtime(), src.mtime_nsec()) > (dst.mtime(), dst.mtime_nsec())
+}
@@ -0,0 +1,390 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    ffi::OsString,
+    fs::File,
+    io,
+    os::unix::{ffi::OsStringExt, fs::FileExt},
+    path::{Path, PathBuf},
+};
+
+use tokio::io::{unix::AsyncFd, Interest};
+
+use crate::{INotify, Watch};
+
+/// A line of `/proc/self/mountinfo`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mount {
+    /// The unique id of the mount
+    pub id: u32,
+
+    /// The id of the parent mount
+    pub parent: u32,
+
+    /// The `(major, minor)` device number of the filesystem
+    pub device: (u32, u32),
+
+    /// The directory within the filesystem which forms the root of this mount
+    pub root: PathBuf,
+
+    /// Where the mount is attached
+    pub mount_point: PathBuf,
+
+    /// The per mount options, `rw,noatime`, ...
+    pub options: String,
+
+    /// The filesystem type, `overlay`, `ext4`, ...
+    pub fstype: String,
+
+    /// The filesystem specific source, often a device
+    pub source: String,
+}
+
+/// A reason events on a mount may be incomplete
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Quirk {
+    /// An overlay, changes made to the lower layers produce no events
+    Overlay,
+
+    /// The filesystem is visible through other mounts, changes made through
+    /// them are only reported when inotify sees the same inode
+    Bind,
+
+    /// A network or userspace filesystem, changes made elsewhere produce no events
+    Remote,
+}
+
+/// What the kernel can be trusted to report for a watch
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Capability {
+    /// The mount the watched path lives on
+    pub mount: Mount,
+
+    /// The reasons events may be missed
+    pub quirks: Vec<Quirk>,
+}
+
+/// A change to the mount table
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MountEvent {
+    /// A filesystem was attached
+    Mounted(Mount),
+
+    /// A filesystem was detached
+    Unmounted(Mount),
+
+    /// The options of a mount changed
+    Remounted(Mount),
+}
+
+/// Reports mounts and unmounts as they happen
+///
+/// The kernel flags `/proc/self/mountinfo` as having priority data whenever
+/// the mount table changes, the table is then reread and diffed against
+/// the previous one.
+pub struct MountWatcher {
+    file: AsyncFd<File>,
+    table: Vec<Mount>,
+    queued: VecDeque<MountEvent>,
+}
+
+impl Mount {
+    /// Read the mount table of the current process
+    pub fn table() -> io::Result<Vec<Mount>> {
+        Self::parse_table(&std::fs::read(MOUNTINFO)?)
+    }
+
+    /// Parse the contents of a mountinfo file
+    pub fn parse_table(bytes: &[u8]) -> io::Result<Vec<Mount>> {
+        bytes
+            .split(|b| *b == b'\n')
+            .filter(|line| !line.is_empty())
+            .map(Mount::parse)
+            .collect()
+    }
+
+    /// Parse a single mountinfo line
+    pub fn parse(line: &[u8]) -> io::Result<Mount> {
+        let fields: Vec<&[u8]> = line.split(|b| *b == b' ').collect();
+        let sep = fields
+            .iter()
+            .position(|f| *f == b"-")
+            .filter(|sep| *sep >= 6 && fields.len() >= sep + 3)
+            .ok_or_else(|| malformed(line))?;
+
+        let number = |field: &[u8]| -> io::Result<u32> {
+            std::str::from_utf8(field)
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .ok_or_else(|| malformed(line))
+        };
+
+        let (major, minor) = split_once(fields[2], b':').ok_or_else(|| malformed(line))?;
+
+        Ok(Mount {
+            id: number(fields[0])?,
+            parent: number(fields[1])?,
+            device: (number(major)?, number(minor)?),
+            root: PathBuf::from(unescape(fields[3])),
+            mount_point: PathBuf::from(unescape(fields[4])),
+            options: String::from_utf8_lossy(fields[5]).into_owned(),
+            fstype: String::from_utf8_lossy(fields[sep + 1]).into_owned(),
+            source: unescape(fields[sep + 2]).to_string_lossy().into_owned(),
+        })
+    }
+
+    /// The mount `path` lives on, the last mounted over a shared mount point wins
+    pub fn containing<'a>(table: &'a [Mount], path: &Path) -> Option<&'a Mount> {
+        table
+            .iter()
+            .enumerate()
+            .filter(|(_, m)| path.starts_with(&m.mount_point))
+            .max_by_key(|(i, m)| (m.mount_point.components().count(), *i))
+            .map(|(_, m)| m)
+    }
+}
+
+impl Capability {
+    /// Determine the quirks affecting `path` from a mount table
+    pub fn of(table: &[Mount], path: &Path) -> Option<Capability> {
+        let mount = Mount::containing(table, path)?;
+        let mut quirks = Vec::new();
+
+        if mount.fstype == "overlay" {
+            quirks.push(Quirk::Overlay);
+        }
+
+        let remote = ["nfs", "nfs4", "cifs", "smb3", "9p", "ceph", "glusterfs", "fuse"];
+        let fstype = mount.fstype.as_str();
+        if remote.contains(&fstype) || fstype.starts_with("fuse.") {
+            quirks.push(Quirk::Remote);
+        }
+
+        let shared = table
+            .iter()
+            .any(|m| m.id != mount.id && m.device == mount.device);
+        if mount.root != Path::new("/") || shared {
+            quirks.push(Quirk::Bind);
+        }
+
+        Some(Capability {
+            mount: mount.clone(),
+            quirks,
+        })
+    }
+
+    /// Events can be trusted to be complete
+    pub fn reliable(&self) -> bool {
+        self.quirks.is_empty()
+    }
+}
+
+impl INotify {
+    /// report how reliable the events of a watch are given the mount it lives on
+    pub fn capability(&self, watch: Watch) -> io::Result<Capability> {
+        let path = self
+            .path(watch)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "unknown watch"))?;
+        let table = Mount::table()?;
+
+        Capability::of(&table, path)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no mount contains the watch"))
+    }
+}
+
+impl MountEvent {
+    /// The mount which changed
+    pub fn mount(&self) -> &Mount {
+        match self {
+            MountEvent::Mounted(mount)
+            | MountEvent::Unmounted(mount)
+            | MountEvent::Remounted(mount) => mount,
+        }
+    }
+}
+
+impl MountWatcher {
+    /// Start watching the mount table of the current process
+    pub fn new() -> io::Result<Self> {
+        let file = AsyncFd::with_interest(File::open(MOUNTINFO)?, Interest::PRIORITY)?;
+        let table = Mount::parse_table(&read_all(file.get_ref())?)?;
+
+        Ok(Self {
+            file,
+            table,
+            queued: VecDeque::new(),
+        })
+    }
+
+    /// The mount table as of the last reported change
+    pub fn table(&self) -> &[Mount] {
+        &self.table
+    }
+
+    /// Wait for the next change to the mount table
+    pub async fn watch(&mut self) -> io::Result<MountEvent> {
+        loop {
+            if let Some(event) = self.queued.pop_front() {
+                return Ok(event);
+            }
+
+            let mut guard = self.file.ready(Interest::PRIORITY).await?;
+            guard.clear_ready();
+
+            let table = Mount::parse_table(&read_all(self.file.get_ref())?)?;
+            self.diff(table);
+        }
+    }
+
+    fn diff(&mut self, table: Vec<Mount>) {
+        let mut previous: HashMap<u32, Mount> = self.table.drain(..).map(|m| (m.id, m)).collect();
+
+        for mount in &table {
+            match previous.remove(&mount.id) {
+                None => self.queued.push_back(MountEvent::Mounted(mount.clone())),
+                Some(old) if old != *mount => {
+                    self.queued.push_back(MountEvent::Remounted(mount.clone()))
+                }
+                Some(_) => (),
+            }
+        }
+
+        let mut gone: Vec<Mount> = previous.into_values().collect();
+        gone.sort_by_key(|m| m.id);
+        self.queued
+            .extend(gone.into_iter().map(MountEvent::Unmounted));
+
+        self.table = table;
+    }
+}
+
+impl INotify {
+    /// re-add the watches beneath a mount point which changed
+    ///
+    /// a path mounted over (or uncovered by an unmount) now refers to a
+    /// different directory, its watch is replaced with the mask it was
+    /// registered with. Every affected watch is returned with the watch
+    /// now covering its path (itself if the path still names the same
+    /// directory) or the error re-adding it, one failure does not stop the
+    /// rest. Watches the kernel already dropped, and whose IGNORED event
+    /// was read, are not known anymore and can not be re-added.
+    pub fn remount(&mut self, mount_point: &Path) -> Vec<(Watch, io::Result<Watch>)> {
+        let affected: Vec<(Watch, PathBuf)> = self
+            .watches()
+            .filter(|(_, path)| path.starts_with(mount_point))
+            .map(|(watch, path)| (watch, path.to_path_buf()))
+            .collect();
+
+        let mut remounted = Vec::new();
+        for (old, path) in affected {
+            let Some(mask) = self.registration(old) else {
+                continue;
+            };
+
+            let new = self.add(&path, mask);
+            if let Ok(new) = new {
+                if new != old {
+                    let _ = self.rm(old);
+                }
+            }
+            remounted.push((old, new));
+        }
+
+        remounted
+    }
+}
+
+const MOUNTINFO: &str = "/proc/self/mountinfo";
+
+/// Read a proc file from the start, regardless of the current offset
+fn read_all(file: &File) -> io::Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 4096];
+
+    loop {
+        let n = file.read_at(&mut chunk, buf.len() as u64)?;
+        if n == 0 {
+            return Ok(buf);
+        }
+
+        buf.extend_from_slice(&chunk[..n]);
+    }
+}
+
+fn split_once(field: &[u8], sep: u8) -> Option<(&[u8], &[u8])> {
+    let i = field.iter().position(|b| *b == sep)?;
+    Some((&field[..i], &field[i + 1..]))
+}
+
+/// Undo the octal escaping (`\040` for a space) the kernel applies to paths
+fn unescape(field: &[u8]) -> OsString {
+    let mut out = Vec::with_capacity(field.len());
+    let mut i = 0;
+
+    while i < field.len() {
+        let digits = field.get(i + 1..i + 4);
+        match digits {
+            Some(d) if field[i] == b'\\' && d.iter().all(|b| (b'0'..=b'7').contains(b)) => {
+                out.push(d.iter().fold(0u8, |acc, b| (acc << 3) | (b - b'0')));
+                i += 4;
+            }
+            _ => {
+                out.push(field[i]);
+                i += 1;
+            }
+        }
+    }
+
+    OsString::from_vec(out)
+}
+
+fn malformed(line: &[u8]) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!(
+            "malformed mountinfo line: {}",
+            String::from_utf8_lossy(line)
+        ),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::scratch;
+    use crate::Mask;
+
+    #[tokio::test]
+    async fn remount_replaces_each_watch_on_its_own() {
+        let root = scratch("remount");
+        std::fs::create_dir_all(root.join("replaced")).unwrap();
+        std::fs::create_dir_all(root.join("gone")).unwrap();
+
+        let mut inotify = INotify::new().unwrap();
+        let kept = inotify.add(&root, Mask::CREATE).unwrap();
+        let replaced = inotify
+            .add(&root.join("replaced"), Mask::CREATE | Mask::ONLYDIR)
+            .unwrap();
+        let gone = inotify.add(&root.join("gone"), Mask::CREATE).unwrap();
+
+        // as if mounted over, the paths name other directories or none
+        std::fs::remove_dir(root.join("replaced")).unwrap();
+        std::fs::create_dir(root.join("replaced")).unwrap();
+        std::fs::remove_dir(root.join("gone")).unwrap();
+
+        let remounted: HashMap<Watch, io::Result<Watch>> =
+            inotify.remount(&root).into_iter().collect();
+        assert_eq!(remounted.len(), 3);
+
+        assert_eq!(*remounted[&kept].as_ref().unwrap(), kept);
+
+        let new = *remounted[&replaced].as_ref().unwrap();
+        assert_ne!(new, replaced);
+        assert_eq!(
+            inotify.registration(new),
+            Some(Mask::CREATE | Mask::ONLYDIR)
+        );
+
+        let err = remounted[&gone].as_ref().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    }
+}
@@ -0,0 +1,129 @@
+use std::{
+    ffi::OsStr,
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+};
+
+const INLINE: usize = 54;
+
+/// An event name stored inline when short
+///
+/// Most names fit without touching the allocator, a [PathBuf] is only
+/// built when asked for.
+#[derive(Clone)]
+pub struct Name(Repr);
+
+#[derive(Clone)]
+enum Repr {
+    Inline { len: u8, buf: [u8; INLINE] },
+    Heap(Box<[u8]>),
+}
+
+impl Name {
+    /// Build a name from raw bytes
+    pub fn new(bytes: &[u8]) -> Name {
+        if bytes.len() <= INLINE {
+            let mut buf = [0u8; INLINE];
+            buf[..bytes.len()].copy_from_slice(bytes);
+
+            Name(Repr::Inline {
+                len: bytes.len() as u8,
+                buf,
+            })
+        } else {
+            Name(Repr::Heap(bytes.into()))
+        }
+    }
+
+    /// The raw bytes of the name
+    pub fn as_bytes(&self) -> &[u8] {
+        match &self.0 {
+            Repr::Inline { len, buf } => &buf[..*len as usize],
+            Repr::Heap(bytes) => bytes,
+        }
+    }
+
+    /// The name as a path
+    pub fn as_path(&self) -> &Path {
+        Path::new(OsStr::from_bytes(self.as_bytes()))
+    }
+
+    /// Copy the name into an owned path
+    pub fn to_path_buf(&self) -> PathBuf {
+        self.as_path().to_path_buf()
+    }
+
+    /// The name has no bytes (the event refers to the watch itself)
+    pub fn is_empty(&self) -> bool {
+        self.as_bytes().is_empty()
+    }
+
+    /// The name did not fit inline
+    pub fn spilled(&self) -> bool {
+        matches!(self.0, Repr::Heap(_))
+    }
+}
+
+impl Default for Name {
+    fn default() -> Self {
+        Name::new(&[])
+    }
+}
+
+impl AsRef<Path> for Name {
+    fn as_ref(&self) -> &Path {
+        self.as_path()
+    }
+}
+
+impl PartialEq for Name {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_bytes() == other.as_bytes()
+    }
+}
+
+impl Eq for Name {}
+
+impl std::fmt::Debug for Name {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Debug::fmt(self.as_path(), f)
+    }
+}
+
+#[cfg(all(test, feature = "tokio"))]
+mod tests {
+    use super::*;
+    use crate::{testing::scratch, INotify, Mask};
+    use std::time::Duration;
+
+    async fn next(inotify: &mut INotify) -> Name {
+        tokio::time::timeout(Duration::from_secs(10), inotify.watch_raw())
+            .await
+            .unwrap()
+            .unwrap()
+            .name
+    }
+
+    #[tokio::test]
+    async fn only_long_names_spill() {
+        let dir = scratch("name");
+        let mut inotify = INotify::new().unwrap();
+        inotify.add(&dir, Mask::CREATE).unwrap();
+
+        let short = "s".repeat(INLINE);
+        let long = "l".repeat(INLINE + 1);
+        std::fs::File::create(dir.join(&short)).unwrap();
+        std::fs::File::create(dir.join(&long)).unwrap();
+
+        // the kernel's padding is not part of either
+        let name = next(&mut inotify).await;
+        assert!(!name.spilled());
+        assert_eq!(name, Name::new(short.as_bytes()));
+        assert_eq!(name.as_path(), Path::new(&short));
+
+        let name = next(&mut inotify).await;
+        assert!(name.spilled());
+        assert_eq!(name, Name::new(long.as_bytes()));
+        assert_eq!(name.to_path_buf(), PathBuf::from(&long));
+    }
+}
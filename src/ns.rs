@@ -0,0 +1,219 @@
+use std::{
+    collections::HashMap,
+    ffi::{c_int, CString},
+    io,
+    os::{
+        fd::{AsRawFd, FromRawFd, OwnedFd},
+        unix::ffi::OsStrExt,
+    },
+    path::{Component, Path, PathBuf},
+};
+
+use crate::{sys, Event, INotify, Mask, Watch};
+
+/// Watch paths inside of another process's mount namespace
+///
+/// Paths are reached through `/proc/<pid>/root` and the process is pinned
+/// with a pidfd so a recycled pid is never mistaken for the original process.
+///
+/// Paths are resolved with openat2 (linux 5.6) as the container sees them:
+/// `..` and symlinks, absolute ones included, stay inside the container
+/// root. The resolved file is watched through its descriptor.
+pub struct Namespace {
+    pid: c_int,
+    pidfd: OwnedFd,
+    root: PathBuf,
+    root_dir: OwnedFd,
+    watches: HashMap<Watch, PathBuf>,
+}
+
+impl Namespace {
+    /// Open the namespace of a running process
+    pub fn open(pid: c_int) -> io::Result<Self> {
+        let fd = unsafe { sys::pidfd_open(pid, 0) };
+        if fd == -1 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let pidfd = unsafe { OwnedFd::from_raw_fd(fd) };
+        let root = PathBuf::from(format!("/proc/{pid}/root"));
+        let root_dir = std::fs::File::open(&root)?.into();
+
+        Ok(Self {
+            pid,
+            pidfd,
+            root,
+            root_dir,
+            watches: HashMap::new(),
+        })
+    }
+
+    /// The pid of the observed process
+    pub fn pid(&self) -> c_int {
+        self.pid
+    }
+
+    /// Check that the observed process has not exited
+    pub fn alive(&self) -> io::Result<bool> {
+        let res = unsafe { sys::pidfd_send_signal(self.pidfd.as_raw_fd(), 0) };
+        if res == -1 {
+            let err = io::Error::last_os_error();
+            if err.raw_os_error() == Some(sys::ESRCH) {
+                return Ok(false);
+            }
+
+            return Err(err);
+        }
+
+        Ok(true)
+    }
+
+    /// Translate a container path into a host path
+    ///
+    /// `..` is resolved lexically, a path climbing above the container
+    /// root is rejected. Symlinks are left to whoever opens the result,
+    /// [Namespace::add] resolves them inside the container instead.
+    pub fn host_path(&self, path: &Path) -> io::Result<PathBuf> {
+        Ok(self.root.join(normalize(path)?))
+    }
+
+    /// Translate a host path back into the container's view
+    pub fn container_path(&self, host: &Path) -> Option<PathBuf> {
+        let rel = host.strip_prefix(&self.root).ok()?;
+
+        Some(Path::new("/").join(rel))
+    }
+
+    /// Add a container path to be watched
+    ///
+    /// The pidfd is checked after the watch is set up, if the process
+    /// has exited the watch is removed and an error is returned.
+    pub fn add(&mut self, inotify: &mut INotify, path: &Path, mask: Mask) -> io::Result<Watch> {
+        let rel = normalize(path)?;
+        let inside = self.open_in_root(&rel)?;
+
+        let through = PathBuf::from(format!("/proc/self/fd/{}", inside.as_raw_fd()));
+        let watch = inotify.add(&through, mask)?;
+
+        if !self.alive()? {
+            let _ = inotify.rm(watch);
+            return Err(io::Error::from_raw_os_error(sys::ESRCH));
+        }
+
+        self.watches.insert(watch, Path::new("/").join(rel));
+
+        Ok(watch)
+    }
+
+    /// an O_PATH descriptor of `rel`, resolved with the container root as `/`
+    fn open_in_root(&self, rel: &Path) -> io::Result<OwnedFd> {
+        let rel = match rel.as_os_str().is_empty() {
+            true => Path::new("."),
+            false => rel,
+        };
+        let cpath = CString::new(rel.as_os_str().as_bytes())?;
+
+        let how = sys::OpenHow {
+            flags: (sys::O_PATH | sys::O_CLOEXEC) as u64,
+            mode: 0,
+            resolve: sys::RESOLVE_IN_ROOT,
+        };
+
+        let fd = unsafe { sys::openat2(self.root_dir.as_raw_fd(), cpath.as_ptr(), &how) };
+        if fd == -1 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(unsafe { OwnedFd::from_raw_fd(fd) })
+    }
+
+    /// Remove a watch added through this namespace
+    pub fn rm(&mut self, inotify: &mut INotify, watch: Watch) -> io::Result<()> {
+        self.watches.remove(&watch);
+        inotify.rm(watch)
+    }
+
+    /// Resolve the container relative path an event refers to
+    pub fn resolve(&self, event: &Event) -> Option<PathBuf> {
+        let base = self.watches.get(&event.watch)?;
+
+        if event.path.as_os_str().is_empty() {
+            Some(base.clone())
+        } else {
+            Some(base.join(&event.path))
+        }
+    }
+}
+
+/// a container path relative to its root, refusing to climb above it
+fn normalize(path: &Path) -> io::Result<PathBuf> {
+    let mut rel = PathBuf::new();
+
+    for comp in path.components() {
+        match comp {
+            Component::RootDir | Component::Prefix(_) | Component::CurDir => (),
+            Component::Normal(name) => rel.push(name),
+            Component::ParentDir if rel.pop() => (),
+            Component::ParentDir => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "path escapes the container root",
+                ))
+            }
+        }
+    }
+
+    Ok(rel)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::scratch;
+    use std::time::Duration;
+
+    /// this process's namespace, with a scratch directory standing in for its root
+    fn rooted_at(root: &Path) -> Namespace {
+        let mut ns = Namespace::open(std::process::id() as c_int).unwrap();
+        ns.root = root.to_path_buf();
+        ns.root_dir = std::fs::File::open(root).unwrap().into();
+        ns
+    }
+
+    #[tokio::test]
+    async fn symlinks_resolve_inside_the_root() {
+        let root = scratch("ns-links");
+        std::fs::create_dir(root.join("etc")).unwrap();
+        std::fs::write(root.join("etc/hostname"), "container").unwrap();
+        std::os::unix::fs::symlink("/etc/hostname", root.join("absolute")).unwrap();
+        std::os::unix::fs::symlink("../../../../etc/hostname", root.join("climbing")).unwrap();
+
+        let mut ns = rooted_at(&root);
+        let mut inotify = INotify::new().unwrap();
+        let absolute = ns.add(&mut inotify, Path::new("/absolute"), Mask::MODIFY).unwrap();
+        let climbing = ns.add(&mut inotify, Path::new("/climbing"), Mask::MODIFY).unwrap();
+
+        // both links lead to the container's file, watched once
+        assert_eq!(absolute, climbing);
+
+        std::fs::write(root.join("etc/hostname"), "renamed").unwrap();
+        let event = tokio::time::timeout(Duration::from_secs(10), inotify.watch())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(event.watch, absolute);
+        assert_eq!(event.mask, Mask::MODIFY);
+        assert_eq!(ns.resolve(&event), Some(PathBuf::from("/climbing")));
+    }
+
+    #[test]
+    fn normalize_stays_in_root() {
+        assert_eq!(
+            normalize(Path::new("/var/./log/../lib")).unwrap(),
+            Path::new("var/lib")
+        );
+        assert_eq!(normalize(Path::new("/")).unwrap(), Path::new(""));
+        assert!(normalize(Path::new("../../etc/shadow")).is_err());
+        assert!(normalize(Path::new("/var/../../etc")).is_err());
+    }
+}
@@ -0,0 +1,164 @@
+use std::{ffi::c_int, mem::size_of};
+
+/// The fixed part of `struct inotify_event`
+#[repr(C)]
+pub(crate) struct EventHeader {
+    pub(crate) wd: c_int,
+    pub(crate) mask: u32,
+    pub(crate) cookie: u32,
+    pub(crate) len: u32,
+}
+
+pub(crate) const HEADER_SIZE: usize = size_of::<EventHeader>();
+
+const _: () = assert!(HEADER_SIZE == 16);
+const _: () = assert!(std::mem::align_of::<EventHeader>() == 4);
+const _: () = assert!(size_of::<c_int>() == 4);
+
+impl EventHeader {
+    /// Decode a header, fields are in native byte order
+    pub(crate) fn parse(bytes: &[u8; HEADER_SIZE]) -> EventHeader {
+        let field = |at: usize| [bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]];
+
+        EventHeader {
+            wd: c_int::from_ne_bytes(field(0)),
+            mask: u32::from_ne_bytes(field(4)),
+            cookie: u32::from_ne_bytes(field(8)),
+            len: u32::from_ne_bytes(field(12)),
+        }
+    }
+}
+
+/// The longest file name, without its NUL
+const NAME_MAX: usize = 255;
+
+/// The longest name field the kernel reports, NAME_MAX and a NUL padded
+/// to a multiple of the header size
+pub(crate) const NAME_LIMIT: usize = (NAME_MAX + 1).next_multiple_of(HEADER_SIZE);
+
+/// Why a frame read from the kernel could not be decoded
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    /// The buffer ends before the event does
+    Truncated {
+        /// Bytes needed for the event
+        needed: usize,
+
+        /// Bytes left in the buffer
+        available: usize,
+    },
+
+    /// The name length is larger than any legal name
+    NameTooLong {
+        /// The reported length
+        len: usize,
+    },
+
+    /// The watch descriptor is not one this instance handed out (strict mode)
+    UnknownWatch {
+        /// The reported watch descriptor
+        wd: c_int,
+    },
+
+    /// The mask has bits the kernel never reports (strict mode)
+    UnknownMask {
+        /// The reported mask
+        mask: u32,
+    },
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseError::Truncated { needed, available } => {
+                write!(f, "truncated event: need {needed} bytes, have {available}")
+            }
+            ParseError::NameTooLong { len } => write!(f, "event name length {len} is too long"),
+            ParseError::UnknownWatch { wd } => write!(f, "unknown watch descriptor {wd}"),
+            ParseError::UnknownMask { mask } => write!(f, "unknown mask bits {mask:X}"),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl From<ParseError> for std::io::Error {
+    fn from(err: ParseError) -> Self {
+        std::io::Error::new(std::io::ErrorKind::InvalidData, err)
+    }
+}
+
+/// Decode the next event in `buf`, returning the header, unpadded name and bytes consumed
+pub(crate) fn next(buf: &[u8]) -> Result<(EventHeader, &[u8], usize), ParseError> {
+    next_within(buf, NAME_LIMIT)
+}
+
+/// [next] for frames whose name field may run up to `limit`, e.g. a full path
+pub(crate) fn next_within(
+    buf: &[u8],
+    limit: usize,
+) -> Result<(EventHeader, &[u8], usize), ParseError> {
+    let Some(head) = buf.first_chunk::<HEADER_SIZE>() else {
+        return Err(ParseError::Truncated {
+            needed: HEADER_SIZE,
+            available: buf.len(),
+        });
+    };
+
+    let header = EventHeader::parse(head);
+    let len = header.len as usize;
+
+    if len > limit {
+        return Err(ParseError::NameTooLong { len });
+    }
+
+    let total = HEADER_SIZE + len;
+    if total > buf.len() {
+        return Err(ParseError::Truncated {
+            needed: total,
+            available: buf.len(),
+        });
+    }
+
+    // names are NUL terminated and padded with NULs to an alignment boundary
+    let name = &buf[HEADER_SIZE..total];
+    let name = match name.iter().position(|b| *b == 0) {
+        Some(end) => &name[..end],
+        None => name,
+    };
+
+    Ok((header, name, total))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(name: &[u8], len: usize) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend_from_slice(&1i32.to_ne_bytes());
+        buf.extend_from_slice(&0x100u32.to_ne_bytes());
+        buf.extend_from_slice(&0u32.to_ne_bytes());
+        buf.extend_from_slice(&(len as u32).to_ne_bytes());
+        buf.extend_from_slice(name);
+        buf.resize(HEADER_SIZE + len, 0);
+        buf
+    }
+
+    #[test]
+    fn name_limit_fits_the_longest_name() {
+        assert_eq!(NAME_LIMIT, 256);
+
+        let name = [b'x'; NAME_MAX];
+        let buf = frame(&name, NAME_LIMIT);
+        let (_, parsed, consumed) = next(&buf).unwrap();
+        assert_eq!(parsed, name);
+        assert_eq!(consumed, HEADER_SIZE + NAME_LIMIT);
+
+        let len = NAME_LIMIT + HEADER_SIZE;
+        assert_eq!(
+            next(&frame(&name, len)).err(),
+            Some(ParseError::NameTooLong { len })
+        );
+    }
+}
@@ -0,0 +1,51 @@
+use std::{future::Future, io};
+
+use tokio::task::JoinSet;
+
+use crate::{Event, INotify};
+
+impl INotify {
+    /// Run an async handler for every event with at most `limit` running at once
+    ///
+    /// Handlers are spawned onto the runtime. The first handler error (or
+    /// panic, surfaced as an io error) stops the loop, aborts the handlers
+    /// still running and is returned.
+    pub async fn for_each_concurrent<F, Fut, E>(
+        &mut self,
+        limit: usize,
+        mut handler: F,
+    ) -> Result<(), E>
+    where
+        F: FnMut(Event) -> Fut,
+        Fut: Future<Output = Result<(), E>> + Send + 'static,
+        E: From<io::Error> + Send + 'static,
+    {
+        let limit = limit.max(1);
+        let mut running: JoinSet<Result<(), E>> = JoinSet::new();
+
+        loop {
+            while running.len() >= limit {
+                if let Some(res) = running.join_next().await {
+                    joined(res)?;
+                }
+            }
+
+            // a watch interrupted by a finished handler loses nothing, it is cancel safe
+            tokio::select! {
+                biased;
+
+                Some(res) = running.join_next(), if !running.is_empty() => joined(res)?,
+                event = self.watch() => {
+                    running.spawn(handler(event?));
+                }
+            }
+        }
+    }
+}
+
+fn joined<E: From<io::Error>>(res: Result<Result<(), E>, tokio::task::JoinError>) -> Result<(), E> {
+    match res {
+        Ok(res) => res,
+        Err(err) => Err(io::Error::other(err).into()),
+    }
+}
@@ -0,0 +1,330 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    io,
+    path::{Path, PathBuf},
+};
+
+use crate::{sys, Event, ExcludeSet, INotify, Mask, Watch, WATCH_FLAGS};
+
+/// Events announcing a new entry in a watched directory
+const ARRIVAL: Mask = Mask(Mask::CREATE.0 | Mask::MOVED_TO.0);
+
+/// Watches several project roots, each with its own settings, as one stream
+///
+/// Events come out of [ProjectWatcher::watch] tagged with the root they
+/// belong to. A directory under two roots is watched once and its events
+/// go to every root whose settings accept them.
+#[derive(Default)]
+pub struct ProjectWatcher {
+    roots: HashMap<RootId, Root>,
+    owners: HashMap<Watch, Vec<RootId>>,
+    queued: VecDeque<ProjectEvent>,
+    next: usize,
+}
+
+/// Identifies a root of a [ProjectWatcher]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RootId(usize);
+
+/// How one root of a [ProjectWatcher] is watched
+#[derive(Debug, Clone)]
+pub struct RootSettings {
+    /// Events of interest
+    pub mask: Mask,
+
+    /// Watch every directory beneath the root, including ones created later
+    pub recursive: bool,
+
+    /// Paths beneath the root left unwatched, along with everything beneath them
+    pub exclude: ExcludeSet,
+}
+
+/// An event with the root it was reported for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProjectEvent {
+    /// The root the event belongs to
+    pub root: RootId,
+
+    /// The event itself
+    pub event: Event,
+}
+
+struct Root {
+    path: PathBuf,
+    settings: RootSettings,
+}
+
+impl Default for RootSettings {
+    fn default() -> Self {
+        Self {
+            mask: Mask::INTEREST,
+            recursive: false,
+            exclude: ExcludeSet::new(),
+        }
+    }
+}
+
+impl Root {
+    /// whether a path lies under an excluded path of this root
+    fn excludes(&self, path: &Path) -> bool {
+        path.starts_with(&self.path) && self.settings.exclude.matches_beneath(&self.path, path)
+    }
+}
+
+impl ProjectWatcher {
+    /// Build a watcher without roots
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start watching a root
+    pub async fn add_root(
+        &mut self,
+        inotify: &mut INotify,
+        path: &Path,
+        settings: RootSettings,
+    ) -> io::Result<RootId> {
+        let id = RootId(self.next);
+        self.next += 1;
+
+        self.roots.insert(
+            id,
+            Root {
+                path: path.to_path_buf(),
+                settings,
+            },
+        );
+
+        if let Err(err) = self.cover(inotify, id, path).await {
+            let _ = self.remove_root(inotify, id);
+            return Err(err);
+        }
+
+        Ok(id)
+    }
+
+    /// Stop watching a root, watches still used by other roots are narrowed
+    pub fn remove_root(&mut self, inotify: &mut INotify, id: RootId) -> io::Result<()> {
+        if self.roots.remove(&id).is_none() {
+            return Ok(());
+        }
+        self.queued.retain(|queued| queued.root != id);
+
+        let mut res = Ok(());
+        let watches: Vec<Watch> = self.owners.keys().copied().collect();
+
+        for watch in watches {
+            let owners = self.owners.get_mut(&watch).expect("listed above");
+            let before = owners.len();
+            owners.retain(|owner| *owner != id);
+            if owners.len() == before {
+                continue;
+            }
+
+            let step = match self.interest(watch) {
+                Some(mask) => inotify.set_mask(watch, mask),
+                None => {
+                    self.owners.remove(&watch);
+                    inotify.rm(watch)
+                }
+            };
+
+            match step {
+                // dropped by the kernel already, its IGNORED is queued
+                Err(err) if err.raw_os_error() == Some(sys::EINVAL) => (),
+                Err(err) if res.is_ok() => res = Err(err),
+                _ => (),
+            }
+        }
+
+        res
+    }
+
+    /// the path a root was added with
+    pub fn root(&self, id: RootId) -> Option<&Path> {
+        self.roots.get(&id).map(|root| root.path.as_path())
+    }
+
+    /// Wait for the next event of any root
+    pub async fn watch(&mut self, inotify: &mut INotify) -> io::Result<ProjectEvent> {
+        loop {
+            if let Some(event) = self.queued.pop_front() {
+                return Ok(event);
+            }
+
+            let event = inotify.watch().await?;
+            self.route(inotify, event).await?;
+        }
+    }
+
+    /// Queue an event for every root accepting it, following new directories
+    async fn route(&mut self, inotify: &mut INotify, event: Event) -> io::Result<()> {
+        if event.mask.contains(Mask::Q_OVERFLOW) {
+            let mut ids: Vec<RootId> = self.roots.keys().copied().collect();
+            ids.sort_by_key(|id| id.0);
+            for root in ids {
+                self.queued.push_back(ProjectEvent {
+                    root,
+                    event: event.clone(),
+                });
+            }
+            return Ok(());
+        }
+
+        let owners = if event.mask.contains(Mask::IGNORED) {
+            self.owners.remove(&event.watch).unwrap_or_default()
+        } else {
+            self.owners.get(&event.watch).cloned().unwrap_or_default()
+        };
+
+        let path = inotify.resolve(&event);
+        let created = event.mask.contains(Mask::ISDIR) && (event.mask & ARRIVAL).0 != 0;
+
+        for id in owners {
+            let Some(root) = self.roots.get(&id) else {
+                continue;
+            };
+
+            let control = (event.mask & Mask::CONTROL).0 != 0;
+            if !control && (event.mask & root.settings.mask).0 == 0 {
+                continue;
+            }
+            if path.as_deref().is_some_and(|path| root.excludes(path)) {
+                continue;
+            }
+
+            if created && root.settings.recursive {
+                if let Some(path) = &path {
+                    match self.cover(inotify, id, path).await {
+                        Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+                        _ => (),
+                    }
+                }
+            }
+
+            self.queued.push_back(ProjectEvent {
+                root: id,
+                event: event.clone(),
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Watch `path` (and beneath it when recursive) on behalf of a root
+    ///
+    /// excluded directories are left out of the walk, a directory other
+    /// roots watch already ends up with their masks and this root's alone
+    async fn cover(&mut self, inotify: &mut INotify, id: RootId, path: &Path) -> io::Result<()> {
+        let settings = &self.roots[&id].settings;
+        let (mask, recursive) = (settings.mask | Mask::MASK_ADD, settings.recursive);
+        let exclude = settings.exclude.clone();
+
+        let watches = if recursive {
+            inotify
+                .add_tree_excluding(path, mask, Some(&exclude), None)
+                .await?
+        } else {
+            vec![inotify.add(path, mask)?]
+        };
+
+        for watch in watches {
+            let owners = self.owners.entry(watch).or_default();
+            if !owners.contains(&id) {
+                owners.push(id);
+            }
+
+            // MASK_ADD keeps whatever the directory was watched for before
+            let interest = self.interest(watch).expect("owned by this root");
+            if inotify.mask(watch) != Some(interest) {
+                let flags = inotify.registration(watch).unwrap_or(Mask(0)) & WATCH_FLAGS;
+                inotify.set_mask(watch, interest | flags)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// the events the remaining owners of a watch are interested in
+    fn interest(&self, watch: Watch) -> Option<Mask> {
+        self.owners
+            .get(&watch)?
+            .iter()
+            .filter_map(|id| self.roots.get(id))
+            .map(|root| root.settings.mask)
+            .reduce(|a, b| a | b)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{testing::scratch, Glob};
+
+    fn mask_of(inotify: &INotify, path: &Path) -> Option<Mask> {
+        let (watch, _) = inotify.watches().find(|(_, watched)| *watched == path)?;
+        inotify.mask(watch)
+    }
+
+    #[tokio::test]
+    async fn shared_directories_keep_only_their_owners_masks() {
+        let dir = scratch("project-shared");
+        std::fs::create_dir_all(dir.join("build/out")).unwrap();
+        std::fs::create_dir(dir.join("src")).unwrap();
+
+        let mut inotify = INotify::new().unwrap();
+        let mut project = ProjectWatcher::new();
+
+        let build = RootSettings {
+            mask: Mask::DELETE,
+            recursive: true,
+            ..RootSettings::default()
+        };
+        let build = project
+            .add_root(&mut inotify, &dir.join("build"), build)
+            .await
+            .unwrap();
+
+        let mut exclude = ExcludeSet::new();
+        exclude.add(Glob::new("**/build").unwrap());
+        let top = RootSettings {
+            mask: Mask::CREATE,
+            recursive: true,
+            exclude,
+        };
+        let top = project.add_root(&mut inotify, &dir, top).await.unwrap();
+
+        // the excluded directory is left to the root watching it
+        assert_eq!(mask_of(&inotify, &dir.join("build")), Some(Mask::DELETE));
+        assert_eq!(mask_of(&inotify, &dir.join("build/out")), Some(Mask::DELETE));
+        assert_eq!(mask_of(&inotify, &dir.join("src")), Some(Mask::CREATE));
+
+        let src = RootSettings {
+            mask: Mask::DELETE,
+            ..RootSettings::default()
+        };
+        project
+            .add_root(&mut inotify, &dir.join("src"), src)
+            .await
+            .unwrap();
+        assert_eq!(
+            mask_of(&inotify, &dir.join("src")),
+            Some(Mask::CREATE | Mask::DELETE)
+        );
+
+        project.remove_root(&mut inotify, top).unwrap();
+        assert_eq!(mask_of(&inotify, &dir.join("src")), Some(Mask::DELETE));
+
+        std::fs::write(dir.join("build/out/f"), "").unwrap();
+        std::fs::remove_file(dir.join("build/out/f")).unwrap();
+        let event = tokio::time::timeout(
+            std::time::Duration::from_secs(10),
+            project.watch(&mut inotify),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+        assert_eq!(event.root, build);
+        assert_eq!(event.event.mask, Mask::DELETE);
+    }
+}
@@ -0,0 +1,221 @@
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap, HashSet},
+    ffi::{c_long, CString, OsString},
+    hash::{Hash, Hasher},
+    io,
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use tokio::time::Instant;
+
+use crate::{sys, INotify, Mask, Name, RawEvent, Watch};
+
+/// What [INotify::add] does with a path on a pseudo filesystem
+///
+/// Files in `/proc`, debugfs, securityfs, tracefs and similar filesystems
+/// are generated on demand, the kernel never reports changes to them.
+/// sysfs is not counted among them, its attributes report MODIFY when
+/// their driver calls `sysfs_notify`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PseudoFs {
+    /// Refuse to watch the path with an [io::ErrorKind::Unsupported] error
+    Reject,
+
+    /// Watch the path and poll it at an interval, reporting differences as
+    /// synthetic MODIFY (, or CREATE and DELETE for directories) events
+    Poll(Duration),
+
+    /// Watch the path anyway, the default
+    #[default]
+    Allow,
+}
+
+pub(crate) struct Polled {
+    path: PathBuf,
+    mask: Mask,
+    state: State,
+}
+
+#[derive(PartialEq)]
+enum State {
+    Contents(u64),
+    Entries(HashSet<OsString>),
+}
+
+pub(crate) struct Poller {
+    interval: Duration,
+    next: Instant,
+    watches: HashMap<Watch, Polled>,
+}
+
+const PROC_SUPER_MAGIC: c_long = 0x9fa0;
+const DEBUGFS_MAGIC: c_long = 0x64626720;
+const SECURITYFS_MAGIC: c_long = 0x73636673;
+const TRACEFS_MAGIC: c_long = 0x74726163;
+
+impl INotify {
+    /// choose how paths on pseudo filesystems (`/proc`, debugfs, ...) are handled
+    pub fn pseudo_fs(&mut self, policy: PseudoFs) {
+        self.pseudo = policy;
+        self.publish_admission();
+    }
+
+    /// refuse or start polling a pseudo filesystem path according to the policy
+    pub(crate) fn check_pseudo(&self, path: &Path) -> io::Result<bool> {
+        check(self.pseudo, path)
+    }
+
+    pub(crate) fn poll_pseudo(&mut self, watch: Watch, path: PathBuf, mask: Mask) {
+        let PseudoFs::Poll(interval) = self.pseudo else {
+            return;
+        };
+
+        let poller = self.poller.get_or_insert_with(|| Poller {
+            interval,
+            next: Instant::now() + interval,
+            watches: HashMap::new(),
+        });
+
+        let state = State::of(&path);
+        poller.watches.insert(watch, Polled { path, mask, state });
+    }
+}
+
+impl Poller {
+    pub(crate) fn deadline(&self) -> Option<Instant> {
+        (!self.watches.is_empty()).then_some(self.next)
+    }
+
+    pub(crate) fn forget(&mut self, watch: Watch) {
+        self.watches.remove(&watch);
+    }
+
+    /// Compare every polled path against its last state
+    pub(crate) async fn poll(&mut self) -> io::Result<Vec<RawEvent>> {
+        let targets: Vec<(Watch, PathBuf)> = self
+            .watches
+            .iter()
+            .map(|(watch, polled)| (*watch, polled.path.clone()))
+            .collect();
+
+        let states = tokio::task::spawn_blocking(move || {
+            targets
+                .into_iter()
+                .map(|(watch, path)| (watch, State::of(&path)))
+                .collect::<Vec<_>>()
+        })
+        .await
+        .map_err(io::Error::other)?;
+
+        let mut events = Vec::new();
+        for (watch, state) in states {
+            let Some(polled) = self.watches.get_mut(&watch) else {
+                continue;
+            };
+
+            polled.diff(watch, &state, &mut events);
+            polled.state = state;
+        }
+
+        self.next = Instant::now() + self.interval;
+        Ok(events)
+    }
+}
+
+impl Polled {
+    fn diff(&self, watch: Watch, state: &State, events: &mut Vec<RawEvent>) {
+        let mut push = |mask: Mask, name: &[u8]| {
+            if self.mask.contains(mask) {
+                events.push(RawEvent {
+                    watch,
+                    mask,
+                    cookie: 0,
+                    name: Name::new(name),
+                });
+            }
+        };
+
+        match (&self.state, state) {
+            (State::Entries(old), State::Entries(new)) => {
+                for name in new.difference(old) {
+                    push(Mask::CREATE, name.as_bytes());
+                }
+                for name in old.difference(new) {
+                    push(Mask::DELETE, name.as_bytes());
+                }
+            }
+            (old, new) if old != new => push(Mask::MODIFY, b""),
+            _ => (),
+        }
+    }
+}
+
+impl State {
+    fn of(path: &Path) -> State {
+        if let Ok(entries) = std::fs::read_dir(path) {
+            let names = entries.flatten().map(|e| e.file_name()).collect();
+            return State::Entries(names);
+        }
+
+        let mut hasher = DefaultHasher::new();
+        match std::fs::read(path) {
+            Ok(contents) => contents.hash(&mut hasher),
+            Err(err) => err.kind().hash(&mut hasher),
+        }
+
+        State::Contents(hasher.finish())
+    }
+}
+
+/// refuse a pseudo filesystem path or tell whether to poll it, by `policy`
+pub(crate) fn check(policy: PseudoFs, path: &Path) -> io::Result<bool> {
+    if policy == PseudoFs::Allow || !is_pseudo(path)? {
+        return Ok(false);
+    }
+
+    match policy {
+        PseudoFs::Reject => Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!(
+                "{} is on a pseudo filesystem which does not report changes",
+                path.display()
+            ),
+        )),
+        _ => Ok(true),
+    }
+}
+
+/// The path lives on a filesystem which never generates inotify events
+pub(crate) fn is_pseudo(path: &Path) -> io::Result<bool> {
+    let path = CString::new(path.as_os_str().as_bytes().to_vec())?;
+
+    // larger than struct statfs, which begins with f_type
+    let mut buf = [0 as c_long; 32];
+    let res = unsafe { sys::statfs(path.as_ptr(), buf.as_mut_ptr().cast()) };
+    if res == -1 {
+        return Err(io::Error::last_os_error());
+    }
+
+    let f_type = unsafe { buf.as_ptr().cast::<sys::FsType>().read() };
+    Ok(matches!(
+        f_type as c_long,
+        PROC_SUPER_MAGIC | DEBUGFS_MAGIC | SECURITYFS_MAGIC | TRACEFS_MAGIC
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn knows_pseudo_filesystems() {
+        assert!(is_pseudo(Path::new("/proc/self")).unwrap());
+        assert!(!is_pseudo(&std::env::temp_dir()).unwrap());
+
+        let err = check(PseudoFs::Reject, Path::new("/proc")).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::Unsupported);
+        assert!(!check(PseudoFs::Allow, Path::new("/proc")).unwrap());
+    }
+}
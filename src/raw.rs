@@ -0,0 +1,208 @@
+use std::{
+    ffi::{c_int, CString, OsStr},
+    io,
+    os::{
+        fd::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd, RawFd},
+        unix::ffi::OsStrExt,
+    },
+    path::Path,
+};
+
+use crate::{inotify_add_watch, inotify_init1, inotify_rm_watch, parse, sys};
+use crate::{Mask, Name, ParseError, RawEvent, Watch, READ_SIZE};
+
+/// The runtime independent core of an inotify instance
+///
+/// Owns the fd and issues the syscalls, but never waits: how to wait for
+/// the fd to become readable is up to the adapter built on top, either
+/// [crate::INotify] (tokio), an executor agnostic adapter or plain
+/// blocking reads. Read bytes are turned into events with [Events].
+#[derive(Debug)]
+pub struct RawINotify {
+    fd: OwnedFd,
+}
+
+/// Events parsed out of bytes read from an inotify fd
+///
+/// Parsing stops at the first malformed event, the framing of anything
+/// after it can not be trusted.
+pub struct Events<'a> {
+    buf: &'a [u8],
+}
+
+impl RawINotify {
+    /// Build an instance whose reads block
+    pub fn new() -> io::Result<Self> {
+        Self::with_flags(sys::IN_CLOEXEC)
+    }
+
+    /// Build an instance whose reads fail with [io::ErrorKind::WouldBlock]
+    /// rather than waiting, for use with a readiness based event loop
+    pub fn nonblocking() -> io::Result<Self> {
+        Self::with_flags(sys::IN_CLOEXEC | sys::IN_NONBLOCK)
+    }
+
+    fn with_flags(flags: c_int) -> io::Result<Self> {
+        let fd = unsafe { inotify_init1(flags) };
+        if fd == -1 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(Self {
+            fd: unsafe { OwnedFd::from_raw_fd(fd) },
+        })
+    }
+
+    /// Add a file (, or directory) to be watched
+    pub fn add(&self, path: &Path, mask: Mask) -> io::Result<Watch> {
+        add_watch(self.fd.as_raw_fd(), path, mask)
+    }
+
+    /// Remove a watch
+    pub fn rm(&self, watch: Watch) -> io::Result<()> {
+        rm_watch(self.fd.as_raw_fd(), watch)
+    }
+
+    /// Bytes of events queued in the kernel
+    pub fn pending(&self) -> io::Result<usize> {
+        pending(self.fd.as_raw_fd())
+    }
+
+    /// Read queued events into `buf`, growing it as needed
+    ///
+    /// Returns the number of bytes read, parse them with [Events::new].
+    pub fn read(&self, buf: &mut Vec<u8>) -> io::Result<usize> {
+        let mut want = self.pending()?.max(READ_SIZE).max(buf.len());
+
+        loop {
+            if buf.len() < want {
+                buf.resize(want, 0);
+            }
+
+            let res = unsafe { sys::read(self.fd.as_raw_fd(), buf.as_mut_ptr().cast(), want) };
+            if res >= 0 {
+                return Ok(res as usize);
+            }
+
+            let err = io::Error::last_os_error();
+            match err.raw_os_error() {
+                // the kernel refuses reads too small for the next event
+                Some(sys::EINVAL) => want *= 2,
+                _ => return Err(err),
+            }
+        }
+    }
+}
+
+impl<'a> Events<'a> {
+    /// Parse the events in `buf`
+    pub fn new(buf: &'a [u8]) -> Self {
+        Self { buf }
+    }
+
+    /// Bytes not parsed yet
+    pub fn remaining(&self) -> usize {
+        self.buf.len()
+    }
+}
+
+impl Iterator for Events<'_> {
+    type Item = Result<RawEvent, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.buf.is_empty() {
+            return None;
+        }
+
+        match parse::next(self.buf) {
+            Ok((header, name, consumed)) => {
+                self.buf = &self.buf[consumed..];
+
+                Some(Ok(RawEvent {
+                    watch: Watch { wd: header.wd },
+                    mask: Mask(header.mask),
+                    cookie: header.cookie,
+                    name: Name::new(name),
+                }))
+            }
+            Err(err) => {
+                self.buf = &[];
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+impl AsRawFd for RawINotify {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd.as_raw_fd()
+    }
+}
+
+impl AsFd for RawINotify {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.fd.as_fd()
+    }
+}
+
+impl From<RawINotify> for OwnedFd {
+    fn from(raw: RawINotify) -> OwnedFd {
+        raw.fd
+    }
+}
+
+#[cfg(feature = "mio")]
+impl mio::event::Source for RawINotify {
+    fn register(
+        &mut self,
+        registry: &mio::Registry,
+        token: mio::Token,
+        interests: mio::Interest,
+    ) -> io::Result<()> {
+        mio::unix::SourceFd(&self.fd.as_raw_fd()).register(registry, token, interests)
+    }
+
+    fn reregister(
+        &mut self,
+        registry: &mio::Registry,
+        token: mio::Token,
+        interests: mio::Interest,
+    ) -> io::Result<()> {
+        mio::unix::SourceFd(&self.fd.as_raw_fd()).reregister(registry, token, interests)
+    }
+
+    fn deregister(&mut self, registry: &mio::Registry) -> io::Result<()> {
+        mio::unix::SourceFd(&self.fd.as_raw_fd()).deregister(registry)
+    }
+}
+
+pub(crate) fn add_watch(fd: c_int, path: &Path, mask: Mask) -> io::Result<Watch> {
+    let os: &OsStr = path.as_ref();
+    let cpath = CString::new(os.as_bytes())?;
+    let res = unsafe { inotify_add_watch(fd, cpath.as_ptr().cast(), mask.0) };
+    if res == -1 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(Watch { wd: res })
+}
+
+pub(crate) fn rm_watch(fd: c_int, watch: Watch) -> io::Result<()> {
+    let res = unsafe { inotify_rm_watch(fd, watch.wd) };
+    if res == -1 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(())
+}
+
+/// bytes of events queued in the kernel (FIONREAD)
+pub(crate) fn pending(fd: c_int) -> io::Result<usize> {
+    let mut pending: c_int = 0;
+    let res = unsafe { sys::ioctl(fd, sys::FIONREAD, &mut pending) };
+    if res == -1 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(pending as usize)
+}
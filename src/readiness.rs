@@ -0,0 +1,185 @@
+use std::{
+    io,
+    os::fd::{AsFd, AsRawFd, BorrowedFd, RawFd},
+};
+
+use crate::{sys, INotify, RawEvent, READ_SIZE};
+
+/// Whether events can be taken without blocking
+///
+/// The inotify fd polls readable, level triggered, for as long as the
+/// kernel queue is not empty. Events already read into the internal buffer
+/// no longer make it readable, so with an edge triggered registration
+/// (mio, `EPOLLET`) [INotify::try_watch_raw] must be called until it
+/// returns `None` before waiting again.
+///
+/// Driving the fd from another loop and [INotify::watch] do not mix, an
+/// interrupted `watch` may still have a read in flight.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Readiness {
+    buffered: bool,
+    pending: usize,
+}
+
+impl Readiness {
+    /// An event can be taken without blocking
+    pub fn is_ready(&self) -> bool {
+        self.buffered || self.pending > 0
+    }
+
+    /// Events were read from the kernel but not yet taken
+    pub fn buffered(&self) -> bool {
+        self.buffered
+    }
+
+    /// Bytes of events queued in the kernel
+    pub fn pending(&self) -> usize {
+        self.pending
+    }
+}
+
+impl INotify {
+    /// check whether an event can be taken without blocking
+    pub fn readiness(&self) -> io::Result<Readiness> {
+        Ok(Readiness {
+            buffered: self.buffered(),
+            pending: self.pending()?,
+        })
+    }
+
+    /// take the next event if one is available, never blocking
+    ///
+    /// meant for loops other than tokio, see [Readiness]
+    pub fn try_watch_raw(&mut self) -> io::Result<Option<RawEvent>> {
+        loop {
+            if !self.buffered() {
+                let pending = self.pending()?;
+                if pending == 0 {
+                    return Ok(None);
+                }
+
+                self.read_now(pending)?;
+            }
+
+            if let Some((event, _, _)) = self.take()? {
+                return Ok(Some(event));
+            }
+        }
+    }
+
+    /// read queued events, which must be pending so the read does not block
+    fn read_now(&mut self, pending: usize) -> io::Result<()> {
+        let want = pending.max(READ_SIZE);
+        if self.buf.len() < want {
+            self.buf.resize(want, 0);
+        }
+
+        let res = unsafe { sys::read(self.fd, self.buf.as_mut_ptr().cast(), want) };
+        if res < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        self.pos = 0;
+        self.end = res as usize;
+        Ok(())
+    }
+}
+
+impl AsRawFd for INotify {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd
+    }
+}
+
+impl AsFd for INotify {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        unsafe { BorrowedFd::borrow_raw(self.fd) }
+    }
+}
+
+#[cfg(feature = "mio")]
+impl mio::event::Source for INotify {
+    fn register(
+        &mut self,
+        registry: &mio::Registry,
+        token: mio::Token,
+        interests: mio::Interest,
+    ) -> io::Result<()> {
+        mio::unix::SourceFd(&self.fd).register(registry, token, interests)
+    }
+
+    fn reregister(
+        &mut self,
+        registry: &mio::Registry,
+        token: mio::Token,
+        interests: mio::Interest,
+    ) -> io::Result<()> {
+        mio::unix::SourceFd(&self.fd).reregister(registry, token, interests)
+    }
+
+    fn deregister(&mut self, registry: &mio::Registry) -> io::Result<()> {
+        mio::unix::SourceFd(&self.fd).deregister(registry)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{testing::scratch, Mask};
+    use std::path::Path;
+
+    #[test]
+    fn ready_until_every_event_is_taken() {
+        let dir = scratch("readiness");
+        let mut inotify = INotify::new().unwrap();
+        inotify.add(&dir, Mask::CREATE).unwrap();
+        assert!(!inotify.readiness().unwrap().is_ready());
+
+        std::fs::File::create(dir.join("a")).unwrap();
+        std::fs::File::create(dir.join("b")).unwrap();
+        let readiness = inotify.readiness().unwrap();
+        assert!(readiness.is_ready() && !readiness.buffered());
+        assert!(readiness.pending() > 0);
+
+        let event = inotify.try_watch_raw().unwrap().unwrap();
+        assert_eq!(event.name.as_path(), Path::new("a"));
+
+        // both were read at once, the second waits in the buffer
+        let readiness = inotify.readiness().unwrap();
+        assert!(readiness.is_ready() && readiness.buffered());
+        assert_eq!(readiness.pending(), 0);
+
+        let event = inotify.try_watch_raw().unwrap().unwrap();
+        assert_eq!(event.name.as_path(), Path::new("b"));
+        assert!(inotify.try_watch_raw().unwrap().is_none());
+        assert!(!inotify.readiness().unwrap().is_ready());
+    }
+
+    #[cfg(feature = "mio")]
+    #[test]
+    fn mio_polls_readable_while_events_are_queued() {
+        use std::time::Duration;
+
+        let dir = scratch("readiness-mio");
+        let mut inotify = INotify::new().unwrap();
+        inotify.add(&dir, Mask::CREATE).unwrap();
+
+        let mut poll = mio::Poll::new().unwrap();
+        let mut events = mio::Events::with_capacity(4);
+        poll.registry()
+            .register(&mut inotify, mio::Token(7), mio::Interest::READABLE)
+            .unwrap();
+
+        poll.poll(&mut events, Some(Duration::ZERO)).unwrap();
+        assert!(events.is_empty());
+
+        std::fs::File::create(dir.join("f")).unwrap();
+        poll.poll(&mut events, Some(Duration::from_secs(10))).unwrap();
+        let event = events.iter().next().unwrap();
+        assert_eq!(event.token(), mio::Token(7));
+        assert!(event.is_readable());
+
+        while inotify.try_watch_raw().unwrap().is_some() {}
+        poll.registry().deregister(&mut inotify).unwrap();
+    }
+}
@@ -0,0 +1,143 @@
+use std::{
+    collections::HashMap,
+    io,
+    path::{Path, PathBuf},
+};
+
+use crate::{canonicalize, sys, INotify, Mask, Watch};
+
+/// What [INotify::reconcile] changed to reach the desired watch set
+#[derive(Debug, Default)]
+pub struct Reconciled {
+    /// Paths that were not watched yet
+    pub added: Vec<(PathBuf, Watch)>,
+
+    /// Watches no longer desired
+    pub removed: Vec<(PathBuf, Watch)>,
+
+    /// Watches whose events of interest were replaced
+    pub updated: Vec<(PathBuf, Watch)>,
+
+    /// Changes that could not be made, the rest still were
+    pub failed: Vec<(PathBuf, io::Error)>,
+}
+
+impl Reconciled {
+    /// whether the watch set already matched
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.removed.is_empty()
+            && self.updated.is_empty()
+            && self.failed.is_empty()
+    }
+}
+
+impl INotify {
+    /// Make the watch set match `desired`, touching only what differs
+    ///
+    /// Watches are matched by the path they were added with, a path listed
+    /// twice takes its last mask. Watches are removed before any is added so
+    /// the watch limit is not hit on the way.
+    pub fn reconcile(&mut self, desired: &[(PathBuf, Mask)]) -> Reconciled {
+        let mut report = Reconciled::default();
+        let mut wanted: HashMap<PathBuf, Mask> = HashMap::new();
+
+        for (path, mask) in desired {
+            match self.recorded(path, *mask) {
+                Ok(path) => {
+                    wanted.insert(path, *mask);
+                }
+                Err(err) => report.failed.push((path.clone(), err)),
+            }
+        }
+
+        let current: Vec<(Watch, PathBuf)> = self
+            .paths
+            .iter()
+            .map(|(watch, path)| (*watch, path.to_path_buf()))
+            .collect();
+
+        for (watch, path) in current {
+            let Some(mask) = wanted.remove(&path) else {
+                match self.rm(watch) {
+                    Ok(()) => report.removed.push((path, watch)),
+                    // dropped by the kernel already, its IGNORED is queued
+                    Err(err) if err.raw_os_error() == Some(sys::EINVAL) => {
+                        report.removed.push((path, watch))
+                    }
+                    Err(err) => report.failed.push((path, err)),
+                }
+                continue;
+            };
+
+            let interest = Mask(mask.0 & Mask::INTEREST.0);
+            if self.mask(watch) == Some(interest) {
+                continue;
+            }
+
+            match self.set_mask(watch, mask) {
+                Ok(()) => report.updated.push((path, watch)),
+                Err(err) => report.failed.push((path, err)),
+            }
+        }
+
+        for (path, mask) in wanted {
+            match self.add(&path, mask) {
+                Ok(watch) => report.added.push((path, watch)),
+                Err(err) => report.failed.push((path, err)),
+            }
+        }
+
+        report
+    }
+
+    /// the path a watch would be recorded under
+    fn recorded(&self, path: &Path, mask: Mask) -> io::Result<PathBuf> {
+        if self.canonical {
+            canonicalize(path, mask.contains(Mask::DONT_FOLLOW))
+        } else {
+            Ok(path.to_path_buf())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{kernel_watches, scratch};
+    use std::os::fd::AsRawFd;
+
+    #[tokio::test]
+    async fn only_the_difference_is_applied() {
+        let dir = scratch("reconcile");
+        for name in ["a", "b", "c"] {
+            std::fs::create_dir(dir.join(name)).unwrap();
+        }
+
+        let mut inotify = INotify::new().unwrap();
+        let a = inotify.add(&dir.join("a"), Mask::CREATE).unwrap();
+        let b = inotify.add(&dir.join("b"), Mask::CREATE).unwrap();
+
+        let desired = vec![
+            (dir.join("b"), Mask::DELETE),
+            (dir.join("c"), Mask::CREATE),
+            (dir.join("missing"), Mask::CREATE),
+        ];
+        let report = inotify.reconcile(&desired);
+        assert_eq!(report.removed, vec![(dir.join("a"), a)]);
+        assert_eq!(report.updated, vec![(dir.join("b"), b)]);
+        assert_eq!(report.added.len(), 1);
+        assert_eq!(report.added[0].0, dir.join("c"));
+        assert_eq!(report.failed.len(), 1);
+        assert_eq!(report.failed[0].0, dir.join("missing"));
+        assert_eq!(report.failed[0].1.kind(), io::ErrorKind::NotFound);
+
+        assert_eq!(inotify.mask(b), Some(Mask::DELETE));
+        assert_eq!(kernel_watches(inotify.as_raw_fd()), 2);
+
+        // already in line, nothing is touched again
+        let report = inotify.reconcile(&desired[..2]);
+        assert!(report.is_empty());
+        assert_eq!(kernel_watches(inotify.as_raw_fd()), 2);
+    }
+}
@@ -0,0 +1,357 @@
+use std::{
+    future::Future,
+    io,
+    os::unix::ffi::OsStrExt,
+    path::Path,
+    pin::Pin,
+    task::{Context, Poll},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use tokio::io::{AsyncRead, ReadBuf};
+
+use crate::{Event, INotify, Mask};
+
+/// How events are serialized by [INotify::into_async_read]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// One JSON object per line
+    ///
+    /// `{"watch":1,"mask":256,"events":["CREATE"],"cookie":0,"path":"a"}`,
+    /// paths which are not UTF-8 are converted lossily.
+    Jsonl,
+
+    /// A little endian `u32` byte length followed by the record
+    ///
+    /// The record is the fixed width, little endian bincode encoding of
+    /// `(i32, u32, u32, Vec<u8>)`: watch, mask, cookie and the raw path
+    /// bytes. That is the layout of bincode 1's `bincode::deserialize` and
+    /// bincode 2's `config::legacy()`, not of bincode 1's `DefaultOptions`
+    /// or bincode 2's `config::standard()` which encode integers as varints.
+    LengthPrefixed,
+
+    /// One JSON object per line as printed by inotify-tools
+    ///
+    /// `inotifywait -m --format '{"watched":"%w","events":"%e","file":"%f","cookie":%c}'`,
+    /// the watched path is empty for the IGNORED ending a watch.
+    InotifyTools,
+
+    /// One S3 event notification per line, for pipelines fed by object storage
+    ///
+    /// The watched path stands in for the bucket and the path beneath it for
+    /// the key. CLOSE_WRITE is `ObjectCreated:Put`, MOVED_TO is
+    /// `ObjectCreated:Copy` and DELETE or MOVED_FROM is
+    /// `ObjectRemoved:Delete`. Other events and directories, which object
+    /// storage has none of, make no record. The `eventSource` is
+    /// `tokinotify` and sizes are left out.
+    S3,
+}
+
+type Pending = Pin<Box<dyn Future<Output = (INotify, io::Result<Event>)> + Send>>;
+
+/// Events serialized as an [AsyncRead], see [INotify::into_async_read]
+///
+/// Reads only fail when watching does, an error is returned once and
+/// reading may continue after it.
+pub struct EventReader {
+    pending: Pending,
+    format: Format,
+    out: Vec<u8>,
+    pos: usize,
+}
+
+impl INotify {
+    /// serialize events into a byte stream, e.g. for `tokio::io::copy`
+    pub fn into_async_read(self, format: Format) -> EventReader {
+        EventReader {
+            pending: next(self),
+            format,
+            out: Vec::new(),
+            pos: 0,
+        }
+    }
+}
+
+fn next(mut inotify: INotify) -> Pending {
+    Box::pin(async move {
+        let event = inotify.watch().await;
+        (inotify, event)
+    })
+}
+
+impl AsyncRead for EventReader {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = &mut *self;
+
+        // formats may make no record for an event, an empty read would be EOF
+        while this.pos >= this.out.len() {
+            let (inotify, event) = match this.pending.as_mut().poll(cx) {
+                Poll::Ready(ready) => ready,
+                Poll::Pending => return Poll::Pending,
+            };
+            this.pending = next(inotify);
+
+            this.out.clear();
+            this.pos = 0;
+            this.format.encode(&event?, &mut this.out);
+        }
+
+        let n = buf.remaining().min(this.out.len() - this.pos);
+        buf.put_slice(&this.out[this.pos..this.pos + n]);
+        this.pos += n;
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl Format {
+    /// append the record for an event to `out`, some formats make none for some events
+    pub fn encode(self, event: &Event, out: &mut Vec<u8>) {
+        self.encode_at(event, out, SystemTime::now());
+    }
+
+    fn encode_at(self, event: &Event, out: &mut Vec<u8>, now: SystemTime) {
+        match self {
+            Format::Jsonl => {
+                out.extend_from_slice(b"{\"watch\":");
+                out.extend_from_slice(event.watch.wd.to_string().as_bytes());
+                out.extend_from_slice(b",\"mask\":");
+                out.extend_from_slice(event.mask.0.to_string().as_bytes());
+                out.extend_from_slice(b",\"events\":[");
+                for (i, name) in event.mask.names().enumerate() {
+                    if i > 0 {
+                        out.push(b',');
+                    }
+                    json_str(name, out);
+                }
+                out.extend_from_slice(b"],\"cookie\":");
+                out.extend_from_slice(event.cookie.to_string().as_bytes());
+                out.extend_from_slice(b",\"path\":");
+                json_str(&event.path.to_string_lossy(), out);
+                out.extend_from_slice(b"}\n");
+            }
+
+            Format::LengthPrefixed => {
+                let path = event.path.as_os_str().as_bytes();
+
+                let len = 4 + 4 + 4 + 8 + path.len();
+                out.extend_from_slice(&(len as u32).to_le_bytes());
+                out.extend_from_slice(&event.watch.wd.to_le_bytes());
+                out.extend_from_slice(&event.mask.0.to_le_bytes());
+                out.extend_from_slice(&event.cookie.to_le_bytes());
+                out.extend_from_slice(&(path.len() as u64).to_le_bytes());
+                out.extend_from_slice(path);
+            }
+
+            Format::InotifyTools => {
+                let names: Vec<&str> = event.mask.names().collect();
+                let watched = event.root().unwrap_or(Path::new(""));
+
+                out.extend_from_slice(b"{\"watched\":");
+                json_str(&watched.to_string_lossy(), out);
+                out.extend_from_slice(b",\"events\":");
+                json_str(&names.join(","), out);
+                out.extend_from_slice(b",\"file\":");
+                json_str(&event.path.to_string_lossy(), out);
+                out.extend_from_slice(b",\"cookie\":");
+                out.extend_from_slice(event.cookie.to_string().as_bytes());
+                out.extend_from_slice(b"}\n");
+            }
+
+            Format::S3 => {
+                let Some(name) = s3_event_name(event.mask) else {
+                    return;
+                };
+                let bucket = event.root().unwrap_or(Path::new(""));
+                let now = now.duration_since(UNIX_EPOCH).unwrap_or_default();
+
+                out.extend_from_slice(b"{\"Records\":[{\"eventVersion\":\"2.1\",");
+                out.extend_from_slice(b"\"eventSource\":\"tokinotify\",\"eventTime\":");
+                json_str(&timestamp(now), out);
+                out.extend_from_slice(b",\"eventName\":");
+                json_str(name, out);
+                out.extend_from_slice(b",\"s3\":{\"s3SchemaVersion\":\"1.0\",\"bucket\":{\"name\":");
+                json_str(&bucket.to_string_lossy(), out);
+                out.extend_from_slice(b"},\"object\":{\"key\":");
+                let key = event.path.strip_prefix(bucket).unwrap_or(&event.path);
+                json_str(&key.to_string_lossy(), out);
+                out.extend_from_slice(b",\"sequencer\":");
+                json_str(&format!("{:016X}", now.as_nanos() as u64), out);
+                out.extend_from_slice(b"}}}]}\n");
+            }
+        }
+    }
+}
+
+fn s3_event_name(mask: Mask) -> Option<&'static str> {
+    if mask.contains(Mask::ISDIR) {
+        None
+    } else if mask.contains(Mask::CLOSE_WRITE) {
+        Some("ObjectCreated:Put")
+    } else if mask.contains(Mask::MOVED_TO) {
+        Some("ObjectCreated:Copy")
+    } else if (mask & (Mask::DELETE | Mask::MOVED_FROM)).0 != 0 {
+        Some("ObjectRemoved:Delete")
+    } else {
+        None
+    }
+}
+
+/// ISO 8601 in UTC with milliseconds, as S3 reports `eventTime` and syslog stamps messages
+pub(crate) fn timestamp(since_epoch: Duration) -> String {
+    let secs = since_epoch.as_secs();
+    let (days, rem) = ((secs / 86400) as i64, secs % 86400);
+
+    // days to a civil date, after Howard Hinnant's `civil_from_days`
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}.{:03}Z",
+        rem / 3600,
+        rem / 60 % 60,
+        rem % 60,
+        since_epoch.subsec_millis()
+    )
+}
+
+fn json_str(s: &str, out: &mut Vec<u8>) {
+    out.push(b'"');
+    for c in s.chars() {
+        match c {
+            '"' => out.extend_from_slice(b"\\\""),
+            '\\' => out.extend_from_slice(b"\\\\"),
+            '\n' => out.extend_from_slice(b"\\n"),
+            '\r' => out.extend_from_slice(b"\\r"),
+            '\t' => out.extend_from_slice(b"\\t"),
+            c if (c as u32) < 0x20 => {
+                out.extend_from_slice(format!("\\u{:04x}", c as u32).as_bytes());
+            }
+            c => {
+                let mut utf8 = [0; 4];
+                out.extend_from_slice(c.encode_utf8(&mut utf8).as_bytes());
+            }
+        }
+    }
+    out.push(b'"');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Watch;
+
+    fn encode(format: Format, event: &Event) -> Vec<u8> {
+        let mut out = Vec::new();
+        let now = UNIX_EPOCH + Duration::from_millis(1_700_000_000_123);
+        format.encode_at(event, &mut out, now);
+        out
+    }
+
+    fn rooted(mask: Mask, path: &str) -> Event {
+        Event::builder(Watch::from_raw(3), mask)
+            .path(path)
+            .root("/data")
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn jsonl() {
+        let event = Event::builder(Watch::from_raw(3), Mask::MOVED_TO | Mask::ISDIR)
+            .cookie(7)
+            .path("/data/a \"b\"\n")
+            .build()
+            .unwrap();
+
+        assert_eq!(
+            String::from_utf8(encode(Format::Jsonl, &event)).unwrap(),
+            "{\"watch\":3,\"mask\":1073741952,\"events\":[\"MOVED_TO\",\"ISDIR\"],\
+             \"cookie\":7,\"path\":\"/data/a \\\"b\\\"\\n\"}\n"
+        );
+    }
+
+    #[test]
+    fn length_prefixed() {
+        let event = Event::builder(Watch::from_raw(3), Mask::MOVED_FROM)
+            .cookie(7)
+            .path("/a")
+            .build()
+            .unwrap();
+
+        #[rustfmt::skip]
+        let expected: &[u8] = &[
+            22, 0, 0, 0, // record length
+            3, 0, 0, 0, // watch
+            0x40, 0, 0, 0, // mask
+            7, 0, 0, 0, // cookie
+            2, 0, 0, 0, 0, 0, 0, 0, // path length, a u64 as bincode writes it
+            b'/', b'a',
+        ];
+        assert_eq!(encode(Format::LengthPrefixed, &event), expected);
+    }
+
+    #[test]
+    fn inotify_tools() {
+        let event = rooted(Mask::CREATE | Mask::ISDIR, "/data/new");
+        assert_eq!(
+            String::from_utf8(encode(Format::InotifyTools, &event)).unwrap(),
+            "{\"watched\":\"/data\",\"events\":\"CREATE,ISDIR\",\"file\":\"/data/new\",\"cookie\":0}\n"
+        );
+
+        let event = Event::new(Watch::from_raw(3), Mask::IGNORED, "");
+        assert_eq!(
+            String::from_utf8(encode(Format::InotifyTools, &event)).unwrap(),
+            "{\"watched\":\"\",\"events\":\"IGNORED\",\"file\":\"\",\"cookie\":0}\n"
+        );
+    }
+
+    #[test]
+    fn s3() {
+        let event = rooted(Mask::CLOSE_WRITE, "/data/dir/key");
+        assert_eq!(
+            String::from_utf8(encode(Format::S3, &event)).unwrap(),
+            "{\"Records\":[{\"eventVersion\":\"2.1\",\"eventSource\":\"tokinotify\",\
+             \"eventTime\":\"2023-11-14T22:13:20.123Z\",\"eventName\":\"ObjectCreated:Put\",\
+             \"s3\":{\"s3SchemaVersion\":\"1.0\",\"bucket\":{\"name\":\"/data\"},\
+             \"object\":{\"key\":\"dir/key\",\"sequencer\":\"17979CFE3D7ED4C0\"}}}]}\n"
+        );
+
+        for (mask, name) in [
+            (Mask::MOVED_TO, Some("ObjectCreated:Copy")),
+            (Mask::DELETE, Some("ObjectRemoved:Delete")),
+            (Mask::MOVED_FROM, Some("ObjectRemoved:Delete")),
+            (Mask::MODIFY, None),
+            (Mask::CLOSE_WRITE | Mask::ISDIR, None),
+        ] {
+            assert_eq!(s3_event_name(mask), name, "{mask:?}");
+        }
+
+        let event = rooted(Mask::CREATE | Mask::ISDIR, "/data/dir");
+        assert!(encode(Format::S3, &event).is_empty());
+    }
+
+    #[test]
+    fn timestamps() {
+        assert_eq!(timestamp(Duration::ZERO), "1970-01-01T00:00:00.000Z");
+        assert_eq!(
+            timestamp(Duration::from_millis(951_782_400_999)),
+            "2000-02-29T00:00:00.999Z"
+        );
+        assert_eq!(
+            timestamp(Duration::from_secs(4_102_444_799)),
+            "2099-12-31T23:59:59.000Z"
+        );
+    }
+}
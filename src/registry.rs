@@ -0,0 +1,142 @@
+use std::{any::Any, path::PathBuf};
+
+use crate::{INotify, Removal, Watch};
+
+/// A user supplied value attached to a watch
+pub type Tag = Box<dyn Any + Send + Sync>;
+
+pub(crate) type ReleaseHook = Box<dyn FnMut(Released) + Send + Sync>;
+
+/// The state released when the kernel drops a watch (IGNORED)
+#[derive(Debug)]
+pub struct Released {
+    /// The watch that was dropped
+    pub watch: Watch,
+
+    /// Why the watch was dropped
+    pub reason: Removal,
+
+    /// The path the watch was added with
+    pub path: Option<PathBuf>,
+
+    /// The tag attached to the watch
+    pub tag: Option<Tag>,
+}
+
+impl INotify {
+    /// Attach a value to a watch, replacing any previous tag
+    ///
+    /// Tags are released with the rest of the watch's state on IGNORED.
+    pub fn set_tag<T: Any + Send + Sync>(&mut self, watch: Watch, tag: T) {
+        self.tags.insert(watch, Box::new(tag));
+    }
+
+    /// The value attached to a watch
+    pub fn tag<T: Any>(&self, watch: Watch) -> Option<&T> {
+        self.tags.get(&watch)?.downcast_ref()
+    }
+
+    /// Mutably access the value attached to a watch
+    pub fn tag_mut<T: Any>(&mut self, watch: Watch) -> Option<&mut T> {
+        self.tags.get_mut(&watch)?.downcast_mut()
+    }
+
+    /// Observe watch state being released after the kernel sends IGNORED
+    pub fn on_released(&mut self, hook: impl FnMut(Released) + Send + Sync + 'static) {
+        self.release_hook = Some(Box::new(hook));
+    }
+
+    /// Drop every piece of state kept for a watch
+    pub(crate) fn forget(&mut self, watch: Watch, reason: Removal) -> Released {
+        self.refund(watch);
+        self.links.remove(&watch);
+        self.masks.remove(&watch);
+        self.epochs.remove(&watch);
+        if let Some(stats) = &mut self.stats {
+            stats.remove(&watch);
+        }
+        if let Some(identities) = &mut self.identities {
+            identities.remove(&watch);
+        }
+        if let Some(poller) = &mut self.poller {
+            poller.forget(watch);
+        }
+
+        Released {
+            watch,
+            reason,
+            path: self.paths.remove(&watch).map(|path| path.to_path_buf()),
+            tag: self.tags.remove(&watch),
+        }
+    }
+
+    pub(crate) fn release(&mut self, watch: Watch, reason: Removal) {
+        // explicitly removed watches were already forgotten by rm
+        if !self.paths.contains_key(&watch) {
+            return;
+        }
+
+        let released = self.forget(watch, reason);
+
+        if let Some(hook) = &mut self.release_hook {
+            hook(released);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{testing::scratch, Mask};
+    use std::{
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    async fn next(inotify: &mut INotify) -> crate::Event {
+        tokio::time::timeout(Duration::from_secs(10), inotify.watch())
+            .await
+            .unwrap()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn released_once_with_tag_and_path() {
+        let dir = scratch("registry");
+        std::fs::write(dir.join("deleted"), "").unwrap();
+        std::fs::write(dir.join("removed"), "").unwrap();
+
+        let released = Arc::new(Mutex::new(Vec::new()));
+        let seen = released.clone();
+
+        let mut inotify = INotify::new().unwrap();
+        inotify.on_released(move |released| seen.lock().unwrap().push(released));
+        let deleted = inotify.add(&dir.join("deleted"), Mask::DELETE_SELF).unwrap();
+        let removed = inotify.add(&dir.join("removed"), Mask::DELETE_SELF).unwrap();
+        inotify.set_tag(deleted, "deleted tag");
+        inotify.set_tag(removed, "removed tag");
+
+        std::fs::remove_file(dir.join("deleted")).unwrap();
+        assert_eq!(next(&mut inotify).await.mask, Mask::DELETE_SELF);
+        assert!(released.lock().unwrap().is_empty());
+
+        assert!(next(&mut inotify).await.mask.contains(Mask::IGNORED));
+        {
+            let released = released.lock().unwrap();
+            assert_eq!(released.len(), 1);
+            assert_eq!(released[0].watch, deleted);
+            assert_eq!(released[0].reason, Removal::Deleted);
+            assert_eq!(released[0].path, Some(dir.join("deleted")));
+            let tag = released[0].tag.as_ref().unwrap().downcast_ref::<&str>();
+            assert_eq!(tag, Some(&"deleted tag"));
+        }
+        assert_eq!(inotify.tag::<&str>(deleted), None);
+        assert_eq!(inotify.path(deleted), None);
+
+        // state dropped by rm is not released a second time
+        inotify.rm(removed).unwrap();
+        let event = next(&mut inotify).await;
+        assert_eq!((event.watch, event.mask), (removed, Mask::IGNORED));
+        assert_eq!(released.lock().unwrap().len(), 1);
+    }
+}
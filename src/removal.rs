@@ -0,0 +1,70 @@
+#[cfg(feature = "tokio")]
+use crate::{Drain, INotify, Mask, RawEvent, Watch};
+
+/// Why the kernel stopped reporting events for a watch
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Removal {
+    /// The filesystem containing the watched object was unmounted
+    Unmounted,
+
+    /// The watched object was deleted
+    Deleted,
+
+    /// The watch was removed with [INotify::rm]
+    ExplicitlyRemoved,
+
+    /// The watch was added with ONESHOT and has fired
+    OneshotFired,
+}
+
+#[cfg(feature = "tokio")]
+impl INotify {
+    /// Keep track of what led up to an IGNORED, resolving the reason when it arrives
+    pub(crate) fn removal(&mut self, event: &RawEvent) -> Option<Removal> {
+        let watch = event.watch;
+
+        if event.mask.contains(Mask::UNMOUNT) {
+            self.dying.insert(watch, Removal::Unmounted);
+        } else if event.mask.contains(Mask::DELETE_SELF) {
+            self.dying.entry(watch).or_insert(Removal::Deleted);
+        } else if self.oneshot.contains(&watch) && !event.mask.contains(Mask::IGNORED) {
+            self.dying.entry(watch).or_insert(Removal::OneshotFired);
+        }
+
+        if !event.mask.contains(Mask::IGNORED) {
+            return None;
+        }
+
+        self.oneshot.remove(&watch);
+        let explicit = self.removed.remove(&watch) | self.draining.remove(&watch);
+        let dying = self.dying.remove(&watch);
+
+        // the kernel always reports UNMOUNT, so anything unexplained was deleted
+        Some(match (explicit, dying) {
+            (true, _) => Removal::ExplicitlyRemoved,
+            (false, Some(reason)) => reason,
+            (false, None) => Removal::Deleted,
+        })
+    }
+
+    /// Note a watch about to be removed, forgetting it now unless its queued events are collected
+    pub(crate) fn removing(&mut self, watch: Watch, drain: Drain) {
+        match drain {
+            Drain::Discard => {
+                self.forget(watch, Removal::ExplicitlyRemoved);
+                self.removed.insert(watch);
+            }
+            Drain::Collect => {
+                self.draining.insert(watch);
+            }
+        }
+    }
+
+    pub(crate) fn note_oneshot(&mut self, watch: Watch, mask: Mask) {
+        if mask.contains(Mask::ONESHOT) {
+            self.oneshot.insert(watch);
+        } else if !mask.contains(Mask::MASK_ADD) {
+            self.oneshot.remove(&watch);
+        }
+    }
+}
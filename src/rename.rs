@@ -0,0 +1,184 @@
+use std::{
+    collections::VecDeque,
+    io,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use crate::{Event, INotify, Mask};
+
+/// An event, or the two halves of a rename joined into one
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    /// An event passed through as read
+    Event(Event),
+
+    /// A MOVED_FROM and MOVED_TO sharing a cookie, reported by the same or different watches
+    Renamed {
+        /// The full path before the rename
+        from: PathBuf,
+
+        /// The full path after the rename
+        to: PathBuf,
+
+        /// The renamed object is a directory
+        dir: bool,
+    },
+
+    /// A directory was renamed, everything beneath `old_prefix` now lies beneath `new_prefix`
+    TreeMoved {
+        /// The directory's full path before the rename
+        old_prefix: PathBuf,
+
+        /// The directory's full path after the rename
+        new_prefix: PathBuf,
+    },
+}
+
+/// Whether a renamed directory is reported as a [Change::TreeMoved]
+///
+/// Descendants of a renamed directory get no events of their own, state
+/// derived from them is relocated with a single prefix rewrite.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TreeMoves {
+    /// Only as [Change::Renamed]
+    #[default]
+    Never,
+
+    /// As [Change::Renamed] followed by [Change::TreeMoved]
+    Also,
+
+    /// As [Change::TreeMoved] in place of [Change::Renamed]
+    Instead,
+}
+
+/// Joins the halves of renames across watches, as seen in recursive watching
+///
+/// A rename between two watched directories is reported as a MOVED_FROM
+/// by one watch and a MOVED_TO by another. A MOVED_FROM is held for the
+/// pairing window until its MOVED_TO arrives, the pair is delivered as one
+/// [Change::Renamed] with full paths. Unpaired halves, moves into or out of
+/// the watched directories, are delivered as they are. Watches beneath a
+/// renamed directory are re-pointed to its new path, as the kernel keeps
+/// them across the rename.
+pub struct Renames {
+    window: Duration,
+    tree_moves: TreeMoves,
+    held: Option<(Event, PathBuf)>,
+    ready: VecDeque<Change>,
+}
+
+impl Renames {
+    /// Hold a MOVED_FROM up to `window` for its MOVED_TO
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            tree_moves: TreeMoves::default(),
+            held: None,
+            ready: VecDeque::new(),
+        }
+    }
+
+    /// Report renamed directories according to `policy`
+    pub fn tree_moves(mut self, policy: TreeMoves) -> Self {
+        self.tree_moves = policy;
+        self
+    }
+
+    /// Wait for the next event or joined rename
+    pub async fn next(&mut self, inotify: &mut INotify) -> io::Result<Change> {
+        loop {
+            if let Some(change) = self.ready.pop_front() {
+                return Ok(change);
+            }
+
+            let event = if self.held.is_some() {
+                // the window closing interrupts the watch, it is cancel safe
+                tokio::select! {
+                    _ = tokio::time::sleep(self.window) => {
+                        self.release();
+                        continue;
+                    }
+                    event = inotify.watch() => event?,
+                }
+            } else {
+                inotify.watch().await?
+            };
+
+            self.observe(inotify, event);
+        }
+    }
+
+    fn observe(&mut self, inotify: &mut INotify, event: Event) {
+        if event.mask.contains(Mask::MOVED_TO) {
+            if let Some((from_event, from)) = self.held.take() {
+                if from_event.cookie == event.cookie {
+                    if let Some(to) = inotify.resolve(&event) {
+                        let dir = event.mask.contains(Mask::ISDIR);
+                        if dir {
+                            inotify.relocate(&from, &to);
+                        }
+
+                        self.renamed(from, to, dir);
+                        return;
+                    }
+                }
+
+                self.held = Some((from_event, from));
+            }
+        }
+
+        self.release();
+
+        if event.mask.contains(Mask::MOVED_FROM) {
+            if let Some(from) = inotify.resolve(&event) {
+                self.held = Some((event, from));
+                return;
+            }
+        }
+
+        self.ready.push_back(Change::Event(event));
+    }
+
+    fn renamed(&mut self, from: PathBuf, to: PathBuf, dir: bool) {
+        let tree = dir && self.tree_moves != TreeMoves::Never;
+
+        if !tree || self.tree_moves == TreeMoves::Also {
+            self.ready.push_back(Change::Renamed {
+                from: from.clone(),
+                to: to.clone(),
+                dir,
+            });
+        }
+        if tree {
+            self.ready.push_back(Change::TreeMoved {
+                old_prefix: from,
+                new_prefix: to,
+            });
+        }
+    }
+
+    /// a held MOVED_FROM went unpaired
+    fn release(&mut self) {
+        if let Some((event, _)) = self.held.take() {
+            self.ready.push_back(Change::Event(event));
+        }
+    }
+}
+
+impl INotify {
+    /// Re-point watches at or beneath `from` to the same place beneath `to`
+    pub(crate) fn relocate(&mut self, from: &Path, to: &Path) {
+        for path in self.paths.values_mut() {
+            if let Ok(rest) = path.strip_prefix(from) {
+                let moved = if rest.as_os_str().is_empty() {
+                    to.to_path_buf()
+                } else {
+                    to.join(rest)
+                };
+                *path = Arc::from(moved);
+            }
+        }
+    }
+}
@@ -0,0 +1,282 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    io,
+    os::unix::fs::MetadataExt,
+    path::PathBuf,
+    time::Duration,
+};
+
+use tokio::time::Instant;
+
+use crate::{Capability, Event, INotify, Mask, Mount, PartialCoverage, Watch};
+
+/// Periodically rescans watched directories for changes inotify missed
+///
+/// Every watched directory is listed each sweep and compared with the
+/// previous listing. Differences are delivered as synthetic CREATE, DELETE
+/// and MODIFY events ([Event::is_synthetic]) alongside kernel events,
+/// which also keep the listing up to date so nothing is reported twice.
+pub struct Rescan {
+    interval: Duration,
+    jitter: Duration,
+    next: Instant,
+    seed: u64,
+    primed: bool,
+    fallback: bool,
+    uncovered: Vec<PathBuf>,
+    swept: HashSet<Watch>,
+    snapshot: HashMap<PathBuf, Entry>,
+    queued: VecDeque<Event>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct Entry {
+    watch: Watch,
+    len: u64,
+    mtime: (i64, i64),
+    ino: u64,
+}
+
+impl Rescan {
+    /// Sweep every `interval`
+    pub fn new(interval: Duration) -> Self {
+        let seed = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0x9E3779B97F4A7C15, |d| d.as_nanos() as u64)
+            | 1;
+
+        Self {
+            interval,
+            jitter: Duration::ZERO,
+            next: Instant::now(),
+            seed,
+            primed: false,
+            fallback: false,
+            uncovered: Vec::new(),
+            swept: HashSet::new(),
+            snapshot: HashMap::new(),
+            queued: VecDeque::new(),
+        }
+    }
+
+    /// Randomly delay each sweep by up to `jitter`, spreading out the load of many watchers
+    pub fn jitter(mut self, jitter: Duration) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Only sweep watches on mounts where events may be missed (see [crate::Capability])
+    ///
+    /// Watches on plain mounts are left to inotify alone, so the sweep costs
+    /// nothing when every watch is reliable.
+    pub fn fallback(mut self) -> Self {
+        self.fallback = true;
+        self
+    }
+
+    /// Also sweep the subtrees a [PartialCoverage] left unwatched, recursively
+    ///
+    /// Their changes are reported on the watch of the covered parent, with
+    /// the path relative to it.
+    pub fn uncovered(mut self, coverage: &PartialCoverage) -> Self {
+        self.uncovered.extend(coverage.uncovered.iter().cloned());
+        self
+    }
+
+    /// Wait for the next kernel or synthetic event
+    pub async fn watch(&mut self, inotify: &mut INotify) -> io::Result<Event> {
+        loop {
+            if let Some(event) = self.queued.pop_front() {
+                return Ok(event);
+            }
+
+            tokio::select! {
+                _ = tokio::time::sleep_until(self.next) => {
+                    self.sweep(inotify).await?;
+                    self.next = Instant::now() + self.interval + self.delay();
+                }
+                event = inotify.watch() => {
+                    let event = event?;
+                    self.observe(inotify, &event);
+                    return Ok(event);
+                }
+            }
+        }
+    }
+
+    /// Rescan now, queueing synthetic events for any differences
+    pub async fn sweep(&mut self, inotify: &INotify) -> io::Result<()> {
+        let table = if self.fallback {
+            Some(Mount::table()?)
+        } else {
+            None
+        };
+
+        let dirs: Vec<(Watch, PathBuf)> = inotify
+            .watches()
+            .filter(|(_, p)| match &table {
+                Some(table) => Capability::of(table, p).is_none_or(|c| !c.reliable()),
+                None => true,
+            })
+            .map(|(w, p)| (w, p.to_path_buf()))
+            .collect();
+        self.swept = dirs.iter().map(|(watch, _)| *watch).collect();
+
+        let trees: Vec<(Watch, PathBuf)> = self
+            .uncovered
+            .iter()
+            .filter_map(|root| {
+                let parent = root.parent()?;
+                let (watch, _) = inotify.watches().find(|(_, p)| *p == parent)?;
+                Some((watch, root.clone()))
+            })
+            .collect();
+
+        let scanned = tokio::task::spawn_blocking(move || scan(dirs, trees))
+            .await
+            .map_err(io::Error::other)?;
+
+        let previous = std::mem::replace(&mut self.snapshot, scanned);
+        if !std::mem::replace(&mut self.primed, true) {
+            return Ok(());
+        }
+
+        let mut changes = Vec::new();
+
+        for (path, entry) in &self.snapshot {
+            let mask = match previous.get(path) {
+                None => Mask::CREATE,
+                Some(old) if old.ino != entry.ino => Mask::CREATE,
+                Some(old) if old != entry => Mask::MODIFY,
+                Some(_) => continue,
+            };
+
+            changes.push((entry.watch, mask, path.clone()));
+        }
+
+        for (path, entry) in previous {
+            if !self.snapshot.contains_key(&path) {
+                changes.push((entry.watch, Mask::DELETE, path));
+            }
+        }
+
+        // deletes first with children before their parents, then what
+        // appeared or changed with parents first, as the kernel reports a tree
+        changes.sort_by(|(_, a_mask, a), (_, b_mask, b)| {
+            let (a_gone, b_gone) = (*a_mask == Mask::DELETE, *b_mask == Mask::DELETE);
+            b_gone
+                .cmp(&a_gone)
+                .then_with(|| if a_gone { b.cmp(a) } else { a.cmp(b) })
+        });
+
+        for (watch, mask, path) in changes {
+            self.queue(inotify, watch, mask, path);
+        }
+
+        Ok(())
+    }
+
+    fn queue(&mut self, inotify: &INotify, watch: Watch, mask: Mask, path: PathBuf) {
+        let name = match inotify.path(watch) {
+            Some(base) => path
+                .strip_prefix(base)
+                .map(|p| p.to_path_buf())
+                .unwrap_or(path),
+            None => path,
+        };
+
+        self.queued.push_back(Event::synthetic(watch, mask, name));
+    }
+
+    /// keep the snapshot up to date, only for what the sweep lists
+    fn observe(&mut self, inotify: &INotify, event: &Event) {
+        if !self.swept.contains(&event.watch) {
+            return;
+        }
+
+        let Some(path) = inotify.resolve(event) else {
+            return;
+        };
+
+        // a watched directory itself is not listed, a watched file is
+        if event.path.as_os_str().is_empty() && !self.snapshot.contains_key(&path) {
+            return;
+        }
+
+        match std::fs::symlink_metadata(&path) {
+            Ok(meta) => {
+                self.snapshot.insert(path, Entry::new(event.watch, &meta));
+            }
+            Err(_) => {
+                self.snapshot.remove(&path);
+            }
+        }
+    }
+
+    fn delay(&mut self) -> Duration {
+        if self.jitter.is_zero() {
+            return Duration::ZERO;
+        }
+
+        // xorshift, good enough to spread sweeps apart
+        self.seed ^= self.seed << 13;
+        self.seed ^= self.seed >> 7;
+        self.seed ^= self.seed << 17;
+
+        let nanos = self.jitter.as_nanos() as u64;
+        Duration::from_nanos(self.seed % nanos.max(1))
+    }
+}
+
+impl Entry {
+    fn new(watch: Watch, meta: &std::fs::Metadata) -> Entry {
+        Entry {
+            watch,
+            len: meta.len(),
+            mtime: (meta.mtime(), meta.mtime_nsec()),
+            ino: meta.ino(),
+        }
+    }
+}
+
+fn scan(dirs: Vec<(Watch, PathBuf)>, trees: Vec<(Watch, PathBuf)>) -> HashMap<PathBuf, Entry> {
+    let mut snapshot = HashMap::new();
+
+    for (watch, dir) in dirs {
+        // watched files (rather than directories) are snapshot directly
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            if let Ok(meta) = std::fs::symlink_metadata(&dir) {
+                snapshot.insert(dir, Entry::new(watch, &meta));
+            }
+            continue;
+        };
+
+        for entry in entries.flatten() {
+            if let Ok(meta) = entry.metadata() {
+                snapshot.insert(entry.path(), Entry::new(watch, &meta));
+            }
+        }
+    }
+
+    // unwatched subtrees, everything beneath is reported on the parent's watch
+    for (watch, root) in trees {
+        let mut stack = vec![root];
+
+        while let Some(dir) = stack.pop() {
+            let Ok(entries) = std::fs::read_dir(&dir) else {
+                continue;
+            };
+
+            for entry in entries.flatten() {
+                if let Ok(meta) = entry.metadata() {
+                    if meta.is_dir() {
+                        stack.push(entry.path());
+                    }
+                    snapshot.insert(entry.path(), Entry::new(watch, &meta));
+                }
+            }
+        }
+    }
+
+    snapshot
+}
@@ -0,0 +1,141 @@
+use std::io;
+
+use crate::{Event, Glob, INotify, Mask, Matcher};
+
+type Handler = Box<dyn FnMut(&Event) + Send>;
+
+/// Routes events from one [INotify] to every matching subscriber
+///
+/// Subscribers filter with a [Matcher] tested against the full path of the
+/// event, so a handler can be registered per file type.
+#[derive(Default)]
+pub struct Router {
+    subscribers: Vec<Subscriber>,
+    next: u64,
+}
+
+struct Subscriber {
+    id: Subscription,
+    matcher: Matcher,
+    handler: Handler,
+}
+
+/// A handle to a subscription on a [Router]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Subscription(u64);
+
+impl Router {
+    /// Build a router without subscribers
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Subscribe to events matching any flag of `mask` and `glob`
+    pub fn on(
+        &mut self,
+        mask: Mask,
+        glob: Glob,
+        handler: impl FnMut(&Event) + Send + 'static,
+    ) -> Subscription {
+        self.subscribe(Matcher::new().mask(mask).glob(glob), handler)
+    }
+
+    /// Subscribe to events accepted by `matcher`
+    pub fn subscribe(
+        &mut self,
+        matcher: Matcher,
+        handler: impl FnMut(&Event) + Send + 'static,
+    ) -> Subscription {
+        let id = Subscription(self.next);
+        self.next += 1;
+
+        self.subscribers.push(Subscriber {
+            id,
+            matcher,
+            handler: Box::new(handler),
+        });
+
+        id
+    }
+
+    /// Remove a subscription
+    pub fn off(&mut self, id: Subscription) {
+        self.subscribers.retain(|s| s.id != id);
+    }
+
+    /// Hand an event to every matching subscriber, returning how many ran
+    pub fn dispatch(&mut self, inotify: &INotify, event: &Event) -> usize {
+        let path = inotify.resolve(event).unwrap_or_else(|| event.path.clone());
+        let mut ran = 0;
+
+        for sub in &mut self.subscribers {
+            if sub.matcher.matches_path(event.mask, &path) {
+                (sub.handler)(event);
+                ran += 1;
+            }
+        }
+
+        ran
+    }
+
+    /// Dispatch events until reading from the kernel fails
+    pub async fn run(&mut self, inotify: &mut INotify) -> io::Result<()> {
+        loop {
+            let event = inotify.watch().await?;
+            self.dispatch(inotify, &event);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::scratch;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn routes_to_matching_subscribers() {
+        let dir = scratch("router");
+        let mut inotify = INotify::new().unwrap();
+        let watch = inotify.add(&dir, Mask::CREATE | Mask::DELETE).unwrap();
+
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let mut router = Router::new();
+
+        let rust = {
+            let seen = seen.clone();
+            router.on(Mask::CREATE, Glob::new("**/*.rs").unwrap(), move |event| {
+                seen.lock().unwrap().push(("rust", event.path.clone()))
+            })
+        };
+        {
+            let seen = seen.clone();
+            router.on(Mask::DELETE, Glob::new("**").unwrap(), move |event| {
+                seen.lock().unwrap().push(("deleted", event.path.clone()))
+            });
+        }
+
+        let created = Event::new(watch, Mask::CREATE, "main.rs");
+        assert_eq!(router.dispatch(&inotify, &created), 1);
+        assert_eq!(
+            router.dispatch(&inotify, &Event::new(watch, Mask::CREATE, "notes.md")),
+            0
+        );
+        assert_eq!(
+            router.dispatch(&inotify, &Event::new(watch, Mask::DELETE, "lib.rs")),
+            1
+        );
+
+        router.off(rust);
+        assert_eq!(router.dispatch(&inotify, &created), 0);
+
+        let seen = seen.lock().unwrap();
+        assert_eq!(
+            *seen,
+            [
+                ("rust", "main.rs".into()),
+                ("deleted", std::path::PathBuf::from("lib.rs")),
+            ]
+        );
+    }
+}
@@ -0,0 +1,141 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    io,
+    path::PathBuf,
+    time::Duration,
+};
+
+use tokio::time::Instant;
+
+use crate::{Event, INotify, Mask};
+
+/// Which events a [Sampler] forwards
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Sampling {
+    /// The first of every `n` events on each path
+    Every(u64),
+
+    /// Each event with this probability, between 0 and 1
+    Probability(f64),
+}
+
+/// What a [Sampler] hands out
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Sample {
+    /// An event chosen to represent its path
+    Event(Event),
+
+    /// Counts of everything seen since the last report
+    Tally(Tally),
+}
+
+/// Counts over one report interval of a [Sampler]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Tally {
+    /// Events read
+    pub seen: u64,
+
+    /// Events forwarded
+    pub forwarded: u64,
+
+    /// Events read by flag name, an event counts once for each flag it carries
+    pub kinds: BTreeMap<&'static str, u64>,
+
+    /// Paths with events
+    pub paths: usize,
+}
+
+/// Forwards a representative subset of events, reporting counts of all of them
+///
+/// For monitoring, where the shape of the traffic matters more than every
+/// MODIFY. Sampling is decided per full path, Q_OVERFLOW, UNMOUNT and
+/// IGNORED are always forwarded. Every report interval a [Tally] of the
+/// events read is handed out and the per path counts start over.
+pub struct Sampler {
+    sampling: Sampling,
+    interval: Duration,
+    next: Instant,
+    seed: u64,
+    counts: HashMap<PathBuf, u64>,
+    tally: Tally,
+}
+
+impl Sampler {
+    /// Sample by `sampling`, reporting counts every `interval`
+    pub fn new(sampling: Sampling, interval: Duration) -> Self {
+        let seed = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0x9E3779B97F4A7C15, |d| d.as_nanos() as u64)
+            | 1;
+
+        Self {
+            sampling,
+            interval,
+            next: Instant::now() + interval,
+            seed,
+            counts: HashMap::new(),
+            tally: Tally::default(),
+        }
+    }
+
+    /// The counts since the last report
+    pub fn tally(&self) -> &Tally {
+        &self.tally
+    }
+
+    /// Wait for the next sampled event or report
+    pub async fn next(&mut self, inotify: &mut INotify) -> io::Result<Sample> {
+        loop {
+            tokio::select! {
+                _ = tokio::time::sleep_until(self.next) => {
+                    self.next = Instant::now() + self.interval;
+                    self.counts.clear();
+
+                    return Ok(Sample::Tally(std::mem::take(&mut self.tally)));
+                }
+                event = inotify.watch() => {
+                    let event = event?;
+                    if self.observe(inotify, &event) {
+                        self.tally.forwarded += 1;
+                        return Ok(Sample::Event(event));
+                    }
+                }
+            }
+        }
+    }
+
+    /// count an event, whether it is forwarded
+    fn observe(&mut self, inotify: &INotify, event: &Event) -> bool {
+        self.tally.seen += 1;
+        for name in event.mask.names() {
+            *self.tally.kinds.entry(name).or_default() += 1;
+        }
+
+        let path = inotify.resolve(event).unwrap_or_else(|| event.path.clone());
+        let count = self.counts.entry(path).or_default();
+        if *count == 0 {
+            self.tally.paths += 1;
+        }
+        *count += 1;
+        let count = *count;
+
+        if (event.mask & Mask::CONTROL).0 != 0 {
+            return true;
+        }
+
+        match self.sampling {
+            Sampling::Every(n) => (count - 1) % n.max(1) == 0,
+            Sampling::Probability(p) => self.random() < p,
+        }
+    }
+
+    /// uniform in [0, 1)
+    fn random(&mut self) -> f64 {
+        // xorshift, good enough to pick samples
+        self.seed ^= self.seed << 13;
+        self.seed ^= self.seed >> 7;
+        self.seed ^= self.seed << 17;
+
+        (self.seed >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
@@ -0,0 +1,75 @@
+use std::{future::poll_fn, io};
+
+use tokio::task::JoinSet;
+use tower_service::Service;
+
+use crate::{Event, INotify};
+
+/// Hands every event to a `tower::Service`, with tower middleware (retry,
+/// rate limit, timeout) wrapping the handling
+///
+/// Up to the concurrency limit calls run at once, one by default so
+/// events are handled in order. Events are only read once the service is
+/// ready and a call slot is free, a slow service leaves events queued in
+/// the kernel rather than buffered, where they overflow once the kernel's
+/// queue is full. Responses are dropped, the first error stops the run.
+pub struct Dispatcher<S> {
+    service: S,
+    concurrency: usize,
+}
+
+impl<S> Dispatcher<S>
+where
+    S: Service<Event>,
+    S::Future: Send + 'static,
+    S::Response: Send + 'static,
+    S::Error: Into<Box<dyn std::error::Error + Send + Sync>> + Send + 'static,
+{
+    /// Dispatch events to `service`
+    pub fn new(service: S) -> Self {
+        Self {
+            service,
+            concurrency: 1,
+        }
+    }
+
+    /// Allow up to `limit` calls in flight, calls may then complete out of order
+    pub fn concurrency(mut self, limit: usize) -> Self {
+        self.concurrency = limit.max(1);
+        self
+    }
+
+    /// the service events are dispatched to
+    pub fn service(&self) -> &S {
+        &self.service
+    }
+
+    /// Dispatch events until reading from the kernel or a call fails
+    ///
+    /// Calls still in flight are aborted when the run stops.
+    pub async fn run(&mut self, inotify: &mut INotify) -> io::Result<()> {
+        let mut in_flight = JoinSet::new();
+
+        loop {
+            while in_flight.len() >= self.concurrency {
+                if let Some(done) = in_flight.join_next().await {
+                    done.map_err(io::Error::other)??;
+                }
+            }
+
+            poll_fn(|cx| self.service.poll_ready(cx))
+                .await
+                .map_err(io::Error::other)?;
+
+            tokio::select! {
+                Some(done) = in_flight.join_next() => done.map_err(io::Error::other)??,
+                event = inotify.watch() => {
+                    let call = self.service.call(event?);
+                    in_flight.spawn(async move {
+                        call.await.map(drop).map_err(io::Error::other)
+                    });
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,239 @@
+use std::{
+    collections::{HashMap, HashSet},
+    io,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use tokio::time::Instant;
+
+use crate::{sys, Event, INotify, Mask, Watch};
+
+/// Events announcing a new entry in a watched directory
+const ARRIVAL: Mask = Mask(Mask::CREATE.0 | Mask::MOVED_TO.0);
+
+/// Events counted as activity inside a new directory
+const ACTIVITY: Mask = Mask(
+    Mask::CREATE.0
+        | Mask::MODIFY.0
+        | Mask::ATTRIB.0
+        | Mask::CLOSE_WRITE.0
+        | Mask::MOVED_FROM.0
+        | Mask::MOVED_TO.0
+        | Mask::DELETE.0,
+);
+
+/// Events after which a directory is gone from its path
+const GONE: Mask = Mask(Mask::DELETE.0 | Mask::MOVED_FROM.0);
+
+/// Reports when a newly created directory has stopped changing
+///
+/// Directories created or moved into a watched directory are watched down
+/// to their leaves until nothing changed beneath them for the quiet period,
+/// then one [Settled] summarizes them, e.g. an extracted archive or a
+/// camera import. Watches added for a directory are removed once it
+/// settled. Other events are consumed, the watched directories need
+/// CREATE and MOVED_TO in their masks.
+pub struct DirSettle {
+    quiet: Duration,
+    arrivals: HashMap<PathBuf, Arrival>,
+}
+
+struct Arrival {
+    last: Instant,
+    watches: Vec<Watch>,
+}
+
+/// A new directory which stopped changing
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Settled {
+    /// The directory
+    pub path: PathBuf,
+
+    /// The number of regular files beneath it
+    pub files: u64,
+
+    /// The total length of those files
+    pub bytes: u64,
+}
+
+impl DirSettle {
+    /// Report new directories once quiet for `quiet`
+    pub fn new(quiet: Duration) -> Self {
+        Self {
+            quiet,
+            arrivals: HashMap::new(),
+        }
+    }
+
+    /// The number of new directories still changing
+    pub fn pending(&self) -> usize {
+        self.arrivals.len()
+    }
+
+    /// Wait for the next new directory to settle
+    pub async fn watch(&mut self, inotify: &mut INotify) -> io::Result<Settled> {
+        loop {
+            if let Some(settled) = self.take_due(inotify).await? {
+                return Ok(settled);
+            }
+
+            let deadline = self.arrivals.values().map(|a| a.last + self.quiet).min();
+
+            tokio::select! {
+                _ = sleep(deadline) => (),
+                event = inotify.watch() => {
+                    let event = event?;
+                    self.record(inotify, &event).await?;
+                }
+            }
+        }
+    }
+
+    async fn record(&mut self, inotify: &mut INotify, event: &Event) -> io::Result<()> {
+        let now = Instant::now();
+
+        if event.mask.contains(Mask::Q_OVERFLOW) {
+            for arrival in self.arrivals.values_mut() {
+                arrival.last = now;
+            }
+            return Ok(());
+        }
+        if event.mask.contains(Mask::IGNORED) {
+            for arrival in self.arrivals.values_mut() {
+                arrival.watches.retain(|watch| *watch != event.watch);
+            }
+            return Ok(());
+        }
+
+        let Some(path) = inotify.resolve(event) else {
+            return Ok(());
+        };
+        let dir = event.mask.contains(Mask::ISDIR);
+
+        if dir && (event.mask & GONE).0 != 0 {
+            if let Some(arrival) = self.arrivals.remove(&path) {
+                release(inotify, arrival.watches)?;
+            }
+        }
+
+        let within = self
+            .arrivals
+            .keys()
+            .find(|root| path.starts_with(root))
+            .cloned();
+
+        if let Some(root) = &within {
+            self.arrivals.get_mut(root).expect("found above").last = now;
+        }
+
+        if !dir || (event.mask & ARRIVAL).0 == 0 {
+            return Ok(());
+        }
+
+        let watches = match follow(inotify, &path).await {
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+            res => res?,
+        };
+
+        match within {
+            Some(root) => {
+                let arrival = self.arrivals.get_mut(&root).expect("found above");
+                arrival.watches.extend(watches);
+            }
+            None => {
+                self.arrivals.insert(path, Arrival { last: now, watches });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Summarize a directory whose quiet period is over, without waiting
+    async fn take_due(&mut self, inotify: &mut INotify) -> io::Result<Option<Settled>> {
+        let now = Instant::now();
+
+        while let Some(path) = self
+            .arrivals
+            .iter()
+            .find(|(_, arrival)| arrival.last + self.quiet <= now)
+            .map(|(path, _)| path.clone())
+        {
+            let arrival = self.arrivals.remove(&path).expect("found above");
+            release(inotify, arrival.watches)?;
+
+            let walked = path.clone();
+            let tally = tokio::task::spawn_blocking(move || tally(&walked))
+                .await
+                .map_err(io::Error::other)?;
+
+            match tally {
+                Ok((files, bytes)) => return Ok(Some(Settled { path, files, bytes })),
+                Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+                Err(err) => return Err(err),
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+/// Watch a new directory and beneath it, returning the watches not present before
+async fn follow(inotify: &mut INotify, path: &Path) -> io::Result<Vec<Watch>> {
+    let existing: HashSet<Watch> = inotify.watches().map(|(watch, _)| watch).collect();
+    let watches = inotify.add_tree(path, ACTIVITY | Mask::MASK_ADD, None).await?;
+
+    Ok(watches
+        .into_iter()
+        .filter(|watch| !existing.contains(watch))
+        .collect())
+}
+
+/// Remove the watches added for a directory, skipping those gone already
+fn release(inotify: &mut INotify, watches: Vec<Watch>) -> io::Result<()> {
+    for watch in watches {
+        match inotify.rm(watch) {
+            Err(err) if err.raw_os_error() == Some(sys::EINVAL) => (),
+            res => res?,
+        }
+    }
+
+    Ok(())
+}
+
+/// The number and total length of regular files beneath a directory
+fn tally(root: &Path) -> io::Result<(u64, u64)> {
+    let mut files = 0;
+    let mut bytes = 0;
+    let mut dirs = vec![root.to_path_buf()];
+
+    while let Some(dir) = dirs.pop() {
+        let entries = match std::fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == io::ErrorKind::NotFound && dir != root => continue,
+            Err(err) => return Err(err),
+        };
+
+        for entry in entries.flatten() {
+            let Ok(meta) = entry.metadata() else {
+                continue;
+            };
+
+            if meta.is_dir() {
+                dirs.push(entry.path());
+            } else if meta.is_file() {
+                files += 1;
+                bytes += meta.len();
+            }
+        }
+    }
+
+    Ok((files, bytes))
+}
+
+async fn sleep(deadline: Option<Instant>) {
+    match deadline {
+        Some(deadline) => tokio::time::sleep_until(deadline).await,
+        None => std::future::pending().await,
+    }
+}
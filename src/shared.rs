@@ -0,0 +1,254 @@
+use std::{
+    ffi::c_int,
+    io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use tokio::sync::{mpsc, watch, Mutex, MutexGuard};
+
+use crate::{
+    add_watch, canonicalize, control::closed, pseudo, sys, Drain, Event, INotify, Mask, PseudoFs,
+    Quota, Watch,
+};
+
+/// An [INotify] usable through a shared reference
+///
+/// [Shared::add] and [Shared::rm] go straight to the kernel and never
+/// wait on [Shared::watch], so a `Shared` can live in an `Arc` and be used
+/// from several `tokio::select!` arms at once.
+///
+/// Paths are canonicalized and checked against the [PseudoFs] policy and
+/// the [Quota] of the instance as with [INotify::add].
+///
+/// After [Shared::close] every call fails with [io::ErrorKind::BrokenPipe].
+/// The descriptor stays open until the last handle is dropped, so a call
+/// racing the close never reaches a recycled descriptor.
+pub struct Shared {
+    fd: c_int,
+    inner: Mutex<INotify>,
+    registrations: mpsc::UnboundedSender<Registration>,
+    admission: Arc<std::sync::Mutex<Admission>>,
+    closed: watch::Sender<bool>,
+}
+
+pub(crate) enum Registration {
+    /// a watch, whether it is polled, and the quota already charged for it
+    Added(Watch, PathBuf, Mask, bool, Option<Quota>),
+    /// a watch about to be removed, an IGNORED read meanwhile is explicit
+    Removing(Watch),
+    /// a watch the kernel refused to remove
+    Kept(Watch),
+    Removed(Watch, Drain),
+}
+
+/// The settings of an [INotify] [Shared::add] follows, updated as they change
+#[derive(Clone)]
+pub(crate) struct Admission {
+    canonical: bool,
+    pseudo: PseudoFs,
+    quota: Option<Quota>,
+}
+
+impl INotify {
+    /// Allow the instance to be used through a shared reference
+    pub fn into_shared(mut self) -> Shared {
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.registrations = Some(rx);
+
+        let admission = Arc::new(std::sync::Mutex::new(self.admission_now()));
+        self.admission = Some(admission.clone());
+
+        Shared {
+            fd: self.fd,
+            inner: Mutex::new(self),
+            registrations: tx,
+            admission,
+            closed: watch::Sender::new(false),
+        }
+    }
+
+    fn admission_now(&self) -> Admission {
+        Admission {
+            canonical: self.canonical,
+            pseudo: self.pseudo,
+            quota: self.quota.clone(),
+        }
+    }
+
+    /// hand changed settings to the [Shared] handle, if there is one
+    pub(crate) fn publish_admission(&self) {
+        if let Some(admission) = &self.admission {
+            *lock(admission) = self.admission_now();
+        }
+    }
+
+    /// Apply watch set changes made through [Shared]
+    pub(crate) fn drain_registrations(&mut self) {
+        let Some(mut registrations) = self.registrations.take() else {
+            return;
+        };
+
+        while let Ok(registration) = registrations.try_recv() {
+            match registration {
+                Registration::Added(watch, path, mask, polled, charged) => {
+                    self.register(watch, path.clone(), mask);
+                    // registering counted the watch again if it is new
+                    if let Some(quota) = charged {
+                        quota.refund();
+                    }
+                    if polled {
+                        self.poll_pseudo(watch, path, mask);
+                    }
+                }
+                Registration::Removing(watch) => {
+                    self.draining.insert(watch);
+                }
+                Registration::Kept(watch) => {
+                    self.draining.remove(&watch);
+                }
+                // its IGNORED was read while the registration was in flight
+                Registration::Removed(watch, _) if !self.paths.contains_key(&watch) => (),
+                Registration::Removed(watch, drain) => self.removing(watch, drain),
+            }
+        }
+
+        self.registrations = Some(registrations);
+    }
+}
+
+impl Shared {
+    /// Add a file (, or directory) to be watched
+    pub fn add(&self, path: &Path, mask: Mask) -> io::Result<Watch> {
+        // held across the syscall, a close waits for it
+        let state = self.closed.borrow();
+        if *state {
+            return Err(closed());
+        }
+
+        let admission = lock(&self.admission).clone();
+        let path = if admission.canonical {
+            canonicalize(path, mask.contains(Mask::DONT_FOLLOW))?
+        } else {
+            path.to_path_buf()
+        };
+
+        let polled = pseudo::check(admission.pseudo, &path)?;
+        let charged = match admission.quota {
+            Some(quota) if quota.claim() => Some(quota),
+            Some(_) if !self.watched(&path) => return Err(io::Error::from_raw_os_error(sys::ENOSPC)),
+            _ => None,
+        };
+
+        let watch = match add_watch(self.fd, &path, mask) {
+            Ok(watch) => watch,
+            Err(err) => {
+                if let Some(quota) = charged {
+                    quota.refund();
+                }
+                return Err(err);
+            }
+        };
+
+        let _ = self
+            .registrations
+            .send(Registration::Added(watch, path, mask, polled, charged));
+
+        Ok(watch)
+    }
+
+    /// whether a path is watched already, and so takes nothing from the quota
+    ///
+    /// the paths are not known while a [Shared::watch] is pending, a path
+    /// is then taken to be new
+    fn watched(&self, path: &Path) -> bool {
+        self.inner.try_lock().is_ok_and(|mut inner| {
+            inner.drain_registrations();
+            inner.paths.values().any(|watched| watched.as_ref() == path)
+        })
+    }
+
+    /// remove a watch
+    ///
+    /// once this returns, [Shared::watch] reports nothing more for the watch but its IGNORED
+    pub fn rm(&self, watch: Watch) -> io::Result<()> {
+        self.rm_with(watch, Drain::Discard)
+    }
+
+    /// remove a watch, choosing what becomes of events already queued for it, see [INotify::rm_with]
+    pub fn rm_with(&self, watch: Watch, drain: Drain) -> io::Result<()> {
+        let state = self.closed.borrow();
+        if *state {
+            return Err(closed());
+        }
+
+        // announced first, a pending watch may read the IGNORED before the
+        // syscall returns, but forgotten only once removed
+        let _ = self.registrations.send(Registration::Removing(watch));
+        if let Err(err) = crate::rm_watch(self.fd, watch) {
+            let _ = self.registrations.send(Registration::Kept(watch));
+            return Err(err);
+        }
+        let _ = self.registrations.send(Registration::Removed(watch, drain));
+
+        Ok(())
+    }
+
+    /// wait for the next event
+    ///
+    /// concurrent callers take turns, each event is delivered once
+    pub async fn watch(&self) -> io::Result<Event> {
+        let mut state = self.closed.subscribe();
+
+        tokio::select! {
+            biased;
+            _ = state.wait_for(|closed| *closed) => Err(closed()),
+            res = async { self.inner.lock().await.watch().await } => res,
+        }
+    }
+
+    /// remove every watch and fail all later calls, waiting on a pending [Shared::watch]
+    ///
+    /// closing twice is an error, the descriptor itself is closed with the last handle
+    pub async fn close(&self) -> io::Result<()> {
+        if self.closed.send_replace(true) {
+            return Err(closed());
+        }
+
+        let mut inner = self.lock().await;
+        inner.commands = None;
+        inner.latest.clear();
+
+        let res = inner.rm_all();
+
+        // the watch interrupted above may leave a read in flight, a later
+        // shutdown only waits a while for one that could not be woken
+        inner.wake();
+
+        res
+    }
+
+    /// whether [Shared::close] was called
+    pub fn is_closed(&self) -> bool {
+        *self.closed.borrow()
+    }
+
+    /// access the underlying instance, waiting for any pending [Shared::watch]
+    pub async fn lock(&self) -> MutexGuard<'_, INotify> {
+        let mut inner = self.inner.lock().await;
+        inner.drain_registrations();
+        inner
+    }
+
+    /// recover the underlying instance, without watches once closed
+    pub fn into_inner(self) -> INotify {
+        let mut inner = self.inner.into_inner();
+        inner.drain_registrations();
+        inner.registrations = None;
+        inner
+    }
+}
+
+fn lock<T>(mutex: &std::sync::Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
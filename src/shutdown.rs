@@ -0,0 +1,143 @@
+use std::{
+    io,
+    os::{
+        fd::{AsRawFd, IntoRawFd},
+        unix::net::UnixStream,
+    },
+    path::PathBuf,
+    time::Duration,
+};
+
+use crate::{add_watch, close, rm_watch, Event, INotify, Mask};
+
+/// How long closing waits on a read in flight no event could be queued for
+const STRANDED: Duration = Duration::from_secs(1);
+
+/// What becomes of events not yet returned when watches go away
+///
+/// Applies to every watch on [INotify::shutdown] and to one watch with
+/// [INotify::rm_with].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Drain {
+    /// Drop them, along with the descriptor on shutdown
+    #[default]
+    Discard,
+
+    /// Keep what was queued up to each IGNORED, returned by shutdown or delivered after `rm_with`
+    Collect,
+}
+
+impl INotify {
+    /// Close the instance, the only way to learn whether closing failed
+    ///
+    /// [Control] halves are disconnected and [INotify::latest] receivers
+    /// see their sender go away. The descriptor is closed exactly once,
+    /// dropping an INotify closes it the same way but ignores errors.
+    ///
+    /// A read left in flight by a cancelled [INotify::watch] is ended by
+    /// queueing an event. Should that fail (e.g. out of watches), closing
+    /// waits a second for the read and otherwise fails with
+    /// [io::ErrorKind::TimedOut], the descriptor is then closed once the
+    /// read returns.
+    ///
+    /// [Control]: crate::Control
+    pub async fn shutdown(mut self, drain: Drain) -> io::Result<Vec<Event>> {
+        self.commands = None;
+
+        #[cfg(feature = "io-uring")]
+        if self.uring.is_some() {
+            self.drop_uring()?;
+        }
+
+        let mut events = Vec::new();
+        if drain == Drain::Collect {
+            // rm drops what is queued for a watch, so read it first
+            self.drain(&mut events).await?;
+
+            self.rm_all()?;
+
+            self.drain(&mut events).await?;
+        }
+
+        self.latest.clear();
+
+        let woken = self.wake();
+
+        // waits out a read in flight, which owns the descriptor until it finishes
+        let file = self.file.into_std();
+        let file = if woken {
+            file.await
+        } else {
+            tokio::time::timeout(STRANDED, file).await.map_err(|_| {
+                io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    "a read in flight could not be ended",
+                )
+            })?
+        };
+
+        let fd = file.into_raw_fd();
+        if unsafe { close(fd) } == -1 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(events)
+    }
+
+    /// queue an event so a read left in flight by a cancelled watch returns
+    ///
+    /// the IGNORED of a short lived hidden watch ends it. The watch is on a
+    /// socket of its own, so no watch of the caller is touched. false if
+    /// the watch could not be added
+    pub(crate) fn wake(&mut self) -> bool {
+        let Ok((socket, _peer)) = UnixStream::pair() else {
+            return false;
+        };
+        let path = PathBuf::from(format!("/proc/self/fd/{}", socket.as_raw_fd()));
+
+        match add_watch(self.fd, &path, Mask::DELETE_SELF) {
+            Ok(watch) => {
+                self.hidden.insert(watch);
+                let _ = rm_watch(self.fd, watch);
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// read everything queued without waiting for more
+    async fn drain(&mut self, events: &mut Vec<Event>) -> io::Result<()> {
+        while let Some(event) = self.try_watch().await? {
+            events.push(event);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::kernel_watches;
+    use std::path::Path;
+
+    #[tokio::test]
+    async fn waking_leaves_watches_alone() {
+        let mut inotify = INotify::new().unwrap();
+        let root = inotify.add(Path::new("/"), Mask::ATTRIB).unwrap();
+
+        assert!(inotify.wake());
+        assert_eq!(kernel_watches(inotify.as_raw_fd()), 1);
+        assert_eq!(inotify.registration(root), Some(Mask::ATTRIB));
+
+        // the hidden IGNORED is read and dropped, nothing is reported
+        let next = tokio::time::timeout(Duration::from_millis(100), inotify.watch()).await;
+        assert!(next.is_err());
+        assert!(inotify.hidden.is_empty());
+        assert_eq!(inotify.path(root), Some(Path::new("/")));
+
+        // the read left in flight is woken the same way
+        let closing = tokio::time::timeout(STRANDED / 2, inotify.shutdown(Drain::Discard));
+        assert!(closing.await.unwrap().unwrap().is_empty());
+    }
+}
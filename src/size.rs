@@ -0,0 +1,131 @@
+use std::{
+    collections::HashMap,
+    io,
+    path::{Path, PathBuf},
+};
+
+use crate::{Event, INotify, Mask};
+
+/// Tracks file sizes so MODIFY and CLOSE_WRITE can report a length delta
+#[derive(Default)]
+pub struct Sizes {
+    lens: HashMap<PathBuf, u64>,
+}
+
+/// The length of a file before and after a write
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SizeChange {
+    /// The path that was written
+    pub path: PathBuf,
+
+    /// The length last observed (`None` if never observed)
+    pub old_len: Option<u64>,
+
+    /// The current length
+    pub new_len: u64,
+}
+
+impl Sizes {
+    /// Build a new size tracker
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record the current length of a path
+    pub async fn track(&mut self, path: &Path) -> io::Result<u64> {
+        let len = tokio::fs::metadata(path).await?.len();
+        self.lens.insert(path.to_path_buf(), len);
+
+        Ok(len)
+    }
+
+    /// Stop tracking a path
+    pub fn untrack(&mut self, path: &Path) {
+        self.lens.remove(path);
+    }
+
+    /// The last observed length of a path
+    pub fn len(&self, path: &Path) -> Option<u64> {
+        self.lens.get(path).copied()
+    }
+
+    /// Report the length delta for a MODIFY or CLOSE_WRITE event
+    ///
+    /// A path already removed reports nothing and is no longer tracked.
+    pub async fn change(
+        &mut self,
+        inotify: &INotify,
+        event: &Event,
+    ) -> io::Result<Option<SizeChange>> {
+        if !event.mask.contains(Mask::MODIFY) && !event.mask.contains(Mask::CLOSE_WRITE) {
+            return Ok(None);
+        }
+
+        let Some(path) = inotify.resolve(event) else {
+            return Ok(None);
+        };
+
+        let new_len = match tokio::fs::metadata(&path).await {
+            Ok(meta) => meta.len(),
+            // removed before it was looked at, as when a log is rotated
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                self.lens.remove(&path);
+                return Ok(None);
+            }
+            Err(err) => return Err(err),
+        };
+        let old_len = self.lens.insert(path.clone(), new_len);
+
+        Ok(Some(SizeChange {
+            path,
+            old_len,
+            new_len,
+        }))
+    }
+}
+
+impl SizeChange {
+    /// The file grew
+    pub fn appended(&self) -> bool {
+        self.old_len.is_some_and(|old| self.new_len > old)
+    }
+
+    /// The file shrank
+    pub fn truncated(&self) -> bool {
+        self.old_len.is_some_and(|old| self.new_len < old)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::scratch;
+
+    #[tokio::test]
+    async fn reports_deltas_and_forgets_removed_files() {
+        let dir = scratch("sizes");
+        let file = dir.join("log");
+
+        let mut inotify = INotify::new().unwrap();
+        let watch = inotify.add(&dir, Mask::MODIFY).unwrap();
+        let modify = Event::new(watch, Mask::MODIFY, "log");
+
+        let mut sizes = Sizes::new();
+        std::fs::write(&file, "abc").unwrap();
+        assert_eq!(sizes.track(&file).await.unwrap(), 3);
+
+        std::fs::write(&file, "abcdef").unwrap();
+        let change = sizes.change(&inotify, &modify).await.unwrap().unwrap();
+        assert_eq!(change.old_len, Some(3));
+        assert_eq!(change.new_len, 6);
+        assert!(change.appended());
+
+        std::fs::write(&file, "a").unwrap();
+        assert!(sizes.change(&inotify, &modify).await.unwrap().unwrap().truncated());
+
+        // rotated away between the event and the look
+        std::fs::remove_file(&file).unwrap();
+        assert_eq!(sizes.change(&inotify, &modify).await.unwrap(), None);
+        assert_eq!(sizes.len(&file), None);
+    }
+}
@@ -0,0 +1,104 @@
+use crate::{Event, Mask};
+#[cfg(feature = "tokio")]
+use {crate::INotify, std::collections::VecDeque};
+
+/// The bits naming what happened, as opposed to qualifiers such as ISDIR
+const KINDS: Mask = Mask(Mask::INTEREST.0 | Mask::CONTROL.0);
+
+impl Event {
+    /// one event per kind of event set in the mask, in bit order
+    ///
+    /// Each part keeps the watch, cookie, path and qualifiers such as
+    /// ISDIR. An IGNORED comes last and alone keeps the removal reason.
+    /// An event of a single kind is returned as is.
+    pub fn split(&self) -> impl Iterator<Item = Event> + '_ {
+        let kinds = self.mask.0 & KINDS.0;
+        let qualifiers = self.mask.0 & !KINDS.0;
+
+        (0..u32::BITS)
+            .map(|bit| 1 << bit)
+            .filter(move |bit| kinds & bit != 0)
+            .map(move |bit| {
+                let mut event = self.clone();
+                event.mask = Mask(bit | qualifiers);
+                if bit != Mask::IGNORED.0 {
+                    event.removal = None;
+                }
+                event
+            })
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl INotify {
+    /// deliver an event with several kinds set as one event per kind, see [Event::split]
+    ///
+    /// The kernel rarely sets more than one, the parts follow each other
+    /// before any later event.
+    pub fn split_kinds(&mut self, enabled: bool) {
+        if !enabled {
+            self.split = None;
+        } else if self.split.is_none() {
+            self.split = Some(VecDeque::new());
+        }
+    }
+
+    /// the next part of a split event
+    pub(crate) fn next_part(&mut self) -> Option<Event> {
+        self.split.as_mut()?.pop_front()
+    }
+
+    /// hand out the first part of an event, queueing the rest
+    pub(crate) fn first_part(&mut self, event: Event) -> Event {
+        let Some(split) = &mut self.split else {
+            return event;
+        };
+        if (event.mask.0 & KINDS.0).count_ones() <= 1 {
+            return event;
+        }
+
+        split.extend(event.split());
+        split.pop_front().unwrap_or(event)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Removal, Watch};
+
+    #[test]
+    fn parts_keep_everything_but_the_other_kinds() {
+        let event = Event::builder(
+            Watch::from_raw(3),
+            Mask::DELETE_SELF | Mask::IGNORED | Mask::ISDIR,
+        )
+        .removal(Removal::Deleted)
+        .build()
+        .unwrap();
+
+        let parts: Vec<Event> = event.split().collect();
+        assert_eq!(parts.len(), 2);
+        assert_eq!(parts[0].mask, Mask::DELETE_SELF | Mask::ISDIR);
+        assert_eq!(parts[0].removal, None);
+        assert_eq!(parts[1].mask, Mask::IGNORED | Mask::ISDIR);
+        assert_eq!(parts[1].removal, Some(Removal::Deleted));
+        assert!(parts.iter().all(|part| part.watch == event.watch));
+
+        let single = Event::new(Watch::from_raw(3), Mask::CREATE | Mask::ISDIR, "d");
+        assert_eq!(single.split().collect::<Vec<_>>(), vec![single.clone()]);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn parts_are_handed_out_in_turn() {
+        let mut inotify = INotify::new().unwrap();
+        let event = Event::new(Watch::from_raw(1), Mask::MODIFY | Mask::CLOSE_WRITE, "f");
+        assert_eq!(inotify.first_part(event.clone()), event);
+
+        inotify.split_kinds(true);
+        assert_eq!(inotify.first_part(event.clone()).mask, Mask::MODIFY);
+        assert_eq!(inotify.next_part().unwrap().mask, Mask::CLOSE_WRITE);
+        assert!(inotify.next_part().is_none());
+    }
+}
@@ -0,0 +1,185 @@
+use std::{collections::HashMap, io, path::PathBuf, time::Duration};
+
+use tokio::time::Instant;
+
+use crate::{Event, INotify, Mask};
+
+/// Events held until their file looks complete
+const GATED: Mask = Mask(Mask::CREATE.0 | Mask::MODIFY.0);
+
+/// Events after which a held file is gone from its path
+const GONE: Mask = Mask(Mask::DELETE.0 | Mask::MOVED_FROM.0);
+
+/// Holds CREATE and MODIFY until a file is large enough or done growing
+///
+/// Uploaders often create an empty placeholder first and fill it in later.
+/// A held event is delivered once its file reaches the minimum size, or
+/// once two checks an interval apart saw the same size. Further events on
+/// a held file are merged into it, a deletion or move drops it. Events on
+/// directories and all other events pass straight through.
+pub struct SizeGate {
+    min_size: Option<u64>,
+    interval: Duration,
+    pending: HashMap<PathBuf, Held>,
+}
+
+struct Held {
+    event: Event,
+    len: u64,
+    check: Instant,
+}
+
+impl SizeGate {
+    /// Check held files every `interval` for a change in size
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            min_size: None,
+            interval,
+            pending: HashMap::new(),
+        }
+    }
+
+    /// Deliver as soon as a file is at least `min_size` bytes
+    pub fn min_size(mut self, min_size: u64) -> Self {
+        self.min_size = Some(min_size);
+        self
+    }
+
+    /// The number of files with held events
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Wait for the next event whose file passed the gate
+    pub async fn watch(&mut self, inotify: &mut INotify) -> io::Result<Event> {
+        loop {
+            if let Some(event) = self.check_due().await {
+                return Ok(event);
+            }
+
+            let next = self.pending.values().map(|held| held.check).min();
+
+            // a due check interrupts the watch, it is cancel safe
+            tokio::select! {
+                _ = sleep(next) => (),
+                event = inotify.watch() => {
+                    if let Some(event) = self.admit(inotify, event?).await {
+                        return Ok(event);
+                    }
+                }
+            }
+        }
+    }
+
+    /// the event to hand out now, `None` if it was held
+    async fn admit(&mut self, inotify: &INotify, event: Event) -> Option<Event> {
+        if event.mask.contains(Mask::ISDIR) {
+            return Some(event);
+        }
+        let Some(path) = inotify.resolve(&event) else {
+            return Some(event);
+        };
+
+        if (event.mask & GONE).0 != 0 {
+            self.pending.remove(&path);
+            return Some(event);
+        }
+        if (event.mask & GATED).0 == 0 {
+            return Some(event);
+        }
+
+        // vanished already, its removal follows
+        let len = tokio::fs::metadata(&path).await.ok()?.len();
+        let large = self.large_enough(len);
+
+        if let Some(held) = self.pending.get_mut(&path) {
+            let mask = held.event.mask | event.mask;
+            held.event = event;
+            held.event.mask = mask;
+
+            if !large {
+                return None;
+            }
+            return self.pending.remove(&path).map(|held| held.event);
+        }
+
+        if large {
+            return Some(event);
+        }
+
+        let check = Instant::now() + self.interval;
+        self.pending.insert(path, Held { event, len, check });
+        None
+    }
+
+    /// Release a held event whose check is due and whose file passed it
+    async fn check_due(&mut self) -> Option<Event> {
+        let now = Instant::now();
+        let due: Vec<PathBuf> = self
+            .pending
+            .iter()
+            .filter(|(_, held)| held.check <= now)
+            .map(|(path, _)| path.clone())
+            .collect();
+
+        for path in due {
+            let Ok(meta) = tokio::fs::metadata(&path).await else {
+                self.pending.remove(&path);
+                continue;
+            };
+
+            let len = meta.len();
+            let large = self.large_enough(len);
+            let held = self.pending.get_mut(&path).expect("due path is pending");
+            if large || len == held.len {
+                return self.pending.remove(&path).map(|held| held.event);
+            }
+
+            held.len = len;
+            held.check = now + self.interval;
+        }
+
+        None
+    }
+
+    fn large_enough(&self, len: u64) -> bool {
+        self.min_size.is_some_and(|min| len >= min)
+    }
+}
+
+async fn sleep(deadline: Option<Instant>) {
+    match deadline {
+        Some(deadline) => tokio::time::sleep_until(deadline).await,
+        None => std::future::pending().await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::scratch;
+
+    #[tokio::test]
+    async fn holds_files_until_large_enough() {
+        let dir = scratch("gate");
+
+        let mut inotify = INotify::new().unwrap();
+        inotify.add(&dir, Mask::CREATE | Mask::MODIFY).unwrap();
+        let mut gate = SizeGate::new(Duration::from_secs(3600)).min_size(4);
+
+        // a placeholder first, held until filled in
+        std::fs::File::create(dir.join("f")).unwrap();
+        let held = tokio::time::timeout(Duration::from_millis(50), gate.watch(&mut inotify));
+        assert!(held.await.is_err());
+        assert_eq!(gate.pending(), 1);
+
+        std::fs::write(dir.join("f"), "full").unwrap();
+        let event = tokio::time::timeout(Duration::from_secs(10), gate.watch(&mut inotify))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(event.mask, Mask::CREATE | Mask::MODIFY);
+        assert_eq!(event.path, PathBuf::from("f"));
+        assert_eq!(gate.pending(), 0);
+    }
+}
@@ -0,0 +1,113 @@
+use std::collections::HashMap;
+
+use tokio::time::Instant;
+
+use crate::{INotify, Mask, Watch};
+
+/// Event counters for a single watch
+#[derive(Debug, Clone, Default)]
+pub struct WatchStats {
+    total: u64,
+    bits: [u64; 32],
+    last: Option<Instant>,
+}
+
+impl WatchStats {
+    /// Every event seen for the watch
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    /// Events carrying any of the flags in `mask`
+    ///
+    /// An event carrying several of the flags is counted once per flag.
+    pub fn count(&self, mask: Mask) -> u64 {
+        (0..32)
+            .filter(|bit| mask.0 & (1 << bit) != 0)
+            .map(|bit| self.bits[bit])
+            .sum()
+    }
+
+    /// When the last event was seen
+    pub fn last(&self) -> Option<Instant> {
+        self.last
+    }
+
+    pub(crate) fn record(&mut self, mask: Mask, now: Instant) {
+        self.total += 1;
+        self.last = Some(now);
+
+        for (bit, count) in self.bits.iter_mut().enumerate() {
+            if mask.0 & (1 << bit) != 0 {
+                *count += 1;
+            }
+        }
+    }
+}
+
+impl INotify {
+    /// Count events per watch
+    ///
+    /// Disabling drops all collected statistics.
+    pub fn track_stats(&mut self, enabled: bool) {
+        if !enabled {
+            self.stats = None;
+        } else if self.stats.is_none() {
+            self.stats = Some(HashMap::new());
+        }
+    }
+
+    /// The statistics collected for a watch
+    pub fn stats(&self, watch: Watch) -> Option<&WatchStats> {
+        self.stats.as_ref()?.get(&watch)
+    }
+
+    /// The `n` watches which have seen the most events
+    pub fn top_watches(&self, n: usize) -> Vec<(Watch, &WatchStats)> {
+        let Some(stats) = &self.stats else {
+            return Vec::new();
+        };
+
+        let mut top: Vec<_> = stats.iter().map(|(w, s)| (*w, s)).collect();
+        top.sort_by_key(|(_, s)| std::cmp::Reverse(s.total));
+        top.truncate(n);
+
+        top
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::scratch;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn removed_watches_leave_no_stats_behind() {
+        let dir = scratch("stats-rm");
+
+        let mut inotify = INotify::new().unwrap();
+        inotify.track_stats(true);
+        let watch = inotify.add(&dir, Mask::CREATE).unwrap();
+
+        std::fs::write(dir.join("f"), "").unwrap();
+        let event = tokio::time::timeout(Duration::from_secs(10), inotify.watch())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(event.mask, Mask::CREATE);
+        assert_eq!(inotify.stats(watch).unwrap().count(Mask::CREATE), 1);
+
+        // queued before the rm, dropped along with the watch
+        std::fs::write(dir.join("g"), "").unwrap();
+        inotify.rm(watch).unwrap();
+        let event = tokio::time::timeout(Duration::from_secs(10), inotify.watch())
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(event.mask.contains(Mask::IGNORED));
+
+        assert!(inotify.stats(watch).is_none());
+        assert!(inotify.top_watches(10).is_empty());
+    }
+}
@@ -0,0 +1,274 @@
+//! A virtual backend delivering only injected events
+//!
+//! Nothing here touches the kernel or the file system, so logic built on
+//! [Event]s runs where inotify does not, e.g. on development machines of
+//! other platforms or in simulators. Watches are bookkeeping only, events
+//! are delivered the way the kernel would deliver them to those watches.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    ffi::c_int,
+    io,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use crate::{Event, Mask, Removal, Watch};
+
+/// Watches without a kernel, delivering events injected by hand
+///
+/// Events are delivered in the order injected, to watches still present
+/// and interested in them. A watch removed with [INotify::rm] reports an
+/// IGNORED and nothing after, as with [crate::INotify].
+pub struct INotify {
+    paths: HashMap<Watch, (Arc<Path>, Mask)>,
+    next: c_int,
+    queue: Arc<Queue>,
+}
+
+/// Injects events into a virtual [INotify] from anywhere, e.g. another task
+#[derive(Clone)]
+pub struct Injector {
+    queue: Arc<Queue>,
+}
+
+#[derive(Default)]
+struct Queue {
+    events: Mutex<VecDeque<Event>>,
+    #[cfg(feature = "tokio")]
+    ready: tokio::sync::Notify,
+}
+
+impl INotify {
+    /// Build a virtual instance without watches
+    pub fn new() -> io::Result<Self> {
+        Ok(Self {
+            paths: HashMap::new(),
+            next: 1,
+            queue: Arc::default(),
+        })
+    }
+
+    /// Add a path to be watched, the path need not exist
+    ///
+    /// Adding a path watched already returns its watch, as the kernel does.
+    pub fn add(&mut self, path: &Path, mask: Mask) -> io::Result<Watch> {
+        let existing = self
+            .paths
+            .iter()
+            .find(|(_, (watched, _))| watched.as_ref() == path)
+            .map(|(watch, (_, prev))| (*watch, *prev));
+
+        let interest = Mask(mask.0 & Mask::INTEREST.0);
+        let (watch, interest) = match existing {
+            Some((watch, prev)) if mask.contains(Mask::MASK_ADD) => (watch, prev | interest),
+            Some((watch, _)) => (watch, interest),
+            None => {
+                let watch = Watch::from_raw(self.next);
+                self.next += 1;
+                (watch, interest)
+            }
+        };
+
+        self.paths.insert(watch, (path.into(), interest));
+        Ok(watch)
+    }
+
+    /// remove a watch, its IGNORED is delivered after events injected before
+    pub fn rm(&mut self, watch: Watch) -> io::Result<()> {
+        let Some((path, _)) = self.paths.remove(&watch) else {
+            return Err(io::Error::from(io::ErrorKind::InvalidInput));
+        };
+
+        let mut ignored = Event::new(watch, Mask::IGNORED, PathBuf::new());
+        ignored.removal = Some(Removal::ExplicitlyRemoved);
+        ignored.root = Some(path);
+        self.inject(ignored);
+
+        Ok(())
+    }
+
+    /// the path a watch was added with
+    pub fn path(&self, watch: Watch) -> Option<&Path> {
+        self.paths.get(&watch).map(|(path, _)| path.as_ref())
+    }
+
+    /// the events a watch is interested in
+    pub fn mask(&self, watch: Watch) -> Option<Mask> {
+        self.paths.get(&watch).map(|(_, mask)| *mask)
+    }
+
+    /// every watch and the path it was added with
+    pub fn watches(&self) -> impl Iterator<Item = (Watch, &Path)> {
+        self.paths.iter().map(|(w, (p, _))| (*w, p.as_ref()))
+    }
+
+    /// the full path an event refers to
+    pub fn resolve(&self, event: &Event) -> Option<PathBuf> {
+        let base = self.path(event.watch)?;
+
+        if event.path.as_os_str().is_empty() {
+            Some(base.to_path_buf())
+        } else {
+            Some(base.join(&event.path))
+        }
+    }
+
+    /// an injector feeding this instance
+    pub fn injector(&self) -> Injector {
+        Injector {
+            queue: self.queue.clone(),
+        }
+    }
+
+    /// Queue an event as if read from the kernel
+    pub fn inject(&self, event: Event) {
+        self.queue.push(event);
+    }
+
+    /// Queue `mask` happening at a full path, for every watch that reports it
+    ///
+    /// A watch on the path reports it with an empty name, a watch on its
+    /// parent with the file name. Returns the number of events queued,
+    /// those the watches are not interested in are left out.
+    pub fn inject_path(&self, path: &Path, mask: Mask) -> usize {
+        let mut queued = 0;
+
+        for (watch, (watched, interest)) in &self.paths {
+            let name = if watched.as_ref() == path {
+                PathBuf::new()
+            } else if path.parent() == Some(watched.as_ref()) {
+                PathBuf::from(path.file_name().unwrap_or_default())
+            } else {
+                continue;
+            };
+
+            if (mask & *interest).0 == 0 {
+                continue;
+            }
+
+            let mut event = Event::new(*watch, mask, name);
+            event.synthetic = true;
+            self.inject(event);
+            queued += 1;
+        }
+
+        queued
+    }
+
+    /// the next event without waiting, `None` when nothing is queued
+    pub fn try_next(&mut self) -> Option<Event> {
+        while let Some(mut event) = self.queue.pop() {
+            if self.deliverable(&mut event) {
+                return Some(event);
+            }
+        }
+
+        None
+    }
+
+    /// wait for the next injected event
+    #[cfg(feature = "tokio")]
+    pub async fn watch(&mut self) -> io::Result<Event> {
+        let queue = self.queue.clone();
+
+        loop {
+            let ready = queue.ready.notified();
+            if let Some(event) = self.try_next() {
+                return Ok(event);
+            }
+            ready.await;
+        }
+    }
+
+    /// whether a watch would report an event, filling in what the instance knows
+    fn deliverable(&self, event: &mut Event) -> bool {
+        if event.mask.contains(Mask::Q_OVERFLOW) || event.mask.contains(Mask::IGNORED) {
+            return true;
+        }
+
+        let Some((root, interest)) = self.paths.get(&event.watch) else {
+            return false;
+        };
+        if (event.mask & (*interest | Mask::CONTROL)).0 == 0 {
+            return false;
+        }
+
+        event.root = Some(root.clone());
+        event.watch_mask = Some(*interest);
+        true
+    }
+}
+
+impl Injector {
+    /// Queue an event as if read from the kernel
+    pub fn inject(&self, event: Event) {
+        self.queue.push(event);
+    }
+}
+
+impl Queue {
+    fn push(&self, event: Event) {
+        self.events
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .push_back(event);
+
+        #[cfg(feature = "tokio")]
+        self.ready.notify_one();
+    }
+
+    fn pop(&self) -> Option<Event> {
+        self.events
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .pop_front()
+    }
+}
+
+#[cfg(all(test, feature = "tokio"))]
+mod tests {
+    use super::*;
+    use crate::testing::scratch;
+    use std::time::Duration;
+
+    async fn next(inotify: &mut crate::INotify) -> Event {
+        tokio::time::timeout(Duration::from_secs(10), inotify.watch())
+            .await
+            .unwrap()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn delivers_what_the_kernel_would() {
+        let dir = scratch("stub");
+        let mut kernel = crate::INotify::new().unwrap();
+        let mut stub = INotify::new().unwrap();
+
+        let real = kernel.add(&dir, Mask::CREATE).unwrap();
+        let virt = stub.add(&dir, Mask::CREATE).unwrap();
+
+        std::fs::File::create(dir.join("f")).unwrap();
+        let expected = next(&mut kernel).await;
+        assert_eq!(stub.inject_path(&dir.join("f"), Mask::CREATE), 1);
+        let event = stub.watch().await.unwrap();
+        assert_eq!(event.mask, expected.mask);
+        assert_eq!(event.path, expected.path);
+        assert_eq!(stub.resolve(&event), kernel.resolve(&expected));
+
+        // nobody is interested in a deletion
+        std::fs::remove_file(dir.join("f")).unwrap();
+        assert_eq!(stub.inject_path(&dir.join("f"), Mask::DELETE), 0);
+
+        kernel.rm(real).unwrap();
+        stub.rm(virt).unwrap();
+        let expected = next(&mut kernel).await;
+        let event = stub.watch().await.unwrap();
+        assert_eq!(event.mask, expected.mask);
+        assert_eq!(event.removal, expected.removal);
+
+        // an event injected after the watch is gone is dropped
+        stub.inject(Event::new(virt, Mask::CREATE, "g"));
+        assert!(stub.try_next().is_none());
+    }
+}
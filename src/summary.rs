@@ -0,0 +1,123 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    io,
+    path::PathBuf,
+    time::Duration,
+};
+
+use tokio::time::Instant;
+
+use crate::{Event, INotify, Mask, Watch};
+
+/// What happened on one watch over an interval of [Summaries]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WatchSummary {
+    /// The watch summarized
+    pub watch: Watch,
+
+    /// The path the watch was added with, unknown once it is gone
+    pub path: Option<PathBuf>,
+
+    /// Events read
+    pub events: u64,
+
+    /// Events read by flag name, an event counts once for each flag it carries
+    pub kinds: BTreeMap<&'static str, u64>,
+
+    /// Distinct paths the events were about
+    pub paths: usize,
+
+    /// An estimate of the bytes written, the sizes of files closed after
+    /// writing, each file counted once
+    pub bytes: u64,
+}
+
+/// Digests of events per watch, handed out once per interval
+///
+/// For dashboards and anomaly detection, where counts matter rather than
+/// single events. Every interval hands out a summary for each watch with
+/// events in it, sorted by watch, empty when nothing happened.
+pub struct Summaries {
+    interval: Duration,
+    next: Instant,
+    watches: HashMap<Watch, Digest>,
+}
+
+#[derive(Default)]
+struct Digest {
+    path: Option<PathBuf>,
+    events: u64,
+    kinds: BTreeMap<&'static str, u64>,
+    paths: HashSet<PathBuf>,
+    written: HashMap<PathBuf, u64>,
+}
+
+impl Summaries {
+    /// Summarize every `interval`
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            next: Instant::now() + interval,
+            watches: HashMap::new(),
+        }
+    }
+
+    /// Wait for the end of the interval, summarizing the events read meanwhile
+    pub async fn next(&mut self, inotify: &mut INotify) -> io::Result<Vec<WatchSummary>> {
+        loop {
+            tokio::select! {
+                _ = tokio::time::sleep_until(self.next) => {
+                    self.next = Instant::now() + self.interval;
+                    return Ok(self.take());
+                }
+                event = inotify.watch() => self.observe(inotify, &event?),
+            }
+        }
+    }
+
+    /// Summarize the events read so far and start a new interval, without waiting
+    pub fn take(&mut self) -> Vec<WatchSummary> {
+        let mut summaries: Vec<WatchSummary> = self
+            .watches
+            .drain()
+            .map(|(watch, digest)| WatchSummary {
+                watch,
+                path: digest.path,
+                events: digest.events,
+                kinds: digest.kinds,
+                paths: digest.paths.len(),
+                bytes: digest.written.values().sum(),
+            })
+            .collect();
+
+        summaries.sort_by_key(|summary| summary.watch.as_raw());
+        summaries
+    }
+
+    fn observe(&mut self, inotify: &INotify, event: &Event) {
+        let digest = self.watches.entry(event.watch).or_default();
+        if digest.path.is_none() {
+            digest.path = inotify.path(event.watch).map(|path| path.to_path_buf());
+        }
+
+        digest.events += 1;
+        for name in event.mask.names() {
+            *digest.kinds.entry(name).or_default() += 1;
+        }
+
+        let path = inotify.resolve(event).unwrap_or_else(|| event.path.clone());
+        if event.mask.contains(Mask::CLOSE_WRITE) {
+            if let Ok(meta) = std::fs::metadata(&path) {
+                digest.written.insert(path.clone(), meta.len());
+            }
+        }
+        digest.paths.insert(path);
+    }
+}
+
+impl INotify {
+    /// Summaries of the events of every watch, once per `interval`
+    pub fn summaries(&self, interval: Duration) -> Summaries {
+        Summaries::new(interval)
+    }
+}
@@ -0,0 +1,107 @@
+use crate::Watch;
+#[cfg(feature = "tokio")]
+use {
+    crate::{INotify, Mask},
+    std::{collections::HashSet, io, path::Path},
+};
+
+/// How symbolic links are treated when adding a watch
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SymlinkPolicy {
+    /// Watch the file a link points to
+    #[default]
+    Follow,
+
+    /// Watch the link itself
+    NoFollow,
+
+    /// Watch both the link and the file it points to
+    WatchBoth,
+}
+
+/// Which side of a symbolic link a watch observes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LinkRole {
+    /// The symbolic link itself
+    Link,
+
+    /// The file the link points to
+    Target,
+}
+
+/// The watches created by [INotify::add_link]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LinkWatch {
+    /// The watch on the link itself
+    pub link: Option<Watch>,
+
+    /// The watch on the resolved target
+    pub target: Option<Watch>,
+}
+
+#[cfg(feature = "tokio")]
+impl INotify {
+    /// Add a watch with an explicit symbolic link policy
+    ///
+    /// Events from watches added this way report their [LinkRole] when `path` is a link.
+    /// When the second watch of [SymlinkPolicy::WatchBoth] can't be added the
+    /// first is removed again, unless it was watched before.
+    pub fn add_link(
+        &mut self,
+        path: &Path,
+        mask: Mask,
+        policy: SymlinkPolicy,
+    ) -> io::Result<LinkWatch> {
+        let is_link = std::fs::symlink_metadata(path)?.file_type().is_symlink();
+        let mask = Mask(mask.0 & !Mask::DONT_FOLLOW.0);
+
+        let mut watches = LinkWatch {
+            link: None,
+            target: None,
+        };
+
+        let both = policy == SymlinkPolicy::WatchBoth && is_link;
+        let known: HashSet<Watch> = match both {
+            true => self.paths.keys().copied().collect(),
+            false => HashSet::new(),
+        };
+        let mut previous = None;
+
+        if policy != SymlinkPolicy::NoFollow {
+            let watch = self.add(path, mask)?;
+            watches.target = Some(watch);
+
+            if is_link {
+                previous = self.links.insert(watch, LinkRole::Target);
+            }
+        }
+
+        if policy == SymlinkPolicy::NoFollow || both {
+            let watch = match self.add(path, mask | Mask::DONT_FOLLOW) {
+                Ok(watch) => watch,
+                Err(err) => {
+                    if let Some(target) = watches.target {
+                        match previous {
+                            Some(role) => self.links.insert(target, role),
+                            None => self.links.remove(&target),
+                        };
+                        self.undo(vec![target], &known);
+                    }
+                    return Err(err);
+                }
+            };
+            watches.link = Some(watch);
+
+            if is_link {
+                self.links.insert(watch, LinkRole::Link);
+            }
+        }
+
+        Ok(watches)
+    }
+
+    /// The side of a symbolic link a watch observes
+    pub fn link_role(&self, watch: Watch) -> Option<LinkRole> {
+        self.links.get(&watch).copied()
+    }
+}
@@ -0,0 +1,353 @@
+use std::ffi::{c_int, c_uint};
+
+#[cfg(not(feature = "libc-backed"))]
+pub(crate) use bindings::*;
+#[cfg(feature = "libc-backed")]
+pub(crate) use libc_bindings::*;
+
+/// Size of the buffer handed to statx, larger than any `struct statx`
+pub(crate) const STATX_SIZE: usize = 0x100;
+
+/// The type of `f_type`, the first field of `struct statfs`
+#[cfg(target_arch = "s390x")]
+pub(crate) type FsType = c_uint;
+#[cfg(not(target_arch = "s390x"))]
+pub(crate) type FsType = std::ffi::c_long;
+
+#[cfg(not(feature = "libc-backed"))]
+mod bindings {
+    use std::ffi::{c_int, c_long, c_uint};
+
+    pub(crate) use arch::*;
+
+    pub(crate) const IN_ACCESS: u32 = 0x00000001;
+    pub(crate) const IN_MODIFY: u32 = 0x00000002;
+    pub(crate) const IN_ATTRIB: u32 = 0x00000004;
+    pub(crate) const IN_CLOSE_WRITE: u32 = 0x00000008;
+    pub(crate) const IN_CLOSE_NOWRITE: u32 = 0x00000010;
+    pub(crate) const IN_OPEN: u32 = 0x00000020;
+    pub(crate) const IN_MOVED_FROM: u32 = 0x00000040;
+    pub(crate) const IN_MOVED_TO: u32 = 0x00000080;
+    pub(crate) const IN_CREATE: u32 = 0x00000100;
+    pub(crate) const IN_DELETE: u32 = 0x00000200;
+    pub(crate) const IN_DELETE_SELF: u32 = 0x00000400;
+    pub(crate) const IN_MOVE_SELF: u32 = 0x00000800;
+    pub(crate) const IN_UNMOUNT: u32 = 0x00002000;
+    pub(crate) const IN_Q_OVERFLOW: u32 = 0x00004000;
+    pub(crate) const IN_IGNORED: u32 = 0x00008000;
+    pub(crate) const IN_ONLYDIR: u32 = 0x01000000;
+    pub(crate) const IN_DONT_FOLLOW: u32 = 0x02000000;
+    pub(crate) const IN_EXCL_UNLINK: u32 = 0x04000000;
+    pub(crate) const IN_MASK_CREATE: u32 = 0x10000000;
+    pub(crate) const IN_MASK_ADD: u32 = 0x20000000;
+    pub(crate) const IN_ISDIR: u32 = 0x40000000;
+    pub(crate) const IN_ONESHOT: u32 = 0x80000000;
+
+    pub(crate) const IN_NONBLOCK: c_int = O_NONBLOCK;
+    pub(crate) const IN_CLOEXEC: c_int = O_CLOEXEC;
+
+    pub(crate) const SYS_PIDFD_OPEN: c_long = SYS_BASE + 434;
+    pub(crate) const SYS_PIDFD_SEND_SIGNAL: c_long = SYS_BASE + 424;
+    pub(crate) const SYS_OPENAT2: c_long = SYS_BASE + 437;
+
+    pub(crate) const ESRCH: c_int = 3;
+    pub(crate) const EINTR: c_int = 4;
+    pub(crate) const ENOTDIR: c_int = 20;
+    #[cfg(feature = "tokio")]
+    pub(crate) const EISDIR: c_int = 21;
+    pub(crate) const EINVAL: c_int = 22;
+    #[cfg(feature = "tokio")]
+    pub(crate) const ENOSPC: c_int = 28;
+    #[cfg(feature = "xattr")]
+    pub(crate) const ERANGE: c_int = 34;
+
+    pub(crate) const AT_EMPTY_PATH: c_int = 0x1000;
+    pub(crate) const STATX_INO: c_uint = 0x100;
+
+    pub(crate) const POLLIN: std::ffi::c_short = 0x1;
+
+    #[repr(C)]
+    pub(crate) struct pollfd {
+        pub(crate) fd: c_int,
+        pub(crate) events: std::ffi::c_short,
+        pub(crate) revents: std::ffi::c_short,
+    }
+
+    #[cfg(feature = "io-uring")]
+    pub(crate) const SYS_IO_URING_SETUP: c_long = SYS_BASE + 425;
+    #[cfg(feature = "io-uring")]
+    pub(crate) const SYS_IO_URING_ENTER: c_long = SYS_BASE + 426;
+    #[cfg(feature = "io-uring")]
+    pub(crate) const SYS_IO_URING_REGISTER: c_long = SYS_BASE + 427;
+
+    #[cfg(feature = "io-uring")]
+    pub(crate) const F_GETFL: c_int = 3;
+    #[cfg(feature = "io-uring")]
+    pub(crate) const F_SETFL: c_int = 4;
+
+    #[cfg(feature = "io-uring")]
+    pub(crate) const PROT_READ: c_int = 0x1;
+    #[cfg(feature = "io-uring")]
+    pub(crate) const PROT_WRITE: c_int = 0x2;
+    #[cfg(feature = "io-uring")]
+    pub(crate) const MAP_SHARED: c_int = 0x01;
+    #[cfg(feature = "io-uring")]
+    pub(crate) const MAP_PRIVATE: c_int = 0x02;
+    #[cfg(feature = "io-uring")]
+    pub(crate) const MAP_FAILED: *mut std::ffi::c_void = !0 as *mut std::ffi::c_void;
+
+    /// The values the kernel defines differently on some architectures
+    #[cfg(not(any(
+        target_arch = "mips",
+        target_arch = "mips32r6",
+        target_arch = "mips64",
+        target_arch = "mips64r6",
+        target_arch = "powerpc",
+        target_arch = "powerpc64",
+        target_arch = "sparc",
+        target_arch = "sparc64"
+    )))]
+    mod arch {
+        use std::ffi::{c_int, c_long, c_ulong};
+
+        pub(crate) const SYS_BASE: c_long = 0;
+        pub(crate) const FIONREAD: c_ulong = 0x541B;
+        pub(crate) const O_NONBLOCK: c_int = 0o4000;
+        pub(crate) const O_CLOEXEC: c_int = 0o2000000;
+        pub(crate) const O_PATH: c_int = 0o10000000;
+        #[cfg(feature = "io-uring")]
+        pub(crate) const MAP_ANONYMOUS: c_int = 0x20;
+        #[cfg(feature = "io-uring")]
+        pub(crate) const MAP_POPULATE: c_int = 0x8000;
+    }
+
+    #[cfg(any(
+        target_arch = "mips",
+        target_arch = "mips32r6",
+        target_arch = "mips64",
+        target_arch = "mips64r6"
+    ))]
+    mod arch {
+        use std::ffi::{c_int, c_long, c_ulong};
+
+        // syscalls are numbered from 4000 for o32 and 5000 for n64
+        #[cfg(target_pointer_width = "32")]
+        pub(crate) const SYS_BASE: c_long = 4000;
+        #[cfg(target_pointer_width = "64")]
+        pub(crate) const SYS_BASE: c_long = 5000;
+        pub(crate) const FIONREAD: c_ulong = 0x467F;
+        pub(crate) const O_NONBLOCK: c_int = 0x80;
+        pub(crate) const O_CLOEXEC: c_int = 0o2000000;
+        pub(crate) const O_PATH: c_int = 0o10000000;
+        #[cfg(feature = "io-uring")]
+        pub(crate) const MAP_ANONYMOUS: c_int = 0x800;
+        #[cfg(feature = "io-uring")]
+        pub(crate) const MAP_POPULATE: c_int = 0x10000;
+    }
+
+    #[cfg(any(target_arch = "powerpc", target_arch = "powerpc64"))]
+    mod arch {
+        use std::ffi::{c_int, c_long, c_ulong};
+
+        pub(crate) const SYS_BASE: c_long = 0;
+        pub(crate) const FIONREAD: c_ulong = 0x4004667F;
+        pub(crate) const O_NONBLOCK: c_int = 0o4000;
+        pub(crate) const O_CLOEXEC: c_int = 0o2000000;
+        pub(crate) const O_PATH: c_int = 0o10000000;
+        #[cfg(feature = "io-uring")]
+        pub(crate) const MAP_ANONYMOUS: c_int = 0x20;
+        #[cfg(feature = "io-uring")]
+        pub(crate) const MAP_POPULATE: c_int = 0x8000;
+    }
+
+    #[cfg(any(target_arch = "sparc", target_arch = "sparc64"))]
+    mod arch {
+        use std::ffi::{c_int, c_long, c_ulong};
+
+        pub(crate) const SYS_BASE: c_long = 0;
+        pub(crate) const FIONREAD: c_ulong = 0x4004667F;
+        pub(crate) const O_NONBLOCK: c_int = 0x4000;
+        pub(crate) const O_CLOEXEC: c_int = 0x400000;
+        pub(crate) const O_PATH: c_int = 0x1000000;
+        #[cfg(feature = "io-uring")]
+        pub(crate) const MAP_ANONYMOUS: c_int = 0x20;
+        #[cfg(feature = "io-uring")]
+        pub(crate) const MAP_POPULATE: c_int = 0x8000;
+    }
+
+    extern "C" {
+        pub(crate) fn syscall(num: c_long, ...) -> c_long;
+
+        pub(crate) fn ioctl(fd: c_int, req: std::ffi::c_ulong, ...) -> c_int;
+
+        pub(crate) fn read(fd: c_int, buf: *mut std::ffi::c_void, count: usize) -> isize;
+
+        pub(crate) fn poll(fds: *mut pollfd, nfds: std::ffi::c_ulong, timeout: c_int) -> c_int;
+
+        #[cfg(feature = "dbus")]
+        pub(crate) fn getuid() -> c_uint;
+
+        pub(crate) fn statx(
+            dirfd: c_int,
+            path: *const std::ffi::c_char,
+            flags: c_int,
+            mask: c_uint,
+            buf: *mut u8,
+        ) -> c_int;
+
+        pub(crate) fn statfs(path: *const std::ffi::c_char, buf: *mut u8) -> c_int;
+
+        #[cfg(feature = "io-uring")]
+        pub(crate) fn fcntl(fd: c_int, cmd: c_int, ...) -> c_int;
+
+        #[cfg(feature = "io-uring")]
+        pub(crate) fn mmap(
+            addr: *mut std::ffi::c_void,
+            len: usize,
+            prot: c_int,
+            flags: c_int,
+            fd: c_int,
+            // off_t, only 32 bits on 32 bit targets without large file offsets
+            offset: c_long,
+        ) -> *mut std::ffi::c_void;
+
+        #[cfg(feature = "io-uring")]
+        pub(crate) fn munmap(addr: *mut std::ffi::c_void, len: usize) -> c_int;
+
+        #[cfg(feature = "xattr")]
+        pub(crate) fn llistxattr(
+            path: *const std::ffi::c_char,
+            list: *mut std::ffi::c_char,
+            size: usize,
+        ) -> isize;
+
+        #[cfg(feature = "xattr")]
+        pub(crate) fn lgetxattr(
+            path: *const std::ffi::c_char,
+            name: *const std::ffi::c_char,
+            value: *mut std::ffi::c_void,
+            size: usize,
+        ) -> isize;
+    }
+}
+
+/// The same names as [bindings], taken from the libc crate
+#[cfg(feature = "libc-backed")]
+mod libc_bindings {
+    pub(crate) use libc::{
+        ioctl, poll, pollfd, read, statfs, statx, syscall, SYS_openat2 as SYS_OPENAT2,
+        SYS_pidfd_open as SYS_PIDFD_OPEN, SYS_pidfd_send_signal as SYS_PIDFD_SEND_SIGNAL,
+        AT_EMPTY_PATH, EINTR, EINVAL, ENOTDIR, ESRCH, FIONREAD, IN_ACCESS, IN_ATTRIB, IN_CLOEXEC,
+        IN_CLOSE_NOWRITE, IN_CLOSE_WRITE, IN_CREATE, IN_DELETE, IN_DELETE_SELF, IN_DONT_FOLLOW,
+        IN_EXCL_UNLINK, IN_IGNORED, IN_ISDIR, IN_MASK_ADD, IN_MASK_CREATE, IN_MODIFY,
+        IN_MOVED_FROM, IN_MOVED_TO, IN_MOVE_SELF, IN_NONBLOCK, IN_ONESHOT, IN_ONLYDIR, IN_OPEN,
+        IN_Q_OVERFLOW, IN_UNMOUNT, O_CLOEXEC, O_PATH, POLLIN, STATX_INO,
+    };
+
+    #[cfg(feature = "xattr")]
+    pub(crate) use libc::{lgetxattr, llistxattr, ERANGE};
+
+    #[cfg(feature = "dbus")]
+    pub(crate) use libc::getuid;
+
+    #[cfg(feature = "tokio")]
+    pub(crate) use libc::{EISDIR, ENOSPC};
+
+    #[cfg(feature = "io-uring")]
+    pub(crate) use libc::{
+        fcntl, mmap, munmap, SYS_io_uring_enter as SYS_IO_URING_ENTER,
+        SYS_io_uring_register as SYS_IO_URING_REGISTER, SYS_io_uring_setup as SYS_IO_URING_SETUP,
+        F_GETFL, F_SETFL, MAP_ANONYMOUS, MAP_FAILED, MAP_POPULATE, MAP_PRIVATE, MAP_SHARED,
+        O_NONBLOCK, PROT_READ, PROT_WRITE,
+    };
+}
+
+/// Refuse resolutions leaving the starting directory, symlinks included
+pub(crate) const RESOLVE_BENEATH: u64 = 0x08;
+
+/// Resolve as if the starting directory were `/`, symlinks included
+pub(crate) const RESOLVE_IN_ROOT: u64 = 0x10;
+
+/// The `struct open_how` of openat2
+#[repr(C)]
+pub(crate) struct OpenHow {
+    pub(crate) flags: u64,
+    pub(crate) mode: u64,
+    pub(crate) resolve: u64,
+}
+
+pub(crate) unsafe fn openat2(dirfd: c_int, path: *const std::ffi::c_char, how: &OpenHow) -> c_int {
+    syscall(
+        SYS_OPENAT2,
+        dirfd,
+        path,
+        how as *const OpenHow,
+        std::mem::size_of::<OpenHow>(),
+    ) as c_int
+}
+
+pub(crate) unsafe fn pidfd_open(pid: c_int, flags: c_uint) -> c_int {
+    syscall(SYS_PIDFD_OPEN, pid, flags) as c_int
+}
+
+pub(crate) unsafe fn pidfd_send_signal(pidfd: c_int, sig: c_int) -> c_int {
+    syscall(
+        SYS_PIDFD_SEND_SIGNAL,
+        pidfd,
+        sig,
+        std::ptr::null::<u8>(),
+        0 as c_uint,
+    ) as c_int
+}
+
+/// The inotify calls issued through `syscall`, for libcs without the wrappers
+#[cfg(feature = "raw-syscall")]
+pub(crate) mod raw {
+    use std::ffi::{c_int, c_long};
+
+    #[cfg(target_arch = "x86_64")]
+    const NR: [c_long; 4] = [294, 254, 255, 3];
+    #[cfg(target_arch = "x86")]
+    const NR: [c_long; 4] = [332, 292, 293, 6];
+    #[cfg(target_arch = "arm")]
+    const NR: [c_long; 4] = [360, 317, 318, 6];
+    #[cfg(any(target_arch = "powerpc", target_arch = "powerpc64"))]
+    const NR: [c_long; 4] = [318, 276, 277, 6];
+    #[cfg(target_arch = "s390x")]
+    const NR: [c_long; 4] = [324, 285, 286, 6];
+    #[cfg(any(
+        target_arch = "aarch64",
+        target_arch = "riscv64",
+        target_arch = "loongarch64"
+    ))]
+    const NR: [c_long; 4] = [26, 27, 28, 57];
+
+    #[cfg(not(any(
+        target_arch = "x86_64",
+        target_arch = "x86",
+        target_arch = "arm",
+        target_arch = "powerpc",
+        target_arch = "powerpc64",
+        target_arch = "s390x",
+        target_arch = "aarch64",
+        target_arch = "riscv64",
+        target_arch = "loongarch64"
+    )))]
+    compile_error!("raw-syscall does not know the syscall numbers of this architecture");
+
+    pub(crate) unsafe fn inotify_init1(flags: c_int) -> c_int {
+        super::syscall(NR[0], flags) as c_int
+    }
+
+    pub(crate) unsafe fn inotify_add_watch(fd: c_int, path: *const u8, mask: u32) -> c_int {
+        super::syscall(NR[1], fd, path, mask) as c_int
+    }
+
+    pub(crate) unsafe fn inotify_rm_watch(fd: c_int, wd: c_int) -> c_int {
+        super::syscall(NR[2], fd, wd) as c_int
+    }
+
+    pub(crate) unsafe fn close(fd: c_int) -> c_int {
+        super::syscall(NR[3], fd) as c_int
+    }
+}
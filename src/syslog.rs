@@ -0,0 +1,315 @@
+use std::{
+    io,
+    net::SocketAddr,
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use tokio::net::{UdpSocket, UnixDatagram};
+
+use crate::{record::timestamp, Event, Glob, INotify, Mask};
+
+const DEFAULT_SOCKET: &str = "/dev/log";
+const DEFAULT_APP: &str = "tokinotify";
+
+/// The structured data element, under the enterprise number RFC 5424 reserves for examples
+const SD_ID: &str = "tokinotify@32473";
+
+/// Where a message comes from, see RFC 5424 section 6.2.1
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Facility {
+    /// Kernel messages
+    Kern = 0,
+    /// User-level messages
+    User = 1,
+    /// The mail system
+    Mail = 2,
+    /// System daemons
+    Daemon = 3,
+    /// Security and authorization messages
+    Auth = 4,
+    /// Messages of syslogd itself
+    Syslog = 5,
+    /// The line printer subsystem
+    Lpr = 6,
+    /// The network news subsystem
+    News = 7,
+    /// The UUCP subsystem
+    Uucp = 8,
+    /// The clock daemon
+    Cron = 9,
+    /// Private security and authorization messages
+    AuthPriv = 10,
+    /// The FTP daemon
+    Ftp = 11,
+    /// Local use 0
+    Local0 = 16,
+    /// Local use 1
+    Local1 = 17,
+    /// Local use 2
+    Local2 = 18,
+    /// Local use 3
+    Local3 = 19,
+    /// Local use 4
+    Local4 = 20,
+    /// Local use 5
+    Local5 = 21,
+    /// Local use 6
+    Local6 = 22,
+    /// Local use 7
+    Local7 = 23,
+}
+
+/// How urgent a message is, see RFC 5424 section 6.2.1
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    /// The system is unusable
+    Emergency = 0,
+    /// Action must be taken immediately
+    Alert = 1,
+    /// Critical conditions
+    Critical = 2,
+    /// Error conditions
+    Error = 3,
+    /// Warning conditions
+    Warning = 4,
+    /// Normal but significant conditions
+    Notice = 5,
+    /// Informational messages
+    Informational = 6,
+    /// Debug-level messages
+    Debug = 7,
+}
+
+enum Transport {
+    Unix(UnixDatagram),
+    Udp(UdpSocket),
+}
+
+/// Forwards events to syslog as RFC 5424 messages
+///
+/// Each event becomes one message with the first flag name as `MSGID`, a
+/// readable text and a `tokinotify@32473` structured data element with
+/// the `path`, `kind` (the flag names joined by `|`), `mask` and `cookie`.
+/// Events are sent as [Facility::User] at [Severity::Notice] unless a
+/// mapping from [SyslogSink::kind] matches.
+pub struct SyslogSink {
+    transport: Transport,
+    app: String,
+    hostname: String,
+    facility: Facility,
+    severity: Severity,
+    kinds: Vec<(Mask, Facility, Severity)>,
+    filters: Vec<(Mask, Glob)>,
+}
+
+impl SyslogSink {
+    /// Connect to the local syslog daemon at `/dev/log`
+    pub fn local() -> io::Result<Self> {
+        Self::unix(Path::new(DEFAULT_SOCKET))
+    }
+
+    /// Connect to a syslog daemon listening on a unix datagram socket at `path`
+    pub fn unix(path: &Path) -> io::Result<Self> {
+        let socket = UnixDatagram::unbound()?;
+        socket.connect(path)?;
+
+        Ok(Self::with(Transport::Unix(socket)))
+    }
+
+    /// Send to a syslog collector over UDP, as RFC 5426 describes
+    pub async fn udp(addr: SocketAddr) -> io::Result<Self> {
+        let local: SocketAddr = if addr.is_ipv4() {
+            ([0, 0, 0, 0], 0).into()
+        } else {
+            ([0u16; 8], 0).into()
+        };
+
+        let socket = UdpSocket::bind(local).await?;
+        socket.connect(addr).await?;
+
+        Ok(Self::with(Transport::Udp(socket)))
+    }
+
+    fn with(transport: Transport) -> Self {
+        let hostname = std::fs::read_to_string("/proc/sys/kernel/hostname")
+            .map(|name| name.trim().to_string())
+            .ok()
+            .filter(|name| !name.is_empty())
+            .unwrap_or_else(|| "-".to_string());
+
+        Self {
+            transport,
+            app: DEFAULT_APP.to_string(),
+            hostname,
+            facility: Facility::User,
+            severity: Severity::Notice,
+            kinds: Vec::new(),
+            filters: Vec::new(),
+        }
+    }
+
+    /// Send as `app` (`APP-NAME`) instead of `tokinotify`
+    pub fn app(mut self, app: &str) -> Self {
+        self.app = header(app, 48);
+        self
+    }
+
+    /// Send as `hostname` (`HOSTNAME`) instead of the kernel's hostname
+    pub fn hostname(mut self, hostname: &str) -> Self {
+        self.hostname = header(hostname, 255);
+        self
+    }
+
+    /// Send events no mapping matches with `facility` and `severity`
+    pub fn default_level(mut self, facility: Facility, severity: Severity) -> Self {
+        self.facility = facility;
+        self.severity = severity;
+        self
+    }
+
+    /// Send events with a flag of `mask` with `facility` and `severity`
+    ///
+    /// Mappings are tried in the order they were added, the first matching
+    /// one is used.
+    pub fn kind(mut self, mask: Mask, facility: Facility, severity: Severity) -> Self {
+        self.kinds.push((mask, facility, severity));
+        self
+    }
+
+    /// Only send events with a flag of `mask` whose full path matches `glob`
+    ///
+    /// Filters accumulate, an event matching any of them is sent. Without
+    /// a filter every event is sent.
+    pub fn filter(mut self, mask: Mask, glob: Glob) -> Self {
+        self.filters.push((mask, glob));
+        self
+    }
+
+    /// Send the message for an event unless filtered, returning whether it was
+    pub async fn emit(&self, inotify: &INotify, event: &Event) -> io::Result<bool> {
+        let path = inotify.resolve(event).unwrap_or_else(|| event.path.clone());
+
+        let wanted = self.filters.is_empty()
+            || self
+                .filters
+                .iter()
+                .any(|(mask, glob)| (*mask & event.mask).0 != 0 && glob.matches(&path));
+        if !wanted {
+            return Ok(false);
+        }
+
+        let msg = self.format(event, &path.to_string_lossy(), SystemTime::now());
+        match &self.transport {
+            Transport::Unix(socket) => socket.send(msg.as_bytes()).await?,
+            Transport::Udp(socket) => socket.send(msg.as_bytes()).await?,
+        };
+
+        Ok(true)
+    }
+
+    /// Send messages until reading from the kernel or writing to syslog fails
+    pub async fn run(&self, inotify: &mut INotify) -> io::Result<()> {
+        loop {
+            let event = inotify.watch().await?;
+            self.emit(inotify, &event).await?;
+        }
+    }
+
+    fn format(&self, event: &Event, path: &str, now: SystemTime) -> String {
+        let (facility, severity) = self
+            .kinds
+            .iter()
+            .find(|(mask, _, _)| (*mask & event.mask).0 != 0)
+            .map_or((self.facility, self.severity), |&(_, f, s)| (f, s));
+        let pri = facility as u8 * 8 + severity as u8;
+
+        let names: Vec<&str> = event.mask.names().collect();
+        let kind = names.join("|");
+        let msgid = names.first().map_or("-", |name| name);
+        let now = timestamp(now.duration_since(UNIX_EPOCH).unwrap_or_default());
+
+        format!(
+            "<{pri}>1 {now} {} {} {} {msgid} [{SD_ID} path=\"{}\" kind=\"{kind}\" mask=\"{}\" cookie=\"{}\"] {kind} {path}",
+            self.hostname,
+            self.app,
+            std::process::id(),
+            param(path),
+            event.mask.0,
+            event.cookie,
+        )
+    }
+}
+
+/// a header field, printable ascii without spaces, `-` when empty
+fn header(value: &str, max: usize) -> String {
+    let value: String = value
+        .chars()
+        .filter(|c| c.is_ascii_graphic())
+        .take(max)
+        .collect();
+
+    if value.is_empty() {
+        "-".to_string()
+    } else {
+        value
+    }
+}
+
+/// escape a structured data parameter value
+fn param(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, '"' | '\\' | ']') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::Watch;
+
+    #[tokio::test]
+    async fn formats_rfc5424() {
+        let sink = SyslogSink::with(Transport::Unix(UnixDatagram::unbound().unwrap()))
+            .hostname("build host")
+            .app("watcher")
+            .kind(Mask::CLOSE_WRITE, Facility::Local3, Severity::Warning);
+        let now = UNIX_EPOCH + Duration::from_millis(1_700_000_000_123);
+        let pid = std::process::id();
+
+        let event = Event::new(Watch::from_raw(1), Mask::CLOSE_WRITE, "");
+        assert_eq!(
+            sink.format(&event, r#"/tmp/a "b\c]d"#, now),
+            format!(
+                r#"<156>1 2023-11-14T22:13:20.123Z buildhost watcher {pid} CLOSE_WRITE [tokinotify@32473 path="/tmp/a \"b\\c\]d" kind="CLOSE_WRITE" mask="8" cookie="0"] CLOSE_WRITE /tmp/a "b\c]d"#
+            )
+        );
+
+        let event = Event::builder(Watch::from_raw(1), Mask::MOVED_FROM | Mask::ISDIR)
+            .cookie(5)
+            .build()
+            .unwrap();
+        assert_eq!(
+            sink.format(&event, "/tmp/d", now),
+            format!(
+                r#"<13>1 2023-11-14T22:13:20.123Z buildhost watcher {pid} MOVED_FROM [tokinotify@32473 path="/tmp/d" kind="MOVED_FROM|ISDIR" mask="1073741888" cookie="5"] MOVED_FROM|ISDIR /tmp/d"#
+            )
+        );
+    }
+
+    #[test]
+    fn headers() {
+        assert_eq!(header("a b\tc", 48), "abc");
+        assert_eq!(header("héllo", 48), "hllo");
+        assert_eq!(header(" ", 48), "-");
+        assert_eq!(header(&"x".repeat(50), 48), "x".repeat(48));
+    }
+}
@@ -0,0 +1,50 @@
+//! Fixtures shared by the unit tests, and by the integration tests
+//! through `tests/common/mod.rs`
+#![allow(dead_code)]
+
+use std::{
+    ops::Deref,
+    os::fd::RawFd,
+    path::{Path, PathBuf},
+};
+
+/// A directory for one test, removed once dropped even when an assertion fails
+#[derive(Debug)]
+pub(crate) struct Scratch(PathBuf);
+
+/// an empty, canonical directory under the temporary directory
+pub(crate) fn scratch(name: &str) -> Scratch {
+    let dir = std::env::temp_dir().join(format!("tokinotify-{name}-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir(&dir).unwrap();
+    Scratch(dir.canonicalize().unwrap())
+}
+
+impl Deref for Scratch {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl AsRef<Path> for Scratch {
+    fn as_ref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for Scratch {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// watches the kernel still holds for the descriptor
+pub(crate) fn kernel_watches(fd: RawFd) -> usize {
+    std::fs::read_to_string(format!("/proc/self/fdinfo/{fd}"))
+        .unwrap()
+        .lines()
+        .filter(|line| line.starts_with("inotify wd:"))
+        .count()
+}
@@ -0,0 +1,325 @@
+use std::{
+    collections::HashSet,
+    ffi::c_int,
+    io,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use crate::{add_watch, canonicalize, pseudo, sys, ExcludeSet, INotify, Mask, PseudoFs, Watch};
+
+/// Progress of an [INotify::add_tree] call
+///
+/// Clones share counters, so a clone can be polled from another task
+/// while the tree is being added.
+#[derive(Debug, Clone, Default)]
+pub struct TreeProgress {
+    dirs: Arc<AtomicUsize>,
+    watches: Arc<AtomicUsize>,
+}
+
+impl TreeProgress {
+    /// Build new progress counters
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Directories visited so far
+    pub fn dirs(&self) -> usize {
+        self.dirs.load(Ordering::Relaxed)
+    }
+
+    /// Watches registered so far
+    pub fn watches(&self) -> usize {
+        self.watches.load(Ordering::Relaxed)
+    }
+}
+
+impl INotify {
+    /// Watch a directory and every directory beneath it
+    ///
+    /// Each level of the tree is split across blocking tasks which list
+    /// and register directories concurrently. Directories removed while
+    /// walking are skipped, any other error aborts the walk and removes
+    /// the watches it added. Directories are checked against the
+    /// [PseudoFs] policy and the [Quota](crate::Quota) as with [INotify::add].
+    pub async fn add_tree(
+        &mut self,
+        root: &Path,
+        mask: Mask,
+        progress: Option<&TreeProgress>,
+    ) -> io::Result<Vec<Watch>> {
+        self.add_tree_excluding(root, mask, None, progress).await
+    }
+
+    /// [INotify::add_tree] neither watching nor descending into directories
+    /// `excludes` matches beneath the root
+    pub(crate) async fn add_tree_excluding(
+        &mut self,
+        root: &Path,
+        mask: Mask,
+        excludes: Option<&ExcludeSet>,
+        progress: Option<&TreeProgress>,
+    ) -> io::Result<Vec<Watch>> {
+        let root = if self.canonical {
+            canonicalize(root, false)?
+        } else {
+            root.to_path_buf()
+        };
+
+        let workers = std::thread::available_parallelism().map_or(4, |n| n.get());
+        let mask = mask | Mask::ONLYDIR;
+        let mut walk = Walk::new(self, mask, progress.cloned().unwrap_or_default());
+        if let Some(excludes) = excludes {
+            walk = walk.excluding(&root, excludes.clone());
+        }
+
+        let known: HashSet<Watch> = self.paths.keys().copied().collect();
+        let mut added = Vec::new();
+        let mut failed = None;
+        let mut level = vec![root];
+
+        while !level.is_empty() && failed.is_none() {
+            let chunk = level.len().div_ceil(workers);
+            let mut tasks = Vec::new();
+
+            for dirs in level.chunks(chunk) {
+                let dirs = dirs.to_vec();
+                let walk = walk.clone();
+
+                tasks.push(tokio::task::spawn_blocking(move || walk.register(dirs)));
+            }
+
+            // every task is awaited, so nothing a sibling added goes unregistered
+            let mut next = Vec::new();
+            for task in tasks {
+                let level = match task.await {
+                    Ok(level) => level,
+                    // what it added before panicking is only known to the walk
+                    Err(err) => {
+                        failed.get_or_insert(io::Error::other(err));
+                        continue;
+                    }
+                };
+
+                next.extend(level.children);
+                for (watch, path, polled) in level.watches {
+                    self.register(watch, path.clone(), mask);
+                    if polled {
+                        self.poll_pseudo(watch, path, mask);
+                    }
+                    added.push(watch);
+                }
+
+                if let Some(err) = level.error {
+                    failed.get_or_insert(err);
+                }
+            }
+
+            level = next;
+        }
+
+        if let Some(err) = failed {
+            self.undo(walk.added(), &known);
+            return Err(err);
+        }
+
+        Ok(added)
+    }
+
+    /// remove the watches a failed walk added, keeping those watched before it
+    pub(crate) fn undo(&mut self, added: Vec<Watch>, known: &HashSet<Watch>) {
+        for watch in added {
+            if !known.contains(&watch) {
+                let _ = self.rm(watch);
+            }
+        }
+    }
+}
+
+/// what the blocking tasks of an [INotify::add_tree] share
+#[derive(Clone)]
+pub(crate) struct Walk {
+    fd: c_int,
+    mask: Mask,
+    progress: TreeProgress,
+    pseudo: PseudoFs,
+    /// watches the [Quota](crate::Quota) still allows, and the paths already watched
+    quota: Option<(Arc<AtomicUsize>, Arc<HashSet<PathBuf>>)>,
+    /// every watch added, recorded at once so a panicking task leaks none
+    added: Arc<Mutex<Vec<Watch>>>,
+    /// the root walked and the rules pruning directories beneath it
+    excludes: Option<(Arc<Path>, ExcludeSet)>,
+}
+
+/// the watches added for one chunk of a level, whether each is polled,
+/// the directories found beneath them and the error stopping the chunk
+struct Level {
+    watches: Vec<(Watch, PathBuf, bool)>,
+    children: Vec<PathBuf>,
+    error: Option<io::Error>,
+}
+
+impl Walk {
+    pub(crate) fn new(inotify: &INotify, mask: Mask, progress: TreeProgress) -> Walk {
+        Walk {
+            fd: inotify.fd,
+            mask,
+            progress,
+            pseudo: inotify.pseudo,
+            quota: inotify.quota.as_ref().map(|quota| {
+                let watched = inotify.paths.values().map(|path| path.to_path_buf()).collect();
+                (Arc::new(AtomicUsize::new(quota.available())), Arc::new(watched))
+            }),
+            added: Arc::default(),
+            excludes: None,
+        }
+    }
+
+    /// leave directories `excludes` matches beneath `root` out of the walk
+    pub(crate) fn excluding(mut self, root: &Path, excludes: ExcludeSet) -> Walk {
+        self.excludes = Some((root.into(), excludes));
+        self
+    }
+
+    /// whether a directory found while walking is left out
+    fn excluded(&self, dir: &Path) -> bool {
+        self.excludes
+            .as_ref()
+            .is_some_and(|(root, excludes)| excludes.matches_beneath(root, dir))
+    }
+
+    /// the watches added so far, by every task of the walk
+    pub(crate) fn added(&self) -> Vec<Watch> {
+        self.added
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone()
+    }
+
+    fn register(&self, dirs: Vec<PathBuf>) -> Level {
+        let mut level = Level {
+            watches: Vec::with_capacity(dirs.len()),
+            children: Vec::new(),
+            error: None,
+        };
+
+        for dir in dirs {
+            if let Err(err) = self.visit(dir, &mut level) {
+                level.error = Some(err);
+                break;
+            }
+        }
+
+        level
+    }
+
+    fn visit(&self, dir: PathBuf, level: &mut Level) -> io::Result<()> {
+        self.progress.dirs.fetch_add(1, Ordering::Relaxed);
+
+        let Some((watch, polled)) = self.add(&dir)? else {
+            return Ok(());
+        };
+        level.watches.push((watch, dir.clone(), polled));
+
+        let entries = match std::fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(err) if vanished(&err) => return Ok(()),
+            Err(err) => return Err(err),
+        };
+
+        for entry in entries {
+            let entry = entry?;
+            if entry.file_type()?.is_dir() && !self.excluded(&entry.path()) {
+                level.children.push(entry.path());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// watch one directory as [INotify::add] would, `None` if it vanished
+    ///
+    /// the watch is returned with whether the directory is polled
+    pub(crate) fn add(&self, dir: &Path) -> io::Result<Option<(Watch, bool)>> {
+        let polled = match pseudo::check(self.pseudo, dir) {
+            Ok(polled) => polled,
+            Err(err) if vanished(&err) => return Ok(None),
+            Err(err) => return Err(err),
+        };
+
+        self.admit(dir)?;
+        let watch = match add_watch(self.fd, dir, self.mask) {
+            Ok(watch) => watch,
+            Err(err) if vanished(&err) => return Ok(None),
+            Err(err) => return Err(err),
+        };
+
+        self.added
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .push(watch);
+        self.progress.watches.fetch_add(1, Ordering::Relaxed);
+        Ok(Some((watch, polled)))
+    }
+
+    /// take a watch from the quota, as [INotify::admit] would
+    fn admit(&self, dir: &Path) -> io::Result<()> {
+        let Some((available, watched)) = &self.quota else {
+            return Ok(());
+        };
+        if watched.contains(dir) {
+            return Ok(());
+        }
+
+        available
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| n.checked_sub(1))
+            .map(drop)
+            .map_err(|_| io::Error::from_raw_os_error(sys::ENOSPC))
+    }
+}
+
+pub(crate) fn vanished(err: &io::Error) -> bool {
+    // ENOTDIR shows up when a directory is replaced by a file mid walk
+    err.kind() == io::ErrorKind::NotFound || err.raw_os_error() == Some(sys::ENOTDIR)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{kernel_watches, scratch};
+    use std::os::fd::AsRawFd;
+
+    #[tokio::test]
+    async fn panicking_task_leaves_its_watches_to_undo() {
+        let root = scratch("walk");
+        std::fs::create_dir_all(root.join("a")).unwrap();
+
+        let mut inotify = INotify::new().unwrap();
+        let kept = inotify.add(&root, Mask::CREATE).unwrap();
+        let known: HashSet<Watch> = inotify.paths.keys().copied().collect();
+
+        let walk = Walk::new(&inotify, Mask::CREATE | Mask::ONLYDIR, TreeProgress::new());
+        let task = {
+            let walk = walk.clone();
+            let dirs = vec![root.to_path_buf(), root.join("a")];
+            tokio::task::spawn_blocking(move || {
+                walk.register(dirs);
+                panic!("after adding");
+            })
+        };
+        assert!(task.await.unwrap_err().is_panic());
+        assert_eq!(kernel_watches(inotify.as_raw_fd()), 2);
+
+        let added = walk.added();
+        assert_eq!(added.len(), 2);
+        assert!(added.contains(&kept));
+
+        inotify.undo(added, &known);
+        assert_eq!(kernel_watches(inotify.as_raw_fd()), 1);
+        assert_eq!(inotify.path(kept), Some(&*root));
+    }
+}
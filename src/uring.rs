@@ -0,0 +1,495 @@
+use std::{
+    ffi::{c_int, c_long, c_uint, c_void},
+    io,
+    os::fd::{AsRawFd, FromRawFd, OwnedFd},
+    sync::atomic::{AtomicU16, AtomicU32, Ordering},
+};
+
+use tokio::io::unix::AsyncFd;
+
+use crate::{sys, INotify, READ_SIZE};
+
+const ENTRIES: u32 = 8;
+
+/// Buffers handed to the kernel, each large enough for any event
+const BUFFERS: u16 = 8;
+const BUFFER_SIZE: usize = 16 * READ_SIZE;
+const BUFFER_GROUP: u16 = 0;
+
+const IORING_OFF_SQ_RING: c_long = 0;
+const IORING_OFF_CQ_RING: c_long = 0x8000000;
+const IORING_OFF_SQES: c_long = 0x10000000;
+
+const IORING_OP_ASYNC_CANCEL: u8 = 14;
+const IORING_OP_READ_MULTISHOT: u8 = 49;
+
+const IOSQE_BUFFER_SELECT: u8 = 1 << 5;
+const IORING_ENTER_GETEVENTS: c_uint = 1;
+const IORING_REGISTER_PBUF_RING: c_uint = 22;
+
+const IORING_CQE_F_BUFFER: u32 = 1;
+const IORING_CQE_F_MORE: u32 = 2;
+const IORING_CQE_BUFFER_SHIFT: u32 = 16;
+
+const ENOBUFS: i32 = 105;
+
+const READ: u64 = 1;
+const CANCEL: u64 = 2;
+
+#[repr(C)]
+#[derive(Default)]
+struct Params {
+    sq_entries: u32,
+    cq_entries: u32,
+    flags: u32,
+    sq_thread_cpu: u32,
+    sq_thread_idle: u32,
+    features: u32,
+    wq_fd: u32,
+    resv: [u32; 3],
+    sq_off: SqOffsets,
+    cq_off: CqOffsets,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct SqOffsets {
+    head: u32,
+    tail: u32,
+    ring_mask: u32,
+    ring_entries: u32,
+    flags: u32,
+    dropped: u32,
+    array: u32,
+    resv1: u32,
+    user_addr: u64,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct CqOffsets {
+    head: u32,
+    tail: u32,
+    ring_mask: u32,
+    ring_entries: u32,
+    overflow: u32,
+    cqes: u32,
+    flags: u32,
+    resv1: u32,
+    user_addr: u64,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct Sqe {
+    opcode: u8,
+    flags: u8,
+    ioprio: u16,
+    fd: i32,
+    off: u64,
+    addr: u64,
+    len: u32,
+    op_flags: u32,
+    user_data: u64,
+    buf_group: u16,
+    personality: u16,
+    splice_fd_in: i32,
+    addr3: u64,
+    pad: u64,
+}
+
+#[repr(C)]
+struct Cqe {
+    user_data: u64,
+    res: i32,
+    flags: u32,
+}
+
+#[repr(C)]
+struct BufReg {
+    ring_addr: u64,
+    ring_entries: u32,
+    bgid: u16,
+    flags: u16,
+    resv: [u64; 3],
+}
+
+#[repr(C)]
+struct Buf {
+    addr: u64,
+    len: u32,
+    bid: u16,
+    resv: u16,
+}
+
+struct Map {
+    ptr: *mut u8,
+    len: usize,
+}
+
+/// Reads events through io_uring instead of epoll and read
+///
+/// A single multishot read stays armed on the inotify fd, the kernel
+/// picks one of a ring of provided buffers for every read it completes.
+pub(crate) struct Uring {
+    ring: AsyncFd<OwnedFd>,
+    inotify: c_int,
+
+    // only reached through the pointers below, held to keep them mapped
+    _sq: Map,
+    _cq: Map,
+    sqes: Map,
+    bufs: Map,
+    pool: Vec<u8>,
+
+    sq_tail: *const AtomicU32,
+    sq_mask: u32,
+    sq_array: *mut u32,
+
+    cq_head: *const AtomicU32,
+    cq_tail: *const AtomicU32,
+    cq_mask: u32,
+    cqes: *const Cqe,
+
+    buf_tail: *const AtomicU16,
+
+    armed: bool,
+}
+
+// the raw pointers all point into mappings owned by the ring, which is only
+// ever touched through &mut
+unsafe impl Send for Uring {}
+unsafe impl Sync for Uring {}
+
+impl INotify {
+    /// read events through io_uring rather than epoll and read
+    ///
+    /// kernels without multishot reads (before 6.7) transparently fall back
+    /// to reading as usual
+    pub fn io_uring(&mut self) -> io::Result<()> {
+        // the kernel only retries reads on readiness when they would not block
+        set_nonblocking(self.fd, true)?;
+
+        match Uring::new(self.fd) {
+            Ok(uring) => {
+                self.uring = Some(uring);
+                Ok(())
+            }
+            Err(err) => {
+                set_nonblocking(self.fd, false)?;
+                Err(err)
+            }
+        }
+    }
+
+    /// return to plain reads, the blocking reads issued then need a blocking fd
+    pub(crate) fn drop_uring(&mut self) -> io::Result<()> {
+        self.uring = None;
+        set_nonblocking(self.fd, false)
+    }
+}
+
+impl Uring {
+    fn new(inotify: c_int) -> io::Result<Uring> {
+        let mut params = Params::default();
+        let fd =
+            unsafe { sys::syscall(sys::SYS_IO_URING_SETUP, ENTRIES, &mut params as *mut Params) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let fd = unsafe { OwnedFd::from_raw_fd(fd as c_int) };
+        let raw = fd.as_raw_fd();
+
+        let sq = Map::ring(
+            raw,
+            IORING_OFF_SQ_RING,
+            params.sq_off.array as usize + params.sq_entries as usize * 4,
+        )?;
+        let cq = Map::ring(
+            raw,
+            IORING_OFF_CQ_RING,
+            params.cq_off.cqes as usize + params.cq_entries as usize * std::mem::size_of::<Cqe>(),
+        )?;
+        let sqes = Map::ring(
+            raw,
+            IORING_OFF_SQES,
+            params.sq_entries as usize * std::mem::size_of::<Sqe>(),
+        )?;
+        let bufs = Map::anonymous(BUFFERS as usize * std::mem::size_of::<Buf>())?;
+
+        let reg = BufReg {
+            ring_addr: bufs.ptr as u64,
+            ring_entries: BUFFERS as u32,
+            bgid: BUFFER_GROUP,
+            flags: 0,
+            resv: [0; 3],
+        };
+        let res = unsafe {
+            sys::syscall(
+                sys::SYS_IO_URING_REGISTER,
+                raw,
+                IORING_REGISTER_PBUF_RING,
+                &reg as *const BufReg,
+                1 as c_uint,
+            )
+        };
+        if res < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let at = |map: &Map, off: u32| unsafe { map.ptr.add(off as usize) };
+
+        let mut uring = Uring {
+            sq_tail: at(&sq, params.sq_off.tail).cast(),
+            sq_mask: unsafe { *at(&sq, params.sq_off.ring_mask).cast::<u32>() },
+            sq_array: at(&sq, params.sq_off.array).cast(),
+
+            cq_head: at(&cq, params.cq_off.head).cast(),
+            cq_tail: at(&cq, params.cq_off.tail).cast(),
+            cq_mask: unsafe { *at(&cq, params.cq_off.ring_mask).cast::<u32>() },
+            cqes: at(&cq, params.cq_off.cqes).cast(),
+
+            // the tail overlays the reserved field of the first buffer
+            buf_tail: at(&bufs, 14).cast(),
+
+            ring: AsyncFd::new(fd)?,
+            inotify,
+            _sq: sq,
+            _cq: cq,
+            sqes,
+            bufs,
+            pool: vec![0; BUFFERS as usize * BUFFER_SIZE],
+            armed: false,
+        };
+
+        for bid in 0..BUFFERS {
+            uring.provide(bid);
+        }
+
+        Ok(uring)
+    }
+
+    /// Wait for the next read, copying it into `out`
+    ///
+    /// Cancel safe, completions stay queued in the ring until taken here.
+    pub(crate) async fn read(&mut self, out: &mut Vec<u8>) -> io::Result<usize> {
+        loop {
+            while let Some(cqe) = self.reap() {
+                if cqe.user_data != READ {
+                    continue;
+                }
+
+                if cqe.flags & IORING_CQE_F_MORE == 0 {
+                    self.armed = false;
+                }
+
+                if cqe.res == -ENOBUFS {
+                    continue;
+                }
+
+                if cqe.res < 0 {
+                    return Err(io::Error::from_raw_os_error(-cqe.res));
+                }
+
+                let len = cqe.res as usize;
+                if cqe.flags & IORING_CQE_F_BUFFER != 0 {
+                    let bid = (cqe.flags >> IORING_CQE_BUFFER_SHIFT) as u16;
+                    let start = bid as usize * BUFFER_SIZE;
+
+                    if out.len() < len {
+                        out.resize(len, 0);
+                    }
+                    out[..len].copy_from_slice(&self.pool[start..start + len]);
+                    self.provide(bid);
+                }
+
+                return Ok(len);
+            }
+
+            if !self.armed {
+                self.arm()?;
+            }
+
+            let mut guard = self.ring.readable().await?;
+            guard.clear_ready();
+        }
+    }
+
+    fn arm(&mut self) -> io::Result<()> {
+        self.submit(Sqe {
+            opcode: IORING_OP_READ_MULTISHOT,
+            flags: IOSQE_BUFFER_SELECT,
+            fd: self.inotify,
+            user_data: READ,
+            buf_group: BUFFER_GROUP,
+            ..Sqe::default()
+        })?;
+
+        self.armed = true;
+        Ok(())
+    }
+
+    fn submit(&mut self, sqe: Sqe) -> io::Result<()> {
+        unsafe {
+            let tail = (*self.sq_tail).load(Ordering::Acquire);
+            let index = tail & self.sq_mask;
+
+            self.sqes.ptr.cast::<Sqe>().add(index as usize).write(sqe);
+            self.sq_array.add(index as usize).write(index);
+            (*self.sq_tail).store(tail.wrapping_add(1), Ordering::Release);
+        }
+
+        self.enter(1, 0)
+    }
+
+    fn enter(&self, submit: c_uint, wait: c_uint) -> io::Result<()> {
+        let flags = if wait > 0 { IORING_ENTER_GETEVENTS } else { 0 };
+        let res = unsafe {
+            sys::syscall(
+                sys::SYS_IO_URING_ENTER,
+                self.ring.as_raw_fd(),
+                submit,
+                wait,
+                flags,
+                std::ptr::null::<c_void>(),
+                0usize,
+            )
+        };
+
+        if res < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(())
+    }
+
+    fn reap(&mut self) -> Option<Cqe> {
+        unsafe {
+            let head = (*self.cq_head).load(Ordering::Relaxed);
+            let tail = (*self.cq_tail).load(Ordering::Acquire);
+            if head == tail {
+                return None;
+            }
+
+            let cqe = self.cqes.add((head & self.cq_mask) as usize).read();
+            (*self.cq_head).store(head.wrapping_add(1), Ordering::Release);
+
+            Some(cqe)
+        }
+    }
+
+    /// Hand a buffer (back) to the kernel
+    fn provide(&mut self, bid: u16) {
+        unsafe {
+            let tail = (*self.buf_tail).load(Ordering::Relaxed);
+            let slot = self
+                .bufs
+                .ptr
+                .cast::<Buf>()
+                .add((tail & (BUFFERS - 1)) as usize);
+
+            (*slot).addr = self.pool.as_mut_ptr().add(bid as usize * BUFFER_SIZE) as u64;
+            (*slot).len = BUFFER_SIZE as u32;
+            (*slot).bid = bid;
+
+            (*self.buf_tail).store(tail.wrapping_add(1), Ordering::Release);
+        }
+    }
+}
+
+impl Drop for Uring {
+    fn drop(&mut self) {
+        if !self.armed {
+            return;
+        }
+
+        // the kernel may still write into the pool until the read is cancelled
+        let cancel = Sqe {
+            opcode: IORING_OP_ASYNC_CANCEL,
+            fd: -1,
+            addr: READ,
+            user_data: CANCEL,
+            ..Sqe::default()
+        };
+
+        if self.submit(cancel).is_err() {
+            // never hand the pool back to the allocator while still armed
+            std::mem::forget(std::mem::take(&mut self.pool));
+            return;
+        }
+
+        while self.armed {
+            match self.reap() {
+                Some(cqe) if cqe.user_data == READ && cqe.flags & IORING_CQE_F_MORE == 0 => {
+                    self.armed = false;
+                }
+                Some(_) => (),
+                None => {
+                    if self.enter(0, 1).is_err() {
+                        std::mem::forget(std::mem::take(&mut self.pool));
+                        return;
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn set_nonblocking(fd: c_int, on: bool) -> io::Result<()> {
+    let flags = unsafe { sys::fcntl(fd, sys::F_GETFL) };
+    if flags == -1 {
+        return Err(io::Error::last_os_error());
+    }
+
+    let flags = if on {
+        flags | sys::O_NONBLOCK
+    } else {
+        flags & !sys::O_NONBLOCK
+    };
+
+    if unsafe { sys::fcntl(fd, sys::F_SETFL, flags) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(())
+}
+
+impl Map {
+    fn ring(fd: c_int, offset: c_long, len: usize) -> io::Result<Map> {
+        Map::new(len, sys::MAP_SHARED | sys::MAP_POPULATE, fd, offset)
+    }
+
+    fn anonymous(len: usize) -> io::Result<Map> {
+        // buffer rings must be page aligned, which mmap guarantees
+        Map::new(len, sys::MAP_PRIVATE | sys::MAP_ANONYMOUS, -1, 0)
+    }
+
+    fn new(len: usize, flags: c_int, fd: c_int, offset: c_long) -> io::Result<Map> {
+        let ptr = unsafe {
+            sys::mmap(
+                std::ptr::null_mut(),
+                len,
+                sys::PROT_READ | sys::PROT_WRITE,
+                flags,
+                fd,
+                offset as _,
+            )
+        };
+
+        if ptr == sys::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(Map {
+            ptr: ptr.cast(),
+            len,
+        })
+    }
+}
+
+impl Drop for Map {
+    fn drop(&mut self) {
+        unsafe { sys::munmap(self.ptr.cast(), self.len) };
+    }
+}
@@ -0,0 +1,173 @@
+use std::{
+    collections::HashMap,
+    io,
+    os::{
+        fd::{AsRawFd, OwnedFd},
+        unix::fs::OpenOptionsExt,
+    },
+    path::PathBuf,
+};
+
+use crate::{sys, INotify, Identity, Watch};
+
+/// Holds an `O_PATH` descriptor per watch to detect paths swapped underneath it
+///
+/// inotify watches follow the inode, not the name. [Validator::validate]
+/// should be called periodically to find watches whose path now names a
+/// different object.
+#[derive(Default)]
+pub struct Validator {
+    pinned: HashMap<Watch, Pinned>,
+}
+
+struct Pinned {
+    fd: OwnedFd,
+    path: PathBuf,
+}
+
+/// A watched path that no longer refers to the watched object
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diverged {
+    /// The watch that diverged
+    pub watch: Watch,
+
+    /// The path the watch was added with
+    pub path: PathBuf,
+
+    /// The identity of the watched object
+    pub pinned: Identity,
+
+    /// The identity of what the path names now, `None` if it is gone
+    pub current: Option<Identity>,
+}
+
+impl Validator {
+    /// Build a new validator
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Hold an `O_PATH` descriptor to a watched path
+    pub fn pin(&mut self, inotify: &INotify, watch: Watch) -> io::Result<()> {
+        let Some(path) = inotify.path(watch) else {
+            return Err(io::Error::new(io::ErrorKind::NotFound, "unknown watch"));
+        };
+
+        let file = std::fs::OpenOptions::new()
+            .read(true)
+            .custom_flags(sys::O_PATH)
+            .open(path)?;
+
+        self.pinned.insert(
+            watch,
+            Pinned {
+                fd: file.into(),
+                path: path.to_path_buf(),
+            },
+        );
+
+        Ok(())
+    }
+
+    /// Release the descriptor held for a watch
+    pub fn unpin(&mut self, watch: Watch) {
+        self.pinned.remove(&watch);
+    }
+
+    /// Check every pinned watch, reporting those that diverged
+    pub fn validate(&self) -> io::Result<Vec<Diverged>> {
+        let mut diverged = Vec::new();
+
+        for (watch, pinned) in &self.pinned {
+            let held = statx_identity(&pinned.fd)?;
+            let current = std::fs::metadata(&pinned.path)
+                .ok()
+                .map(|meta| Identity::from(&meta));
+
+            if current != Some(held) {
+                diverged.push(Diverged {
+                    watch: *watch,
+                    path: pinned.path.clone(),
+                    pinned: held,
+                    current,
+                });
+            }
+        }
+
+        Ok(diverged)
+    }
+}
+
+fn statx_identity(fd: &OwnedFd) -> io::Result<Identity> {
+    let mut buf = [0u8; sys::STATX_SIZE];
+    let res = unsafe {
+        sys::statx(
+            fd.as_raw_fd(),
+            c"".as_ptr(),
+            sys::AT_EMPTY_PATH,
+            sys::STATX_INO,
+            buf.as_mut_ptr().cast(),
+        )
+    };
+
+    if res == -1 {
+        return Err(io::Error::last_os_error());
+    }
+
+    let field = |at: usize| u32::from_ne_bytes(buf[at..at + 4].try_into().unwrap());
+
+    let ino = u64::from_ne_bytes(buf[32..40].try_into().unwrap());
+    let major = field(136) as u64;
+    let minor = field(140) as u64;
+
+    // glibc's makedev, to agree with st_dev
+    let dev = ((major & 0xfffff000) << 32)
+        | ((major & 0xfff) << 8)
+        | ((minor & 0xffffff00) << 12)
+        | (minor & 0xff);
+
+    Ok(Identity { dev, ino })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{testing::scratch, Mask};
+
+    #[test]
+    fn finds_paths_swapped_underneath() {
+        let dir = scratch("validate");
+        std::fs::write(dir.join("kept"), "").unwrap();
+        std::fs::write(dir.join("swapped"), "").unwrap();
+
+        let mut inotify = INotify::new().unwrap();
+        let kept = inotify.add(&dir.join("kept"), Mask::MODIFY).unwrap();
+        let swapped = inotify.add(&dir.join("swapped"), Mask::MODIFY).unwrap();
+
+        let mut validator = Validator::new();
+        validator.pin(&inotify, kept).unwrap();
+        validator.pin(&inotify, swapped).unwrap();
+        assert_eq!(validator.validate().unwrap(), []);
+
+        let pinned = Identity::of(&dir.join("swapped")).unwrap();
+        std::fs::rename(dir.join("swapped"), dir.join("old")).unwrap();
+        std::fs::write(dir.join("swapped"), "").unwrap();
+        let current = Identity::of(&dir.join("swapped")).unwrap();
+
+        assert_eq!(
+            validator.validate().unwrap(),
+            [Diverged {
+                watch: swapped,
+                path: dir.join("swapped"),
+                pinned,
+                current: Some(current),
+            }]
+        );
+
+        std::fs::remove_file(dir.join("swapped")).unwrap();
+        assert_eq!(validator.validate().unwrap()[0].current, None);
+
+        validator.unpin(swapped);
+        assert_eq!(validator.validate().unwrap(), []);
+    }
+}
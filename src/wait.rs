@@ -0,0 +1,422 @@
+use std::{
+    ffi::{OsStr, OsString},
+    io,
+    path::{Path, PathBuf},
+};
+
+use crate::{add_watch, sys, INotify, Mask, Name, RawEvent, Watch};
+
+/// Events on a parent directory announcing that an entry appeared
+const ARRIVAL: Mask = Mask(Mask::CREATE.0 | Mask::MOVED_TO.0);
+
+/// Where a path added with [INotify::add_or_wait] stands
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    /// The path exists and is watched directly
+    Direct(Watch),
+
+    /// The path is missing, the parent directory is watched until it appears
+    Waiting(Watch),
+}
+
+/// The paths waited on under one parent directory watch
+pub(crate) struct Waiting {
+    /// Added for the wait alone, its events are hidden and it goes when done
+    owned: bool,
+    dir: PathBuf,
+    entries: Vec<Entry>,
+}
+
+struct Entry {
+    name: OsString,
+    path: PathBuf,
+    mask: Mask,
+}
+
+impl INotify {
+    /// Add a watch, or wait for the path to be created when it is missing
+    ///
+    /// A missing path is waited on through its parent directory. Once it
+    /// shows up it is watched with `mask` and a synthetic CREATE (or
+    /// MOVED_TO) is reported for the new watch, changes made before the
+    /// watch was in place are not. See [INotify::phase] for how far along it is.
+    ///
+    /// A parent watched only for waiting counts against the quota. A
+    /// parent watched already gets its registered mask back once no path
+    /// is waited on under it.
+    pub fn add_or_wait(&mut self, path: &Path, mask: Mask) -> io::Result<Phase> {
+        match self.add(path, mask) {
+            Err(err) if err.kind() == io::ErrorKind::NotFound => (),
+            res => return res.map(Phase::Direct),
+        }
+
+        let (Some(dir), Some(name)) = (path.parent(), path.file_name()) else {
+            return Err(io::ErrorKind::NotFound.into());
+        };
+        let dir = if dir.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            dir
+        };
+
+        if !self.waiting.values().any(|waiting| waiting.dir == dir) {
+            // arrivals on a pseudo filesystem are never reported, nor polled
+            if self.check_pseudo(dir)? {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    "can't wait on a polled pseudo filesystem",
+                ));
+            }
+            self.admit(dir)?;
+        }
+
+        let parent = add_watch(self.fd, dir, ARRIVAL | Mask::ONLYDIR | Mask::MASK_ADD)?;
+        if !self.waiting.contains_key(&parent) {
+            let owned = !self.paths.contains_key(&parent);
+            if owned {
+                self.charge_wait();
+            }
+
+            self.waiting.insert(
+                parent,
+                Waiting {
+                    owned,
+                    dir: dir.to_path_buf(),
+                    entries: Vec::new(),
+                },
+            );
+        }
+
+        self.waiting
+            .get_mut(&parent)
+            .expect("inserted above")
+            .entries
+            .push(Entry {
+                name: name.to_os_string(),
+                path: path.to_path_buf(),
+                mask,
+            });
+
+        // it may have been created before the parent was watched
+        match self.add_waited(parent, path, mask) {
+            Ok(watch) => {
+                self.arrived(parent, name)?;
+                Ok(Phase::Direct(watch))
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Phase::Waiting(parent)),
+            Err(err) => {
+                self.arrived(parent, name)?;
+                Err(err)
+            }
+        }
+    }
+
+    /// how far along a path added with [INotify::add_or_wait] is
+    pub fn phase(&self, path: &Path) -> Option<Phase> {
+        for (parent, waiting) in &self.waiting {
+            if waiting.entries.iter().any(|entry| entry.path == path) {
+                return Some(Phase::Waiting(*parent));
+            }
+        }
+
+        self.paths
+            .iter()
+            .find(|(_, added)| ***added == *path)
+            .map(|(watch, _)| Phase::Direct(*watch))
+    }
+
+    /// Convert waits satisfied by an event, true when the event is internal
+    pub(crate) fn arrive(&mut self, event: &RawEvent) -> io::Result<bool> {
+        let watch = event.watch;
+
+        if self.hidden.contains(&watch) {
+            if event.mask.contains(Mask::IGNORED) {
+                self.hidden.remove(&watch);
+            }
+            return Ok(true);
+        }
+
+        let Some(waiting) = self.waiting.get(&watch) else {
+            return Ok(false);
+        };
+        let owned = waiting.owned;
+
+        if event.mask.contains(Mask::IGNORED) {
+            self.waiting.remove(&watch);
+            if owned {
+                self.refund_wait();
+            }
+            return Ok(owned);
+        }
+
+        if event.mask.0 & ARRIVAL.0 == 0 {
+            return Ok(owned);
+        }
+
+        // arrivals the caller's own watch did not ask for are hidden too
+        let asked = self.masks.get(&watch).map_or(0, |mask| mask.0);
+        let hide = owned || asked & event.mask.0 & ARRIVAL.0 == 0;
+
+        let name = event.name.as_path().as_os_str();
+        let Some(entry) = waiting.entries.iter().find(|entry| entry.name == name) else {
+            return Ok(hide);
+        };
+
+        let (path, mask) = (entry.path.clone(), entry.mask);
+        match self.add_waited(watch, &path, mask) {
+            Ok(direct) => {
+                self.polled.push_back(RawEvent {
+                    watch: direct,
+                    mask: Mask(event.mask.0 & (ARRIVAL.0 | Mask::ISDIR.0)),
+                    cookie: 0,
+                    name: Name::new(b""),
+                });
+                self.arrived(watch, name)?;
+            }
+
+            // gone again already, keep waiting
+            Err(err) if err.kind() == io::ErrorKind::NotFound => (),
+            Err(err) => {
+                self.arrived(watch, name)?;
+                return Err(err);
+            }
+        }
+
+        Ok(hide)
+    }
+
+    /// Add a path waited on under `parent`
+    fn add_waited(&mut self, parent: Watch, path: &Path, mask: Mask) -> io::Result<Watch> {
+        // the last wait hands its parent's place in the quota to the new watch
+        let handoff = self
+            .waiting
+            .get(&parent)
+            .is_some_and(|waiting| waiting.owned && waiting.entries.len() == 1);
+
+        if handoff {
+            self.refund_wait();
+        }
+        let added = self.add(path, mask);
+        if handoff {
+            self.charge_wait();
+        }
+
+        added
+    }
+
+    /// Stop waiting for a name, dropping the parent watch when it was only for
+    /// waiting and otherwise restoring its registered mask
+    fn arrived(&mut self, parent: Watch, name: &OsStr) -> io::Result<()> {
+        let Some(waiting) = self.waiting.get_mut(&parent) else {
+            return Ok(());
+        };
+
+        waiting.entries.retain(|entry| entry.name != name);
+        if !waiting.entries.is_empty() {
+            return Ok(());
+        }
+
+        let waiting = self.waiting.remove(&parent).expect("checked above");
+        if waiting.owned {
+            self.refund_wait();
+            self.hidden.insert(parent);
+            self.rm(parent)?;
+        } else if let Some(registration) = self.registration(parent) {
+            if registration.0 & ARRIVAL.0 != ARRIVAL.0 {
+                self.set_mask(parent, registration)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Stop waiting for the paths `keep` returns false for, see [INotify::retain]
+    pub(crate) fn retain_waits(
+        &mut self,
+        keep: &mut impl FnMut(&Path, Mask) -> bool,
+    ) -> io::Result<()> {
+        let dropped: Vec<(Watch, OsString)> = self
+            .waiting
+            .iter()
+            .flat_map(|(parent, waiting)| {
+                waiting.entries.iter().map(move |entry| (*parent, entry))
+            })
+            .filter(|(_, entry)| !keep(&entry.path, entry.mask))
+            .map(|(parent, entry)| (parent, entry.name.clone()))
+            .collect();
+
+        let mut res = Ok(());
+        for (parent, name) in dropped {
+            match self.arrived(parent, &name) {
+                // its IGNORED is already queued
+                Err(err) if err.raw_os_error() == Some(sys::EINVAL) => (),
+                Err(err) if res.is_ok() => res = Err(err),
+                _ => (),
+            }
+        }
+
+        res
+    }
+
+    /// parents watched only for waiting
+    pub(crate) fn owned_waits(&self) -> usize {
+        self.waiting.values().filter(|waiting| waiting.owned).count()
+    }
+
+    /// A parent waited on was added explicitly, it now belongs to the caller
+    pub(crate) fn adopt(&mut self, watch: Watch, path: &Path, mask: Mask) {
+        let Some(waiting) = self.waiting.get_mut(&watch) else {
+            return;
+        };
+
+        // counted from now on as the caller's watch
+        if waiting.owned {
+            waiting.owned = false;
+            self.refund_wait();
+        }
+
+        // a plain add replaces the mask, the wait still needs its events
+        if !mask.contains(Mask::MASK_ADD) {
+            let _ = add_watch(self.fd, path, ARRIVAL | Mask::MASK_ADD);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        testing::{kernel_watches, scratch},
+        Drain, Lanes, WatchBudget,
+    };
+    use std::{os::fd::AsRawFd, time::Duration};
+
+    /// the mask the kernel holds for a watch
+    fn kernel_mask(inotify: &INotify, watch: Watch) -> u32 {
+        let info = std::fs::read_to_string(format!("/proc/self/fdinfo/{}", inotify.as_raw_fd()));
+        let prefix = format!("inotify wd:{:x} ", watch.wd);
+        let line = info.unwrap().lines().find(|line| line.starts_with(&prefix)).unwrap().to_string();
+        let mask = line.split(' ').find_map(|field| field.strip_prefix("mask:")).unwrap();
+        u32::from_str_radix(mask, 16).unwrap()
+    }
+
+    async fn next(inotify: &mut INotify) -> crate::Event {
+        tokio::time::timeout(Duration::from_secs(10), inotify.watch())
+            .await
+            .unwrap()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn arrival_becomes_a_direct_watch() {
+        let dir = scratch("wait-arrival");
+        let path = dir.join("f");
+
+        let mut inotify = INotify::new().unwrap();
+        let quota = WatchBudget::new(1).shared();
+        inotify.set_quota(quota.clone());
+
+        let Phase::Waiting(parent) = inotify.add_or_wait(&path, Mask::MODIFY).unwrap() else {
+            panic!("nothing was created yet");
+        };
+        assert_eq!(inotify.phase(&path), Some(Phase::Waiting(parent)));
+        assert_eq!(quota.used(), 1);
+
+        // the parent holds the only watch of the quota
+        let other = inotify.add(&dir.join("missing"), Mask::MODIFY);
+        assert_eq!(other.unwrap_err().raw_os_error(), Some(sys::ENOSPC));
+
+        std::fs::write(dir.join("unrelated"), "").unwrap();
+        std::fs::write(&path, "").unwrap();
+
+        let event = next(&mut inotify).await;
+        assert!(event.is_synthetic());
+        assert_eq!(event.mask, Mask::CREATE);
+        assert_eq!(inotify.phase(&path), Some(Phase::Direct(event.watch)));
+
+        std::fs::write(&path, "changed").unwrap();
+        let event = next(&mut inotify).await;
+        assert_eq!(event.mask, Mask::MODIFY);
+        assert_eq!(inotify.path(event.watch), Some(path.as_path()));
+
+        assert_eq!(kernel_watches(inotify.as_raw_fd()), 1);
+        assert_eq!(quota.used(), 1);
+    }
+
+    #[tokio::test]
+    async fn arrival_restores_a_watched_parent() {
+        let dir = scratch("wait-owned");
+        let path = dir.join("f");
+
+        let mut inotify = INotify::new().unwrap();
+        let parent = inotify.add(&dir, Mask::DELETE).unwrap();
+        assert_eq!(
+            inotify.add_or_wait(&path, Mask::MODIFY).unwrap(),
+            Phase::Waiting(parent)
+        );
+        assert_eq!(kernel_mask(&inotify, parent), (Mask::DELETE | ARRIVAL).0);
+
+        std::fs::write(&path, "").unwrap();
+        let event = next(&mut inotify).await;
+        assert!(event.is_synthetic());
+        assert_ne!(event.watch, parent);
+
+        assert_eq!(inotify.registration(parent), Some(Mask::DELETE));
+        assert_eq!(kernel_mask(&inotify, parent), Mask::DELETE.0);
+
+        std::fs::remove_file(&path).unwrap();
+        let event = loop {
+            let event = next(&mut inotify).await;
+            if event.watch == parent {
+                break event;
+            }
+        };
+        assert_eq!(event.mask, Mask::DELETE);
+    }
+
+    #[tokio::test]
+    async fn hidden_events_do_not_stall_lanes() {
+        let waited = scratch("wait-lanes-waited");
+        let watched = scratch("wait-lanes-watched");
+
+        let mut inotify = INotify::new().unwrap();
+        let watch = inotify.add(&watched, Mask::CREATE).unwrap();
+        inotify.add_or_wait(&waited.join("f"), Mask::MODIFY).unwrap();
+
+        // the hidden CREATE is the last thing queued
+        std::fs::write(watched.join("g"), "").unwrap();
+        std::fs::write(waited.join("other"), "").unwrap();
+
+        let mut lanes = Lanes::new();
+        let event = tokio::time::timeout(Duration::from_secs(10), lanes.watch(&mut inotify))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(event.watch, watch);
+        assert_eq!(event.path, Path::new("g"));
+    }
+
+    #[tokio::test]
+    async fn shutdown_ends_pending_waits() {
+        let dir = scratch("wait-shutdown");
+        std::fs::create_dir(dir.join("sub")).unwrap();
+
+        let mut inotify = INotify::new().unwrap();
+        inotify.add(&dir, Mask::DELETE).unwrap();
+        inotify.add_or_wait(&dir.join("f"), Mask::MODIFY).unwrap();
+        inotify.add_or_wait(&dir.join("sub/f"), Mask::MODIFY).unwrap();
+        assert_eq!(kernel_watches(inotify.as_raw_fd()), 2);
+
+        inotify.rm_all().unwrap();
+        assert_eq!(kernel_watches(inotify.as_raw_fd()), 0);
+        assert_eq!(inotify.phase(&dir.join("f")), None);
+        assert_eq!(inotify.phase(&dir.join("sub/f")), None);
+
+        inotify.add_or_wait(&dir.join("sub/f"), Mask::MODIFY).unwrap();
+        let events = tokio::time::timeout(Duration::from_secs(10), inotify.shutdown(Drain::Collect))
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(events.iter().all(|event| !event.is_synthetic()));
+    }
+}
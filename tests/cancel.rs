@@ -0,0 +1,90 @@
+#![cfg(feature = "tokio")]
+
+//! Dropping an [INotify::watch] future before it completes.
+//!
+//! Timeouts, `select!` and the adapters built on [INotify::watch] all
+//! cancel it, nothing queued or sent to it may be lost when they do.
+
+mod common;
+
+use std::{future::Future, path::PathBuf, task::Poll, time::Duration};
+
+use tokinotify::{INotify, Mask, WatchCommand};
+
+use common::scratch;
+
+#[tokio::test]
+async fn control_survives_a_cancelled_watch() {
+    let dir = scratch("cancel-control");
+    let sub = dir.join("sub");
+    std::fs::create_dir(&sub).unwrap();
+
+    let mut inotify = INotify::new().unwrap();
+    inotify.add(&dir, Mask::CREATE).unwrap();
+    let control = inotify.control(4);
+
+    let idle = tokio::time::timeout(Duration::from_millis(20), inotify.watch()).await;
+    assert!(idle.is_err());
+
+    let sent = control
+        .send(WatchCommand::Add {
+            path: sub.clone(),
+            mask: Mask::CREATE,
+        })
+        .await;
+    if sent.is_err() {
+        // complete the read still armed, the runtime waits for it
+        std::fs::File::create(dir.join("wake")).unwrap();
+    }
+    sent.unwrap();
+
+    // the command is applied while waiting, then the read armed above completes
+    let applied = tokio::time::timeout(Duration::from_millis(20), inotify.watch()).await;
+    assert!(applied.is_err());
+    assert_eq!(inotify.watches().count(), 2);
+
+    std::fs::File::create(sub.join("f")).unwrap();
+    let event = tokio::time::timeout(Duration::from_secs(10), inotify.watch())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(event.mask, Mask::CREATE);
+    assert_eq!(event.path, PathBuf::from("f"));
+}
+
+#[tokio::test]
+async fn no_event_lost_to_cancelled_watches() {
+    let dir = scratch("cancel-events");
+
+    let mut inotify = INotify::new().unwrap();
+    inotify.track_identity(true);
+    inotify.add(&dir, Mask::CREATE).unwrap();
+
+    let names: Vec<String> = (0..32).map(|i| i.to_string()).collect();
+    for name in &names {
+        std::fs::File::create(dir.join(name)).unwrap();
+    }
+
+    // every call is polled once and dropped, reads and identity lookups alike
+    let mut seen = Vec::new();
+    let collect = async {
+        while seen.len() < names.len() {
+            let polled = {
+                let mut watch = std::pin::pin!(inotify.watch());
+                std::future::poll_fn(|cx| Poll::Ready(watch.as_mut().poll(cx))).await
+            };
+            if let Poll::Ready(event) = polled {
+                let event = event.unwrap();
+                assert!(event.identity().is_some());
+                seen.push(event.path.to_str().unwrap().to_string());
+            }
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+    };
+    let collected = tokio::time::timeout(Duration::from_secs(10), collect).await;
+    if collected.is_err() {
+        std::fs::File::create(dir.join("wake")).unwrap();
+    }
+    collected.unwrap();
+    assert_eq!(seen, names);
+}
@@ -0,0 +1,128 @@
+#![cfg(feature = "tokio")]
+
+mod common;
+
+use std::{fs::OpenOptions, io::Write, time::Duration};
+
+use tokinotify::{Classified, Classifier, Event, INotify, Mask};
+
+use common::scratch;
+
+const TRACKED: Mask = Mask::MODIFY
+    .union(Mask::CREATE)
+    .union(Mask::DELETE)
+    .union(Mask::MOVED_FROM)
+    .union(Mask::MOVED_TO);
+
+async fn next(inotify: &mut INotify) -> Event {
+    tokio::time::timeout(Duration::from_secs(10), inotify.watch())
+        .await
+        .expect("an event")
+        .unwrap()
+}
+
+#[tokio::test]
+async fn classifies_a_log_file() {
+    let dir = scratch("classify-log");
+    let log = dir.join("log");
+    std::fs::write(&log, "a").unwrap();
+
+    let mut inotify = INotify::new().unwrap();
+    inotify.add(&dir, TRACKED).unwrap();
+    let mut classifier = Classifier::new();
+    classifier.track(&log).await.unwrap();
+
+    OpenOptions::new()
+        .append(true)
+        .open(&log)
+        .unwrap()
+        .write_all(b"bc")
+        .unwrap();
+    let event = next(&mut inotify).await;
+    assert_eq!(
+        classifier.classify(&inotify, &event).await.unwrap(),
+        Some(Classified::Appended {
+            path: log.clone(),
+            bytes: 2,
+        })
+    );
+
+    OpenOptions::new()
+        .write(true)
+        .open(&log)
+        .unwrap()
+        .set_len(1)
+        .unwrap();
+    let event = next(&mut inotify).await;
+    assert_eq!(
+        classifier.classify(&inotify, &event).await.unwrap(),
+        Some(Classified::Truncated {
+            path: log.clone(),
+            len: 1,
+        })
+    );
+
+    // rotated, a new file renamed into place
+    std::fs::write(dir.join("next"), "").unwrap();
+    let mut rotated = None;
+    std::fs::rename(dir.join("next"), &log).unwrap();
+    while rotated.is_none() {
+        let event = next(&mut inotify).await;
+        rotated = classifier.classify(&inotify, &event).await.unwrap();
+    }
+    assert_eq!(
+        rotated,
+        Some(Classified::Rotated {
+            path: log.clone(),
+            replaced_by: Some(dir.join("next")),
+        })
+    );
+
+    std::fs::remove_file(&log).unwrap();
+    let event = next(&mut inotify).await;
+    assert_eq!(
+        classifier.classify(&inotify, &event).await.unwrap(),
+        Some(Classified::Removed { path: log })
+    );
+}
+
+#[tokio::test]
+async fn files_gone_before_classifying() {
+    let dir = scratch("classify-gone");
+    let log = dir.join("log");
+    std::fs::write(&log, "a").unwrap();
+
+    let mut inotify = INotify::new().unwrap();
+    inotify.add(&dir, TRACKED).unwrap();
+    let mut classifier = Classifier::new();
+    classifier.track(&log).await.unwrap();
+
+    // written, then removed before the write is looked at
+    std::fs::write(&log, "ab").unwrap();
+    std::fs::remove_file(&log).unwrap();
+
+    let mut classified = Vec::new();
+    while !classified
+        .iter()
+        .any(|c| matches!(c, Classified::Removed { .. }))
+    {
+        let event = next(&mut inotify).await;
+        if let Some(c) = classifier.classify(&inotify, &event).await.unwrap() {
+            classified.push(c);
+        }
+    }
+    assert_eq!(classified, [Classified::Removed { path: log.clone() }]);
+
+    // created in place and gone again, still a rotation
+    std::fs::write(&log, "").unwrap();
+    std::fs::remove_file(&log).unwrap();
+
+    let event = next(&mut inotify).await;
+    assert_eq!(
+        classifier.classify(&inotify, &event).await.unwrap(),
+        Some(Classified::Rotated {
+            path: log.clone(),
+            replaced_by: None,
+        })
+    );
+}
@@ -0,0 +1,8 @@
+//! Fixtures shared by the integration tests, the same file the unit tests use
+// each test file picks what it needs
+#![allow(unused_imports)]
+
+#[path = "../../src/testing.rs"]
+mod testing;
+
+pub(crate) use testing::{kernel_watches, scratch};
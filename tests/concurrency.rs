@@ -0,0 +1,378 @@
+#![cfg(feature = "tokio")]
+
+//! Interleavings of add, rm, watch and close through [Shared].
+//!
+//! Every operation is a syscall on the same descriptor, which a model
+//! checker can not follow, so the interleavings come from real threads.
+//! Tests share one lock, descriptor checks would race with each other.
+
+mod common;
+
+use std::{
+    collections::HashSet,
+    io::ErrorKind,
+    os::fd::{AsRawFd, RawFd},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+use tokinotify::{INotify, Mask, Removal, Shared, Watch};
+
+use common::{kernel_watches, scratch};
+
+const DIRS: usize = 8;
+const ROUNDS: usize = 50;
+
+static SERIAL: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+fn dirs(root: &Path) -> Vec<PathBuf> {
+    (0..DIRS)
+        .map(|i| {
+            let dir = root.join(i.to_string());
+            std::fs::create_dir(&dir).unwrap();
+            dir
+        })
+        .collect()
+}
+
+/// keep modifying a file in every directory until stopped
+fn churn(dirs: &[PathBuf], stop: &Arc<AtomicBool>) -> Vec<thread::JoinHandle<()>> {
+    dirs.iter()
+        .cloned()
+        .map(|dir| {
+            let stop = stop.clone();
+            thread::spawn(move || {
+                let path = dir.join("file");
+                while !stop.load(Ordering::Relaxed) {
+                    std::fs::write(&path, b"x").unwrap();
+                    thread::sleep(Duration::from_micros(50));
+                }
+            })
+        })
+        .collect()
+}
+
+fn is_open(fd: RawFd) -> bool {
+    std::fs::read_link(format!("/proc/self/fd/{fd}")).is_ok()
+}
+
+/// whether the descriptor closes, a blocking read that just ended may still hold it
+async fn closes(fd: RawFd) -> bool {
+    for _ in 0..1000 {
+        if !is_open(fd) {
+            return true;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+
+    false
+}
+
+async fn raw_fd(shared: &Shared) -> RawFd {
+    shared.lock().await.as_raw_fd()
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn no_events_after_rm_returns() {
+    let _serial = SERIAL.lock().await;
+    let root = scratch("concurrency-rm");
+    let dirs = dirs(&root);
+
+    let shared = Arc::new(INotify::new().unwrap().into_shared());
+    let removed: Arc<Mutex<HashSet<Watch>>> = Arc::default();
+
+    let stop = Arc::new(AtomicBool::new(false));
+    let writers = churn(&dirs, &stop);
+
+    let removers: Vec<_> = dirs
+        .iter()
+        .cloned()
+        .map(|dir| {
+            let shared = shared.clone();
+            let removed = removed.clone();
+            tokio::spawn(async move {
+                let mut watches = Vec::new();
+                for _ in 0..ROUNDS {
+                    let watch = shared.add(&dir, Mask::MODIFY | Mask::CREATE).unwrap();
+                    tokio::time::sleep(Duration::from_micros(200)).await;
+                    shared.rm(watch).unwrap();
+
+                    removed.lock().unwrap().insert(watch);
+                    watches.push(watch);
+                }
+                watches
+            })
+        })
+        .collect();
+
+    let consumer = {
+        let shared = shared.clone();
+        let removed = removed.clone();
+        tokio::spawn(async move {
+            let mut ignored = HashSet::new();
+            loop {
+                // anything removed before this call started must stay quiet
+                let before = removed.lock().unwrap().clone();
+                let event = tokio::time::timeout(Duration::from_secs(10), shared.watch())
+                    .await
+                    .expect("an IGNORED was dropped")
+                    .unwrap();
+
+                assert!(!event.mask.contains(Mask::Q_OVERFLOW));
+
+                if event.mask.contains(Mask::IGNORED) {
+                    assert_eq!(event.removal(), Some(Removal::ExplicitlyRemoved));
+                    assert!(ignored.insert(event.watch), "IGNORED reported twice");
+                } else {
+                    assert!(
+                        !before.contains(&event.watch),
+                        "{:?} reported for a removed watch",
+                        event.mask
+                    );
+                }
+
+                if ignored.len() == DIRS * ROUNDS {
+                    return ignored;
+                }
+            }
+        })
+    };
+
+    let mut added = HashSet::new();
+    for remover in removers {
+        for watch in remover.await.unwrap() {
+            assert!(added.insert(watch), "watch descriptor handed out twice");
+        }
+    }
+
+    let ignored = consumer.await.unwrap();
+    assert_eq!(ignored, added);
+
+    stop.store(true, Ordering::Relaxed);
+    for writer in writers {
+        writer.join().unwrap();
+    }
+
+    let fd = raw_fd(&shared).await;
+    assert_eq!(kernel_watches(fd), 0);
+    assert_eq!(shared.lock().await.watches().count(), 0);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn concurrent_add_rm_leaks_no_watches() {
+    let _serial = SERIAL.lock().await;
+    let root = scratch("concurrency-leak");
+    let dirs = dirs(&root);
+
+    let shared = Arc::new(INotify::new().unwrap().into_shared());
+    let fd = raw_fd(&shared).await;
+
+    // one keeper per directory stays, the rest come and go around it
+    let mut keep = Vec::new();
+    for dir in &dirs {
+        keep.push(shared.add(dir, Mask::CREATE).unwrap());
+    }
+
+    let tasks: Vec<_> = dirs
+        .iter()
+        .cloned()
+        .map(|dir| {
+            let shared = shared.clone();
+            tokio::spawn(async move {
+                let file = dir.join("file");
+                std::fs::write(&file, b"").unwrap();
+
+                for _ in 0..ROUNDS {
+                    let watch = shared.add(&file, Mask::MODIFY).unwrap();
+                    tokio::task::yield_now().await;
+                    shared.rm(watch).unwrap();
+                }
+            })
+        })
+        .collect();
+
+    // reading concurrently must not resurrect removed watches, reads
+    // run on the blocking pool so the reader ends with the keepers
+    let reader = {
+        let shared = shared.clone();
+        let mut keepers: HashSet<Watch> = keep.iter().copied().collect();
+        tokio::spawn(async move {
+            while !keepers.is_empty() {
+                let event = shared.watch().await.unwrap();
+                if event.mask.contains(Mask::IGNORED) {
+                    keepers.remove(&event.watch);
+                }
+            }
+        })
+    };
+
+    for task in tasks {
+        task.await.unwrap();
+    }
+
+    assert_eq!(kernel_watches(fd), DIRS);
+
+    for watch in &keep {
+        shared.rm(*watch).unwrap();
+    }
+
+    assert_eq!(kernel_watches(fd), 0);
+
+    tokio::time::timeout(Duration::from_secs(10), reader)
+        .await
+        .expect("an IGNORED was dropped")
+        .unwrap();
+
+    let inotify = shared.lock().await;
+    assert_eq!(inotify.watches().count(), 0);
+    drop(inotify);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn closes_once_after_concurrent_use() {
+    let _serial = SERIAL.lock().await;
+    let root = scratch("concurrency-drop");
+
+    let shared = Arc::new(INotify::new().unwrap().into_shared());
+    let fd = raw_fd(&shared).await;
+
+    let reader = {
+        let shared = shared.clone();
+        tokio::spawn(async move {
+            let _ = shared.watch().await;
+        })
+    };
+
+    let churner = {
+        let shared = shared.clone();
+        let root = root.to_path_buf();
+        tokio::spawn(async move {
+            loop {
+                if let Ok(watch) = shared.add(&root, Mask::CREATE) {
+                    let _ = shared.rm(watch);
+                }
+                tokio::task::yield_now().await;
+            }
+        })
+    };
+
+    tokio::time::sleep(Duration::from_millis(20)).await;
+
+    // the reader finishes on its own, a read in flight would outlive an abort
+    tokio::time::timeout(Duration::from_secs(10), reader)
+        .await
+        .expect("no event arrived")
+        .unwrap();
+
+    churner.abort();
+    let _ = churner.await;
+
+    assert!(is_open(fd));
+    drop(Arc::into_inner(shared).expect("every task released the instance"));
+    assert!(closes(fd).await);
+
+    // a descriptor reusing the number must survive, it would not after a second close
+    let file = std::fs::File::open(&root).unwrap();
+    let reused = file.as_raw_fd();
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    assert!(is_open(reused));
+    drop(file);
+
+    let inotify = INotify::new().unwrap().into_shared();
+    let fd = raw_fd(&inotify).await;
+    inotify.into_inner().close().await.unwrap();
+    assert!(!is_open(fd));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn calls_after_close_fail() {
+    let _serial = SERIAL.lock().await;
+    let root = scratch("concurrency-close");
+    let dirs = dirs(&root);
+
+    let shared = Arc::new(INotify::new().unwrap().into_shared());
+    let fd = raw_fd(&shared).await;
+
+    let reader = {
+        let shared = shared.clone();
+        tokio::spawn(async move {
+            loop {
+                if let Err(err) = shared.watch().await {
+                    return err;
+                }
+            }
+        })
+    };
+
+    // adds racing the close either land before it and are removed, or fail
+    let adders: Vec<_> = dirs
+        .iter()
+        .cloned()
+        .map(|dir| {
+            let shared = shared.clone();
+            tokio::spawn(async move {
+                loop {
+                    match shared.add(&dir, Mask::CREATE) {
+                        Ok(_) => tokio::task::yield_now().await,
+                        Err(err) => return err,
+                    }
+                }
+            })
+        })
+        .collect();
+
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    shared.close().await.unwrap();
+
+    let err = tokio::time::timeout(Duration::from_secs(10), reader)
+        .await
+        .expect("watch outlived the close")
+        .unwrap();
+    assert_eq!(err.kind(), ErrorKind::BrokenPipe);
+
+    for adder in adders {
+        assert_eq!(adder.await.unwrap().kind(), ErrorKind::BrokenPipe);
+    }
+
+    assert!(shared.is_closed());
+    assert_eq!(kernel_watches(fd), 0);
+    assert_eq!(shared.lock().await.watches().count(), 0);
+
+    let err = shared.add(&root, Mask::CREATE).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::BrokenPipe);
+    let err = shared.watch().await.unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::BrokenPipe);
+    let err = shared.close().await.unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::BrokenPipe);
+
+    // the descriptor lives on with the handles, nothing can reuse its number
+    assert!(is_open(fd));
+    drop(Arc::into_inner(shared).expect("every task released the instance"));
+    assert!(closes(fd).await);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn close_after_cancelled_watch() {
+    let _serial = SERIAL.lock().await;
+    let root = scratch("concurrency-cancelled");
+
+    let mut inotify = INotify::new().unwrap();
+    inotify.add(&root, Mask::CREATE).unwrap();
+
+    // leaves a read in flight that nothing on the watch will complete
+    let watched = tokio::time::timeout(Duration::from_millis(100), inotify.watch()).await;
+    assert!(watched.is_err());
+
+    let closed = tokio::time::timeout(Duration::from_secs(3), inotify.close()).await;
+    if closed.is_err() {
+        // end the read, the runtime waits for it before the test can fail
+        std::fs::write(root.join("wake"), b"").unwrap();
+        panic!("close waited on the cancelled read");
+    }
+    closed.unwrap().unwrap();
+}
@@ -0,0 +1,119 @@
+#![cfg(feature = "tokio")]
+
+mod common;
+
+use std::time::Duration;
+
+use tokinotify::{INotify, Mirror, OrphanMove, SyncOp};
+
+use common::scratch;
+
+async fn next(mirror: &mut Mirror, inotify: &mut INotify) -> SyncOp {
+    tokio::time::timeout(Duration::from_secs(10), mirror.next(inotify))
+        .await
+        .expect("an operation")
+        .unwrap()
+}
+
+#[tokio::test]
+async fn start_plans_the_difference() {
+    let root = scratch("mirror-start");
+    let (src, dst) = (root.join("src"), root.join("dst"));
+    std::fs::create_dir_all(src.join("d")).unwrap();
+    std::fs::write(src.join("d/f"), "x").unwrap();
+    std::fs::create_dir(&dst).unwrap();
+    std::fs::write(dst.join("gone"), "x").unwrap();
+
+    let mut inotify = INotify::new().unwrap();
+    let mut mirror = Mirror::new(&src, &dst);
+    mirror.start(&mut inotify).await.unwrap();
+
+    let mut ops = Vec::new();
+    while mirror.pending() > 0 {
+        ops.push(next(&mut mirror, &mut inotify).await);
+    }
+
+    assert!(ops.contains(&SyncOp::Mkdir(dst.join("d"))));
+    assert!(ops.contains(&SyncOp::Copy {
+        from: src.join("d/f"),
+        to: dst.join("d/f"),
+    }));
+    assert!(ops.contains(&SyncOp::Remove(dst.join("gone"))));
+
+    // parents before children
+    let mkdir = ops
+        .iter()
+        .position(|op| *op == SyncOp::Mkdir(dst.join("d")));
+    let copy = ops.iter().position(|op| matches!(op, SyncOp::Copy { .. }));
+    assert!(mkdir < copy);
+}
+
+#[tokio::test]
+async fn events_become_operations() {
+    let root = scratch("mirror-events");
+    let (src, dst) = (root.join("src"), root.join("dst"));
+    std::fs::create_dir_all(&src).unwrap();
+    std::fs::create_dir_all(&dst).unwrap();
+
+    let mut inotify = INotify::new().unwrap();
+    let mut mirror = Mirror::new(&src, &dst);
+    mirror.start(&mut inotify).await.unwrap();
+    assert_eq!(mirror.pending(), 0);
+
+    std::fs::write(src.join("a"), "x").unwrap();
+    assert_eq!(
+        next(&mut mirror, &mut inotify).await,
+        SyncOp::Copy {
+            from: src.join("a"),
+            to: dst.join("a"),
+        }
+    );
+
+    std::fs::rename(src.join("a"), src.join("b")).unwrap();
+    assert_eq!(
+        next(&mut mirror, &mut inotify).await,
+        SyncOp::Rename {
+            from: dst.join("a"),
+            to: dst.join("b"),
+        }
+    );
+
+    std::fs::remove_file(src.join("b")).unwrap();
+    assert_eq!(
+        next(&mut mirror, &mut inotify).await,
+        SyncOp::Remove(dst.join("b"))
+    );
+}
+
+#[tokio::test]
+async fn unpaired_moves_follow_the_policy() {
+    let root = scratch("mirror-orphans");
+    let (src, dst) = (root.join("src"), root.join("dst"));
+    std::fs::create_dir_all(&src).unwrap();
+    std::fs::create_dir_all(&dst).unwrap();
+    std::fs::write(src.join("a"), "x").unwrap();
+    std::fs::write(dst.join("a"), "x").unwrap();
+
+    let mut inotify = INotify::new().unwrap();
+    let mut mirror = Mirror::new(&src, &dst)
+        .move_window(Duration::from_millis(50))
+        .orphans(OrphanMove::MovedOut);
+    mirror.start(&mut inotify).await.unwrap();
+    assert_eq!(mirror.pending(), 0);
+
+    // moved out of the tree, then a file written while the move is held
+    std::fs::rename(src.join("a"), root.join("a")).unwrap();
+    std::fs::write(src.join("b"), "x").unwrap();
+
+    assert_eq!(
+        next(&mut mirror, &mut inotify).await,
+        SyncOp::MovedOut(dst.join("a"))
+    );
+    assert_eq!(
+        next(&mut mirror, &mut inotify).await,
+        SyncOp::Copy {
+            from: src.join("b"),
+            to: dst.join("b"),
+        }
+    );
+}
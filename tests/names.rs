@@ -0,0 +1,57 @@
+#![cfg(feature = "tokio")]
+
+mod common;
+
+use std::path::Path;
+
+use tokinotify::{INotify, Mask};
+
+use common::scratch;
+
+#[tokio::test]
+async fn maximum_length_names() {
+    let dir = scratch("maxname");
+    let mut inotify = INotify::new().unwrap();
+    inotify.add(&dir, Mask::CREATE).unwrap();
+
+    let long = "x".repeat(255);
+    std::fs::write(dir.join(&long), b"").unwrap();
+
+    let event = inotify.watch().await.unwrap();
+    assert!(event.mask.contains(Mask::CREATE));
+    assert_eq!(event.path, Path::new(&long));
+}
+
+#[tokio::test]
+async fn multi_event_frames() {
+    let dir = scratch("frames");
+    let mut inotify = INotify::new().unwrap();
+    inotify.add(&dir, Mask::CREATE).unwrap();
+
+    let names: Vec<String> = (0..64)
+        .map(|i| format!("{i}-{}", "y".repeat(i * 3 % 250)))
+        .collect();
+
+    for name in &names {
+        std::fs::write(dir.join(name), b"").unwrap();
+    }
+
+    for expected in &names {
+        let event = inotify.watch().await.unwrap();
+        assert_eq!(event.path, Path::new(expected));
+    }
+}
+
+#[tokio::test]
+async fn padding_is_stripped() {
+    let dir = scratch("padding");
+    let mut inotify = INotify::new().unwrap();
+    inotify.add(&dir, Mask::CREATE).unwrap();
+
+    for name in ["a", "ab", "abcdefghijklmno", "abcdefghijklmnop"] {
+        std::fs::write(dir.join(name), b"").unwrap();
+
+        let event = inotify.watch_raw().await.unwrap();
+        assert_eq!(event.name.as_bytes(), name.as_bytes());
+    }
+}
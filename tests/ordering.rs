@@ -0,0 +1,285 @@
+#![cfg(feature = "tokio")]
+
+mod common;
+
+use std::{
+    collections::HashMap,
+    os::fd::AsRawFd,
+    path::{Path, PathBuf},
+    thread,
+    time::Duration,
+};
+
+use tokinotify::{blocking, Drain, INotify, Mask, Removal, Watch};
+
+use common::{kernel_watches, scratch};
+
+const WRITERS: usize = 4;
+const FILES: usize = 500;
+
+fn writer_dirs(root: &Path) -> Vec<PathBuf> {
+    (0..WRITERS)
+        .map(|w| {
+            let dir = root.join(format!("w{w}"));
+            std::fs::create_dir(&dir).unwrap();
+            dir
+        })
+        .collect()
+}
+
+/// Writers each create then delete files in their own directory, the
+/// events of every directory must come back complete and in order.
+///
+/// Names are distinct so the kernel never merges events, and the total
+/// stays below the default queue size so it never overflows.
+fn start(dirs: &[PathBuf]) -> Vec<thread::JoinHandle<()>> {
+    dirs.iter()
+        .cloned()
+        .map(|dir| {
+            thread::spawn(move || {
+                for i in 0..FILES {
+                    let path = dir.join(i.to_string());
+                    std::fs::File::create(&path).unwrap();
+                    std::fs::remove_file(&path).unwrap();
+                }
+            })
+        })
+        .collect()
+}
+
+/// the events each watch should see, in order
+fn expected() -> Vec<(Mask, String)> {
+    (0..FILES)
+        .flat_map(|i| [(Mask::CREATE, i.to_string()), (Mask::DELETE, i.to_string())])
+        .collect()
+}
+
+fn check(seen: HashMap<Watch, Vec<(Mask, String)>>) {
+    assert_eq!(seen.len(), WRITERS);
+
+    let expected = expected();
+    for events in seen.values() {
+        assert_eq!(events.len(), expected.len());
+        for (got, want) in events.iter().zip(&expected) {
+            assert_eq!(got, want);
+        }
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn per_watch_fifo_under_concurrent_writers() {
+    let root = scratch("ordering");
+    let dirs = writer_dirs(&root);
+
+    let mut inotify = INotify::new().unwrap();
+    for dir in &dirs {
+        inotify.add(dir, Mask::CREATE | Mask::DELETE).unwrap();
+    }
+
+    let writers = start(&dirs);
+
+    let mut seen: HashMap<Watch, Vec<(Mask, String)>> = HashMap::new();
+    for _ in 0..WRITERS * FILES * 2 {
+        let event = tokio::time::timeout(Duration::from_secs(10), inotify.watch())
+            .await
+            .expect("an event was dropped")
+            .unwrap();
+
+        assert!(!event.mask.contains(Mask::Q_OVERFLOW));
+        let mask = event.mask & (Mask::CREATE | Mask::DELETE);
+        let name = event.path.to_string_lossy().into_owned();
+        seen.entry(event.watch).or_default().push((mask, name));
+    }
+
+    for writer in writers {
+        writer.join().unwrap();
+    }
+
+    check(seen);
+}
+
+#[test]
+fn blocking_per_watch_fifo_under_concurrent_writers() {
+    let root = scratch("ordering-blocking");
+    let dirs = writer_dirs(&root);
+
+    let mut inotify = blocking::INotify::new().unwrap();
+    for dir in &dirs {
+        inotify.add(dir, Mask::CREATE | Mask::DELETE).unwrap();
+    }
+
+    let writers = start(&dirs);
+
+    let mut seen: HashMap<Watch, Vec<(Mask, String)>> = HashMap::new();
+    for _ in 0..WRITERS * FILES * 2 {
+        let event = inotify
+            .next_event_timeout(Duration::from_secs(10))
+            .unwrap()
+            .expect("an event was dropped");
+
+        assert!(!event.mask.contains(Mask::Q_OVERFLOW));
+        let mask = event.mask & (Mask::CREATE | Mask::DELETE);
+        let name = event.path.to_string_lossy().into_owned();
+        seen.entry(event.watch).or_default().push((mask, name));
+    }
+
+    for writer in writers {
+        writer.join().unwrap();
+    }
+
+    check(seen);
+}
+
+/// Files are created under a watch about to be removed and then under a
+/// watch kept, the removed watch's events are queued before `rm_with`.
+async fn removed_while_queued(drain: Drain) -> Vec<(Watch, Mask, String)> {
+    let root = scratch(&format!("ordering-rm-{drain:?}"));
+    let dirs = writer_dirs(&root);
+
+    let mut inotify = INotify::new().unwrap();
+    let removed = inotify.add(&dirs[0], Mask::CREATE).unwrap();
+    let kept = inotify.add(&dirs[1], Mask::CREATE).unwrap();
+
+    std::fs::File::create(dirs[0].join("a")).unwrap();
+    std::fs::File::create(dirs[0].join("b")).unwrap();
+    std::fs::File::create(dirs[1].join("c")).unwrap();
+
+    inotify.rm_with(removed, drain).unwrap();
+
+    let mut seen = Vec::new();
+    loop {
+        let event = tokio::time::timeout(Duration::from_secs(10), inotify.watch())
+            .await
+            .expect("the IGNORED was dropped")
+            .unwrap();
+
+        if event.watch == removed && event.mask.contains(Mask::CREATE) {
+            assert_eq!(event.root(), Some(dirs[0].as_path()));
+        }
+
+        let name = event.path.to_string_lossy().into_owned();
+        seen.push((event.watch, event.mask, name));
+
+        if event.mask.contains(Mask::IGNORED) {
+            assert_eq!(event.removal(), Some(Removal::ExplicitlyRemoved));
+            break;
+        }
+    }
+
+    assert_eq!(inotify.path(removed), None);
+    assert_eq!(inotify.path(kept), Some(dirs[1].as_path()));
+
+    seen
+}
+
+#[tokio::test]
+async fn rm_discards_queued_events() {
+    let seen = removed_while_queued(Drain::Discard).await;
+
+    let names: Vec<&str> = seen.iter().map(|(_, _, name)| name.as_str()).collect();
+    assert_eq!(names, ["c", ""]);
+    assert!(seen[1].1.contains(Mask::IGNORED));
+}
+
+#[tokio::test]
+async fn rm_with_collect_delivers_queued_events() {
+    let seen = removed_while_queued(Drain::Collect).await;
+
+    let names: Vec<&str> = seen.iter().map(|(_, _, name)| name.as_str()).collect();
+    assert_eq!(names, ["a", "b", "c", ""]);
+    assert_eq!(seen[0].0, seen[3].0);
+}
+
+#[tokio::test]
+async fn failed_rm_keeps_the_watch() {
+    let root = scratch("ordering-rm-failed");
+    let dir = root.join("gone");
+    std::fs::create_dir(&dir).unwrap();
+
+    let mut inotify = INotify::new().unwrap();
+    let watch = inotify.add(&dir, Mask::DELETE_SELF).unwrap();
+
+    // the kernel drops the watch, its IGNORED is queued but not yet read
+    std::fs::remove_dir(&dir).unwrap();
+    let err = inotify.rm(watch).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    assert_eq!(inotify.path(watch), Some(dir.as_path()));
+
+    let mut seen = Vec::new();
+    loop {
+        let event = tokio::time::timeout(Duration::from_secs(10), inotify.watch())
+            .await
+            .expect("the IGNORED was dropped")
+            .unwrap();
+        assert_eq!(event.watch, watch);
+        seen.push(event.mask);
+
+        if event.mask.contains(Mask::IGNORED) {
+            assert_eq!(event.removal(), Some(Removal::Deleted));
+            break;
+        }
+    }
+
+    assert_eq!(seen, [Mask::DELETE_SELF, Mask::IGNORED]);
+    assert_eq!(inotify.path(watch), None);
+}
+
+#[tokio::test]
+async fn retain_removes_what_keep_rejects() {
+    let root = scratch("ordering-retain");
+    let dirs = writer_dirs(&root);
+
+    let mut inotify = INotify::new().unwrap();
+    let created = inotify.add(&dirs[0], Mask::CREATE).unwrap();
+    let deleted = inotify.add(&dirs[1], Mask::DELETE).unwrap();
+    let named = inotify.add(&dirs[2], Mask::CREATE).unwrap();
+
+    inotify
+        .retain(|path, mask| mask.contains(Mask::DELETE) || path == dirs[2])
+        .unwrap();
+    assert_eq!(inotify.path(created), None);
+    assert_eq!(inotify.path(deleted), Some(dirs[1].as_path()));
+    assert_eq!(inotify.path(named), Some(dirs[2].as_path()));
+    assert_eq!(kernel_watches(inotify.as_raw_fd()), 2);
+
+    let event = tokio::time::timeout(Duration::from_secs(10), inotify.watch())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!((event.watch, event.mask), (created, Mask::IGNORED));
+}
+
+#[tokio::test]
+async fn rm_all_skips_watches_the_kernel_dropped() {
+    let root = scratch("ordering-rm-all");
+    let dirs = writer_dirs(&root);
+
+    let mut inotify = INotify::new().unwrap();
+    for dir in &dirs {
+        inotify.add(dir, Mask::CREATE).unwrap();
+    }
+
+    // its IGNORED is queued but not read yet
+    std::fs::remove_dir(&dirs[0]).unwrap();
+
+    inotify.rm_all().unwrap();
+    assert_eq!(kernel_watches(inotify.as_raw_fd()), 0);
+
+    // the dropped watch is forgotten once its IGNORED is read
+    let dropped = inotify
+        .watches()
+        .map(|(watch, _)| watch)
+        .collect::<Vec<_>>();
+    assert_eq!(dropped.len(), 1);
+    loop {
+        let event = tokio::time::timeout(Duration::from_secs(10), inotify.watch())
+            .await
+            .unwrap()
+            .unwrap();
+        if event.watch == dropped[0] && event.mask.contains(Mask::IGNORED) {
+            assert_eq!(event.removal(), Some(Removal::Deleted));
+            break;
+        }
+    }
+    assert_eq!(inotify.watches().count(), 0);
+}
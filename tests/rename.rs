@@ -0,0 +1,99 @@
+#![cfg(feature = "tokio")]
+
+mod common;
+
+use std::{path::PathBuf, time::Duration};
+
+use tokinotify::{Change, INotify, Mask, Renames, TreeMoves};
+
+use common::scratch;
+
+async fn next(renames: &mut Renames, inotify: &mut INotify) -> Change {
+    tokio::time::timeout(Duration::from_secs(10), renames.next(inotify))
+        .await
+        .expect("a change")
+        .unwrap()
+}
+
+const MOVES: Mask = Mask::MOVED_FROM.union(Mask::MOVED_TO);
+
+#[tokio::test]
+async fn joins_renames_across_watches() {
+    let root = scratch("rename-join");
+    let (a, b) = (root.join("a"), root.join("b"));
+    std::fs::create_dir(&a).unwrap();
+    std::fs::create_dir(&b).unwrap();
+    std::fs::write(a.join("f"), "x").unwrap();
+
+    let mut inotify = INotify::new().unwrap();
+    inotify.add(&a, MOVES).unwrap();
+    inotify.add(&b, MOVES).unwrap();
+    let mut renames = Renames::new(Duration::from_secs(10));
+
+    std::fs::rename(a.join("f"), b.join("g")).unwrap();
+    assert_eq!(
+        next(&mut renames, &mut inotify).await,
+        Change::Renamed {
+            from: a.join("f"),
+            to: b.join("g"),
+            dir: false,
+        }
+    );
+}
+
+#[tokio::test]
+async fn tree_moves_relocate_watches() {
+    let root = scratch("rename-tree");
+    std::fs::create_dir_all(root.join("old/sub")).unwrap();
+
+    let mut inotify = INotify::new().unwrap();
+    inotify.add(&root, MOVES).unwrap();
+    let sub = inotify.add(&root.join("old/sub"), MOVES).unwrap();
+    let mut renames = Renames::new(Duration::from_secs(10)).tree_moves(TreeMoves::Also);
+
+    std::fs::rename(root.join("old"), root.join("new")).unwrap();
+    assert_eq!(
+        next(&mut renames, &mut inotify).await,
+        Change::Renamed {
+            from: root.join("old"),
+            to: root.join("new"),
+            dir: true,
+        }
+    );
+    assert_eq!(
+        next(&mut renames, &mut inotify).await,
+        Change::TreeMoved {
+            old_prefix: root.join("old"),
+            new_prefix: root.join("new"),
+        }
+    );
+    assert_eq!(inotify.path(sub), Some(root.join("new/sub").as_path()));
+}
+
+#[tokio::test]
+async fn unpaired_halves_pass_through() {
+    let root = scratch("rename-unpaired");
+    let watched = root.join("watched");
+    std::fs::create_dir(&watched).unwrap();
+    std::fs::write(watched.join("f"), "x").unwrap();
+
+    let mut inotify = INotify::new().unwrap();
+    inotify.add(&watched, MOVES | Mask::CREATE).unwrap();
+    let mut renames = Renames::new(Duration::from_millis(50));
+
+    // moved out, then an event arriving while the MOVED_FROM is held
+    std::fs::rename(watched.join("f"), root.join("f")).unwrap();
+    std::fs::write(watched.join("g"), "x").unwrap();
+
+    let Change::Event(event) = next(&mut renames, &mut inotify).await else {
+        panic!("a plain event");
+    };
+    assert_eq!(event.mask, Mask::MOVED_FROM);
+    assert_eq!(event.path, PathBuf::from("f"));
+
+    let Change::Event(event) = next(&mut renames, &mut inotify).await else {
+        panic!("a plain event");
+    };
+    assert_eq!(event.mask, Mask::CREATE);
+    assert_eq!(event.path, PathBuf::from("g"));
+}
@@ -0,0 +1,95 @@
+#![cfg(feature = "tokio")]
+
+//! Sweeps of [Rescan] against kernel events on the same directories.
+
+mod common;
+
+use std::{os::unix::fs::PermissionsExt, time::Duration};
+
+use tokinotify::{Event, INotify, Mask, Rescan};
+
+use common::scratch;
+
+async fn next(rescan: &mut Rescan, inotify: &mut INotify) -> Event {
+    tokio::time::timeout(Duration::from_secs(10), rescan.watch(inotify))
+        .await
+        .expect("no event")
+        .unwrap()
+}
+
+#[tokio::test]
+async fn fallback_ignores_events_of_reliable_watches() {
+    let dir = scratch("rescan-fallback");
+    let mut inotify = INotify::new().unwrap();
+    let watch = inotify.add(&dir, Mask::CREATE | Mask::ATTRIB).unwrap();
+    if !inotify.capability(watch).unwrap().reliable() {
+        // every watch is swept, there is nothing to tell apart
+        return;
+    }
+
+    let mut rescan = Rescan::new(Duration::from_secs(3600)).fallback();
+    rescan.sweep(&inotify).await.unwrap();
+
+    std::fs::File::create(dir.join("f")).unwrap();
+    let mut perms = std::fs::metadata(&dir).unwrap().permissions();
+    perms.set_mode(0o700);
+    std::fs::set_permissions(&dir, perms).unwrap();
+
+    let event = next(&mut rescan, &mut inotify).await;
+    assert_eq!((event.mask, event.path), (Mask::CREATE, "f".into()));
+    let event = next(&mut rescan, &mut inotify).await;
+    assert_eq!(
+        (event.mask, event.path),
+        (Mask::ATTRIB | Mask::ISDIR, "".into())
+    );
+
+    rescan.sweep(&inotify).await.unwrap();
+
+    std::fs::File::create(dir.join("g")).unwrap();
+    let event = next(&mut rescan, &mut inotify).await;
+    assert!(!event.is_synthetic(), "{event:?}");
+    assert_eq!((event.mask, event.path), (Mask::CREATE, "g".into()));
+}
+
+#[tokio::test]
+async fn sweeps_report_deletes_first_in_path_order() {
+    let dir = scratch("rescan-order");
+    for name in ["b", "d", "f"] {
+        std::fs::write(dir.join(name), "").unwrap();
+    }
+
+    let mut inotify = INotify::new().unwrap();
+    inotify.add(&dir, Mask::DELETE_SELF).unwrap();
+
+    let mut rescan = Rescan::new(Duration::from_secs(3600));
+    rescan.sweep(&inotify).await.unwrap();
+
+    for name in ["b", "f"] {
+        std::fs::remove_file(dir.join(name)).unwrap();
+    }
+    for name in ["e", "a", "c"] {
+        std::fs::write(dir.join(name), "").unwrap();
+    }
+    std::fs::write(dir.join("d"), "changed").unwrap();
+    rescan.sweep(&inotify).await.unwrap();
+
+    let mut events = Vec::new();
+    for _ in 0..6 {
+        let event = next(&mut rescan, &mut inotify).await;
+        events.push((event.mask, event.path.to_string_lossy().into_owned()));
+    }
+
+    let expected = [
+        (Mask::DELETE, "f"),
+        (Mask::DELETE, "b"),
+        (Mask::CREATE, "a"),
+        (Mask::CREATE, "c"),
+        (Mask::MODIFY, "d"),
+        (Mask::CREATE, "e"),
+    ];
+    let expected: Vec<_> = expected
+        .into_iter()
+        .map(|(mask, path)| (mask, path.to_string()))
+        .collect();
+    assert_eq!(events, expected);
+}
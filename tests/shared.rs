@@ -0,0 +1,85 @@
+#![cfg(feature = "tokio")]
+
+//! Adding and removing through [Shared] as [INotify] does.
+
+mod common;
+
+use std::{io::ErrorKind, path::PathBuf, time::Duration};
+
+use tokinotify::{INotify, Mask, PseudoFs, WatchBudget};
+
+use common::scratch;
+
+#[tokio::test]
+async fn add_follows_the_quota() {
+    let dir = scratch("shared-quota");
+    std::fs::create_dir(dir.join("a")).unwrap();
+    std::fs::create_dir(dir.join("b")).unwrap();
+
+    let mut inotify = INotify::new().unwrap();
+    inotify.set_quota(WatchBudget::new(1).reserve(1).unwrap());
+    let shared = inotify.into_shared();
+
+    let a = shared.add(&dir.join("a"), Mask::CREATE).unwrap();
+    let err = shared.add(&dir.join("b"), Mask::CREATE).unwrap_err();
+    assert_eq!(err.raw_os_error(), Some(28), "ENOSPC");
+
+    // a watched path takes nothing more
+    assert_eq!(shared.add(&dir.join("a"), Mask::MODIFY).unwrap(), a);
+    assert_eq!(shared.lock().await.watches().count(), 1);
+}
+
+#[tokio::test]
+async fn add_follows_the_pseudo_policy() {
+    let mut inotify = INotify::new().unwrap();
+    inotify.pseudo_fs(PseudoFs::Reject);
+    let shared = inotify.into_shared();
+
+    let err = shared
+        .add(&PathBuf::from("/proc/self/fdinfo"), Mask::CREATE)
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Unsupported);
+    assert_eq!(shared.lock().await.watches().count(), 0);
+}
+
+#[tokio::test]
+async fn add_canonicalizes() {
+    let dir = scratch("shared-canonical");
+    std::fs::create_dir(dir.join("a")).unwrap();
+
+    let mut inotify = INotify::new().unwrap();
+    inotify.canonicalize(true);
+    let shared = inotify.into_shared();
+
+    // settings changed after sharing apply as well
+    shared.lock().await.pseudo_fs(PseudoFs::Reject);
+    assert!(shared
+        .add(&PathBuf::from("/proc/self/fdinfo"), Mask::CREATE)
+        .is_err());
+
+    let watch = shared.add(&dir.join("a/../a"), Mask::CREATE).unwrap();
+    let canonical = dir.join("a").canonicalize().unwrap();
+    assert_eq!(shared.lock().await.path(watch), Some(canonical.as_path()));
+}
+
+#[tokio::test]
+async fn failed_rm_keeps_the_watch() {
+    let dir = scratch("shared-rm");
+    let sub = dir.join("sub");
+    std::fs::create_dir(&sub).unwrap();
+
+    let shared = INotify::new().unwrap().into_shared();
+    let watch = shared.add(&sub, Mask::DELETE_SELF).unwrap();
+
+    // the kernel drops the watch with the directory, its IGNORED is still queued
+    std::fs::remove_dir(&sub).unwrap();
+    assert!(shared.rm(watch).is_err());
+    assert!(shared.lock().await.path(watch).is_some());
+
+    let event = tokio::time::timeout(Duration::from_secs(10), shared.watch())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(event.watch, watch);
+    assert!(event.mask.contains(Mask::DELETE_SELF));
+}
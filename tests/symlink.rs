@@ -0,0 +1,112 @@
+#![cfg(feature = "tokio")]
+
+mod common;
+
+use std::{os::fd::AsRawFd, time::Duration};
+
+use tokinotify::{INotify, LinkRole, Mask, SymlinkPolicy};
+
+use common::{kernel_watches, scratch};
+
+#[test]
+fn watches_both_sides_of_a_link() {
+    let root = scratch("link-both");
+    std::fs::write(root.join("target"), "").unwrap();
+    std::os::unix::fs::symlink(root.join("target"), root.join("link")).unwrap();
+
+    let mut inotify = INotify::new().unwrap();
+    let watches = inotify
+        .add_link(&root.join("link"), Mask::ATTRIB, SymlinkPolicy::WatchBoth)
+        .unwrap();
+
+    let (target, link) = (watches.target.unwrap(), watches.link.unwrap());
+    assert_ne!(target, link);
+    assert_eq!(inotify.link_role(target), Some(LinkRole::Target));
+    assert_eq!(inotify.link_role(link), Some(LinkRole::Link));
+}
+
+#[test]
+fn failed_link_watch_removes_the_target_watch() {
+    let root = scratch("link-rollback");
+    std::fs::create_dir(root.join("target")).unwrap();
+    std::os::unix::fs::symlink(root.join("target"), root.join("link")).unwrap();
+
+    // the target is a directory, the link itself is not
+    let mut inotify = INotify::new().unwrap();
+    let err = inotify
+        .add_link(
+            &root.join("link"),
+            Mask::CREATE | Mask::ONLYDIR,
+            SymlinkPolicy::WatchBoth,
+        )
+        .unwrap_err();
+    assert_eq!(err.raw_os_error(), Some(20), "ENOTDIR");
+
+    assert_eq!(inotify.watches().count(), 0);
+    assert_eq!(kernel_watches(inotify.as_raw_fd()), 0);
+}
+
+#[test]
+fn failed_link_watch_keeps_a_target_watched_before() {
+    let root = scratch("link-known");
+    std::fs::create_dir(root.join("target")).unwrap();
+    std::os::unix::fs::symlink(root.join("target"), root.join("link")).unwrap();
+
+    let mut inotify = INotify::new().unwrap();
+    let kept = inotify.add(&root.join("target"), Mask::CREATE).unwrap();
+
+    inotify
+        .add_link(
+            &root.join("link"),
+            Mask::CREATE | Mask::ONLYDIR,
+            SymlinkPolicy::WatchBoth,
+        )
+        .unwrap_err();
+
+    let watches: Vec<_> = inotify.watches().map(|(watch, _)| watch).collect();
+    assert_eq!(watches, [kept]);
+    assert_eq!(inotify.link_role(kept), None);
+    assert_eq!(kernel_watches(inotify.as_raw_fd()), 1);
+}
+
+#[tokio::test]
+async fn canonical_paths_resolve_linked_ancestors() {
+    let root = scratch("link-canonical");
+    std::fs::create_dir_all(root.join("real/sub")).unwrap();
+    std::os::unix::fs::symlink(root.join("real"), root.join("link")).unwrap();
+
+    let mut inotify = INotify::new().unwrap();
+    inotify.canonicalize(true);
+    let watch = inotify
+        .add(&root.join("link/sub/.."), Mask::CREATE)
+        .unwrap();
+    assert_eq!(inotify.path(watch), Some(root.join("real").as_path()));
+
+    // the same directory by another name is the same watch
+    assert_eq!(
+        inotify.add(&root.join("link"), Mask::CREATE).unwrap(),
+        watch
+    );
+
+    std::fs::File::create(root.join("link/f")).unwrap();
+    let event = tokio::time::timeout(Duration::from_secs(10), inotify.watch())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(event.watch, watch);
+    assert_eq!(event.path, root.join("real/f"));
+}
+
+#[test]
+fn canonical_paths_keep_a_final_link_not_followed() {
+    let root = scratch("link-canonical-nofollow");
+    std::fs::write(root.join("target"), "").unwrap();
+    std::os::unix::fs::symlink(root.join("target"), root.join("link")).unwrap();
+
+    let mut inotify = INotify::new().unwrap();
+    inotify.canonicalize(true);
+    let watch = inotify
+        .add(&root.join("link"), Mask::ATTRIB | Mask::DONT_FOLLOW)
+        .unwrap();
+    assert_eq!(inotify.path(watch), Some(root.join("link").as_path()));
+}
@@ -0,0 +1,168 @@
+#![cfg(feature = "tokio")]
+
+//! Timed behavior under a paused tokio clock.
+//!
+//! A pending read from the kernel holds off auto advance, so time moves by
+//! hand: either events are made with [Event::new], or [advancing] steps the
+//! clock while a reader waits out its timer.
+
+mod common;
+
+use std::{future::Future, path::PathBuf, time::Duration};
+
+use tokinotify::{
+    Change, Debounce, DirSettle, Drain, Event, GuardAction, INotify, Mask, RateGuard, Renames,
+};
+use tokio::time::Instant;
+
+use common::scratch;
+
+/// Drive `fut` to completion, moving the clock `step` whenever it is pending
+async fn advancing<F: Future>(fut: F, step: Duration) -> F::Output {
+    tokio::pin!(fut);
+
+    loop {
+        tokio::select! {
+            biased;
+            out = &mut fut => return out,
+            _ = tokio::task::yield_now() => tokio::time::advance(step).await,
+        }
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn guard_pause_follows_the_clock() {
+    let dir = scratch("guard-pause");
+    let mut inotify = INotify::new().unwrap();
+    let watch = inotify.add(&dir, Mask::MODIFY).unwrap();
+    let event = Event::new(watch, Mask::MODIFY, "f");
+
+    let mut guard = RateGuard::with_action(1, GuardAction::Pause(Duration::from_secs(10)));
+    assert!(guard.observe(&mut inotify, &event).unwrap().is_none());
+    assert!(guard.observe(&mut inotify, &event).unwrap().is_some());
+    assert_eq!(
+        inotify.mask(watch),
+        Some(Mask::DELETE_SELF | Mask::MOVE_SELF)
+    );
+
+    tokio::time::advance(Duration::from_secs(9)).await;
+    assert!(guard.resume(&mut inotify).unwrap().is_empty());
+
+    tokio::time::advance(Duration::from_secs(1)).await;
+    assert_eq!(guard.resume(&mut inotify).unwrap(), vec![watch]);
+    assert_eq!(inotify.mask(watch), Some(Mask::MODIFY));
+}
+
+#[tokio::test(start_paused = true)]
+async fn guard_window_follows_the_clock() {
+    let dir = scratch("guard-window");
+    let mut inotify = INotify::new().unwrap();
+    let watch = inotify.add(&dir, Mask::MODIFY).unwrap();
+    let event = Event::new(watch, Mask::MODIFY, "f");
+
+    let mut guard = RateGuard::new(1);
+    assert!(guard.observe(&mut inotify, &event).unwrap().is_none());
+
+    // a new one second window
+    tokio::time::advance(Duration::from_secs(1)).await;
+    assert!(guard.observe(&mut inotify, &event).unwrap().is_none());
+}
+
+#[tokio::test(start_paused = true)]
+async fn guard_keeps_watch_flags() {
+    let dir = scratch("guard-flags");
+    std::fs::create_dir(dir.join("target")).unwrap();
+    std::os::unix::fs::symlink(dir.join("target"), dir.join("link")).unwrap();
+
+    let mut inotify = INotify::new().unwrap();
+    let mask = Mask::ATTRIB | Mask::DONT_FOLLOW | Mask::EXCL_UNLINK;
+    let watch = inotify.add(&dir.join("link"), mask).unwrap();
+    let event = Event::new(watch, Mask::ATTRIB, "");
+
+    let mut guard = RateGuard::with_action(1, GuardAction::Pause(Duration::from_secs(1)));
+    guard.observe(&mut inotify, &event).unwrap();
+    let guarded = guard.observe(&mut inotify, &event).unwrap().unwrap();
+    assert_eq!(guarded.original, mask);
+    assert_eq!(
+        inotify.registration(watch),
+        Some(Mask::DELETE_SELF | Mask::MOVE_SELF | Mask::DONT_FOLLOW | Mask::EXCL_UNLINK)
+    );
+
+    tokio::time::advance(Duration::from_secs(1)).await;
+    assert_eq!(guard.resume(&mut inotify).unwrap(), vec![watch]);
+    assert_eq!(inotify.registration(watch), Some(mask));
+}
+
+#[tokio::test(start_paused = true)]
+async fn debounce_waits_out_the_quiet_period() {
+    let dir = scratch("time-debounce");
+    let mut inotify = INotify::new().unwrap();
+    inotify.add(&dir, Mask::MODIFY | Mask::CLOSE_WRITE).unwrap();
+
+    let quiet = Duration::from_secs(5);
+    let mut debounce = Debounce::new(quiet);
+    let started = Instant::now();
+    std::fs::write(dir.join("f"), "data").unwrap();
+
+    let event = advancing(debounce.watch(&mut inotify), Duration::from_millis(100))
+        .await
+        .unwrap();
+    assert!(started.elapsed() >= quiet);
+    assert_eq!(event.mask, Mask::MODIFY | Mask::CLOSE_WRITE);
+    assert_eq!(event.path, PathBuf::from("f"));
+
+    inotify.shutdown(Drain::Discard).await.unwrap();
+}
+
+#[tokio::test(start_paused = true)]
+async fn settle_waits_out_the_quiet_period() {
+    let dir = scratch("time-settle");
+    let mut inotify = INotify::new().unwrap();
+    inotify.add(&dir, Mask::CREATE | Mask::MOVED_TO).unwrap();
+
+    let quiet = Duration::from_secs(5);
+    let mut settle = DirSettle::new(quiet);
+    let started = Instant::now();
+    std::fs::create_dir(dir.join("import")).unwrap();
+    std::fs::write(dir.join("import/photo"), "data").unwrap();
+
+    let settled = advancing(settle.watch(&mut inotify), Duration::from_millis(100))
+        .await
+        .unwrap();
+    assert!(started.elapsed() >= quiet);
+    assert_eq!(settled.path, dir.join("import"));
+    assert_eq!((settled.files, settled.bytes), (1, 4));
+    assert_eq!(settle.pending(), 0);
+
+    inotify.shutdown(Drain::Discard).await.unwrap();
+}
+
+#[tokio::test(start_paused = true)]
+async fn unpaired_moves_wait_out_the_window() {
+    let dir = scratch("time-renames");
+    let outside = scratch("time-renames-outside");
+    std::fs::write(dir.join("f"), "").unwrap();
+
+    let mut inotify = INotify::new().unwrap();
+    let watch = inotify
+        .add(&dir, Mask::MOVED_FROM | Mask::MOVED_TO)
+        .unwrap();
+
+    let window = Duration::from_secs(5);
+    let mut renames = Renames::new(window);
+    let started = Instant::now();
+    std::fs::rename(dir.join("f"), outside.join("f")).unwrap();
+
+    let change = advancing(renames.next(&mut inotify), Duration::from_millis(100))
+        .await
+        .unwrap();
+    assert!(started.elapsed() >= window);
+    let Change::Event(event) = change else {
+        panic!("expected the lone MOVED_FROM, got {change:?}");
+    };
+    assert_eq!(event.watch, watch);
+    assert_eq!(event.mask, Mask::MOVED_FROM);
+    assert_eq!(event.path, PathBuf::from("f"));
+
+    inotify.shutdown(Drain::Discard).await.unwrap();
+}
@@ -0,0 +1,97 @@
+#![cfg(feature = "tokio")]
+
+mod common;
+
+use std::{io::ErrorKind, os::fd::AsRawFd, path::Path};
+
+use tokinotify::{INotify, Mask, PseudoFs, WatchBudget};
+
+use common::{kernel_watches, scratch};
+
+#[tokio::test]
+async fn failed_walk_removes_its_watches() {
+    let root = scratch("tree-rollback");
+    for a in 0..4 {
+        for b in 0..4 {
+            std::fs::create_dir_all(root.join(format!("{a}/{b}"))).unwrap();
+        }
+    }
+
+    let mut inotify = INotify::new().unwrap();
+    let kept = inotify.add(&root, Mask::CREATE).unwrap();
+    inotify.set_quota(WatchBudget::new(8).reserve(8).unwrap());
+
+    let err = inotify
+        .add_tree(&root, Mask::CREATE, None)
+        .await
+        .unwrap_err();
+    assert_eq!(err.raw_os_error(), Some(28), "ENOSPC");
+
+    let watches: Vec<_> = inotify.watches().map(|(watch, _)| watch).collect();
+    assert_eq!(watches, [kept]);
+    assert_eq!(kernel_watches(inotify.as_raw_fd()), 1);
+}
+
+#[tokio::test]
+async fn within_counts_watched_directories_once() {
+    let root = scratch("within-quota");
+    for a in 0..4 {
+        std::fs::create_dir_all(root.join(format!("{a}/x"))).unwrap();
+    }
+
+    let mut inotify = INotify::new().unwrap();
+    inotify.add(&root, Mask::CREATE).unwrap();
+    inotify.set_quota(WatchBudget::new(4).reserve(4).unwrap());
+
+    // the root is watched already, three more directories fit
+    let coverage = inotify
+        .add_tree_within(&root, Mask::CREATE, None)
+        .await
+        .unwrap();
+    assert_eq!(coverage.watches.len(), 4);
+    assert_eq!(coverage.covered[0], *root);
+    assert_eq!(coverage.uncovered.len(), 4);
+    assert_eq!(kernel_watches(inotify.as_raw_fd()), 4);
+}
+
+#[tokio::test]
+async fn within_follows_the_pseudo_policy() {
+    let mut inotify = INotify::new().unwrap();
+    inotify.pseudo_fs(PseudoFs::Reject);
+
+    let err = inotify
+        .add_tree_within(Path::new("/proc/self/fdinfo"), Mask::CREATE, None)
+        .await
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Unsupported);
+    assert_eq!(kernel_watches(inotify.as_raw_fd()), 0);
+}
+
+#[tokio::test]
+async fn failed_walk_within_removes_its_watches() {
+    let root = scratch("within-rollback");
+    let name = "d".repeat(200);
+
+    // two chains short enough to create, joined into one too deep to watch
+    let chain = |top: &Path| {
+        let deep = (0..12).fold(top.to_path_buf(), |path, _| path.join(&name));
+        std::fs::create_dir_all(&deep).unwrap();
+        deep
+    };
+    let deep = chain(&root.join("a"));
+    chain(&root.join("b"));
+    std::fs::rename(root.join("b"), deep.join("b")).unwrap();
+
+    let mut inotify = INotify::new().unwrap();
+    let kept = inotify.add(&root, Mask::CREATE).unwrap();
+
+    let err = inotify
+        .add_tree_within(&root, Mask::CREATE, None)
+        .await
+        .unwrap_err();
+    assert_eq!(err.raw_os_error(), Some(36), "ENAMETOOLONG");
+
+    let watches: Vec<_> = inotify.watches().map(|(watch, _)| watch).collect();
+    assert_eq!(watches, [kept]);
+    assert_eq!(kernel_watches(inotify.as_raw_fd()), 1);
+}